@@ -0,0 +1,166 @@
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::{fs, io};
+
+/// Contents of an `--accounts` file, like:
+///
+/// ```toml
+/// [[account]]
+/// name = "customer-a"
+/// role_arn = "arn:aws:iam::111111111111:role/bulk-edit"
+/// region = "us-east-1"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Accounts {
+    #[serde(default)]
+    account: Vec<Account>,
+}
+
+/// Where to run, in addition to the command line's other options.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Account {
+    /// How to refer to the account when reporting (by default, the role's
+    /// account ID, or the region).
+    pub name: Option<String>,
+    /// The role to assume (otherwise, the usual credentials are used, such as
+    /// to run in several regions of the same account).
+    pub role_arn: Option<String>,
+    pub external_id: Option<String>,
+    pub mfa_serial: Option<String>,
+    /// The region of the table (otherwise, `--region` or the default).
+    pub region: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum AccountsError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    /// The file lists no accounts.
+    Empty,
+    /// This account (from 1) has an `external_id` or `mfa_serial`, but no
+    /// `role_arn`.
+    NoRole(usize),
+}
+
+impl Display for AccountsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccountsError::Io(e) => f.write_fmt(format_args!("error reading accounts: {}", e)),
+            AccountsError::Parse(e) => f.write_fmt(format_args!("error parsing accounts: {}", e)),
+            AccountsError::Empty => f.write_str("the accounts file lists no [[account]]"),
+            AccountsError::NoRole(i) => f.write_fmt(format_args!(
+                "account {} has external_id or mfa_serial, but no role_arn",
+                i
+            )),
+        }
+    }
+}
+
+/// Reads the accounts to run in, in order.
+pub fn load(path: &Path) -> Result<Vec<Account>, AccountsError> {
+    let contents = fs::read_to_string(path).map_err(AccountsError::Io)?;
+    let accounts = toml::from_str::<Accounts>(&contents)
+        .map_err(AccountsError::Parse)?
+        .account;
+    if accounts.is_empty() {
+        return Err(AccountsError::Empty);
+    }
+    for (i, account) in accounts.iter().enumerate() {
+        if account.role_arn.is_none()
+            && (account.external_id.is_some() || account.mfa_serial.is_some())
+        {
+            return Err(AccountsError::NoRole(i + 1));
+        }
+    }
+    Ok(accounts)
+}
+
+impl Account {
+    /// How to refer to the account (the `i`th, from 1) when reporting.
+    pub fn label(&self, i: usize) -> String {
+        let account_id = self
+            .role_arn
+            .as_deref()
+            .and_then(|arn| arn.split(':').nth(4))
+            .filter(|id| !id.is_empty());
+        match (&self.name, account_id, &self.region) {
+            (Some(name), ..) => name.clone(),
+            (None, Some(account_id), Some(region)) => format!("{} ({})", account_id, region),
+            (None, Some(account_id), None) => account_id.to_string(),
+            (None, None, Some(region)) => region.clone(),
+            (None, None, None) => format!("account {}", i),
+        }
+    }
+
+    /// Command line options to run in the account.
+    pub fn args(&self) -> Vec<String> {
+        [
+            ("--role-arn", &self.role_arn),
+            ("--external-id", &self.external_id),
+            ("--mfa-serial", &self.mfa_serial),
+            ("--region", &self.region),
+        ]
+        .into_iter()
+        .filter_map(|(option, value)| Some([option.to_string(), value.clone()?]))
+        .flatten()
+        .collect()
+    }
+}
+
+/// `args` without any occurrences of `option` (as `--option value` or
+/// `--option=value`).
+pub fn without_option(args: &[String], option: &str) -> Vec<String> {
+    let mut ret = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == option {
+            args.next();
+        } else if !arg
+            .strip_prefix(option)
+            .is_some_and(|rest| rest.starts_with('='))
+        {
+            ret.push(arg.clone());
+        }
+    }
+    ret
+}
+
+/// How a run in one account ended, from its progress events.
+#[derive(Debug, Default)]
+pub struct Outcome {
+    /// How many items it wrote, if it got to writing.
+    pub written: Option<usize>,
+    /// Whether the operator (or the payload) stopped it before writing.
+    pub canceled: bool,
+    /// The last error it reported.
+    pub error: Option<String>,
+}
+
+impl Outcome {
+    /// Reads the progress events that a run wrote to `path` with
+    /// `--progress-file`, if any.
+    pub fn read(path: &Path) -> Self {
+        let mut ret = Self::default();
+        let contents = fs::read_to_string(path).unwrap_or_default();
+        for event in contents
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        {
+            match event["event"].as_str() {
+                Some("written") | Some("done") => {
+                    if let Some(written) = event["written"].as_u64() {
+                        ret.written = Some(written as usize);
+                    }
+                }
+                Some("phase") if event["phase"] == "canceled" => ret.canceled = true,
+                Some("error") => ret.error = event["message"].as_str().map(String::from),
+                _ => {}
+            }
+        }
+        ret
+    }
+}
@@ -0,0 +1,82 @@
+use aws_sdk_dynamodb::model::AttributeValue;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Attribute paths that items may have, like `address.zip`. A path allows
+/// everything beneath it.
+#[derive(Debug, Default)]
+pub struct AllowList {
+    children: HashMap<String, AllowList>,
+    /// Whether everything beneath is allowed.
+    all: bool,
+}
+
+impl AllowList {
+    /// Allows a dot-separated path.
+    pub fn allow(&mut self, path: &str) {
+        let mut node = self;
+        for segment in path.split('.') {
+            node = node.children.entry(segment.to_string()).or_default();
+        }
+        node.all = true;
+    }
+
+    /// Allows the `properties` of a JSON Schema, recursively. Schemas that
+    /// don't list properties allow everything beneath them.
+    pub fn allow_schema(&mut self, schema: &Value) {
+        match schema.get("properties").and_then(Value::as_object) {
+            Some(properties) => {
+                for (name, property) in properties {
+                    self.children
+                        .entry(name.clone())
+                        .or_default()
+                        .allow_schema(property);
+                }
+            }
+            None => self.all = true,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty() && !self.all
+    }
+
+    /// Removes attributes of `item` that aren't allowed, returning their paths.
+    /// Lists and non-map values are never reached into.
+    pub fn drop_unknown(&self, item: &mut HashMap<String, AttributeValue>) -> Vec<String> {
+        let mut ret = Vec::new();
+        self.drop_inner("", item, &mut ret);
+        ret.sort_unstable();
+        ret
+    }
+
+    fn drop_inner(
+        &self,
+        path: &str,
+        map: &mut HashMap<String, AttributeValue>,
+        ret: &mut Vec<String>,
+    ) {
+        if self.all {
+            return;
+        }
+        map.retain(|key, value| {
+            let path = if path.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", path, key)
+            };
+            match self.children.get(key) {
+                Some(child) => {
+                    if let AttributeValue::M(map) = value {
+                        child.drop_inner(&path, map, ret);
+                    }
+                    true
+                }
+                None => {
+                    ret.push(path);
+                    false
+                }
+            }
+        });
+    }
+}
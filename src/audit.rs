@@ -0,0 +1,239 @@
+use aws_sdk_dynamodb::error::{BatchWriteItemError, DescribeTableError};
+use aws_sdk_dynamodb::model::{AttributeValue, PutRequest, WriteRequest};
+use aws_sdk_dynamodb::types::SdkError;
+use aws_sdk_dynamodb::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+/// The most requests that `BatchWriteItem` accepts at once.
+const BATCH_SIZE: usize = 25;
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Somewhere to keep the audit trail (one JSON record per item written), as
+/// listed under `audit` in `--config`, like `{ file = "audit.jsonl" }`,
+/// `{ s3 = "s3://bucket/audit/" }`, or `{ table = "bulk_edit_audit" }`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+pub enum Sink {
+    /// A local file, appended to.
+    File(PathBuf),
+    /// A prefix under which each run uploads one object, named after the run,
+    /// with the `aws` CLI.
+    S3(String),
+    /// A DynamoDB table with a string partition key and no sort key, which
+    /// gets one item per record.
+    Table(String),
+}
+
+impl Display for Sink {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Sink::File(path) => f.write_fmt(format_args!("file {}", path.display())),
+            Sink::S3(url) => f.write_fmt(format_args!("S3 {}", url)),
+            Sink::Table(table) => f.write_fmt(format_args!("table {}", table)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AuditError {
+    /// An `s3` sink doesn't start with `s3://`.
+    Url(String),
+    Describe(Box<SdkError<DescribeTableError>>),
+    /// A `table` sink doesn't have a string partition key and no sort key.
+    Key(String),
+    File(io::Error),
+    /// The `aws` CLI couldn't be run, or failed.
+    S3(String),
+    Put(Box<SdkError<BatchWriteItemError>>),
+}
+
+impl Display for AuditError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditError::Url(url) => f.write_fmt(format_args!(
+                "audit sink '{}' is invalid (expected e.g. s3://bucket/audit/)",
+                url
+            )),
+            AuditError::Describe(e) => {
+                f.write_fmt(format_args!("error describing audit table: {}", e))
+            }
+            AuditError::Key(table) => f.write_fmt(format_args!(
+                "audit table {} must have a string partition key and no sort key",
+                table
+            )),
+            AuditError::File(e) => f.write_fmt(format_args!("error writing audit file: {}", e)),
+            AuditError::S3(e) => f.write_fmt(format_args!("error uploading audit trail: {}", e)),
+            AuditError::Put(e) => f.write_fmt(format_args!("error writing audit table: {}", e)),
+        }
+    }
+}
+
+/// Sends a run's audit trail to every sink.
+pub struct Audit<'a> {
+    client: &'a Client,
+    /// Identifies the run, in its records and the names of its S3 objects.
+    run: String,
+    /// Each sink, with the partition key of `table` sinks.
+    sinks: Vec<(Sink, Option<String>)>,
+    /// Like `--region us-east-1`, passed to the `aws` CLI.
+    cli_args: Vec<String>,
+}
+
+impl<'a> Audit<'a> {
+    /// Checks that every sink is usable, before anything is written.
+    pub async fn new(
+        client: &'a Client,
+        run: String,
+        sinks: Vec<Sink>,
+        cli_args: Vec<String>,
+    ) -> Result<Self, AuditError> {
+        let mut checked = Vec::new();
+        for sink in sinks {
+            let key = match &sink {
+                Sink::File(_) => None,
+                Sink::S3(url) => {
+                    if !url.starts_with("s3://") {
+                        return Err(AuditError::Url(url.clone()));
+                    }
+                    None
+                }
+                Sink::Table(table) => {
+                    let description = crate::describe_table(client, table)
+                        .await
+                        .map_err(|e| AuditError::Describe(Box::new(e)))?;
+                    let key_names = crate::key_names(&description);
+                    if key_names.len() != 1
+                        || !crate::is_string_attribute(&description, &key_names[0])
+                    {
+                        return Err(AuditError::Key(table.clone()));
+                    }
+                    key_names.into_iter().next()
+                }
+            };
+            checked.push((sink, key));
+        }
+        Ok(Self {
+            client,
+            run,
+            sinks: checked,
+            cli_args,
+        })
+    }
+
+    pub fn run(&self) -> &str {
+        &self.run
+    }
+
+    /// Writes `records` (JSON objects) to every sink, returning the sinks
+    /// that failed, and why. Failing sinks don't stop the others.
+    pub async fn write(&self, records: &[Value]) -> Vec<(&Sink, AuditError)> {
+        let mut errors = Vec::new();
+        if records.is_empty() {
+            return errors;
+        }
+        let lines = records
+            .iter()
+            .map(|record| format!("{}\n", record))
+            .collect::<String>();
+        for (sink, key) in &self.sinks {
+            let result = match sink {
+                Sink::File(path) => OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .and_then(|mut file| file.write_all(lines.as_bytes()))
+                    .map_err(AuditError::File),
+                Sink::S3(url) => self.upload(url, &lines).await,
+                Sink::Table(table) => self.put(table, key.as_deref().unwrap(), records).await,
+            };
+            if let Err(e) = result {
+                errors.push((sink, e));
+            }
+        }
+        errors
+    }
+
+    async fn upload(&self, url: &str, lines: &str) -> Result<(), AuditError> {
+        let url = format!("{}/{}.jsonl", url.trim_end_matches('/'), self.run);
+        let mut child = tokio::process::Command::new("aws")
+            .args(&self.cli_args)
+            .args(["s3", "cp", "-", &url])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| AuditError::S3(format!("error running aws: {}", e)))?;
+        let mut stdin = child.stdin.take().unwrap();
+        stdin
+            .write_all(lines.as_bytes())
+            .await
+            .map_err(|e| AuditError::S3(e.to_string()))?;
+        drop(stdin);
+        let status = child
+            .wait()
+            .await
+            .map_err(|e| AuditError::S3(e.to_string()))?;
+        if !status.success() {
+            return Err(AuditError::S3(format!("aws s3 cp exited with {}", status)));
+        }
+        Ok(())
+    }
+
+    async fn put(&self, table: &str, key: &str, records: &[Value]) -> Result<(), AuditError> {
+        for (i, chunk) in records.chunks(BATCH_SIZE).enumerate() {
+            let mut requests = chunk
+                .iter()
+                .enumerate()
+                .map(|(j, record)| {
+                    let seq = i * BATCH_SIZE + j;
+                    let mut item = HashMap::from([
+                        (
+                            key.to_string(),
+                            AttributeValue::S(format!("{}#{}", self.run, seq)),
+                        ),
+                        (String::from("run"), AttributeValue::S(self.run.clone())),
+                        (String::from("seq"), AttributeValue::N(seq.to_string())),
+                    ]);
+                    if let Value::Object(fields) = record {
+                        for (name, value) in fields {
+                            if name != "run" && name != key {
+                                item.insert(name.clone(), crate::json::from_plain_json(value));
+                            }
+                        }
+                    }
+                    WriteRequest::builder()
+                        .put_request(PutRequest::builder().set_item(Some(item)).build())
+                        .build()
+                })
+                .collect::<Vec<_>>();
+            let mut backoff = Duration::from_millis(100);
+            while !requests.is_empty() {
+                let output = self
+                    .client
+                    .batch_write_item()
+                    .request_items(table, requests)
+                    .send()
+                    .await
+                    .map_err(|e| AuditError::Put(Box::new(e)))?;
+                requests = output
+                    .unprocessed_items
+                    .and_then(|mut unprocessed| unprocessed.remove(table))
+                    .unwrap_or_default();
+                if !requests.is_empty() {
+                    // DynamoDB returns unprocessed items when throttling the batch.
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+        Ok(())
+    }
+}
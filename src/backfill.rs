@@ -0,0 +1,168 @@
+use crate::Reads;
+use aws_sdk_dynamodb::error::ScanError;
+use aws_sdk_dynamodb::model::{
+    AttributeValue, KeySchemaElement, ReturnConsumedCapacity, ScalarAttributeType, Select,
+    TableDescription,
+};
+use aws_sdk_dynamodb::types::SdkError;
+use aws_sdk_dynamodb::Client;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How often the index is counted while waiting for it to converge.
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A secondary index whose key attributes are being backfilled.
+pub struct Index {
+    pub name: String,
+    /// The index's key attributes that aren't also the table's, which items
+    /// may therefore be missing, with the types the table defines for them.
+    pub keys: Vec<(String, ScalarAttributeType)>,
+}
+
+/// What writing an item would do to the index.
+pub enum Entry<'a> {
+    /// The item would have all the index's key attributes.
+    Indexed,
+    /// The item would still be missing this key attribute, so it would stay
+    /// out of the index.
+    Missing(&'a str),
+    /// The item would have this key attribute with this type, rather than the
+    /// one the table defines, which DynamoDB rejects.
+    Mistyped(&'a str, &'static str, &'a str),
+}
+
+impl Index {
+    /// Finds the global or local secondary index called `name`, or returns the
+    /// names of the table's indexes.
+    pub fn find(description: &TableDescription, name: &str) -> Result<Self, Vec<String>> {
+        let global = description
+            .global_secondary_indexes
+            .iter()
+            .flatten()
+            .map(|index| (index.index_name.as_deref(), index.key_schema.as_deref()));
+        let local = description
+            .local_secondary_indexes
+            .iter()
+            .flatten()
+            .map(|index| (index.index_name.as_deref(), index.key_schema.as_deref()));
+        let indexes = global.chain(local);
+        let key_schema = match indexes
+            .clone()
+            .find(|(index_name, _)| *index_name == Some(name))
+        {
+            Some((_, key_schema)) => key_schema.unwrap_or_default(),
+            None => {
+                return Err(indexes
+                    .filter_map(|(index_name, _)| index_name.map(str::to_string))
+                    .collect());
+            }
+        };
+        let table_keys = crate::key_names(description);
+        let keys = key_schema
+            .iter()
+            .filter_map(|k: &KeySchemaElement| k.attribute_name.clone())
+            .filter(|attribute| !table_keys.contains(attribute))
+            .map(|attribute| {
+                let kind = description
+                    .attribute_definitions
+                    .iter()
+                    .flatten()
+                    .find(|a| a.attribute_name.as_deref() == Some(attribute.as_str()))
+                    .and_then(|a| a.attribute_type.clone())
+                    .unwrap_or(ScalarAttributeType::S);
+                (attribute, kind)
+            })
+            .collect();
+        Ok(Self {
+            name: name.to_string(),
+            keys,
+        })
+    }
+
+    /// A filter expression (with its names) matching items missing any of the
+    /// index's key attributes.
+    pub fn missing_expression(&self) -> (String, HashMap<String, String>) {
+        let names = self
+            .keys
+            .iter()
+            .enumerate()
+            .map(|(i, (attribute, _))| (format!("#bi{}", i), attribute.clone()))
+            .collect::<HashMap<_, _>>();
+        let expression = (0..self.keys.len())
+            .map(|i| format!("attribute_not_exists(#bi{})", i))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        (expression, names)
+    }
+
+    /// What writing `item` would do to the index.
+    pub fn entry(&self, item: &HashMap<String, AttributeValue>) -> Entry<'_> {
+        for (attribute, kind) in &self.keys {
+            let value = match item.get(attribute) {
+                Some(value) => value,
+                None => return Entry::Missing(attribute),
+            };
+            let type_name = crate::type_name(value);
+            if type_name != kind.as_str() {
+                return Entry::Mistyped(attribute, type_name, kind.as_str());
+            }
+        }
+        Entry::Indexed
+    }
+}
+
+/// Counts the items in `index` of `table`, a page at a time (with eventually
+/// consistent reads, since global secondary indexes don't offer others).
+pub async fn count(
+    client: &Client,
+    table: &str,
+    index: &str,
+    reads: &Reads,
+) -> Result<u64, SdkError<ScanError>> {
+    let mut count = 0;
+    let mut last_evaluated_key = None;
+    loop {
+        reads.wait().await;
+        let request = client
+            .scan()
+            .table_name(table)
+            .index_name(index)
+            .select(Select::Count)
+            .return_consumed_capacity(ReturnConsumedCapacity::Total)
+            .set_exclusive_start_key(last_evaluated_key);
+        let output = reads.retry.run(|| request.clone().send()).await?;
+        reads.consumed(output.consumed_capacity.as_ref());
+        count += output.count.max(0) as u64;
+        last_evaluated_key = output.last_evaluated_key;
+        if last_evaluated_key.is_none() {
+            return Ok(count);
+        }
+    }
+}
+
+/// Counts the items in `index` until there are at least `expected`, or
+/// `timeout` has passed, returning the last count.
+pub async fn converge(
+    client: &Client,
+    table: &str,
+    index: &str,
+    expected: u64,
+    timeout: Duration,
+    reads: &Reads,
+) -> Result<u64, SdkError<ScanError>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let count = count(client, table, index, reads).await?;
+        if count >= expected || Instant::now() + POLL_INTERVAL > deadline {
+            return Ok(count);
+        }
+        log!(
+            "index {} has {} of {} item(s), waiting for it to catch up...",
+            index,
+            count,
+            expected
+        );
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
@@ -0,0 +1,65 @@
+use aws_sdk_dynamodb::error::{CreateBackupError, DescribeBackupError};
+use aws_sdk_dynamodb::model::BackupStatus;
+use aws_sdk_dynamodb::types::SdkError;
+use aws_sdk_dynamodb::Client;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+/// How often to check whether a backup is available.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub enum BackupError {
+    Create(Box<SdkError<CreateBackupError>>),
+    Describe(Box<SdkError<DescribeBackupError>>),
+    /// The backup was deleted before it became available.
+    Deleted(String),
+}
+
+impl Display for BackupError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackupError::Create(e) => f.write_fmt(format_args!("error creating backup: {}", e)),
+            BackupError::Describe(e) => f.write_fmt(format_args!("error checking backup: {}", e)),
+            BackupError::Deleted(arn) => f.write_fmt(format_args!(
+                "backup {} was deleted before it became available",
+                arn
+            )),
+        }
+    }
+}
+
+/// Creates an on-demand backup of `table` named `name`, waiting until it's
+/// available, and returns its ARN.
+pub async fn backup(client: &Client, table: &str, name: &str) -> Result<String, BackupError> {
+    let details = client
+        .create_backup()
+        .table_name(table)
+        .backup_name(name)
+        .send()
+        .await
+        .map_err(|e| BackupError::Create(Box::new(e)))?
+        .backup_details;
+    let arn = details
+        .as_ref()
+        .and_then(|d| d.backup_arn.clone())
+        .unwrap_or_default();
+    let mut status = details.and_then(|d| d.backup_status);
+    loop {
+        match status {
+            Some(BackupStatus::Available) => return Ok(arn),
+            Some(BackupStatus::Deleted) => return Err(BackupError::Deleted(arn)),
+            _ => log!("waiting for backup {}...", arn),
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+        status = client
+            .describe_backup()
+            .backup_arn(&arn)
+            .send()
+            .await
+            .map_err(|e| BackupError::Describe(Box::new(e)))?
+            .backup_description
+            .and_then(|d| d.backup_details)
+            .and_then(|d| d.backup_status);
+    }
+}
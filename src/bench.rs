@@ -0,0 +1,243 @@
+use crate::throttle::is_throttling;
+use aws_sdk_dynamodb::error::{PutItemError, ScanError};
+use aws_sdk_dynamodb::model::{AttributeValue, ReturnConsumedCapacity, ScalarAttributeType};
+use aws_sdk_dynamodb::types::{Blob, SdkError};
+use aws_sdk_dynamodb::Client;
+use rand::Rng;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+/// A level is only worth its extra concurrency if it beats every lower level
+/// by this fraction.
+const MIN_GAIN: f64 = 0.1;
+
+#[derive(Debug)]
+pub enum BenchError {
+    Scan(Box<SdkError<ScanError>>),
+    Write(Box<SdkError<PutItemError>>),
+}
+
+impl Display for BenchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BenchError::Scan(e) => f.write_fmt(format_args!("error scanning: {}", e)),
+            BenchError::Write(e) => f.write_fmt(format_args!("error writing: {}", e)),
+        }
+    }
+}
+
+/// What one level of concurrency achieved.
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub concurrency: u32,
+    /// Items scanned, or writes made.
+    pub items: u64,
+    /// Capacity units consumed.
+    pub units: f64,
+    /// Requests rejected due to throughput limits.
+    pub throttled: u64,
+    pub elapsed: Duration,
+}
+
+impl Step {
+    pub fn items_per_sec(&self) -> f64 {
+        self.items as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    pub fn units_per_sec(&self) -> f64 {
+        self.units / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Tallies shared by the concurrent workers of a step.
+#[derive(Default)]
+struct Counters {
+    items: AtomicU64,
+    /// Capacity units, in thousandths.
+    units: AtomicU64,
+    throttled: AtomicU64,
+}
+
+impl Counters {
+    fn add(&self, items: u64, units: Option<f64>) {
+        self.items.fetch_add(items, Ordering::Relaxed);
+        self.units
+            .fetch_add((units.unwrap_or(0.0) * 1000.0) as u64, Ordering::Relaxed);
+    }
+
+    fn step(&self, concurrency: u32, elapsed: Duration) -> Step {
+        Step {
+            concurrency,
+            items: self.items.load(Ordering::Relaxed),
+            units: self.units.load(Ordering::Relaxed) as f64 / 1000.0,
+            throttled: self.throttled.load(Ordering::Relaxed),
+            elapsed,
+        }
+    }
+}
+
+/// Concurrency levels to try: powers of two up to `max`, and `max` itself.
+pub fn levels(max: u32) -> Vec<u32> {
+    let mut levels = std::iter::successors(Some(1u32), |&n| n.checked_mul(2))
+        .take_while(|&n| n < max)
+        .collect::<Vec<_>>();
+    levels.push(max.max(1));
+    levels
+}
+
+/// The level to recommend: the lowest (before any throttling) that gets within
+/// [`MIN_GAIN`] of the most throughput.
+pub fn recommend(steps: &[Step]) -> Option<&Step> {
+    let unthrottled = steps
+        .iter()
+        .take_while(|step| step.throttled == 0)
+        .collect::<Vec<_>>();
+    let best = unthrottled
+        .iter()
+        .map(|step| step.items_per_sec())
+        .fold(0.0, f64::max);
+    unthrottled
+        .into_iter()
+        .find(|step| step.items_per_sec() >= best * (1.0 - MIN_GAIN))
+        .or_else(|| steps.first())
+}
+
+/// Runs `segments` concurrent segmented scans of `table` for `duration`,
+/// starting over whenever a segment is finished.
+pub async fn scans(
+    client: &Client,
+    table: &str,
+    segments: u32,
+    consistent_read: bool,
+    duration: Duration,
+) -> Result<Step, BenchError> {
+    let counters = Arc::new(Counters::default());
+    let start = Instant::now();
+    let deadline = start + duration;
+    let workers = (0..segments)
+        .map(|segment| {
+            let client = client.clone();
+            let table = table.to_string();
+            let counters = Arc::clone(&counters);
+            tokio::spawn(async move {
+                let mut last_evaluated_key = None;
+                let mut backoff = Duration::from_millis(100);
+                while Instant::now() < deadline {
+                    let result = client
+                        .scan()
+                        .table_name(&table)
+                        .set_segment((segments > 1).then_some(segment as i32))
+                        .set_total_segments((segments > 1).then_some(segments as i32))
+                        .consistent_read(consistent_read)
+                        .return_consumed_capacity(ReturnConsumedCapacity::Total)
+                        .set_exclusive_start_key(last_evaluated_key.take())
+                        .send()
+                        .await;
+                    match result {
+                        Ok(output) => {
+                            counters.add(
+                                output.items.map_or(0, |items| items.len() as u64),
+                                output.consumed_capacity.and_then(|c| c.capacity_units),
+                            );
+                            last_evaluated_key = output.last_evaluated_key;
+                            backoff = Duration::from_millis(100);
+                        }
+                        Err(e) if is_throttling(&e) => {
+                            counters.throttled.fetch_add(1, Ordering::Relaxed);
+                            tokio::time::sleep(backoff).await;
+                            backoff = (backoff * 2).min(MAX_BACKOFF);
+                        }
+                        Err(e) => return Err(BenchError::Scan(Box::new(e))),
+                    }
+                }
+                Ok(())
+            })
+        })
+        .collect::<Vec<_>>();
+    for worker in workers {
+        worker.await.expect("scan worker panicked")?;
+    }
+    Ok(counters.step(segments, start.elapsed()))
+}
+
+/// Keeps `concurrency` conditional writes to `table` in flight for
+/// `duration`. Their condition can never hold, so they consume write capacity
+/// without writing anything.
+pub async fn writes(
+    client: &Client,
+    table: &str,
+    key_types: &[(String, ScalarAttributeType)],
+    concurrency: u32,
+    duration: Duration,
+) -> Result<Step, BenchError> {
+    let counters = Arc::new(Counters::default());
+    let start = Instant::now();
+    let deadline = start + duration;
+    let workers = (0..concurrency)
+        .map(|_| {
+            let client = client.clone();
+            let table = table.to_string();
+            let key_types = key_types.to_vec();
+            let counters = Arc::clone(&counters);
+            tokio::spawn(async move {
+                let mut backoff = Duration::from_millis(100);
+                while Instant::now() < deadline {
+                    // Random keys spread the writes over partitions.
+                    let item = random_key(&key_types);
+                    let result = client
+                        .put_item()
+                        .table_name(&table)
+                        .set_item(Some(item))
+                        .condition_expression("attribute_exists(#k) AND attribute_not_exists(#k)")
+                        .expression_attribute_names("#k", &key_types[0].0)
+                        .send()
+                        .await;
+                    match result {
+                        // A failed condition still costs one unit (for a
+                        // small item).
+                        Err(SdkError::ServiceError { err, .. })
+                            if err.is_conditional_check_failed_exception() =>
+                        {
+                            counters.add(1, Some(1.0));
+                            backoff = Duration::from_millis(100);
+                        }
+                        Ok(_) => counters.add(1, Some(1.0)),
+                        Err(e) if is_throttling(&e) => {
+                            counters.throttled.fetch_add(1, Ordering::Relaxed);
+                            tokio::time::sleep(backoff).await;
+                            backoff = (backoff * 2).min(MAX_BACKOFF);
+                        }
+                        Err(e) => return Err(BenchError::Write(Box::new(e))),
+                    }
+                }
+                Ok(())
+            })
+        })
+        .collect::<Vec<_>>();
+    for worker in workers {
+        worker.await.expect("write worker panicked")?;
+    }
+    Ok(counters.step(concurrency, start.elapsed()))
+}
+
+fn random_key(key_types: &[(String, ScalarAttributeType)]) -> HashMap<String, AttributeValue> {
+    let mut rng = rand::thread_rng();
+    key_types
+        .iter()
+        .map(|(name, kind)| {
+            let value = match kind {
+                ScalarAttributeType::N => AttributeValue::N(rng.gen::<u32>().to_string()),
+                ScalarAttributeType::B => AttributeValue::B(Blob::new(rng.gen::<[u8; 16]>())),
+                _ => AttributeValue::S(format!(
+                    "dynamodb_bulk_edit#bench#{:016x}",
+                    rng.gen::<u64>()
+                )),
+            };
+            (name.clone(), value)
+        })
+        .collect()
+}
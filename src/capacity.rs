@@ -0,0 +1,223 @@
+use aws_sdk_dynamodb::error::{DescribeTableError, UpdateTableError};
+use aws_sdk_dynamodb::model::{
+    BillingMode, GlobalSecondaryIndexDescription, GlobalSecondaryIndexUpdate, IndexStatus,
+    ProjectionType, ProvisionedThroughput, TableDescription, TableStatus,
+    UpdateGlobalSecondaryIndexAction,
+};
+use aws_sdk_dynamodb::types::SdkError;
+use aws_sdk_dynamodb::Client;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+/// Provisioned throughput of the table and any GSIs that were raised, so they
+/// can be put back once the write phase is over.
+pub struct Boost {
+    table: String,
+    original_table: Option<(i64, i64)>,
+    original_indexes: Vec<(String, i64, i64)>,
+}
+
+#[derive(Debug)]
+pub enum BoostError {
+    Describe(SdkError<DescribeTableError>),
+    Update(SdkError<UpdateTableError>),
+}
+
+impl Display for BoostError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BoostError::Describe(e) => f.write_fmt(format_args!("error describing table: {}", e)),
+            BoostError::Update(e) => f.write_fmt(format_args!("error updating table: {}", e)),
+        }
+    }
+}
+
+/// Raises the write capacity of the table, and of every GSI that a write to
+/// `attributes` would propagate to, to at least `wcu`.
+///
+/// `attributes` are the root-level attribute names that may change, or `None`
+/// if any of them may (e.g. because of a non-root rule).
+///
+/// Returns `None` if nothing needed raising (on-demand table, or capacity
+/// already high enough).
+pub async fn boost(
+    client: &Client,
+    table: &str,
+    wcu: i64,
+    attributes: Option<&[&str]>,
+) -> Result<Option<Boost>, BoostError> {
+    let description = describe(client, table).await?;
+
+    if matches!(
+        description
+            .billing_mode_summary
+            .as_ref()
+            .and_then(|s| s.billing_mode.as_ref()),
+        Some(BillingMode::PayPerRequest)
+    ) {
+        log!("table is on-demand, not boosting write capacity.");
+        return Ok(None);
+    }
+
+    let mut boost = Boost {
+        table: table.to_string(),
+        original_table: None,
+        original_indexes: Vec::new(),
+    };
+
+    let mut request = client.update_table().table_name(table);
+
+    if let Some((rcu, original)) = description
+        .provisioned_throughput
+        .as_ref()
+        .and_then(|t| Some((t.read_capacity_units?, t.write_capacity_units?)))
+    {
+        if original < wcu {
+            log!(
+                "boosting table write capacity from {} to {}...",
+                original,
+                wcu
+            );
+            request = request.provisioned_throughput(throughput(rcu, wcu));
+            boost.original_table = Some((rcu, original));
+        }
+    }
+
+    for index in description.global_secondary_indexes.unwrap_or_default() {
+        if !index_affected(&index, attributes) {
+            continue;
+        }
+        let name = match index.index_name {
+            Some(name) => name,
+            None => continue,
+        };
+        if let Some((rcu, original)) = index
+            .provisioned_throughput
+            .as_ref()
+            .and_then(|t| Some((t.read_capacity_units?, t.write_capacity_units?)))
+        {
+            if original < wcu {
+                log!(
+                    "boosting index '{}' write capacity from {} to {}...",
+                    name,
+                    original,
+                    wcu
+                );
+                request = request.global_secondary_index_updates(index_update(&name, rcu, wcu));
+                boost.original_indexes.push((name, rcu, original));
+            }
+        }
+    }
+
+    if boost.original_table.is_none() && boost.original_indexes.is_empty() {
+        return Ok(None);
+    }
+
+    request.send().await.map_err(BoostError::Update)?;
+    wait_until_active(client, table).await?;
+
+    Ok(Some(boost))
+}
+
+impl Boost {
+    /// Puts the write capacity back to what it was before [`boost`].
+    pub async fn restore(&self, client: &Client) -> Result<(), BoostError> {
+        let mut request = client.update_table().table_name(&self.table);
+        if let Some((rcu, wcu)) = self.original_table {
+            request = request.provisioned_throughput(throughput(rcu, wcu));
+        }
+        for (name, rcu, wcu) in &self.original_indexes {
+            request = request.global_secondary_index_updates(index_update(name, *rcu, *wcu));
+        }
+        request.send().await.map_err(BoostError::Update)?;
+        Ok(())
+    }
+}
+
+impl Display for Boost {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some((_, wcu)) = self.original_table {
+            f.write_fmt(format_args!("table '{}' to {} WCU", self.table, wcu))?;
+        }
+        for (i, (name, _, wcu)) in self.original_indexes.iter().enumerate() {
+            if i > 0 || self.original_table.is_some() {
+                f.write_str(", ")?;
+            }
+            f.write_fmt(format_args!("index '{}' to {} WCU", name, wcu))?;
+        }
+        Ok(())
+    }
+}
+
+fn index_affected(index: &GlobalSecondaryIndexDescription, attributes: Option<&[&str]>) -> bool {
+    let attributes = match attributes {
+        Some(attributes) => attributes,
+        None => return true,
+    };
+    let projection = index.projection.as_ref();
+    if matches!(
+        projection.and_then(|p| p.projection_type.as_ref()),
+        Some(ProjectionType::All)
+    ) {
+        return true;
+    }
+    let keys = index
+        .key_schema
+        .iter()
+        .flatten()
+        .filter_map(|k| k.attribute_name.as_deref());
+    let non_keys = projection
+        .and_then(|p| p.non_key_attributes.as_ref())
+        .into_iter()
+        .flatten()
+        .map(String::as_str);
+    keys.chain(non_keys).any(|a| attributes.contains(&a))
+}
+
+fn throughput(rcu: i64, wcu: i64) -> ProvisionedThroughput {
+    ProvisionedThroughput::builder()
+        .read_capacity_units(rcu)
+        .write_capacity_units(wcu)
+        .build()
+}
+
+fn index_update(name: &str, rcu: i64, wcu: i64) -> GlobalSecondaryIndexUpdate {
+    GlobalSecondaryIndexUpdate::builder()
+        .update(
+            UpdateGlobalSecondaryIndexAction::builder()
+                .index_name(name)
+                .provisioned_throughput(throughput(rcu, wcu))
+                .build(),
+        )
+        .build()
+}
+
+async fn describe(client: &Client, table: &str) -> Result<TableDescription, BoostError> {
+    client
+        .describe_table()
+        .table_name(table)
+        .send()
+        .await
+        .map_err(BoostError::Describe)
+        .map(|output| {
+            output
+                .table
+                .unwrap_or_else(|| TableDescription::builder().build())
+        })
+}
+
+async fn wait_until_active(client: &Client, table: &str) -> Result<(), BoostError> {
+    loop {
+        let description = describe(client, table).await?;
+        let table_active = matches!(description.table_status, Some(TableStatus::Active));
+        let indexes_active = description
+            .global_secondary_indexes
+            .iter()
+            .flatten()
+            .all(|i| matches!(i.index_status, Some(IndexStatus::Active)));
+        if table_active && indexes_active {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    }
+}
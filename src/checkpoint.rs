@@ -0,0 +1,216 @@
+use crate::json::{item_from_json, item_to_json};
+use aws_sdk_dynamodb::model::AttributeValue;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// A run's progress, journaled to a local file as newline-delimited JSON, so
+/// that `--resume` can continue it after an interruption or error.
+///
+/// The first line holds the command line arguments, then each scan page (with
+/// its items and where the segment got to) and each written item's key is
+/// appended as it happens, so nothing is ever rewritten.
+pub struct Checkpoint {
+    file: Mutex<File>,
+    /// Where each segment's scan got to, and what it returned, by a previous run.
+    segments: HashMap<u32, Segment>,
+    /// Keys (as [`id`]s) of the items that a previous run wrote.
+    written: HashSet<String>,
+}
+
+/// What a previous run scanned of one segment.
+#[derive(Debug, Clone, Default)]
+pub struct Segment {
+    pub items: Vec<HashMap<String, AttributeValue>>,
+    pub last_evaluated_key: Option<HashMap<String, AttributeValue>>,
+    /// Whether the segment was scanned to the end.
+    pub finished: bool,
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    /// The file exists, with a run that hasn't finished.
+    Unfinished,
+    /// The file's run finished, so there is nothing to resume.
+    Finished,
+    /// The file's run had different command line arguments.
+    Args(String),
+    /// This line (from 1) isn't a checkpoint record.
+    Malformed(usize),
+}
+
+impl Display for CheckpointError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckpointError::Io(e) => f.write_fmt(format_args!("error with checkpoint: {}", e)),
+            CheckpointError::Unfinished => f.write_str(
+                "the checkpoint file has an unfinished run (pass --resume to continue it)",
+            ),
+            CheckpointError::Finished => {
+                f.write_str("the checkpoint file's run already finished, so there is nothing to resume")
+            }
+            CheckpointError::Args(args) => f.write_fmt(format_args!(
+                "the checkpoint file has a run with different arguments:\n  {}\nrepeat them to resume it.",
+                args
+            )),
+            CheckpointError::Malformed(line) => f.write_fmt(format_args!(
+                "checkpoint file line {} is malformed",
+                line
+            )),
+        }
+    }
+}
+
+/// Identifies a key, for comparing keys.
+pub fn id(key: &HashMap<String, AttributeValue>) -> String {
+    item_to_json(key).to_string()
+}
+
+impl Checkpoint {
+    /// Starts a new checkpoint file for a run with `args`, replacing any
+    /// finished one.
+    pub fn create(path: &Path, args: &str) -> Result<Self, CheckpointError> {
+        if path.exists() && !read(path)?.done {
+            return Err(CheckpointError::Unfinished);
+        }
+        let file = File::create(path).map_err(CheckpointError::Io)?;
+        let checkpoint = Self {
+            file: Mutex::new(file),
+            segments: HashMap::new(),
+            written: HashSet::new(),
+        };
+        checkpoint
+            .append(&json!({ "args": args }))
+            .map_err(CheckpointError::Io)?;
+        Ok(checkpoint)
+    }
+
+    /// Continues the run in a checkpoint file, which must have been for `args`.
+    pub fn resume(path: &Path, args: &str) -> Result<Self, CheckpointError> {
+        let journal = read(path)?;
+        if journal.done {
+            return Err(CheckpointError::Finished);
+        }
+        if journal.args != args {
+            return Err(CheckpointError::Args(journal.args));
+        }
+        let file = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map_err(CheckpointError::Io)?;
+        Ok(Self {
+            file: Mutex::new(file),
+            segments: journal.segments,
+            written: journal.written,
+        })
+    }
+
+    /// What a previous run scanned of `segment` (0 without segments).
+    pub fn segment(&self, segment: u32) -> Segment {
+        self.segments.get(&segment).cloned().unwrap_or_default()
+    }
+
+    /// Whether a previous run wrote the item with `key`.
+    pub fn was_written(&self, key: &HashMap<String, AttributeValue>) -> bool {
+        self.written.contains(&id(key))
+    }
+
+    /// How many items a previous run wrote.
+    pub fn written_count(&self) -> usize {
+        self.written.len()
+    }
+
+    /// Records a scan page of `segment` (0 without segments).
+    pub fn page(
+        &self,
+        segment: u32,
+        items: &[HashMap<String, AttributeValue>],
+        last_evaluated_key: Option<&HashMap<String, AttributeValue>>,
+    ) -> io::Result<()> {
+        self.append(&json!({
+            "segment": segment,
+            "items": items.iter().map(item_to_json).collect::<Vec<_>>(),
+            "last_evaluated_key": last_evaluated_key.map(item_to_json),
+        }))
+    }
+
+    /// Records that the item with `key` was written.
+    pub fn written(&self, key: &HashMap<String, AttributeValue>) -> io::Result<()> {
+        self.append(&json!({ "written": item_to_json(key) }))
+    }
+
+    /// Records that the run finished, so the file can be replaced.
+    pub fn done(&self) -> io::Result<()> {
+        self.append(&json!({ "done": true }))
+    }
+
+    fn append(&self, record: &Value) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+        writeln!(file, "{}", record)?;
+        file.flush()
+    }
+}
+
+/// What a checkpoint file says.
+struct Journal {
+    args: String,
+    segments: HashMap<u32, Segment>,
+    written: HashSet<String>,
+    done: bool,
+}
+
+fn read(path: &Path) -> Result<Journal, CheckpointError> {
+    let file = File::open(path).map_err(CheckpointError::Io)?;
+    let mut journal = Journal {
+        args: String::new(),
+        segments: HashMap::new(),
+        written: HashSet::new(),
+        done: false,
+    };
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(CheckpointError::Io)?;
+        let malformed = || CheckpointError::Malformed(i + 1);
+        // An interrupted append leaves a partial last line, which is ignored.
+        let record = match serde_json::from_str::<Value>(&line) {
+            Ok(record) => record,
+            Err(e) if e.is_eof() => break,
+            Err(_) => return Err(malformed()),
+        };
+        if i == 0 {
+            journal.args = record["args"].as_str().ok_or_else(malformed)?.to_string();
+        } else if let Some(key) = record.get("written") {
+            let key = item_from_json(key).ok_or_else(malformed)?;
+            journal.written.insert(id(&key));
+        } else if let Some(segment) = record.get("segment") {
+            let segment = segment
+                .as_u64()
+                .and_then(|s| u32::try_from(s).ok())
+                .ok_or_else(malformed)?;
+            let items = record["items"]
+                .as_array()
+                .ok_or_else(malformed)?
+                .iter()
+                .map(item_from_json)
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(malformed)?;
+            let last_evaluated_key = match &record["last_evaluated_key"] {
+                Value::Null => None,
+                key => Some(item_from_json(key).ok_or_else(malformed)?),
+            };
+            let saved = journal.segments.entry(segment).or_default();
+            saved.items.extend(items);
+            saved.finished = last_evaluated_key.is_none();
+            saved.last_evaluated_key = last_evaluated_key;
+        } else if record.get("done").is_some() {
+            journal.done = true;
+        } else {
+            return Err(malformed());
+        }
+    }
+    Ok(journal)
+}
@@ -0,0 +1,53 @@
+use crate::audit::Sink;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::{fs, io};
+
+/// Contents of a `--config` file, which supplements the command line.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Same syntax as `--rename`, applied after any given on the command line.
+    #[serde(default)]
+    pub rename: Vec<String>,
+    /// Attribute paths, like `address.zip`, that `--drop-unknown` keeps.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+    /// Where to keep a record of every item written (in addition to each
+    /// other), like `audit = [{ file = "audit.jsonl" }, { s3 = "s3://bucket/audit/" }]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub audit: Vec<Sink>,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(e) => f.write_fmt(format_args!("error reading config: {}", e)),
+            ConfigError::Parse(e) => f.write_fmt(format_args!("error parsing config: {}", e)),
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        toml::from_str(&contents).map_err(ConfigError::Parse)
+    }
+
+    /// Writes the config, preceded by `comment` (one `#` line per line).
+    pub fn save(&self, path: &Path, comment: &str) -> io::Result<()> {
+        let mut contents = comment
+            .lines()
+            .map(|line| format!("# {}\n", line))
+            .collect::<String>();
+        contents.push_str(&toml::to_string(self).map_err(io::Error::other)?);
+        fs::write(path, contents)
+    }
+}
@@ -0,0 +1,211 @@
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static LEDGER: OnceLock<Ledger> = OnceLock::new();
+
+/// A tag attributing the run's consumed capacity, like `migration=rename-ownerId`.
+#[derive(Debug, Clone)]
+pub struct CostTag {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Debug)]
+pub struct CostTagParseError(String);
+
+impl Display for CostTagParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "cost tag '{}' is invalid (expected e.g. migration=rename-ownerId)",
+            self.0
+        ))
+    }
+}
+
+impl FromStr for CostTag {
+    type Err = CostTagParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((key, value)) if !key.is_empty() => Ok(Self {
+                key: key.to_string(),
+                value: value.to_string(),
+            }),
+            _ => Err(CostTagParseError(s.to_string())),
+        }
+    }
+}
+
+/// How to write the cost report.
+#[derive(Debug, Clone, Copy)]
+pub enum CostFormat {
+    /// A header, then one row per hour: `hour,table,rcu,wcu`, then a column
+    /// per tag.
+    Csv,
+    /// One JSON object per hour, like
+    /// `{"hour": "...", "table": "...", "rcu": 1.5, "wcu": 2, "tags": {...}}`.
+    Json,
+}
+
+#[derive(Debug)]
+pub struct CostFormatParseError(String);
+
+impl Display for CostFormatParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "cost format '{}' is invalid (expected csv or json)",
+            self.0
+        ))
+    }
+}
+
+impl FromStr for CostFormat {
+    type Err = CostFormatParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(CostFormatParseError(s.to_string())),
+        }
+    }
+}
+
+/// The capacity the run consumed on its table, by the hour it was consumed
+/// in, as reported by DynamoDB.
+pub struct Ledger {
+    table: String,
+    tags: Vec<CostTag>,
+    report: Option<(PathBuf, CostFormat)>,
+    /// Read and write capacity units, by the epoch second the hour starts.
+    hours: Mutex<BTreeMap<i64, (f64, f64)>>,
+}
+
+impl Ledger {
+    pub fn new(table: String, tags: Vec<CostTag>, report: Option<(PathBuf, CostFormat)>) -> Self {
+        Self {
+            table,
+            tags,
+            report,
+            hours: Mutex::default(),
+        }
+    }
+}
+
+/// Starts recording consumed capacity, if there are tags to attribute it to
+/// or a report to write.
+pub fn init(ledger: Ledger) {
+    if !ledger.tags.is_empty() || ledger.report.is_some() {
+        let _ = LEDGER.set(ledger);
+    }
+}
+
+/// Records read capacity units consumed now.
+pub fn read(units: f64) {
+    consumed(units, 0.0);
+}
+
+/// Records write capacity units consumed now.
+pub fn write(units: f64) {
+    consumed(0.0, units);
+}
+
+fn consumed(rcu: f64, wcu: f64) {
+    if let Some(ledger) = LEDGER.get() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        let mut hours = ledger.hours.lock().unwrap();
+        let hour = hours.entry(now - now % 3600).or_default();
+        hour.0 += rcu;
+        hour.1 += wcu;
+    }
+}
+
+/// Logs the capacity the run consumed, and writes the report, if any.
+/// Failing to write it is reported, but not fatal.
+pub fn report() {
+    let ledger = match LEDGER.get() {
+        Some(ledger) => ledger,
+        None => return,
+    };
+    let hours = ledger.hours.lock().unwrap();
+    let (rcu, wcu) = hours
+        .values()
+        .fold((0.0, 0.0), |(rcu, wcu), hour| (rcu + hour.0, wcu + hour.1));
+    let tags = ledger
+        .tags
+        .iter()
+        .map(|tag| format!("{}={}", tag.key, tag.value))
+        .collect::<Vec<_>>();
+    if tags.is_empty() {
+        log!("consumed {:.1} RCU and {:.1} WCU.", rcu, wcu);
+    } else {
+        log!(
+            "consumed {:.1} RCU and {:.1} WCU, tagged {}.",
+            rcu,
+            wcu,
+            tags.join(",")
+        );
+    }
+    let (path, format) = match &ledger.report {
+        Some(report) => report,
+        None => return,
+    };
+    let hour_string = |hour: i64| {
+        aws_smithy_types::DateTime::from_secs(hour)
+            .fmt(aws_smithy_types::date_time::Format::DateTime)
+            .unwrap_or_default()
+    };
+    let written = match format {
+        CostFormat::Csv => csv::Writer::from_path(path).and_then(|mut writer| {
+            let mut header = vec!["hour", "table", "rcu", "wcu"];
+            header.extend(ledger.tags.iter().map(|tag| tag.key.as_str()));
+            writer.write_record(&header)?;
+            for (hour, (rcu, wcu)) in hours.iter() {
+                let mut row = vec![
+                    hour_string(*hour),
+                    ledger.table.clone(),
+                    rcu.to_string(),
+                    wcu.to_string(),
+                ];
+                row.extend(ledger.tags.iter().map(|tag| tag.value.clone()));
+                writer.write_record(&row)?;
+            }
+            writer.flush().map_err(csv::Error::from)
+        }),
+        CostFormat::Json => {
+            let tags = ledger
+                .tags
+                .iter()
+                .map(|tag| (tag.key.clone(), Value::String(tag.value.clone())))
+                .collect::<Map<_, _>>();
+            let lines = hours
+                .iter()
+                .map(|(hour, (rcu, wcu))| {
+                    format!(
+                        "{}\n",
+                        json!({
+                            "hour": hour_string(*hour),
+                            "table": ledger.table,
+                            "rcu": rcu,
+                            "wcu": wcu,
+                            "tags": tags,
+                        })
+                    )
+                })
+                .collect::<String>();
+            std::fs::write(path, lines).map_err(csv::Error::from)
+        }
+    };
+    match written {
+        Ok(()) => log!("wrote the cost report to {}.", path.display()),
+        Err(e) => log!("error writing cost report: {}", e),
+    }
+}
@@ -0,0 +1,186 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// An exact decimal number, as stored in a DynamoDB `N`, so that arithmetic on
+/// it never picks up floating-point artifacts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decimal {
+    negative: bool,
+    /// Significant digits, most significant first, without leading zeros.
+    digits: Vec<u8>,
+    /// The value is `digits * 10^-scale`.
+    scale: i64,
+}
+
+#[derive(Debug)]
+pub struct DecimalParseError(String);
+
+impl Display for DecimalParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("'{}' is not a number", self.0))
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = DecimalParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || DecimalParseError(s.to_string());
+        let (negative, unsigned) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (mantissa, exponent) = match unsigned.split_once(['e', 'E']) {
+            Some((mantissa, exponent)) => {
+                (mantissa, exponent.parse::<i64>().map_err(|_| invalid())?)
+            }
+            None => (unsigned, 0),
+        };
+        let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if integer.is_empty() && fraction.is_empty()
+            || !integer
+                .chars()
+                .chain(fraction.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        let digits = integer
+            .bytes()
+            .chain(fraction.bytes())
+            .map(|b| b - b'0')
+            .collect();
+        Ok(Self {
+            negative,
+            digits,
+            scale: fraction.len() as i64 - exponent,
+        }
+        .normalized())
+    }
+}
+
+impl Display for Decimal {
+    /// Formats without an exponent or insignificant zeros, like `-12.5`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.digits.is_empty() {
+            return f.write_str("0");
+        }
+        if self.negative {
+            f.write_str("-")?;
+        }
+        let digits = self
+            .digits
+            .iter()
+            .map(|d| char::from(b'0' + d))
+            .collect::<String>();
+        if self.scale <= 0 {
+            f.write_str(&digits)?;
+            f.write_str(&"0".repeat(-self.scale as usize))
+        } else if self.scale as usize >= digits.len() {
+            f.write_str("0.")?;
+            f.write_str(&"0".repeat(self.scale as usize - digits.len()))?;
+            f.write_str(&digits)
+        } else {
+            let (integer, fraction) = digits.split_at(digits.len() - self.scale as usize);
+            f.write_fmt(format_args!("{}.{}", integer, fraction))
+        }
+    }
+}
+
+impl Decimal {
+    /// Strips leading zeros, and trailing zeros by raising the exponent.
+    fn normalized(mut self) -> Self {
+        let leading = self.digits.iter().take_while(|&&d| d == 0).count();
+        self.digits.drain(..leading);
+        while self.digits.last() == Some(&0) {
+            self.digits.pop();
+            self.scale -= 1;
+        }
+        if self.digits.is_empty() {
+            self.negative = false;
+            self.scale = 0;
+        }
+        self
+    }
+
+    /// How many digits are significant, which determines how much space
+    /// DynamoDB needs for the number.
+    pub fn significant_digits(&self) -> usize {
+        self.digits.len()
+    }
+
+    /// Multiplies by `10^exponent`, which is always exact.
+    pub fn shift(mut self, exponent: i64) -> Self {
+        if !self.digits.is_empty() {
+            self.scale -= exponent;
+        }
+        self
+    }
+
+    /// Rounds to `places` decimal places, with ties going to the even
+    /// neighbor (so that rounding many values doesn't bias their sum).
+    pub fn round(mut self, places: i64) -> Self {
+        let dropped = self.scale - places;
+        if dropped <= 0 {
+            return self;
+        }
+        let kept = self.digits.len() as i64 - dropped;
+        // The first dropped digit, and whether any after it are non-zero.
+        let (first, rest) = match kept {
+            k if k < 0 => (0, true),
+            k => {
+                let k = k as usize;
+                (self.digits[k], self.digits[k + 1..].iter().any(|&d| d != 0))
+            }
+        };
+        self.digits.truncate(kept.max(0) as usize);
+        self.scale = places;
+        let odd = self.digits.last().is_some_and(|d| d % 2 == 1);
+        if first > 5 || first == 5 && (rest || odd) {
+            self.increment();
+        }
+        self.normalized()
+    }
+
+    /// Adds one unit in the last place.
+    fn increment(&mut self) {
+        for digit in self.digits.iter_mut().rev() {
+            if *digit == 9 {
+                *digit = 0;
+            } else {
+                *digit += 1;
+                return;
+            }
+        }
+        self.digits.insert(0, 1);
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let sign = |d: &Self| match (d.digits.is_empty(), d.negative) {
+            (true, _) => 0,
+            (false, true) => -1,
+            (false, false) => 1,
+        };
+        // Position of the most significant digit, for comparing magnitudes.
+        let magnitude = |d: &Self| d.digits.len() as i64 - d.scale;
+        let ordering = sign(self).cmp(&sign(other)).then_with(|| {
+            magnitude(self)
+                .cmp(&magnitude(other))
+                .then_with(|| self.digits.cmp(&other.digits))
+        });
+        if self.negative && other.negative {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
@@ -0,0 +1,113 @@
+use crate::render;
+use aws_sdk_dynamodb::model::AttributeValue;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// A difference between the old and new version of an item, at a dot-separated
+/// attribute path.
+pub enum Change<'a> {
+    Removed(String, &'a AttributeValue),
+    Added(String, &'a AttributeValue),
+    Changed(String, &'a AttributeValue, &'a AttributeValue),
+}
+
+/// Lists the differences between `old` and `new`, recursing into maps present
+/// in both, in attribute name order.
+pub fn changes<'a>(
+    old: &'a HashMap<String, AttributeValue>,
+    new: &'a HashMap<String, AttributeValue>,
+) -> Vec<Change<'a>> {
+    let mut ret = Vec::new();
+    changes_inner("", old, new, &mut ret);
+    ret
+}
+
+/// Like [`changes`], but leaving out changes at or under the `ignored`
+/// dot-separated paths.
+pub fn changes_ignoring<'a>(
+    old: &'a HashMap<String, AttributeValue>,
+    new: &'a HashMap<String, AttributeValue>,
+    ignored: &[String],
+) -> Vec<Change<'a>> {
+    let mut ret = changes(old, new);
+    ret.retain(|change| {
+        let path = change.path();
+        !ignored.iter().any(|ignored| {
+            path.strip_prefix(ignored.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+        })
+    });
+    ret
+}
+
+fn changes_inner<'a>(
+    path: &str,
+    old: &'a HashMap<String, AttributeValue>,
+    new: &'a HashMap<String, AttributeValue>,
+    ret: &mut Vec<Change<'a>>,
+) {
+    let mut keys = old.keys().chain(new.keys()).collect::<Vec<_>>();
+    keys.sort_unstable();
+    keys.dedup();
+
+    for key in keys {
+        let path = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", path, key)
+        };
+        match (old.get(key), new.get(key)) {
+            (Some(AttributeValue::M(old)), Some(AttributeValue::M(new))) => {
+                changes_inner(&path, old, new, ret)
+            }
+            (Some(old), Some(new)) if old != new => ret.push(Change::Changed(path, old, new)),
+            (Some(old), None) => ret.push(Change::Removed(path, old)),
+            (None, Some(new)) => ret.push(Change::Added(path, new)),
+            _ => {}
+        }
+    }
+}
+
+impl Change<'_> {
+    pub fn path(&self) -> &str {
+        match self {
+            Change::Removed(path, _) | Change::Added(path, _) | Change::Changed(path, ..) => path,
+        }
+    }
+
+    /// The change as JSON, like `{"path": "a.b", "old": {"S": "x"}, "new": null}`
+    /// (with values as [`render::value`] writes them).
+    pub fn to_json(&self) -> Value {
+        let (path, old, new) = match self {
+            Change::Removed(path, old) => (path, Some(*old), None),
+            Change::Added(path, new) => (path, None, Some(*new)),
+            Change::Changed(path, old, new) => (path, Some(*old), Some(*new)),
+        };
+        json!({
+            "path": path,
+            "old": old.map(render::value),
+            "new": new.map(render::value),
+        })
+    }
+}
+
+impl Display for Change<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Removed(path, value) => {
+                f.write_fmt(format_args!("- {}: {}", path, render::text(value)))
+            }
+            Change::Added(path, value) => {
+                f.write_fmt(format_args!("+ {}: {}", path, render::text(value)))
+            }
+            Change::Changed(path, old, new) => f.write_fmt(format_args!(
+                "- {}: {}\n+ {}: {}",
+                path,
+                render::text(old),
+                path,
+                render::text(new)
+            )),
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use crate::json::item_from_json;
+use aws_sdk_dynamodb::error::{DescribeExportError, ExportTableToPointInTimeError};
+use aws_sdk_dynamodb::model::{AttributeValue, ExportFormat, ExportStatus};
+use aws_sdk_dynamodb::types::SdkError;
+use aws_sdk_dynamodb::Client;
+use aws_smithy_types::DateTime;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::time::Duration;
+use std::{fs, io};
+use tokio::process::Command;
+
+/// How often to check whether an export has finished.
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Debug)]
+pub enum ExportError {
+    Start(Box<SdkError<ExportTableToPointInTimeError>>),
+    Describe(Box<SdkError<DescribeExportError>>),
+    /// The export failed, with DynamoDB's reason.
+    Failed(String),
+    Io(io::Error),
+    /// The `aws` CLI or `gzip` couldn't be run, or failed.
+    Command(String),
+    /// A line of an export data file isn't an item in DynamoDB JSON.
+    Malformed(String),
+}
+
+impl Display for ExportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportError::Start(e) => f.write_fmt(format_args!(
+                "error starting export (is point-in-time recovery enabled?): {}",
+                e
+            )),
+            ExportError::Describe(e) => f.write_fmt(format_args!("error checking export: {}", e)),
+            ExportError::Failed(reason) => f.write_fmt(format_args!("export failed: {}", reason)),
+            ExportError::Io(e) => f.write_fmt(format_args!("error reading export: {}", e)),
+            ExportError::Command(e) => f.write_fmt(format_args!("error downloading export: {}", e)),
+            ExportError::Malformed(file) => f.write_fmt(format_args!(
+                "export data file {} has a malformed item",
+                file
+            )),
+        }
+    }
+}
+
+/// Exports `table_arn` as of `time` (in epoch seconds) to S3 with point-in-time
+/// recovery, waiting until it finishes, and returns the S3 key of its
+/// manifest.
+pub async fn export(
+    client: &Client,
+    table_arn: &str,
+    time: i64,
+    bucket: &str,
+    prefix: Option<&str>,
+) -> Result<String, ExportError> {
+    let output = client
+        .export_table_to_point_in_time()
+        .table_arn(table_arn)
+        .export_time(DateTime::from_secs(time))
+        .s3_bucket(bucket)
+        .set_s3_prefix(prefix.map(String::from))
+        .export_format(ExportFormat::DynamodbJson)
+        .send()
+        .await
+        .map_err(|e| ExportError::Start(Box::new(e)))?;
+    let arn = output
+        .export_description
+        .and_then(|d| d.export_arn)
+        .unwrap_or_default();
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        let description = client
+            .describe_export()
+            .export_arn(&arn)
+            .send()
+            .await
+            .map_err(|e| ExportError::Describe(Box::new(e)))?
+            .export_description
+            .unwrap_or_else(|| aws_sdk_dynamodb::model::ExportDescription::builder().build());
+        match description.export_status {
+            Some(ExportStatus::Completed) => {
+                return Ok(description.export_manifest.unwrap_or_default())
+            }
+            Some(ExportStatus::Failed) => {
+                return Err(ExportError::Failed(
+                    description.failure_message.unwrap_or_default(),
+                ))
+            }
+            _ => log!("waiting for export {}...", arn),
+        }
+    }
+}
+
+/// Downloads the items of a finished export into memory, with the `aws` CLI
+/// (given `cli_args` like `--region us-east-1`) and `gzip`.
+pub async fn load(
+    bucket: &str,
+    manifest: &str,
+    cli_args: &[String],
+) -> Result<Vec<HashMap<String, AttributeValue>>, ExportError> {
+    // Data files are under `data/`, next to the manifest.
+    let export_dir = manifest.rsplit_once('/').map_or("", |(dir, _)| dir);
+    let local = std::env::temp_dir().join(format!(
+        "dynamodb_bulk_edit-{}",
+        export_dir.rsplit('/').next().unwrap_or_default()
+    ));
+    run(Command::new("aws").args(cli_args).args([
+        "s3",
+        "cp",
+        "--recursive",
+        "--quiet",
+        &format!("s3://{}/{}/data/", bucket, export_dir),
+        &local.to_string_lossy(),
+    ]))
+    .await?;
+    let items = read(&local).await;
+    let _ = fs::remove_dir_all(&local);
+    items
+}
+
+async fn read(dir: &Path) -> Result<Vec<HashMap<String, AttributeValue>>, ExportError> {
+    let mut items = Vec::new();
+    for entry in fs::read_dir(dir).map_err(ExportError::Io)? {
+        let path = entry.map_err(ExportError::Io)?.path();
+        let contents = run(Command::new("gzip").arg("-dc").arg(&path)).await?;
+        for line in contents.lines().filter(|line| !line.is_empty()) {
+            let item = serde_json::from_str::<Value>(line)
+                .ok()
+                .and_then(|line| item_from_json(line.get("Item")?))
+                .ok_or_else(|| ExportError::Malformed(path.display().to_string()))?;
+            items.push(item);
+        }
+    }
+    Ok(items)
+}
+
+/// Runs `command`, returning its standard output.
+async fn run(command: &mut Command) -> Result<String, ExportError> {
+    let output = command
+        .output()
+        .await
+        .map_err(|e| ExportError::Command(e.to_string()))?;
+    if !output.status.success() {
+        return Err(ExportError::Command(format!(
+            "{} ({})",
+            String::from_utf8_lossy(&output.stderr).trim(),
+            output.status
+        )));
+    }
+    String::from_utf8(output.stdout).map_err(|e| ExportError::Command(e.to_string()))
+}
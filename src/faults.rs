@@ -0,0 +1,166 @@
+use aws_smithy_client::erase::DynConnector;
+use aws_smithy_http::body::SdkBody;
+use aws_smithy_http::result::ConnectorError;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tower::Service;
+
+/// How often to simulate a failure, like `p=0.01`, optionally with a seed for
+/// the same sequence of failures each time, like `p=0.01,seed=42`.
+#[derive(Debug, Clone, Copy)]
+pub struct FaultRate {
+    pub p: f64,
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct FaultRateParseError(String);
+
+impl Display for FaultRateParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "fault rate '{}' is invalid (expected e.g. p=0.01 or p=0.01,seed=42)",
+            self.0
+        ))
+    }
+}
+
+impl FromStr for FaultRate {
+    type Err = FaultRateParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || FaultRateParseError(s.to_string());
+        let mut p = None;
+        let mut seed = None;
+        for part in s.split(',') {
+            match part.trim().split_once('=').ok_or_else(err)? {
+                ("p", value) => p = Some(value.parse::<f64>().map_err(|_| err())?),
+                ("seed", value) => seed = Some(value.parse().map_err(|_| err())?),
+                _ => return Err(err()),
+            }
+        }
+        match p {
+            Some(p) if (0.0..=1.0).contains(&p) => Ok(Self { p, seed }),
+            _ => Err(err()),
+        }
+    }
+}
+
+/// A failure to simulate.
+#[derive(Debug, Clone, Copy)]
+enum Fault {
+    Throttled,
+    /// Only for conditional writes.
+    ConditionFailed,
+    /// The connection is reset before the request is sent.
+    Reset,
+    /// The request is sent (and applied), but the connection is reset before
+    /// the response arrives.
+    Dropped,
+}
+
+/// A connector that fails a fraction of requests, as if DynamoDB throttled
+/// them, failed their conditions, or the network dropped them, and sends the
+/// rest on to a real connector. For exercising retries, checkpoints, and
+/// resuming in tests and rehearsals, never in production.
+#[derive(Clone)]
+pub struct Faults {
+    inner: DynConnector,
+    p: f64,
+    rng: Arc<Mutex<StdRng>>,
+}
+
+impl Faults {
+    pub fn new(inner: DynConnector, rate: FaultRate) -> Self {
+        let rng = match rate.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        Self {
+            inner,
+            p: rate.p,
+            rng: Arc::new(Mutex::new(rng)),
+        }
+    }
+
+    /// Which failure (if any) to simulate for `request`.
+    fn pick(&self, request: &http::Request<SdkBody>) -> Option<Fault> {
+        let mut rng = self.rng.lock().unwrap();
+        if !rng.gen_bool(self.p) {
+            return None;
+        }
+        let conditional = request
+            .body()
+            .bytes()
+            .is_some_and(|body| String::from_utf8_lossy(body).contains("\"ConditionExpression\""));
+        let faults: &[Fault] = if conditional {
+            &[
+                Fault::Throttled,
+                Fault::ConditionFailed,
+                Fault::Reset,
+                Fault::Dropped,
+            ]
+        } else {
+            &[Fault::Throttled, Fault::Reset, Fault::Dropped]
+        };
+        Some(faults[rng.gen_range(0..faults.len())])
+    }
+}
+
+/// A DynamoDB error response, like DynamoDB's own.
+fn error_response(code: &str) -> http::Response<SdkBody> {
+    let body = format!(
+        r#"{{"__type":"com.amazonaws.dynamodb.v20120810#{}","message":"injected fault"}}"#,
+        code
+    );
+    http::Response::builder()
+        .status(400)
+        .header("Content-Type", "application/x-amz-json-1.0")
+        .header("x-amzn-RequestId", "injected-fault")
+        .body(SdkBody::from(body))
+        .unwrap()
+}
+
+fn reset() -> ConnectorError {
+    ConnectorError::io(Box::new(io::Error::new(
+        io::ErrorKind::ConnectionReset,
+        "injected fault",
+    )))
+}
+
+impl Service<http::Request<SdkBody>> for Faults {
+    type Response = http::Response<SdkBody>;
+    type Error = ConnectorError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<SdkBody>) -> Self::Future {
+        match self.pick(&request) {
+            None => Box::pin(self.inner.call(request)),
+            Some(Fault::Throttled) => {
+                Box::pin(async { Ok(error_response("ProvisionedThroughputExceededException")) })
+            }
+            Some(Fault::ConditionFailed) => {
+                Box::pin(async { Ok(error_response("ConditionalCheckFailedException")) })
+            }
+            Some(Fault::Reset) => Box::pin(async { Err(reset()) }),
+            Some(Fault::Dropped) => {
+                let sent = self.inner.call(request);
+                Box::pin(async {
+                    let _ = sent.await;
+                    Err(reset())
+                })
+            }
+        }
+    }
+}
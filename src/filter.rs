@@ -0,0 +1,778 @@
+use crate::decimal::Decimal;
+use aws_sdk_dynamodb::model::{AttributeValue, ScalarAttributeType, TableDescription};
+use aws_smithy_types::date_time::Format;
+use aws_smithy_types::DateTime;
+use aws_smithy_types::{base64, Blob};
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A primary key, like `pk=USER#42,sk=PROFILE`, identifying an item to fetch
+/// or never to modify.
+#[derive(Debug, Clone)]
+pub struct Key(Vec<(String, String)>);
+
+#[derive(Debug)]
+pub struct KeyParseError(String);
+
+impl Display for KeyParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "key '{}' is invalid (expected e.g. pk=USER#42,sk=PROFILE)",
+            self.0
+        ))
+    }
+}
+
+impl FromStr for Key {
+    type Err = KeyParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(|pair| {
+                pair.split_once('=')
+                    .filter(|(name, _)| !name.is_empty())
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .ok_or_else(|| KeyParseError(s.to_string()))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, (name, value)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            f.write_fmt(format_args!("{}={}", name, value))?;
+        }
+        Ok(())
+    }
+}
+
+impl Key {
+    /// Whether this names exactly the attributes of the primary key.
+    pub fn is_full_key(&self, key_names: &[String]) -> bool {
+        self.0.len() == key_names.len()
+            && key_names
+                .iter()
+                .all(|name| self.0.iter().any(|(n, _)| n == name))
+    }
+
+    /// The key as attribute values, typed like the table defines them (`S`,
+    /// `N`, or base64 `B`), or the name of the first attribute that isn't a
+    /// valid value of its type.
+    pub fn to_item(
+        &self,
+        description: &TableDescription,
+    ) -> Result<HashMap<String, AttributeValue>, String> {
+        self.0
+            .iter()
+            .map(|(name, value)| {
+                let attribute_type = description
+                    .attribute_definitions
+                    .iter()
+                    .flatten()
+                    .find(|a| a.attribute_name.as_deref() == Some(name))
+                    .and_then(|a| a.attribute_type.as_ref());
+                let value = match attribute_type {
+                    Some(ScalarAttributeType::N) => value
+                        .parse::<Decimal>()
+                        .ok()
+                        .map(|_| AttributeValue::N(value.clone())),
+                    Some(ScalarAttributeType::B) => base64::decode(value)
+                        .ok()
+                        .map(|b| AttributeValue::B(Blob::new(b))),
+                    _ => Some(AttributeValue::S(value.clone())),
+                };
+                value
+                    .map(|value| (name.clone(), value))
+                    .ok_or_else(|| name.clone())
+            })
+            .collect()
+    }
+
+    pub fn matches(&self, item: &HashMap<String, AttributeValue>) -> bool {
+        self.0.iter().all(|(name, value)| {
+            item.get(name).and_then(scalar_string).as_deref() == Some(value.as_str())
+        })
+    }
+}
+
+/// Whether TTL will delete `item`, because its `ttl_attribute` (which TTL only
+/// honors if it is a number) is at or before `now` in epoch seconds.
+pub fn is_expired(item: &HashMap<String, AttributeValue>, ttl_attribute: &str, now: i64) -> bool {
+    match item.get(ttl_attribute) {
+        Some(AttributeValue::N(n)) => n.parse::<f64>().is_ok_and(|expiry| expiry <= now as f64),
+        _ => false,
+    }
+}
+
+/// The plain string form of a key-like value (`S`, `N`, or base64 `B`).
+pub fn scalar_string(value: &AttributeValue) -> Option<String> {
+    match value {
+        AttributeValue::S(s) => Some(s.clone()),
+        AttributeValue::N(n) => Some(n.clone()),
+        AttributeValue::B(b) => Some(base64::encode(b.as_ref())),
+        _ => None,
+    }
+}
+
+/// A regex that a key attribute's value must match, like `pk=~^TENANT#`.
+#[derive(Debug, Clone)]
+pub struct KeyPattern {
+    pub name: String,
+    pub regex: Regex,
+}
+
+#[derive(Debug)]
+pub enum KeyPatternParseError {
+    MissingOperator(String),
+    InvalidRegex(regex::Error),
+}
+
+impl Display for KeyPatternParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyPatternParseError::MissingOperator(s) => f.write_fmt(format_args!(
+                "key pattern '{}' is invalid (expected e.g. pk=~^TENANT#)",
+                s
+            )),
+            KeyPatternParseError::InvalidRegex(e) => e.fmt(f),
+        }
+    }
+}
+
+impl FromStr for KeyPattern {
+    type Err = KeyPatternParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, regex) = s
+            .split_once("=~")
+            .filter(|(name, _)| !name.is_empty())
+            .ok_or_else(|| KeyPatternParseError::MissingOperator(s.to_string()))?;
+        Ok(Self {
+            name: name.to_string(),
+            regex: Regex::new(regex).map_err(KeyPatternParseError::InvalidRegex)?,
+        })
+    }
+}
+
+impl KeyPattern {
+    pub fn matches(&self, item: &HashMap<String, AttributeValue>) -> bool {
+        item.get(&self.name)
+            .and_then(scalar_string)
+            .is_some_and(|value| self.regex.is_match(&value))
+    }
+
+    /// The literal text that every match must begin with, so that DynamoDB can
+    /// pre-filter with `begins_with`.
+    pub fn literal_prefix(&self) -> Option<String> {
+        let pattern = self.regex.as_str();
+        // Each alternative may begin differently (even `^a|^b`).
+        if has_top_level_alternation(pattern) {
+            return None;
+        }
+        let mut chars = pattern.strip_prefix('^')?.chars().peekable();
+        let mut prefix = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' | '.' | '(' | ')' | '[' | ']' | '|' | '^' | '$' | '+' => break,
+                // These make the preceding character optional.
+                '?' | '*' | '{' => {
+                    prefix.pop();
+                    break;
+                }
+                c => {
+                    if matches!(chars.peek(), Some('?' | '*' | '{')) {
+                        break;
+                    }
+                    prefix.push(c);
+                }
+            }
+        }
+        Some(prefix).filter(|p| !p.is_empty())
+    }
+}
+
+/// Whether `pattern` has a `|` outside of any group or character class.
+fn has_top_level_alternation(pattern: &str) -> bool {
+    let mut chars = pattern.chars().peekable();
+    let mut depth = 0usize;
+    let mut in_class = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' if !in_class => {
+                in_class = true;
+                // A `]` first in a class is a literal.
+                chars.next_if_eq(&'^');
+                chars.next_if_eq(&']');
+            }
+            ']' if in_class => in_class = false,
+            _ if in_class => {}
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            '|' if depth == 0 => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// A condition on an attribute that items must meet, checked client-side, like
+/// `status=S:ACTIVE`, `token=B:3q2+7w==`, `tags contains S:beta`, or
+/// `roles!=SS:admin,owner`.
+///
+/// Literals are typed: `S`, `N` (compared numerically), `B` (base64), or a set
+/// of them (`SS`, `NS`, or `BS`, comma-separated, compared regardless of
+/// order). `contains` tests membership in a set or list.
+#[derive(Debug, Clone)]
+pub struct Where {
+    path: String,
+    operator: Operator,
+    value: AttributeValue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Contains,
+}
+
+#[derive(Debug)]
+pub enum WhereParseError {
+    Invalid(String),
+    InvalidNumber(String),
+    InvalidBase64(String),
+}
+
+impl Display for WhereParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WhereParseError::Invalid(s) => f.write_fmt(format_args!(
+                "condition '{}' is invalid (expected e.g. status=S:ACTIVE, token=B:3q2+7w==, or tags contains S:beta)",
+                s
+            )),
+            WhereParseError::InvalidNumber(n) => {
+                f.write_fmt(format_args!("'{}' is not a number", n))
+            }
+            WhereParseError::InvalidBase64(b) => {
+                f.write_fmt(format_args!("'{}' is not valid base64", b))
+            }
+        }
+    }
+}
+
+impl FromStr for Where {
+    type Err = WhereParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || WhereParseError::Invalid(s.to_string());
+        let (path, operator, literal) = if let Some((path, literal)) = s.split_once(" contains ") {
+            (path, Operator::Contains, literal)
+        } else if let Some((path, literal)) = s.split_once("!=") {
+            (path, Operator::NotEqual, literal)
+        } else if let Some((path, literal)) = s.split_once('=') {
+            (path, Operator::Equal, literal)
+        } else {
+            return Err(invalid());
+        };
+        let path = path.trim();
+        if path.is_empty() || path.split('.').any(str::is_empty) {
+            return Err(invalid());
+        }
+        let (kind, literal) = literal.trim_start().split_once(':').ok_or_else(invalid)?;
+        let number = |n: &str| {
+            n.parse::<Decimal>()
+                .map(|_| n.to_string())
+                .map_err(|_| WhereParseError::InvalidNumber(n.to_string()))
+        };
+        let binary = |b: &str| {
+            base64::decode(b)
+                .map(Blob::new)
+                .map_err(|_| WhereParseError::InvalidBase64(b.to_string()))
+        };
+        let elements = || literal.split(',');
+        let value = match kind {
+            "S" => AttributeValue::S(literal.to_string()),
+            "N" => AttributeValue::N(number(literal)?),
+            "B" => AttributeValue::B(binary(literal)?),
+            "SS" => AttributeValue::Ss(elements().map(String::from).collect()),
+            "NS" => AttributeValue::Ns(elements().map(number).collect::<Result<_, _>>()?),
+            "BS" => AttributeValue::Bs(elements().map(binary).collect::<Result<_, _>>()?),
+            _ => return Err(invalid()),
+        };
+        if operator == Operator::Contains
+            && matches!(
+                value,
+                AttributeValue::Ss(_) | AttributeValue::Ns(_) | AttributeValue::Bs(_)
+            )
+        {
+            return Err(invalid());
+        }
+        Ok(Self {
+            path: path.to_string(),
+            operator,
+            value,
+        })
+    }
+}
+
+impl Where {
+    pub fn matches(&self, item: &HashMap<String, AttributeValue>) -> bool {
+        let actual = get_path(item, &self.path);
+        match self.operator {
+            Operator::Equal => actual.is_some_and(|a| equal(a, &self.value)),
+            Operator::NotEqual => !actual.is_some_and(|a| equal(a, &self.value)),
+            Operator::Contains => match actual {
+                Some(AttributeValue::L(list)) => list.iter().any(|a| equal(a, &self.value)),
+                Some(set) => set_elements(set).is_some_and(|elements| {
+                    elements.iter().any(|element| equal(element, &self.value))
+                }),
+                None => false,
+            },
+        }
+    }
+}
+
+/// The elements of a set, as scalars.
+fn set_elements(value: &AttributeValue) -> Option<Vec<AttributeValue>> {
+    match value {
+        AttributeValue::Ss(ss) => Some(ss.iter().cloned().map(AttributeValue::S).collect()),
+        AttributeValue::Ns(ns) => Some(ns.iter().cloned().map(AttributeValue::N).collect()),
+        AttributeValue::Bs(bs) => Some(bs.iter().cloned().map(AttributeValue::B).collect()),
+        _ => None,
+    }
+}
+
+/// Whether two values are equal, comparing numbers numerically and sets
+/// regardless of order.
+fn equal(a: &AttributeValue, b: &AttributeValue) -> bool {
+    match (a, b) {
+        (AttributeValue::N(a), AttributeValue::N(b)) => {
+            match (a.parse::<Decimal>(), b.parse::<Decimal>()) {
+                (Ok(a), Ok(b)) => a.cmp(&b).is_eq(),
+                _ => a == b,
+            }
+        }
+        _ => match (set_elements(a), set_elements(b)) {
+            (Some(a_elements), Some(b_elements)) => {
+                std::mem::discriminant(a) == std::mem::discriminant(b)
+                    && a_elements
+                        .iter()
+                        .all(|a| b_elements.iter().any(|b| equal(a, b)))
+                    && b_elements
+                        .iter()
+                        .all(|b| a_elements.iter().any(|a| equal(a, b)))
+            }
+            _ => a == b,
+        },
+    }
+}
+
+/// A query `KeyConditionExpression`: a partition key, and optionally a prefix
+/// of the (string) sort key.
+#[derive(Debug, Clone)]
+pub struct KeyCondition {
+    pub partition_key: (String, AttributeValue),
+    pub sort_key_prefix: Option<(String, String)>,
+}
+
+impl KeyCondition {
+    /// The expression, and its name and value placeholders (which are `#k0`,
+    /// `:k0`, `#k1`, and `:k1`).
+    pub fn expression(
+        &self,
+    ) -> (
+        String,
+        HashMap<String, String>,
+        HashMap<String, AttributeValue>,
+    ) {
+        let mut expression = String::from("#k0 = :k0");
+        let mut names = HashMap::from([(String::from("#k0"), self.partition_key.0.clone())]);
+        let mut values = HashMap::from([(String::from(":k0"), self.partition_key.1.clone())]);
+        if let Some((name, prefix)) = &self.sort_key_prefix {
+            expression.push_str(" AND begins_with(#k1, :k1)");
+            names.insert(String::from("#k1"), name.clone());
+            values.insert(String::from(":k1"), AttributeValue::S(prefix.clone()));
+        }
+        (expression, names, values)
+    }
+
+    /// Whether `item` is in the queried range.
+    pub fn matches(&self, item: &HashMap<String, AttributeValue>) -> bool {
+        item.get(&self.partition_key.0) == Some(&self.partition_key.1)
+            && self.sort_key_prefix.iter().all(|(name, prefix)| {
+                matches!(item.get(name), Some(AttributeValue::S(s)) if s.starts_with(prefix.as_str()))
+            })
+    }
+}
+
+/// Numbers from which time attributes are taken to be epoch milliseconds, not
+/// seconds (in seconds, the year 5138; in milliseconds, 1973).
+const MILLIS_THRESHOLD: i64 = 100_000_000_000;
+
+/// A scan `FilterExpression`, built up from `AND`ed conditions.
+#[derive(Debug, Default, Clone)]
+pub struct ScanFilter {
+    conditions: Vec<String>,
+    pub names: HashMap<String, String>,
+    pub values: HashMap<String, AttributeValue>,
+}
+
+impl ScanFilter {
+    /// Adds a condition, given a function that makes it from placeholders for
+    /// `name` and `value`.
+    pub fn and(
+        &mut self,
+        name: &str,
+        value: AttributeValue,
+        condition: impl FnOnce(&str, &str) -> String,
+    ) {
+        self.and_values(name, vec![value], |n, v| condition(n, &v[0]));
+    }
+
+    /// Like [`ScanFilter::and`], with any number of values.
+    pub fn and_values(
+        &mut self,
+        name: &str,
+        values: Vec<AttributeValue>,
+        condition: impl FnOnce(&str, &[String]) -> String,
+    ) {
+        let i = self.conditions.len();
+        let name_placeholder = format!("#f{}", i);
+        let value_placeholders = (0..values.len())
+            .map(|j| format!(":f{}_{}", i, j))
+            .collect::<Vec<_>>();
+        self.conditions.push(format!(
+            "({})",
+            condition(&name_placeholder, &value_placeholders)
+        ));
+        self.names.insert(name_placeholder, name.to_string());
+        self.values
+            .extend(value_placeholders.into_iter().zip(values));
+    }
+
+    /// Adds a condition written as a filter expression, with its own
+    /// placeholders, returning any placeholder that is already in use.
+    pub fn and_expression(
+        &mut self,
+        expression: &str,
+        names: HashMap<String, String>,
+        values: HashMap<String, AttributeValue>,
+    ) -> Result<(), String> {
+        for placeholder in names.keys() {
+            if self.names.contains_key(placeholder) {
+                return Err(placeholder.clone());
+            }
+        }
+        for placeholder in values.keys() {
+            if self.values.contains_key(placeholder) {
+                return Err(placeholder.clone());
+            }
+        }
+        self.conditions.push(format!("({})", expression));
+        self.names.extend(names);
+        self.values.extend(values);
+        Ok(())
+    }
+
+    /// Limits the scan to items whose `attribute` is in `[since, until)`,
+    /// whether stored as epoch seconds, epoch milliseconds, or an ISO 8601
+    /// UTC string.
+    pub fn time_range(&mut self, attribute: &str, since: Option<&Time>, until: Option<&Time>) {
+        let mut values = Vec::new();
+        let mut number_conditions = Vec::new();
+        let mut string_conditions = Vec::new();
+        // Placeholder indices are assigned in the order values are pushed.
+        let mut bound = |time: &Time, op: &str, values: &mut Vec<AttributeValue>| {
+            let i = values.len();
+            values.push(AttributeValue::N(time.secs.to_string()));
+            values.push(AttributeValue::N((time.secs * 1000).to_string()));
+            values.push(AttributeValue::S(time.string.clone()));
+            number_conditions.push((op.to_string(), i, i + 1));
+            string_conditions.push((op.to_string(), i + 2));
+        };
+        if let Some(since) = since {
+            bound(since, ">=", &mut values);
+        }
+        if let Some(until) = until {
+            bound(until, "<", &mut values);
+        }
+        if values.is_empty() {
+            return;
+        }
+        // Each kind of number is limited to its own magnitudes, so that (with
+        // just one bound) epoch milliseconds don't all pass as far-future
+        // seconds, nor seconds as long-ago milliseconds.
+        let threshold = values.len();
+        values.push(AttributeValue::N(MILLIS_THRESHOLD.to_string()));
+        self.and_values(attribute, values, |n, v| {
+            let secs = number_conditions
+                .iter()
+                .map(|(op, s, _)| format!("{} {} {}", n, op, v[*s]))
+                .chain([format!("{} < {}", n, v[threshold])])
+                .collect::<Vec<_>>()
+                .join(" AND ");
+            let millis = number_conditions
+                .iter()
+                .map(|(op, _, ms)| format!("{} {} {}", n, op, v[*ms]))
+                .chain([format!("{} >= {}", n, v[threshold])])
+                .collect::<Vec<_>>()
+                .join(" AND ");
+            let strings = string_conditions
+                .iter()
+                .map(|(op, s)| format!("{} {} {}", n, op, v[*s]))
+                .collect::<Vec<_>>()
+                .join(" AND ");
+            format!("({}) OR ({}) OR ({})", secs, millis, strings)
+        });
+    }
+
+    /// The filter expression, or `None` if there are no conditions.
+    pub fn expression(&self) -> Option<String> {
+        if self.conditions.is_empty() {
+            None
+        } else {
+            Some(self.conditions.join(" AND "))
+        }
+    }
+}
+
+/// A point in time given as `2023-01-01` or `2023-01-01T12:00:00Z` (UTC).
+#[derive(Debug, Clone)]
+pub struct Time {
+    secs: i64,
+    /// How the time compares against ISO 8601 strings.
+    string: String,
+    date_only: bool,
+}
+
+#[derive(Debug)]
+pub struct TimeParseError(String);
+
+impl Display for TimeParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "time '{}' is invalid (expected e.g. 2023-01-01 or 2023-01-01T12:00:00Z)",
+            self.0
+        ))
+    }
+}
+
+impl FromStr for Time {
+    type Err = TimeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let date_only = !s.contains('T');
+        let full = if date_only {
+            format!("{}T00:00:00Z", s)
+        } else {
+            s.to_string()
+        };
+        let time = DateTime::from_str(&full, Format::DateTime)
+            .map_err(|_| TimeParseError(s.to_string()))?;
+        Ok(Self {
+            secs: time.secs(),
+            string: if date_only { s.to_string() } else { full },
+            date_only,
+        })
+    }
+}
+
+impl Display for Time {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.string)
+    }
+}
+
+impl Time {
+    /// The time `secs` seconds after the Unix epoch.
+    pub fn from_secs(secs: i64) -> Self {
+        Self {
+            secs,
+            string: DateTime::from_secs(secs)
+                .fmt(Format::DateTime)
+                .unwrap_or_default(),
+            date_only: false,
+        }
+    }
+
+    pub fn secs(&self) -> i64 {
+        self.secs
+    }
+
+    /// For an exclusive upper bound: a bare date means the end of that day.
+    pub fn end_of_day(&self) -> Self {
+        if !self.date_only {
+            return self.clone();
+        }
+        let secs = self.secs + 24 * 60 * 60;
+        let string = DateTime::from_secs(secs)
+            .fmt(Format::DateTime)
+            .map(|s| s[..10].to_string())
+            .unwrap_or_else(|_| self.string.clone());
+        Self {
+            secs,
+            string,
+            date_only: true,
+        }
+    }
+}
+
+/// A length of time like `30m`, `12h`, or `7d`.
+#[derive(Debug, Clone, Copy)]
+pub struct Period(pub Duration);
+
+#[derive(Debug)]
+pub struct PeriodParseError(String);
+
+impl Display for PeriodParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "period '{}' is invalid (expected e.g. 90s, 30m, 12h, 7d, or 2w)",
+            self.0
+        ))
+    }
+}
+
+impl FromStr for Period {
+    type Err = PeriodParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || PeriodParseError(s.to_string());
+        let split = s.find(|c: char| !c.is_ascii_digit()).ok_or_else(err)?;
+        let (n, unit) = s.split_at(split);
+        let n = n.parse::<u64>().map_err(|_| err())?;
+        let unit_secs = match unit {
+            "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            "w" => 7 * 24 * 60 * 60,
+            _ => return Err(err()),
+        };
+        n.checked_mul(unit_secs)
+            .map(|secs| Self(Duration::from_secs(secs)))
+            .ok_or_else(err)
+    }
+}
+
+/// The attribute of `item` at a dot-separated path, like `address.zip`.
+pub fn get_path<'a>(
+    item: &'a HashMap<String, AttributeValue>,
+    path: &str,
+) -> Option<&'a AttributeValue> {
+    let (parent, name) = match path.rsplit_once('.') {
+        Some((parent, name)) => match get_path(item, parent)? {
+            AttributeValue::M(map) => (map, name),
+            _ => return None,
+        },
+        None => (item, path),
+    };
+    parent.get(name)
+}
+
+/// Like [`get_path`], but mutable.
+pub fn get_path_mut<'a>(
+    item: &'a mut HashMap<String, AttributeValue>,
+    path: &str,
+) -> Option<&'a mut AttributeValue> {
+    let (parent, name) = match path.rsplit_once('.') {
+        Some((parent, name)) => match get_path_mut(item, parent)? {
+            AttributeValue::M(map) => (map, name),
+            _ => return None,
+        },
+        None => (item, path),
+    };
+    parent.get_mut(name)
+}
+
+/// Whether `item` has an attribute at a dot-separated path, like `address.zip`.
+pub fn has_path(item: &HashMap<String, AttributeValue>, path: &str) -> bool {
+    get_path(item, path).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefix(pattern: &str) -> Option<String> {
+        format!("pk=~{}", pattern)
+            .parse::<KeyPattern>()
+            .unwrap()
+            .literal_prefix()
+    }
+
+    #[test]
+    fn literal_prefix() {
+        assert_eq!(prefix("^USER#").as_deref(), Some("USER#"));
+        assert_eq!(prefix("^USER#(a|b)").as_deref(), Some("USER#"));
+        assert_eq!(prefix("^a[|]").as_deref(), Some("a"));
+        assert_eq!(prefix("^ab?").as_deref(), Some("a"));
+        assert_eq!(prefix("^a|^b"), None);
+        assert_eq!(prefix("^USER#|^ORG#"), None);
+        assert_eq!(prefix("^a[|]|b"), None);
+        assert_eq!(prefix("^a{0}"), None);
+        assert_eq!(prefix("^(?i)a"), None);
+        assert_eq!(prefix("USER#"), None);
+    }
+
+    fn time_range(since: Option<&str>, until: Option<&str>) -> ScanFilter {
+        let mut filter = ScanFilter::default();
+        filter.time_range(
+            "at",
+            since.map(|t| t.parse().unwrap()).as_ref(),
+            until.map(|t| t.parse().unwrap()).as_ref(),
+        );
+        filter
+    }
+
+    #[test]
+    fn time_range_since() {
+        let filter = time_range(Some("2023-01-01"), None);
+        assert_eq!(
+            filter.expression().unwrap(),
+            "((#f0 >= :f0_0 AND #f0 < :f0_3) OR (#f0 >= :f0_1 AND #f0 >= :f0_3) OR (#f0 >= :f0_2))"
+        );
+        assert_eq!(
+            filter.values[":f0_0"],
+            AttributeValue::N("1672531200".into())
+        );
+        assert_eq!(
+            filter.values[":f0_1"],
+            AttributeValue::N("1672531200000".into())
+        );
+        assert_eq!(
+            filter.values[":f0_3"],
+            AttributeValue::N("100000000000".into())
+        );
+    }
+
+    #[test]
+    fn time_range_until() {
+        let filter = time_range(None, Some("2023-01-01"));
+        assert_eq!(
+            filter.expression().unwrap(),
+            "((#f0 < :f0_0 AND #f0 < :f0_3) OR (#f0 < :f0_1 AND #f0 >= :f0_3) OR (#f0 < :f0_2))"
+        );
+    }
+
+    #[test]
+    fn time_range_both() {
+        let filter = time_range(Some("2023-01-01"), Some("2024-01-01"));
+        assert_eq!(
+            filter.expression().unwrap(),
+            "((#f0 >= :f0_0 AND #f0 < :f0_3 AND #f0 < :f0_6) OR (#f0 >= :f0_1 AND #f0 < :f0_4 AND #f0 >= :f0_6) OR (#f0 >= :f0_2 AND #f0 < :f0_5))"
+        );
+        assert!(time_range(None, None).expression().is_none());
+    }
+}
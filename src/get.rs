@@ -0,0 +1,71 @@
+use crate::json;
+use aws_sdk_dynamodb::error::BatchGetItemError;
+use aws_sdk_dynamodb::model::{AttributeValue, KeysAndAttributes, ReturnConsumedCapacity};
+use aws_sdk_dynamodb::types::SdkError;
+use aws_sdk_dynamodb::Client;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+/// The most keys that `BatchGetItem` accepts at once.
+const BATCH_SIZE: usize = 100;
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Fetches the items of `table` with the primary keys `keys`, returning them
+/// in the order of their keys (so that the order is the same each time), and
+/// skipping keys without an item. Repeated keys are only fetched once.
+pub async fn get(
+    client: &Client,
+    table: &str,
+    key_names: &[String],
+    keys: &[HashMap<String, AttributeValue>],
+    reads: &crate::Reads,
+) -> Result<Vec<HashMap<String, AttributeValue>>, SdkError<BatchGetItemError>> {
+    let id = |key: &HashMap<String, AttributeValue>| json::item_to_json(key).to_string();
+    let mut seen = HashSet::new();
+    let keys = keys
+        .iter()
+        .filter(|key| seen.insert(id(key)))
+        .collect::<Vec<_>>();
+    let mut found = HashMap::new();
+    for chunk in keys.chunks(BATCH_SIZE) {
+        let mut pending = chunk.iter().map(|&key| key.clone()).collect::<Vec<_>>();
+        let mut backoff = Duration::from_millis(100);
+        while !pending.is_empty() {
+            let request = KeysAndAttributes::builder()
+                .set_keys(Some(pending))
+                .consistent_read(reads.consistent_read)
+                .build();
+            reads.wait().await;
+            let request = client
+                .batch_get_item()
+                .request_items(table, request)
+                .return_consumed_capacity(ReturnConsumedCapacity::Total);
+            let output = reads.retry.run(|| request.clone().send()).await?;
+            for capacity in output.consumed_capacity.iter().flatten() {
+                reads.consumed(Some(capacity));
+            }
+            for item in output
+                .responses
+                .and_then(|mut responses| responses.remove(table))
+                .unwrap_or_default()
+            {
+                found.insert(id(&crate::plan::key(key_names, &item)), item);
+            }
+            pending = output
+                .unprocessed_keys
+                .and_then(|mut unprocessed| unprocessed.remove(table))
+                .and_then(|k| k.keys)
+                .unwrap_or_default();
+            if !pending.is_empty() {
+                // DynamoDB returns unprocessed keys when throttling the batch,
+                // or when the response would be too large.
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+    Ok(keys
+        .iter()
+        .filter_map(|key| found.remove(&id(key)))
+        .collect())
+}
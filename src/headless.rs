@@ -0,0 +1,138 @@
+use serde_json::{json, Value};
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Environment variable that, if set, holds a JSON object with every input,
+/// for running without a terminal (e.g. in Lambda or Fargate).
+pub const PAYLOAD_VAR: &str = "DYNAMODB_BULK_EDIT_PAYLOAD";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Makes all further output newline-delimited JSON on stdout.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Prints a message for the operator, as a JSON `log` event if headless.
+pub fn log(message: &str) {
+    if enabled() {
+        emit("log", json!({ "message": message }));
+    } else {
+        eprintln!("{}", message);
+    }
+}
+
+/// Prints requested output (like a plan), as a JSON `output` event if headless.
+pub fn output(text: &str) {
+    if enabled() {
+        emit("output", json!({ "text": text }));
+    } else {
+        println!("{}", text);
+    }
+}
+
+/// Prints one event to stdout, as from `--progress-fd 1`.
+pub fn emit(event: &str, mut fields: Value) {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default();
+    if let Value::Object(map) = &mut fields {
+        map.insert(String::from("event"), json!(event));
+        map.insert(String::from("time"), json!(time));
+    }
+    println!("{}", fields);
+}
+
+/// A headless run's inputs.
+#[derive(Debug)]
+pub struct Payload {
+    /// Equivalent command line arguments, starting with the program name.
+    pub args: Vec<String>,
+    /// Whether to proceed without asking for confirmation.
+    pub confirm: bool,
+}
+
+#[derive(Debug)]
+pub enum PayloadError {
+    Json(serde_json::Error),
+    NotObject,
+    /// An option's value isn't a string, number, boolean, or array of them.
+    Value(String),
+}
+
+impl Display for PayloadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PayloadError::Json(e) => f.write_fmt(format_args!("error parsing payload: {}", e)),
+            PayloadError::NotObject => f.write_str("payload must be a JSON object"),
+            PayloadError::Value(key) => f.write_fmt(format_args!(
+                "payload value of '{}' must be a string, number, boolean, or array of them",
+                key
+            )),
+        }
+    }
+}
+
+impl Payload {
+    /// Parses a JSON object whose keys are long option names (`table`,
+    /// `exclude_key`, etc.), with `true` for flags and arrays for repeated
+    /// options. The optional `command` is an array of arguments, like
+    /// `["validate", "--schema", "item.schema.json"]`, and `confirm` replaces
+    /// typing `Y`.
+    pub fn parse(payload: &str) -> Result<Self, PayloadError> {
+        let object = match serde_json::from_str(payload).map_err(PayloadError::Json)? {
+            Value::Object(object) => object,
+            _ => return Err(PayloadError::NotObject),
+        };
+        let mut ret = Self {
+            args: vec![String::from("dynamodb_bulk_edit")],
+            confirm: false,
+        };
+        let mut command = Vec::new();
+        for (key, value) in object {
+            match key.as_str() {
+                "confirm" => {
+                    ret.confirm = value
+                        .as_bool()
+                        .ok_or_else(|| PayloadError::Value(key.clone()))?
+                }
+                "command" => command = strings(&key, value)?,
+                _ => {
+                    let option = format!("--{}", key.replace('_', "-"));
+                    match value {
+                        Value::Bool(true) => ret.args.push(option),
+                        Value::Bool(false) | Value::Null => {}
+                        value => {
+                            // Joined with `=`, in case values begin with `-`.
+                            for value in strings(&key, value)? {
+                                ret.args.push(format!("{}={}", option, value));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        ret.args.extend(command);
+        Ok(ret)
+    }
+}
+
+/// A scalar, or array of scalars, as strings.
+fn strings(key: &str, value: Value) -> Result<Vec<String>, PayloadError> {
+    let scalar = |value: Value| match value {
+        Value::String(s) => Ok(s),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        _ => Err(PayloadError::Value(key.to_string())),
+    };
+    match value {
+        Value::Array(values) => values.into_iter().map(scalar).collect(),
+        value => Ok(vec![scalar(value)?]),
+    }
+}
@@ -0,0 +1,234 @@
+use aws_sdk_dynamodb::client::fluent_builders::Scan;
+use aws_sdk_dynamodb::error::{DeleteItemError, PutItemError, ScanError};
+use aws_sdk_dynamodb::model::{AttributeValue, ReturnConsumedCapacity, TableDescription};
+use aws_sdk_dynamodb::output::ScanOutput;
+use aws_sdk_dynamodb::types::SdkError;
+use aws_sdk_dynamodb::Client;
+use std::collections::HashMap;
+use std::future::Future;
+
+/// Names of the table's partition key and, if any, sort key.
+pub fn key_names(description: &TableDescription) -> Vec<String> {
+    description
+        .key_schema
+        .iter()
+        .flatten()
+        .filter_map(|k| k.attribute_name.clone())
+        .collect()
+}
+
+/// The key attributes of `item`.
+pub fn key(
+    key_names: &[String],
+    item: &HashMap<String, AttributeValue>,
+) -> HashMap<String, AttributeValue> {
+    key_names
+        .iter()
+        .filter_map(|name| Some((name.clone(), item.get(name)?.clone())))
+        .collect()
+}
+
+/// A page of a scan: its items, and the key to continue from (`None` at the
+/// end).
+pub type Page = (
+    Vec<HashMap<String, AttributeValue>>,
+    Option<HashMap<String, AttributeValue>>,
+);
+
+/// Scans every item of `table` with `segments` concurrent segmented scans (if
+/// more than one), returning the items of each segment in turn.
+pub async fn scan(
+    client: &Client,
+    table: &str,
+    segments: u32,
+    consistent_read: bool,
+) -> Result<Vec<HashMap<String, AttributeValue>>, SdkError<ScanError>> {
+    let starts = vec![Some(None); segments as usize];
+    let segments = scan_with(
+        client,
+        table,
+        starts,
+        consistent_read,
+        |request| request.send(),
+        |_, _| {},
+    )
+    .await?;
+    Ok(segments.into_iter().flatten().collect())
+}
+
+/// Like [`scan`], with a segment for each of `starts`, continuing from its key
+/// (`Some(None)` from the beginning, or `None` if it's finished). Each page's
+/// request is sent with `send` (which can add to it, pace it, or retry it), and
+/// each page read is passed to `page` with its segment, before the next page
+/// of that segment is read. Returns the items each segment read.
+pub async fn scan_with<S, F, P, E>(
+    client: &Client,
+    table: &str,
+    starts: Vec<Option<Option<HashMap<String, AttributeValue>>>>,
+    consistent_read: bool,
+    send: S,
+    page: P,
+) -> Result<Vec<Vec<HashMap<String, AttributeValue>>>, E>
+where
+    S: Fn(Scan) -> F,
+    F: Future<Output = Result<ScanOutput, E>>,
+    P: Fn(u32, &Page),
+{
+    let segments = starts.len() as u32;
+    let (send, page) = (&send, &page);
+    let scans = starts
+        .into_iter()
+        .enumerate()
+        .map(|(segment, start)| async move {
+            let mut items = Vec::new();
+            let mut last_evaluated_key = match start {
+                Some(start) => start,
+                None => return Ok(items),
+            };
+            loop {
+                let request = scan_request(
+                    client,
+                    table,
+                    (segments > 1).then_some((segment as u32, segments)),
+                    consistent_read,
+                    last_evaluated_key,
+                );
+                let output = send(request).await?;
+                let read = (output.items.unwrap_or_default(), output.last_evaluated_key);
+                page(segment as u32, &read);
+                let (mut read, lek) = read;
+                items.append(&mut read);
+                last_evaluated_key = lek;
+                if last_evaluated_key.is_none() {
+                    return Ok(items);
+                }
+            }
+        });
+    futures_util::future::try_join_all(scans).await
+}
+
+/// The request for a page of `segment` (its index, and how many there are, if
+/// the scan is segmented) of a scan of `table`, continuing from `start`.
+pub fn scan_request(
+    client: &Client,
+    table: &str,
+    segment: Option<(u32, u32)>,
+    consistent_read: bool,
+    start: Option<HashMap<String, AttributeValue>>,
+) -> Scan {
+    client
+        .scan()
+        .table_name(table)
+        .set_segment(segment.map(|(segment, _)| segment as i32))
+        .set_total_segments(segment.map(|(_, total)| total as i32))
+        .consistent_read(consistent_read)
+        .set_exclusive_start_key(start)
+}
+
+/// The longest expression that DynamoDB accepts, in bytes.
+pub const MAX_EXPRESSION_LENGTH: usize = 4096;
+
+/// A condition that an item still has the attributes it was scanned with.
+pub struct Guard {
+    pub key: HashMap<String, AttributeValue>,
+    pub condition_expression: String,
+    pub names: HashMap<String, String>,
+    pub values: HashMap<String, AttributeValue>,
+    /// Whether the condition only checks some of the attributes (the key
+    /// first, then by name), since checking all of them would be longer than
+    /// DynamoDB allows, so the item must be read back and compared first. Even
+    /// so, a change to the unchecked attributes between the read and the write
+    /// goes unnoticed, and is overwritten.
+    pub partial: bool,
+}
+
+impl Guard {
+    pub fn new(key_names: &[String], old: &HashMap<String, AttributeValue>) -> Self {
+        let mut attributes = old.iter().collect::<Vec<_>>();
+        attributes.sort_unstable_by_key(|(name, _)| (!key_names.contains(name), *name));
+        let mut ret = Self {
+            key: key(key_names, old),
+            condition_expression: String::new(),
+            names: HashMap::new(),
+            values: HashMap::new(),
+            partial: false,
+        };
+        for (i, (name, value)) in attributes.into_iter().enumerate() {
+            let condition = format!("#a{} = :a{}", i, i);
+            let separator = if i > 0 { " AND " } else { "" };
+            if ret.condition_expression.len() + separator.len() + condition.len()
+                > MAX_EXPRESSION_LENGTH
+            {
+                ret.partial = true;
+                break;
+            }
+            ret.condition_expression.push_str(separator);
+            ret.condition_expression.push_str(&condition);
+            ret.names.insert(format!("#a{}", i), name.clone());
+            ret.values.insert(format!(":a{}", i), value.clone());
+        }
+        ret
+    }
+}
+
+/// The error for an item found to have changed when read back, like a failed
+/// condition.
+pub fn changed_since_scan() -> aws_sdk_dynamodb::Error {
+    aws_sdk_dynamodb::Error::ConditionalCheckFailedException(
+        aws_sdk_dynamodb::error::ConditionalCheckFailedException::builder()
+            .message("the item changed since it was scanned")
+            .build(),
+    )
+}
+
+/// Puts `item`, as long as `guard` holds, returning the write capacity units
+/// consumed.
+pub async fn put(
+    client: &Client,
+    table: &str,
+    guard: &Guard,
+    item: &HashMap<String, AttributeValue>,
+) -> Result<f64, SdkError<PutItemError>> {
+    let mut req = client
+        .put_item()
+        .table_name(table)
+        .set_item(Some(item.clone()))
+        .return_consumed_capacity(ReturnConsumedCapacity::Total);
+    if !guard.condition_expression.is_empty() {
+        req = req
+            .condition_expression(&guard.condition_expression)
+            .set_expression_attribute_names(Some(guard.names.clone()))
+            .set_expression_attribute_values(Some(guard.values.clone()));
+    }
+    req.send().await.map(|output| {
+        output
+            .consumed_capacity
+            .and_then(|c| c.capacity_units)
+            .unwrap_or_default()
+    })
+}
+
+/// Deletes the item that `guard` is for, as long as it holds, returning the
+/// write capacity units consumed.
+pub async fn delete(
+    client: &Client,
+    table: &str,
+    guard: &Guard,
+) -> Result<f64, SdkError<DeleteItemError>> {
+    client
+        .delete_item()
+        .table_name(table)
+        .set_key(Some(guard.key.clone()))
+        .condition_expression(&guard.condition_expression)
+        .set_expression_attribute_names(Some(guard.names.clone()))
+        .set_expression_attribute_values(Some(guard.values.clone()))
+        .return_consumed_capacity(ReturnConsumedCapacity::Total)
+        .send()
+        .await
+        .map(|output| {
+            output
+                .consumed_capacity
+                .and_then(|c| c.capacity_units)
+                .unwrap_or_default()
+        })
+}
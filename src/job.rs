@@ -0,0 +1,226 @@
+use crate::items::{self, changed_since_scan, Guard};
+use crate::rename::{OverwritePolicy, Replace};
+use crate::rule::{self, Rule};
+use aws_sdk_dynamodb::error::{DescribeTableError, ScanError};
+use aws_sdk_dynamodb::model::TableDescription;
+use aws_sdk_dynamodb::types::SdkError;
+use aws_sdk_dynamodb::Client;
+use std::fmt::{Display, Formatter};
+
+/// A bulk rename, for embedding in other programs (like migration Lambdas)
+/// instead of running the binary: scans the table, applies the rules to each
+/// item, and, unless any item can't be renamed cleanly, puts each changed item
+/// with a condition that it's still as scanned.
+///
+/// ```no_run
+/// # async fn run(client: aws_sdk_dynamodb::Client) -> Result<(), dynamodb_bulk_edit::JobError> {
+/// use dynamodb_bulk_edit::rule::{Path, Rule};
+///
+/// let summary = dynamodb_bulk_edit::BulkEditJob::new(client, "users")
+///     .rename("userName>username".parse().unwrap())
+///     .rule(Rule::remove(Path::root("legacyId")).unwrap())
+///     .scan_parallelism(4)
+///     .run()
+///     .await?;
+/// println!("wrote {} of {} item(s)", summary.written, summary.scanned);
+/// # Ok(())
+/// # }
+/// ```
+pub struct BulkEditJob {
+    client: Client,
+    table: String,
+    rules: Vec<Rule>,
+    allow_overwrite: bool,
+    scan_parallelism: u32,
+    consistent_read: bool,
+    dry_run: bool,
+}
+
+/// What a [`BulkEditJob`] did.
+#[derive(Debug, Default)]
+pub struct Summary {
+    /// Items scanned.
+    pub scanned: usize,
+    /// Items that the rules changed.
+    pub changed: usize,
+    /// Changed items written (none for a dry run).
+    pub written: usize,
+    /// Attributes renamed.
+    pub replacements: usize,
+    /// Renames that replaced an existing attribute.
+    pub overwrites: usize,
+}
+
+#[derive(Debug)]
+pub enum JobError {
+    Describe(Box<SdkError<DescribeTableError>>),
+    Scan(Box<SdkError<ScanError>>),
+    /// Rules would overwrite existing attributes at these paths, against their
+    /// policy, so nothing was written.
+    Overwritten(Vec<String>),
+    /// The rules (by index) both wrote this path, so nothing was written.
+    Collision(usize, usize, String),
+    /// Writing an item failed (e.g. it changed since it was scanned) after this
+    /// many items were written.
+    Write(usize, Box<aws_sdk_dynamodb::Error>),
+}
+
+impl Display for JobError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobError::Describe(e) => f.write_fmt(format_args!("error describing table: {}", e)),
+            JobError::Scan(e) => f.write_fmt(format_args!("error scanning: {}", e)),
+            JobError::Overwritten(paths) => f.write_fmt(format_args!(
+                "{} replacement(s) would overwrite existing attributes, e.g. {}",
+                paths.len(),
+                paths[0]
+            )),
+            JobError::Collision(a, b, path) => {
+                f.write_fmt(format_args!("rules {} and {} both wrote {}", a, b, path))
+            }
+            JobError::Write(written, e) => f.write_fmt(format_args!(
+                "after {} item(s) written, error writing item: {}",
+                written, e
+            )),
+        }
+    }
+}
+
+impl BulkEditJob {
+    pub fn new(client: Client, table: impl Into<String>) -> Self {
+        Self {
+            client,
+            table: table.into(),
+            rules: Vec::new(),
+            allow_overwrite: false,
+            scan_parallelism: 1,
+            consistent_read: false,
+            dry_run: false,
+        }
+    }
+
+    /// Adds a rule, applied after the ones before it.
+    pub fn rename(self, rule: Replace) -> Self {
+        self.rule(Rule::Rename(rule))
+    }
+
+    /// Adds a rule built with [`Rule`], applied after the ones before it.
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Whether rules without their own policy may overwrite existing
+    /// attributes (like `--allow-overwrite`).
+    pub fn allow_overwrite(mut self, allow_overwrite: bool) -> Self {
+        self.allow_overwrite = allow_overwrite;
+        self
+    }
+
+    /// Scans with this many concurrent segments (at least 1).
+    pub fn scan_parallelism(mut self, segments: u32) -> Self {
+        self.scan_parallelism = segments.max(1);
+        self
+    }
+
+    pub fn consistent_read(mut self, consistent_read: bool) -> Self {
+        self.consistent_read = consistent_read;
+        self
+    }
+
+    /// Plans without writing anything.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub async fn run(mut self) -> Result<Summary, JobError> {
+        let default_overwrite = if self.allow_overwrite {
+            OverwritePolicy::Overwrite
+        } else {
+            OverwritePolicy::Error
+        };
+        for rule in &mut self.rules {
+            if let Rule::Rename(rule) = rule {
+                rule.overwrite.get_or_insert(default_overwrite);
+            }
+        }
+        let description = self
+            .client
+            .describe_table()
+            .table_name(&self.table)
+            .send()
+            .await
+            .map_err(|e| JobError::Describe(Box::new(e)))?
+            .table
+            .unwrap_or_else(|| TableDescription::builder().build());
+        let key_names = items::key_names(&description);
+        let scanned = items::scan(
+            &self.client,
+            &self.table,
+            self.scan_parallelism,
+            self.consistent_read,
+        )
+        .await
+        .map_err(|e| JobError::Scan(Box::new(e)))?;
+
+        let (mut replacements, mut overwrites) = (0, 0);
+        let mut overwritten = Vec::new();
+        let mut dirty = Vec::new();
+        for old in &scanned {
+            let mut new = old.clone();
+            let mut result = rule::apply(&self.rules, &mut new);
+            if let Some((a, b, path)) = result.collisions.pop() {
+                return Err(JobError::Collision(a, b, path));
+            }
+            overwritten.append(&mut result.overwritten);
+            replacements += result.replacements;
+            overwrites += result.overwrites;
+            if new != *old {
+                dirty.push((old, new));
+            }
+        }
+        if !overwritten.is_empty() {
+            return Err(JobError::Overwritten(overwritten));
+        }
+
+        let mut summary = Summary {
+            scanned: scanned.len(),
+            changed: dirty.len(),
+            written: 0,
+            replacements,
+            overwrites,
+        };
+        if self.dry_run {
+            return Ok(summary);
+        }
+        for (old, new) in dirty {
+            let guard = Guard::new(&key_names, old);
+            let written = async {
+                if guard.partial {
+                    // The condition can't check every attribute, so the rest
+                    // are compared first.
+                    let current = self
+                        .client
+                        .get_item()
+                        .table_name(&self.table)
+                        .set_key(Some(guard.key.clone()))
+                        .consistent_read(true)
+                        .send()
+                        .await?;
+                    if current.item.as_ref() != Some(old) {
+                        return Err(changed_since_scan());
+                    }
+                }
+                items::put(&self.client, &self.table, &guard, &new).await?;
+                Ok(())
+            }
+            .await;
+            if let Err(e) = written {
+                return Err(JobError::Write(summary.written, Box::new(e)));
+            }
+            summary.written += 1;
+        }
+        Ok(summary)
+    }
+}
@@ -0,0 +1,141 @@
+use aws_sdk_dynamodb::model::AttributeValue;
+use aws_smithy_types::{base64, Blob};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+/// Converts an item to DynamoDB JSON (the format used by the AWS CLI).
+pub fn item_to_json(item: &HashMap<String, AttributeValue>) -> Value {
+    Value::Object(
+        item.iter()
+            .map(|(k, v)| (k.clone(), to_json(v)))
+            .collect::<Map<_, _>>(),
+    )
+}
+
+/// Converts a value to DynamoDB JSON (the format used by the AWS CLI).
+pub fn to_json(value: &AttributeValue) -> Value {
+    match value {
+        AttributeValue::B(b) => json!({ "B": base64::encode(b.as_ref()) }),
+        AttributeValue::Bool(b) => json!({ "BOOL": b }),
+        AttributeValue::Bs(bs) => json!({
+            "BS": bs.iter().map(|b| base64::encode(b.as_ref())).collect::<Vec<_>>()
+        }),
+        AttributeValue::L(l) => json!({ "L": l.iter().map(to_json).collect::<Vec<_>>() }),
+        AttributeValue::M(m) => json!({ "M": item_to_json(m) }),
+        AttributeValue::N(n) => json!({ "N": n }),
+        AttributeValue::Ns(ns) => json!({ "NS": ns }),
+        AttributeValue::Null(n) => json!({ "NULL": n }),
+        AttributeValue::S(s) => json!({ "S": s }),
+        AttributeValue::Ss(ss) => json!({ "SS": ss }),
+        _ => Value::Null,
+    }
+}
+
+/// Converts DynamoDB JSON (as in table exports) to a value, or `None` if it is
+/// malformed.
+pub fn from_json(value: &Value) -> Option<AttributeValue> {
+    let object = value.as_object().filter(|o| o.len() == 1)?;
+    let (kind, value) = object.iter().next()?;
+    let string = |v: &Value| v.as_str().map(String::from);
+    let strings = |v: &Value| v.as_array()?.iter().map(string).collect::<Option<Vec<_>>>();
+    let binary = |v: &Value| base64::decode(v.as_str()?).ok().map(Blob::new);
+    Some(match kind.as_str() {
+        "B" => AttributeValue::B(binary(value)?),
+        "BOOL" => AttributeValue::Bool(value.as_bool()?),
+        "BS" => AttributeValue::Bs(
+            value
+                .as_array()?
+                .iter()
+                .map(binary)
+                .collect::<Option<_>>()?,
+        ),
+        "L" => AttributeValue::L(
+            value
+                .as_array()?
+                .iter()
+                .map(from_json)
+                .collect::<Option<_>>()?,
+        ),
+        "M" => AttributeValue::M(item_from_json(value)?),
+        "N" => AttributeValue::N(string(value)?),
+        "NS" => AttributeValue::Ns(strings(value)?),
+        "NULL" => AttributeValue::Null(value.as_bool()?),
+        "S" => AttributeValue::S(string(value)?),
+        "SS" => AttributeValue::Ss(strings(value)?),
+        _ => return None,
+    })
+}
+
+/// Converts an item from DynamoDB JSON, like [`from_json`] does for a map.
+pub fn item_from_json(value: &Value) -> Option<HashMap<String, AttributeValue>> {
+    value
+        .as_object()?
+        .iter()
+        .map(|(k, v)| Some((k.clone(), from_json(v)?)))
+        .collect()
+}
+
+/// Converts plain JSON to the equivalent native value, e.g. objects to `M`.
+/// Numbers keep every digit as written (with serde_json's `arbitrary_precision`),
+/// since `N` has up to 38.
+pub fn from_plain_json(value: &Value) -> AttributeValue {
+    match value {
+        Value::Null => AttributeValue::Null(true),
+        Value::Bool(b) => AttributeValue::Bool(*b),
+        Value::Number(n) => AttributeValue::N(n.to_string()),
+        Value::String(s) => AttributeValue::S(s.clone()),
+        Value::Array(a) => AttributeValue::L(a.iter().map(from_plain_json).collect()),
+        Value::Object(o) => AttributeValue::M(
+            o.iter()
+                .map(|(k, v)| (k.clone(), from_plain_json(v)))
+                .collect(),
+        ),
+    }
+}
+
+/// Converts a value to plain JSON, e.g. `M` to an object, with binary as base64
+/// and sets as sorted arrays. Objects are sorted by key, so the output is
+/// canonical.
+pub fn to_plain_json(value: &AttributeValue) -> Value {
+    let number = |n: &String| serde_json::from_str(n).unwrap_or_else(|_| json!(n));
+    let sorted = |mut values: Vec<String>| {
+        values.sort_unstable();
+        values
+    };
+    match value {
+        AttributeValue::B(b) => json!(base64::encode(b.as_ref())),
+        AttributeValue::Bool(b) => json!(b),
+        AttributeValue::Bs(bs) => json!(sorted(
+            bs.iter().map(|b| base64::encode(b.as_ref())).collect()
+        )),
+        AttributeValue::L(l) => Value::Array(l.iter().map(to_plain_json).collect()),
+        AttributeValue::M(m) => Value::Object(
+            m.iter()
+                .map(|(k, v)| (k.clone(), to_plain_json(v)))
+                .collect(),
+        ),
+        AttributeValue::N(n) => number(n),
+        AttributeValue::Ns(ns) => {
+            let mut numbers = ns.iter().collect::<Vec<_>>();
+            numbers.sort_unstable_by(|a, b| {
+                let (a, b) = (a.parse::<f64>(), b.parse::<f64>());
+                a.ok()
+                    .partial_cmp(&b.ok())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            Value::Array(numbers.into_iter().map(number).collect())
+        }
+        AttributeValue::S(s) => json!(s),
+        AttributeValue::Ss(ss) => json!(sorted(ss.clone())),
+        _ => Value::Null,
+    }
+}
+
+/// Converts an item to plain JSON, like [`to_plain_json`] does for a map.
+pub fn item_to_plain_json(item: &HashMap<String, AttributeValue>) -> Value {
+    Value::Object(
+        item.iter()
+            .map(|(k, v)| (k.clone(), to_plain_json(v)))
+            .collect::<Map<_, _>>(),
+    )
+}
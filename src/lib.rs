@@ -0,0 +1,31 @@
+//! Bulk renames of DynamoDB attributes (including nested ones), for running
+//! from other programs with a [`BulkEditJob`], or piece by piece with the
+//! [`rename`] rules (which [`rule`] builds in code, and defines the semantics
+//! of) and the [`items`] scans and conditional writes that the
+//! `dynamodb_bulk_edit` binary is built on.
+
+pub mod items;
+mod job;
+pub mod rename;
+pub mod rule;
+
+use aws_sdk_dynamodb::model::AttributeValue;
+pub use job::{BulkEditJob, JobError, Summary};
+pub use rename::{OverwritePolicy, Replace, ReplaceParseError};
+
+/// The DynamoDB type descriptor of `value`, like `S` or `M`.
+pub fn type_name(value: &AttributeValue) -> &'static str {
+    match value {
+        AttributeValue::B(_) => "B",
+        AttributeValue::Bool(_) => "BOOL",
+        AttributeValue::Bs(_) => "BS",
+        AttributeValue::L(_) => "L",
+        AttributeValue::M(_) => "M",
+        AttributeValue::N(_) => "N",
+        AttributeValue::Ns(_) => "NS",
+        AttributeValue::Null(_) => "NULL",
+        AttributeValue::S(_) => "S",
+        AttributeValue::Ss(_) => "SS",
+        _ => "?",
+    }
+}
@@ -1,21 +1,144 @@
+/// Like `eprintln!`, but a JSON `log` event on stdout when headless.
+macro_rules! log {
+    ($($arg:tt)*) => {
+        crate::headless::log(&format!($($arg)*))
+    };
+}
+
+/// Like `println!`, but a JSON `output` event when headless.
+macro_rules! output {
+    ($($arg:tt)*) => {
+        crate::headless::output(&format!($($arg)*))
+    };
+}
+
+mod accounts;
+mod allow;
+mod audit;
+mod backfill;
+mod backup;
+mod bench;
+mod capacity;
+mod checkpoint;
+mod config;
+mod cost;
+mod decimal;
+mod diff;
+mod export;
+mod faults;
+mod filter;
+mod get;
+mod headless;
+mod json;
+mod names;
+mod notify;
+mod plan;
+mod progress;
+mod refs;
+mod render;
+mod replay;
+mod report;
+mod role;
+mod seed;
+mod state;
+mod stats;
+mod throttle;
+mod traffic;
+mod transform;
+mod undo;
+mod validate;
+mod view;
+
+use allow::AllowList;
+use audit::Audit;
 use aws_config::default_provider::credentials::DefaultCredentialsChain;
+use aws_config::default_provider::region::DefaultRegionChain;
 use aws_config::timeout;
 use aws_config::timeout::Api;
-use aws_sdk_dynamodb::error::{PutItemError, ScanError};
-use aws_sdk_dynamodb::model::AttributeValue;
+use aws_sdk_dynamodb::client::fluent_builders;
+use aws_sdk_dynamodb::error::{
+    DescribeTableError, DescribeTimeToLiveError, GetItemError, QueryError, ScanError,
+    TransactWriteItemsError, TransactWriteItemsErrorKind, UpdateItemError,
+};
+use aws_sdk_dynamodb::model::{
+    self, AttributeValue, ConsumedCapacity, DeleteRequest, PutRequest, ReturnConsumedCapacity,
+    ScalarAttributeType, StreamViewType, TableDescription, TimeToLiveStatus, TransactWriteItem,
+    WriteRequest,
+};
+use aws_sdk_dynamodb::output::ScanOutput;
 use aws_sdk_dynamodb::types::SdkError;
-use aws_sdk_dynamodb::{Client, Region};
+use aws_sdk_dynamodb::{Client, Endpoint, Region, RetryConfig};
+use aws_smithy_client::erase::DynConnector;
+use aws_smithy_client::{conns, hyper_ext::Adapter};
 use aws_smithy_types::tristate::TriState;
-use regex::{Match, Regex};
+use aws_types::credentials::ProvideCredentials;
+use checkpoint::Checkpoint;
+use config::Config;
+use cost::{CostFormat, CostTag};
+use dynamodb_bulk_edit::items::{self, changed_since_scan, key_names, Guard, Page};
+use dynamodb_bulk_edit::rename::{
+    affected_root_attributes, is_plain_name, remove, replace, would_change, OverwritePolicy,
+    Remove, Replace, ReplaceResult, EVERY_ELEMENT,
+};
+use dynamodb_bulk_edit::rule::{self, Rule};
+use dynamodb_bulk_edit::type_name;
+use faults::{FaultRate, Faults};
+use filter::{
+    has_path, is_expired, Key, KeyCondition, KeyParseError, KeyPattern, Period, ScanFilter, Time,
+    Where,
+};
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use headless::Payload;
+use plan::PlanFormat;
+use progress::Progress;
+use rand::seq::SliceRandom;
+use render::Render;
+use report::Percent;
+use role::Role;
+use seed::Template;
+use serde_json::json;
+use state::{State, Status};
+use stats::Stats;
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::{Display, Formatter};
+use std::io;
 use std::io::BufRead;
-use std::str::FromStr;
-use std::time::Duration;
-use std::{io, process};
-use structopt::lazy_static::lazy_static;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use structopt::StructOpt;
+use throttle::{failure, is_throttling, Budget, Failure, Retry, Throttle};
+use transform::{
+    Convert, Derive, Derived, Encoding, MapSource, Mapped, RegexReplace, ReplaceValue, Round, Set,
+    Split, UnknownPolicy, ValueMap,
+};
+use validate::Validator;
+
+/// Environment variable that, if set (other than to `0` or `false`), has the
+/// same effect as `--yes`.
+const YES_VAR: &str = "DYNAMODB_BULK_EDIT_YES";
+
+/// How many items to list when reporting problems with them.
+const LISTED: usize = 20;
+
+/// The most items that `TransactWriteItems` accepts at once, for
+/// `--transactional`.
+const TRANSACTION_SIZE: usize = 100;
+
+/// The most data that `TransactWriteItems` accepts at once, in bytes.
+const MAX_TRANSACTION_BYTES: usize = 4 * 1024 * 1024;
+
+/// The most requests that `BatchWriteItem` accepts at once, for `--batch`.
+const BATCH_SIZE: usize = 25;
+
+/// How many pages each scan segment may read ahead of the pages being
+/// processed, which bounds how many scanned items are held at once.
+const PAGES_AHEAD: usize = 2;
 
 #[derive(StructOpt)]
 struct Options {
@@ -23,172 +146,5303 @@ struct Options {
     region: Option<String>,
     #[structopt(long)]
     profile: Option<String>,
+    /// Assume this IAM role (e.g. for a table in another account), with the
+    /// credentials otherwise used.
+    #[structopt(long)]
+    role_arn: Option<String>,
+    /// External ID that the role's trust policy requires.
+    #[structopt(long, requires = "role-arn")]
+    external_id: Option<String>,
+    /// Serial number (or ARN) of the MFA device that the role's trust policy
+    /// requires. The code is asked for unless `--mfa-token` is given.
+    #[structopt(long, requires = "role-arn")]
+    mfa_serial: Option<String>,
+    /// Current code of the `--mfa-serial` device.
+    #[structopt(long, requires = "mfa-serial")]
+    mfa_token: Option<String>,
+    /// Run the same command on the table of the same name in each account
+    /// (and region) listed in this TOML file, one after another, assuming each
+    /// account's role, then report how each run went. `{account}` in any
+    /// argument is replaced by the account's name, for per-account files.
+    #[structopt(long)]
+    accounts: Option<PathBuf>,
+    /// When the run finishes or aborts, POST a JSON summary to this URL (with
+    /// `curl`).
+    #[structopt(long)]
+    notify_webhook: Option<String>,
+    /// When the run finishes or aborts, publish a JSON summary to this SNS
+    /// topic ARN (with the `aws` CLI).
+    #[structopt(long)]
+    notify_sns_topic: Option<String>,
+    /// Attribute the capacity the run consumes to this tag, like
+    /// `migration=rename-ownerId`, when logging it and in `--cost-report`.
+    #[structopt(long)]
+    cost_tag: Vec<CostTag>,
+    /// Write the capacity the run consumed on the table, per hour (with each
+    /// `--cost-tag` as a column), to this file, for cost attribution tooling.
+    #[structopt(long)]
+    cost_report: Option<PathBuf>,
+    /// Format of `--cost-report`: csv or json (newline-delimited).
+    #[structopt(long, default_value = "csv")]
+    cost_format: CostFormat,
+    /// Send DynamoDB requests here instead, like `http://localhost:8000` for
+    /// DynamoDB Local or LocalStack.
+    #[structopt(long)]
+    endpoint_url: Option<String>,
     #[structopt(long)]
     timeout: Option<u64>,
     #[structopt(long)]
     table: String,
+    /// TOML file with additional options (currently `rename = [...]`).
+    #[structopt(long)]
+    config: Option<PathBuf>,
+    /// Where to write the inverse rules, if the run can be cleanly rolled back.
+    /// `{time}` is replaced by when the run started (in Unix seconds), so that
+    /// by default, later runs don't overwrite them.
+    #[structopt(long, default_value = "rollback-{time}.toml")]
+    rollback_file: PathBuf,
+    /// Before replacing each item, append it (and what replaces it) to this
+    /// file, as a line of DynamoDB JSON, so `rollback` can restore it.
+    #[structopt(long)]
+    undo_journal: Option<PathBuf>,
+    /// Before writing, create an on-demand backup of the table, and wait for
+    /// it to become available (aborting if it can't be created), for tables
+    /// without point-in-time recovery.
+    #[structopt(long)]
+    backup_before: bool,
+    /// Never modify the item with this primary key, like `pk=USER#42,sk=PROFILE`.
+    #[structopt(long)]
+    exclude_key: Vec<Key>,
+    /// File with one `--exclude-key` per line.
+    #[structopt(long)]
+    exclude_keys_file: Option<PathBuf>,
+    /// Instead of scanning, fetch (and so only modify) the item with this
+    /// primary key, like `pk=USER#42,sk=PROFILE`.
+    #[structopt(long)]
+    key: Vec<Key>,
+    /// File with one `--key` per line.
+    #[structopt(long)]
+    keys_file: Option<PathBuf>,
+    /// Instead of scanning, query (and so only modify) the items with this
+    /// partition key, like `pk=TENANT#alpha`.
+    #[structopt(long)]
+    partition_key: Option<Key>,
+    /// With `--partition-key`, only query items whose (string) sort key starts
+    /// with this.
+    #[structopt(long, requires = "partition-key")]
+    sort_key_begins_with: Option<String>,
+    /// Read items with strongly consistent reads.
+    #[structopt(long)]
+    consistent_read: bool,
+    /// Only modify items whose key attribute matches a regex, like `pk=~^TENANT#(alpha|beta)#`.
+    #[structopt(long)]
+    key_pattern: Vec<KeyPattern>,
+    /// Only scan (and so modify) items matching a DynamoDB filter expression,
+    /// like `#s = :s AND attribute_exists(legacyId)`.
+    #[structopt(long)]
+    filter: Option<String>,
+    /// JSON object of `--filter`'s attribute name placeholders, like
+    /// `{"#s": "status"}`.
+    #[structopt(long, requires = "filter")]
+    filter_names: Option<String>,
+    /// JSON object of `--filter`'s value placeholders, in DynamoDB JSON, like
+    /// `{":s": {"S": "active"}}`.
+    #[structopt(long, requires = "filter")]
+    filter_values: Option<String>,
+    /// Only modify items meeting a condition on an attribute, like
+    /// `status=S:ACTIVE`, `token!=B:3q2+7w==` (base64), or `tags contains S:beta`.
+    #[structopt(long = "where")]
+    conditions: Vec<Where>,
+    /// Only modify items whose `--time-attr` is at or after this time, like `2023-01-01`.
+    #[structopt(long)]
+    since: Option<Time>,
+    /// Only modify items whose `--time-attr` is before this time (or, for a bare
+    /// date, on or before that day).
+    #[structopt(long)]
+    until: Option<Time>,
+    /// Root-level timestamp attribute for `--since`/`--until`, stored as epoch
+    /// seconds, epoch milliseconds, or an ISO 8601 UTC string.
+    #[structopt(long)]
+    time_attr: Option<String>,
+    /// Don't modify items that the table's TTL has already expired.
+    #[structopt(long)]
+    skip_expired: bool,
+    /// Don't match attributes with more path segments than this (1 = root level).
+    #[structopt(long)]
+    max_depth: Option<usize>,
+    /// Write unconditionally, 25 items per `BatchWriteItem`, instead of one
+    /// conditional write per item (which is much slower, but detects items
+    /// modified since the scan).
+    #[structopt(long)]
+    batch: bool,
+    /// Write each item with an `UpdateItem` that only sets and removes the
+    /// attributes that changed, on the condition that they (rather than all
+    /// attributes) still have their old values, so concurrent changes to other
+    /// attributes are kept and large items take less to write.
+    #[structopt(long)]
+    update_mode: bool,
+    /// Write up to 100 items at once with `TransactWriteItems`, each on the
+    /// same condition as when written alone, so that either all of them are
+    /// written or none are. Items with the same partition key are kept in the
+    /// same transaction whenever they fit.
+    #[structopt(long)]
+    transactional: bool,
+    /// Write this many items at once, each still conditionally.
+    #[structopt(long, default_value = "1")]
+    write_concurrency: usize,
+    /// Temporarily raise provisioned write capacity to this many WCU while writing.
+    #[structopt(long)]
+    boost_wcu: Option<i64>,
+    /// Seconds of sustained throttling after which to pause and ask what to do.
+    #[structopt(long, default_value = "60")]
+    throttle_pause_after: u64,
+    /// Retry a read or write that failed transiently (a timeout, a dropped
+    /// connection, or a server error), or a read that was throttled, up to this
+    /// many times, with jittered exponential backoff.
+    #[structopt(long, default_value = "10")]
+    max_retries: u32,
+    /// When an item's conditional write fails because it changed since it was
+    /// scanned, read it again, re-apply `--rename` and `--remove` to it as it
+    /// is now, and write that instead, up to this many times per item, rather
+    /// than stopping the run.
+    #[structopt(long, default_value = "0")]
+    conflict_retries: usize,
+    /// Simulate throttles, failed conditions, and dropped connections on this
+    /// fraction of item reads and writes, like `p=0.01` (or `p=0.01,seed=42`
+    /// to repeat the same ones), to rehearse retrying and resuming. Never in
+    /// production.
+    #[structopt(long, hidden = true)]
+    inject_faults: Option<FaultRate>,
+    /// Start writing at this many writes per second, ramping up while the table keeps up.
+    #[structopt(long)]
+    slow_start: Option<f64>,
+    /// Pace scans, queries, and reads to consume at most this many read capacity
+    /// units per second (leaving the rest of a provisioned table's capacity to
+    /// its application).
+    #[structopt(long)]
+    max_rcu: Option<f64>,
+    /// Pace writes to consume at most this many write capacity units per second.
+    #[structopt(long)]
+    max_wcu: Option<f64>,
+    /// Periodically print write throughput, conflicts, retries, and ETA.
+    #[structopt(long)]
+    stats: bool,
+    /// Seconds between `--stats` lines.
+    #[structopt(long, default_value = "10")]
+    stats_interval: u64,
+    /// Write newline-delimited JSON progress events to this file descriptor.
+    #[structopt(long)]
+    progress_fd: Option<i32>,
+    /// Write newline-delimited JSON progress events to this file.
+    #[structopt(long)]
+    progress_file: Option<PathBuf>,
+    /// Print this many randomly selected pending changes before confirming.
+    #[structopt(long)]
+    preview: Option<usize>,
+    /// Abort without writing if more than this many items would be modified.
+    #[structopt(long)]
+    max_changes: Option<usize>,
+    /// Abort without writing if any item would grow by more than this, like `20%`.
+    #[structopt(long)]
+    max_growth: Option<Percent>,
+    /// Before writing, sample the table's stream (or, without one, CloudWatch)
+    /// for this long, like `60s`, and report how much concurrent write traffic
+    /// the items to edit are getting, which makes their conditional writes fail.
+    #[structopt(long)]
+    traffic_check: Option<Period>,
+    /// Proceed even if replacements would overwrite existing attributes (unless
+    /// a rule says otherwise with a `:error`, `:skip`, or `:overwrite` suffix).
+    #[structopt(long)]
+    allow_overwrite: bool,
+    /// Print every pending change to stdout in this format (partiql or aws-cli) before confirming.
+    #[structopt(long)]
+    plan_format: Option<PlanFormat>,
+    /// How to write items and values in previews, diffs, reports, audit
+    /// records, and messages: dynamodb (DynamoDB JSON), plain (plain JSON), or
+    /// compact (for reading).
+    #[structopt(long, default_value = "dynamodb")]
+    render: Render,
+    /// Proceed without asking to confirm (also enabled by setting
+    /// `DYNAMODB_BULK_EDIT_YES`).
+    #[structopt(long)]
+    yes: bool,
+    /// Print the diff of every pending change (or of `--preview` many), then
+    /// stop without asking to confirm or writing anything.
+    #[structopt(long)]
+    dry_run: bool,
+    /// Attribute paths to leave out of reviewed diffs, like
+    /// `lastSeen,stats.viewCount`. Items that only change in them aren't written.
+    #[structopt(long, use_delimiter = true)]
+    diff_ignore: Vec<String>,
+    /// Write every pending change to this file as newline-delimited JSON, for
+    /// offline review.
+    #[structopt(long)]
+    diff_out: Option<PathBuf>,
+    /// Delete matching items instead of renaming attributes.
+    #[structopt(long)]
+    delete: bool,
+    /// With `--delete`, set the table's TTL attribute to this far in the future,
+    /// like `7d`, instead of deleting items right away.
+    #[structopt(long)]
+    via_ttl: Option<Period>,
+    #[structopt(flatten)]
+    edit: Edits,
+    /// JSON Schema whose `properties` (recursively) make up the `--drop-unknown`
+    /// allow-list.
+    #[structopt(long)]
+    allow_schema: Option<PathBuf>,
+    /// DynamoDB table (with a string partition key) to save progress in, so
+    /// that an interrupted run can be resumed by repeating it, from any machine.
+    #[structopt(long)]
+    state_table: Option<String>,
+    /// Names the run in `--state-table` (default: the table being edited), so
+    /// that several can be tracked at once.
+    #[structopt(long)]
+    job_id: Option<String>,
+    /// Journal progress to this local file (each scan page, and the key of each
+    /// item written), so that the run can be continued with `--resume` after an
+    /// interruption or error.
+    #[structopt(long, conflicts_with = "resume")]
+    checkpoint: Option<PathBuf>,
+    /// Continue the run journaled in this `--checkpoint` file, which must have
+    /// been started with the same other arguments, skipping the items it
+    /// already wrote (and journaling further progress to it).
+    #[structopt(long)]
+    resume: Option<PathBuf>,
+    /// Quarantine items whose edits took longer than this many seconds,
+    /// discarding them. Re-encrypting is abandoned then, but other edits can't
+    /// be interrupted, so a hanging one still hangs the run.
+    #[structopt(long)]
+    item_timeout: Option<u64>,
+    /// Where to write quarantined items (whose edits panicked or timed out) as
+    /// newline-delimited JSON.
+    #[structopt(long)]
+    quarantine_file: Option<PathBuf>,
+    /// Scan with this many concurrent segments, for large tables.
+    #[structopt(long, default_value = "1")]
+    scan_parallelism: u32,
+    /// Plan and write each scan page as it arrives, instead of scanning the
+    /// whole table first, so that tables too big to hold in memory can be
+    /// edited. There's no plan to review, so this requires `--yes`.
+    #[structopt(long)]
+    stream: bool,
+    /// Plan, report, and write separately for each value of this attribute
+    /// (e.g. one tenant at a time), asking before each one after the first.
+    #[structopt(long)]
+    partition_by: Option<String>,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+/// The edits to make to each item, which may also be given after the `edit`
+/// subcommand.
+#[derive(StructOpt)]
+struct Edits {
+    /// Rename an attribute at a path, like `userName>username`.
     #[structopt(long)]
     rename: Vec<Replace>,
+    /// Mark matching items as deleted, by setting `deleted` to true and
+    /// `deletedAt` to the current time.
+    #[structopt(long)]
+    soft_delete: bool,
+    /// Remove the marks left by `--soft-delete` from matching items.
+    #[structopt(long)]
+    undelete: bool,
+    /// Re-encrypt this root-level attribute, holding KMS ciphertext as binary
+    /// or a base64 string, from `--from-key` to `--to-key`.
+    #[structopt(long)]
+    reencrypt: Vec<String>,
+    /// KMS key that `--reencrypt` attributes are currently encrypted under.
+    #[structopt(long)]
+    from_key: Option<String>,
+    /// KMS key to re-encrypt `--reencrypt` attributes under.
+    #[structopt(long)]
+    to_key: Option<String>,
+    /// Convert a root-level attribute between base64 strings and binary, like
+    /// `payload:base64-to-binary` or `payload:binary-to-base64`.
+    #[structopt(long)]
+    encode: Vec<Encoding>,
+    /// Replace a root-level string attribute holding a JSON object or array with
+    /// the equivalent map or list.
+    #[structopt(long)]
+    parse_json: Vec<String>,
+    /// Replace a root-level map or list attribute with its canonical JSON, as a string.
+    #[structopt(long)]
+    stringify: Vec<String>,
+    /// Extract regex captures from a root-level string attribute into other
+    /// attributes, like `fullName:/(\S+)\s+(\S+)/ -> firstName,lastName`. Suffix
+    /// with `:remove` to also remove the source.
+    #[structopt(long)]
+    split: Vec<Split>,
+    /// Substitute a regex in a string attribute at a path, with `$1` or
+    /// `${name}` for captures, like `url:/^http:\/\/(.+)$/https:\/\/$1/`.
+    #[structopt(long)]
+    regex_replace: Vec<RegexReplace>,
+    /// Set a root-level attribute from a template over other root-level
+    /// attributes, like `displayName = "{firstName} {lastName}"`, skipping items
+    /// missing any of them.
+    #[structopt(long)]
+    derive: Vec<Derive>,
+    /// Set an attribute at a path to a string (`S`), number (`N`), or boolean
+    /// (`BOOL`), which may reference root-level attributes, like
+    /// `gsi1pk=S:TENANT#{tenantId}` or `flags.active=BOOL:false`, skipping items
+    /// missing any of them.
+    #[structopt(long)]
+    set: Vec<Set>,
+    /// Like `--set`, but only for items missing the attribute.
+    #[structopt(long)]
+    default: Vec<Set>,
+    /// Replace a string or number value at a path, like `status:active>ACTIVE`,
+    /// at an attribute name at any level, like `*currency:usd>USD`, or
+    /// anywhere, like `*:N/A>n/a`.
+    #[structopt(long)]
+    replace_value: Vec<ReplaceValue>,
+    /// Translate a root-level string or number attribute's values with a
+    /// headerless CSV of `old,new` pairs, like `countryCode<-mapping.csv`.
+    #[structopt(long)]
+    map: Vec<MapSource>,
+    /// Refuse to proceed if any `--map` attribute has a value absent from its mapping.
+    #[structopt(long)]
+    strict_map: bool,
+    /// Translate a root-level string or number attribute's values, like
+    /// `status: active->ACTIVE, inactive->INACTIVE`. Requires `--remap-unknown`.
+    #[structopt(long)]
+    remap: Vec<ValueMap>,
+    /// What to do with values outside a `--remap`: fail, skip (the whole item,
+    /// as scanned, and report it), or pass (leave the value alone).
+    #[structopt(long)]
+    remap_unknown: Option<UnknownPolicy>,
+    /// Convert a root-level number attribute exactly, like `durationMs:ms-to-s`,
+    /// `timeout:s-to-ms`, `priceCents:cents-to-decimal("USD")`, or
+    /// `price:decimal-to-cents("USD")` (which rounds ties to even).
+    #[structopt(long)]
+    convert: Vec<Convert>,
+    /// Round root-level number attributes to a number of decimal places (ties
+    /// to even), like `lat:6,lng:6`.
+    #[structopt(long)]
+    round: Vec<Round>,
+    /// Sort a root-level list of scalars (by type, then value).
+    #[structopt(long)]
+    list_sort: Vec<String>,
+    /// Remove all but the first of equal elements of a root-level list.
+    #[structopt(long)]
+    list_dedupe: Vec<String>,
+    /// Convert a root-level list of strings, numbers, or binary to a set,
+    /// dropping duplicates.
+    #[structopt(long)]
+    to_set: Vec<String>,
+    /// Convert a root-level set to a list, in sorted order.
+    #[structopt(long)]
+    to_list: Vec<String>,
+    /// Remove an attribute at a path, like `meta.legacyId`, or (with `*`) at
+    /// any level ending in that path, like `*legacyId`.
+    #[structopt(long)]
+    remove: Vec<Remove>,
+    /// Remove attributes that aren't on the allow-list (`allow = [...]` in
+    /// `--config`, and/or `--allow-schema`), after printing every change.
+    #[structopt(long)]
+    drop_unknown: bool,
+    /// Write the planned changes (each item as read, and what to write in its
+    /// place) to this JSON file for review, instead of writing them, so that
+    /// `apply` can make them later.
+    #[structopt(long)]
+    plan_out: Option<PathBuf>,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Make the edits given after it to the matching items, the same as
+    /// giving them before any subcommand.
+    Edit(Box<Edits>),
+    /// Make the changes in a `--plan-out` file, with the same conditions as if
+    /// they had been made right away, so that items changed since they were
+    /// planned aren't overwritten.
+    Apply {
+        /// Plan file written by `--plan-out`.
+        plan: PathBuf,
+    },
+    /// Delete the matching items, the same as `--delete`.
+    Delete {
+        /// Set the table's TTL attribute to this far in the future, like `7d`,
+        /// instead of deleting items right away.
+        #[structopt(long)]
+        via_ttl: Option<Period>,
+    },
+    /// Check items, as plain JSON, against a JSON Schema, and report violations.
+    /// Any edits are only made to violating items, to repair them.
+    Validate {
+        /// JSON Schema file.
+        #[structopt(long)]
+        schema: PathBuf,
+        /// Where to write violations as newline-delimited JSON (default: stdout).
+        #[structopt(long)]
+        report: Option<PathBuf>,
+    },
+    /// Count items having and missing each required attribute, and list the
+    /// keys of those missing it (e.g. for a follow-up `--default` run).
+    Coverage {
+        /// Dot-separated attribute path, like `address.zip`.
+        #[structopt(long, required = true)]
+        require: Vec<String>,
+        /// Where to write one JSON line per attribute (default: stdout).
+        #[structopt(long)]
+        report: Option<PathBuf>,
+    },
+    /// Report items whose reference attribute names a nonexistent item in
+    /// another table.
+    CheckRefs {
+        /// Attribute holding the reference (items without it are ignored).
+        #[structopt(long)]
+        attr: String,
+        /// Table that the references point into.
+        #[structopt(long)]
+        references_table: String,
+        /// Partition key of the referenced table (which must not have a sort key).
+        #[structopt(long)]
+        references_key: String,
+        /// Where to write dangling references as newline-delimited JSON
+        /// (default: stdout).
+        #[structopt(long)]
+        report: Option<PathBuf>,
+    },
+    /// Print some attributes (at any level) of each item read, as a table or
+    /// CSV, without modifying anything, like to check the relevant slice of
+    /// the data before and after an edit.
+    View {
+        /// Columns, like `pk, sk, meta.version as v, orders[0].total`: paths
+        /// like in rules, where `[0]` is the first element of a list, and
+        /// `[*]` lists the value in every element.
+        #[structopt(long)]
+        select: view::Select,
+        /// `table` or `csv`.
+        #[structopt(long, default_value = "table")]
+        format: view::ViewFormat,
+    },
+    /// Report attribute names (at any level) with invisible, control,
+    /// unusual whitespace, or look-alike characters, like a trailing space,
+    /// along with a rename rule to fix each one.
+    CheckNames {
+        /// Where to write one JSON line per suspicious name (default: stdout).
+        #[structopt(long)]
+        report: Option<PathBuf>,
+    },
+    /// Export the table as of a past time (with point-in-time recovery, to S3),
+    /// and report how the matching items have changed since. Any rules only
+    /// limit the comparison to the attributes they name.
+    Compare {
+        /// Time to compare against, like `2024-03-01T00:00:00Z`.
+        #[structopt(long)]
+        as_of: Time,
+        /// S3 bucket to export to.
+        #[structopt(long)]
+        s3_bucket: String,
+        #[structopt(long)]
+        s3_prefix: Option<String>,
+        /// Where to write changed items as newline-delimited JSON (default: stdout).
+        #[structopt(long)]
+        report: Option<PathBuf>,
+    },
+    /// Summarize the matching items.
+    Stats {
+        /// Numeric attribute to report the count, min, max, mean, and
+        /// percentiles of.
+        #[structopt(long)]
+        attr: Vec<String>,
+        /// Attribute to count the items holding each distinct value of, most
+        /// frequent first.
+        #[structopt(long)]
+        group_by: Vec<String>,
+        /// Report the distribution of item sizes, and the largest items.
+        #[structopt(long)]
+        sizes: bool,
+        /// Count the items in each partition, most first (to find hot keys).
+        #[structopt(long)]
+        key_distribution: bool,
+        /// How many partitions `--key-distribution` lists.
+        #[structopt(long, default_value = "10")]
+        top: usize,
+        /// Only summarize this many randomly chosen items, scanning only
+        /// enough randomly chosen segments of the table to choose them from.
+        #[structopt(long)]
+        sample: Option<usize>,
+    },
+    /// Write synthetic items into the table (e.g. a scratch table or DynamoDB
+    /// Local, to rehearse edits against), instead of editing it.
+    Seed {
+        /// An item in DynamoDB JSON, whose strings may include generators like
+        /// `{uuid}`, `{seq}`, `{int:18..90}`, `{lorem:5}`, `{choice:a|b}`,
+        /// `{timestamp:2023-01-01..2024-01-01}`, or `{epoch:...}`.
+        #[structopt(long)]
+        template: PathBuf,
+        /// How many items to write.
+        #[structopt(long)]
+        count: usize,
+    },
+    /// Measure the sustainable read (and optionally write) throughput at
+    /// increasing concurrency, without modifying anything, and recommend
+    /// `--scan-parallelism` and `--write-concurrency` for the real run.
+    Bench {
+        /// How long to measure for in total, like `60s` or `5m`.
+        #[structopt(long, default_value = "60s")]
+        duration: Period,
+        /// Also measure writes, with conditional writes (that never succeed) to
+        /// this table, so as not to use the real table's write capacity.
+        #[structopt(long)]
+        scratch_table: Option<String>,
+        /// Highest concurrency to try.
+        #[structopt(long, default_value = "32")]
+        max_concurrency: u32,
+    },
+    /// Give the items missing a secondary index's key attributes those
+    /// attributes, computed by the edits (like `--derive` or `--set`), then
+    /// wait for the index's item count to reach the number expected.
+    BackfillIndex {
+        /// Name of the global or local secondary index.
+        #[structopt(long)]
+        index: String,
+        /// How long to wait for the index to catch up after writing, like `5m`.
+        #[structopt(long, default_value = "5m")]
+        verify_timeout: Period,
+    },
+    /// Remove attributes (or, with `--delete`, whole items) from the items
+    /// older than a retention age. Items are only touched once, so it can run
+    /// on a schedule (with `--yes`, and `audit` in `--config` for a trail).
+    EnforceRetention {
+        /// Attribute to remove, at a path like `--remove`.
+        #[structopt(long)]
+        attr: Vec<Remove>,
+        /// Retention age, like `90d`.
+        #[structopt(long)]
+        older_than: Period,
+        /// Attribute holding each item's time, as epoch seconds, epoch
+        /// milliseconds, or an ISO 8601 UTC string.
+        #[structopt(long)]
+        time_attr: String,
+    },
+    /// Make the changes recorded in an audit trail again, to the items as they
+    /// are now: each item is only written if every attribute an audited change
+    /// touched still has its old value (or already has its new one), so a
+    /// partially applied run can be re-driven, or its changes copied to another
+    /// table.
+    Replay {
+        /// Audit file to replay, as written by a `{ file = ... }` audit sink
+        /// with the default `--render`.
+        #[structopt(long)]
+        audit: PathBuf,
+        /// Only show what would be written (like --dry-run).
+        #[structopt(long)]
+        dry_run: bool,
+    },
+    /// Restore the items replaced by runs with `--undo-journal`, to how they
+    /// were before the first of them: each item is only written if it's still
+    /// as the last of them wrote it, so later changes aren't lost.
+    Rollback {
+        /// Undo journal, as written by `--undo-journal`.
+        journal: PathBuf,
+        /// Only show what would be written (like --dry-run).
+        #[structopt(long)]
+        dry_run: bool,
+    },
 }
 
-struct Replace {
-    root: bool,
-    prefix: String,
-    from: String,
-    to: String,
+impl Options {
+    /// Flags given for edits that modify items (rather than delete them).
+    fn edits(&self) -> Vec<&'static str> {
+        [
+            ("rename", !self.edit.rename.is_empty()),
+            ("soft-delete", self.edit.soft_delete),
+            ("undelete", self.edit.undelete),
+            ("reencrypt", !self.edit.reencrypt.is_empty()),
+            ("encode", !self.edit.encode.is_empty()),
+            ("parse-json", !self.edit.parse_json.is_empty()),
+            ("stringify", !self.edit.stringify.is_empty()),
+            ("split", !self.edit.split.is_empty()),
+            ("regex-replace", !self.edit.regex_replace.is_empty()),
+            ("derive", !self.edit.derive.is_empty()),
+            ("set", !self.edit.set.is_empty()),
+            ("default", !self.edit.default.is_empty()),
+            ("replace-value", !self.edit.replace_value.is_empty()),
+            ("map", !self.edit.map.is_empty()),
+            ("remap", !self.edit.remap.is_empty()),
+            ("convert", !self.edit.convert.is_empty()),
+            ("round", !self.edit.round.is_empty()),
+            ("list-sort", !self.edit.list_sort.is_empty()),
+            ("list-dedupe", !self.edit.list_dedupe.is_empty()),
+            ("to-set", !self.edit.to_set.is_empty()),
+            ("to-list", !self.edit.to_list.is_empty()),
+            ("remove", !self.edit.remove.is_empty()),
+            ("drop-unknown", self.edit.drop_unknown),
+        ]
+        .into_iter()
+        .filter_map(|(edit, given)| given.then_some(edit))
+        .collect()
+    }
 }
 
-#[derive(Debug)]
-enum ReplaceParseError {
-    MissingArrow,
-    InvalidAttribute(String),
-    Unsupported,
+#[tokio::main]
+async fn main() {
+    run().await;
+    notify::send(0);
 }
 
-impl Display for ReplaceParseError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ReplaceParseError::MissingArrow => f.write_str("replacement missing arrow ('>')"),
-            ReplaceParseError::InvalidAttribute(a) => {
-                f.write_fmt(format_args!("attribute '{}' is invalid", a))
+async fn run() {
+    let payload = match std::env::var(headless::PAYLOAD_VAR) {
+        Ok(payload) => {
+            headless::enable();
+            match Payload::parse(&payload) {
+                Ok(payload) => Some(payload),
+                Err(e) => {
+                    log!("{}", e);
+                    notify::exit(1);
+                }
+            }
+        }
+        Err(_) => None,
+    };
+    let args = match &payload {
+        Some(payload) => payload.args.clone(),
+        None => std::env::args().collect(),
+    };
+    let mut options = match Options::from_iter_safe(&args) {
+        Ok(options) => options,
+        Err(e) if payload.is_some() => {
+            log!("{}", e.message);
+            notify::exit(1);
+        }
+        Err(e) => e.exit(),
+    };
+    render::set(options.render);
+    // `edit` and `delete` only group their options under a verb.
+    match options.command.take() {
+        Some(Command::Edit(edit)) => {
+            if !options.edits().is_empty() {
+                log!("edits can't be given both before and after `edit`");
+                notify::exit(1);
+            }
+            let plan_out = options.edit.plan_out.take();
+            options.edit = *edit;
+            options.edit.plan_out = options.edit.plan_out.take().or(plan_out);
+        }
+        Some(Command::Delete { via_ttl }) => {
+            options.delete = true;
+            options.via_ttl = options.via_ttl.or(via_ttl);
+        }
+        command => options.command = command,
+    }
+    if let Some(path) = options
+        .rollback_file
+        .to_str()
+        .filter(|p| p.contains("{time}"))
+    {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        options.rollback_file = PathBuf::from(path.replace("{time}", &started.to_string()));
+    }
+    if let Some(path) = &options.accounts {
+        fan_out(path, &options, &args);
+    }
+
+    let mut allow_list = AllowList::default();
+    let mut audit_sinks = Vec::new();
+    if let Some(path) = &options.config {
+        let config = match Config::load(path) {
+            Ok(config) => config,
+            Err(e) => {
+                log!("{}", e);
+                notify::exit(1);
+            }
+        };
+        for rename in config.rename {
+            match rename.parse() {
+                Ok(replacement) => options.edit.rename.push(replacement),
+                Err(e) => {
+                    log!("error in config rule '{}': {}", rename, e);
+                    notify::exit(1);
+                }
+            }
+        }
+        for path in &config.allow {
+            allow_list.allow(path);
+        }
+        audit_sinks = config.audit;
+    }
+    if let Some(path) = &options.allow_schema {
+        let schema = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()));
+        match schema {
+            Ok(schema) => allow_list.allow_schema(&schema),
+            Err(e) => {
+                log!("error reading allow schema: {}", e);
+                notify::exit(1);
+            }
+        }
+    }
+    if let Some(Command::EnforceRetention {
+        attr,
+        older_than,
+        time_attr,
+    }) = &mut options.command
+    {
+        let (attr, older_than, time_attr) = (std::mem::take(attr), *older_than, time_attr.clone());
+        if !options.edits().is_empty() {
+            log!("enforce-retention can't be combined with edits");
+            notify::exit(1);
+        }
+        if options.since.is_some() || options.until.is_some() || options.time_attr.is_some() {
+            log!("enforce-retention can't be combined with --since, --until, or --time-attr");
+            notify::exit(1);
+        }
+        if attr.is_empty() != options.delete {
+            log!("enforce-retention needs either --attr or --delete, but not both");
+            notify::exit(1);
+        }
+        if audit_sinks.is_empty() {
+            log!("without `audit` in --config, enforce-retention keeps no audit trail.");
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        let until = Time::from_secs(now - older_than.0.as_secs() as i64);
+        log!(
+            "enforcing retention on items with {} before {}.",
+            time_attr,
+            until
+        );
+        options.edit.remove = attr;
+        options.until = Some(until);
+        options.time_attr = Some(time_attr);
+    }
+    // With `replay`, the audited edits to make again.
+    let mut replayed = Vec::new();
+    if let Some(Command::Replay { audit, dry_run }) = &options.command {
+        if !options.edits().is_empty() || options.delete {
+            log!("replay can't be combined with edits or --delete");
+            notify::exit(1);
+        }
+        if !options.key.is_empty() || options.keys_file.is_some() {
+            log!("replay can't be combined with --key or --keys-file");
+            notify::exit(1);
+        }
+        replayed = match replay::load(audit) {
+            Ok(records) => records,
+            Err(e) => {
+                log!("{}", e);
+                notify::exit(1);
+            }
+        };
+        let mut tables = replayed
+            .iter()
+            .map(|r| r.table.as_str())
+            .collect::<Vec<_>>();
+        tables.sort_unstable();
+        tables.dedup();
+        match tables.as_slice() {
+            [] => {
+                log!("the audit file has no records, so there's nothing to replay.");
+                return;
+            }
+            [table] => log!(
+                "replaying {} audited edit(s) of table {}.",
+                replayed.len(),
+                table
+            ),
+            _ => {
+                log!(
+                    "the audit file has records for several tables ({}), so it can't be replayed at once",
+                    tables.join(", ")
+                );
+                notify::exit(1);
+            }
+        }
+        options.dry_run |= *dry_run;
+        // The items are fetched by key, once each.
+        let mut seen = HashSet::new();
+        options.key = replayed
+            .iter()
+            .filter(|r| seen.insert(r.key.to_string()))
+            .map(|r| r.key.clone())
+            .collect();
+    }
+    // With `apply`, the planned changes to make.
+    let mut applying = Vec::new();
+    if let Some(Command::Apply { plan }) = &options.command {
+        if !options.edits().is_empty() || options.delete || options.edit.plan_out.is_some() {
+            log!("apply can't be combined with edits, --delete, or --plan-out");
+            notify::exit(1);
+        }
+        let reading = [
+            ("--key", !options.key.is_empty()),
+            ("--keys-file", options.keys_file.is_some()),
+            ("--partition-key", options.partition_key.is_some()),
+            ("--filter", options.filter.is_some()),
+            ("--where", !options.conditions.is_empty()),
+            ("--key-pattern", !options.key_pattern.is_empty()),
+            ("--since", options.since.is_some()),
+            ("--until", options.until.is_some()),
+            ("--stream", options.stream),
+        ];
+        if let Some((option, _)) = reading.iter().find(|(_, given)| *given) {
+            log!(
+                "apply makes the changes to the items planned, so it can't be combined with {}",
+                option
+            );
+            notify::exit(1);
+        }
+        applying = match plan::load(plan, &options.table) {
+            Ok(changes) => changes,
+            Err(e) => {
+                log!("{}", e);
+                notify::exit(1);
+            }
+        };
+        if applying.is_empty() {
+            log!("the plan has no changes, so there's nothing to apply.");
+            return;
+        }
+    }
+    if let Some(Command::Rollback { dry_run, .. }) = &options.command {
+        if !options.edits().is_empty() || options.delete || options.edit.plan_out.is_some() {
+            log!("rollback can't be combined with edits, --delete, or --plan-out");
+            notify::exit(1);
+        }
+        let reading = [
+            ("--key", !options.key.is_empty()),
+            ("--keys-file", options.keys_file.is_some()),
+            ("--partition-key", options.partition_key.is_some()),
+            ("--filter", options.filter.is_some()),
+            ("--since", options.since.is_some()),
+            ("--until", options.until.is_some()),
+            ("--stream", options.stream),
+        ];
+        if let Some((option, _)) = reading.iter().find(|(_, given)| *given) {
+            log!(
+                "rollback restores the items journaled, so it can't be combined with {}",
+                option
+            );
+            notify::exit(1);
+        }
+        options.dry_run |= *dry_run;
+    }
+    if options.edit.drop_unknown && allow_list.is_empty() {
+        log!("--drop-unknown requires an allow-list, from --config or --allow-schema");
+        notify::exit(1);
+    }
+
+    let default_overwrite = if options.allow_overwrite {
+        OverwritePolicy::Overwrite
+    } else {
+        OverwritePolicy::Error
+    };
+    for replacement in &mut options.edit.rename {
+        replacement.overwrite.get_or_insert(default_overwrite);
+        if let Some(max_depth) = options.max_depth {
+            replacement.max_depth = Some(
+                replacement
+                    .max_depth
+                    .map_or(max_depth, |d| d.min(max_depth)),
+            );
+        }
+    }
+
+    if options.delete {
+        if let Some(edit) = options.edits().first() {
+            log!("--delete can't be combined with --{}", edit);
+            notify::exit(1);
+        }
+    }
+    let reencrypt_keys = match (&options.edit.from_key, &options.edit.to_key) {
+        (Some(from_key), Some(to_key)) if !options.edit.reencrypt.is_empty() => {
+            Some((from_key, to_key))
+        }
+        (None, None) if options.edit.reencrypt.is_empty() => None,
+        _ => {
+            log!("--reencrypt, --from-key, and --to-key must be given together");
+            notify::exit(1);
+        }
+    };
+    let remap_unknown = match options.edit.remap_unknown {
+        Some(policy) => policy,
+        None if options.edit.remap.is_empty() => UnknownPolicy::Pass,
+        None => {
+            log!("--remap requires --remap-unknown (fail, skip, or pass)");
+            notify::exit(1);
+        }
+    };
+    if options.edit.soft_delete && options.edit.undelete {
+        log!("--soft-delete can't be combined with --undelete");
+        notify::exit(1);
+    }
+    if options.write_concurrency == 0 {
+        log!("--write-concurrency must be at least 1");
+        notify::exit(1);
+    }
+    if options.write_concurrency > 1 && options.batch {
+        log!("--write-concurrency doesn't apply to --batch");
+        notify::exit(1);
+    }
+    if options.transactional && options.batch {
+        log!("--transactional can't be combined with --batch, since BatchWriteItem writes unconditionally");
+        notify::exit(1);
+    }
+    if options.write_concurrency > 1 && options.transactional {
+        log!("--write-concurrency doesn't apply to --transactional");
+        notify::exit(1);
+    }
+    if options.update_mode && options.batch {
+        log!("--update-mode can't be combined with --batch, since BatchWriteItem only puts whole items");
+        notify::exit(1);
+    }
+    if options.conflict_retries > 0 {
+        if let Some(edit) = options
+            .edits()
+            .into_iter()
+            .find(|edit| !matches!(*edit, "rename" | "remove"))
+        {
+            log!(
+                "--conflict-retries only re-applies --rename and --remove, so it can't be combined with --{}",
+                edit
+            );
+            notify::exit(1);
+        }
+        if options.delete || options.command.is_some() {
+            log!("--conflict-retries only applies to editing items with --rename and --remove");
+            notify::exit(1);
+        }
+        if options.filter.is_some() || options.since.is_some() || options.until.is_some() {
+            log!("--conflict-retries can't be combined with --filter, --since, or --until, which the scan applies, so can't be checked again");
+            notify::exit(1);
+        }
+        if options.batch || options.transactional {
+            log!("--conflict-retries doesn't apply to --batch or --transactional");
+            notify::exit(1);
+        }
+    }
+    if options.scan_parallelism == 0 {
+        log!("--scan-parallelism must be at least 1");
+        notify::exit(1);
+    }
+    if options.stats_interval == 0 {
+        log!("--stats-interval must be at least 1");
+        notify::exit(1);
+    }
+    for (option, rate) in [
+        ("--max-rcu", options.max_rcu),
+        ("--max-wcu", options.max_wcu),
+        ("--slow-start", options.slow_start),
+    ] {
+        if rate.is_some_and(|rate| !(rate > 0.0 && rate.is_finite())) {
+            log!("{} must be positive", option);
+            notify::exit(1);
+        }
+    }
+    if options.partition_by.is_some() && options.state_table.is_some() {
+        // Resuming relies on items being written in scan order.
+        log!("--partition-by can't be combined with --state-table");
+        notify::exit(1);
+    }
+    if options.job_id.is_some() && options.state_table.is_none() {
+        log!("--job-id requires --state-table");
+        notify::exit(1);
+    }
+    if (options.checkpoint.is_some() || options.resume.is_some()) && options.state_table.is_some() {
+        log!("--checkpoint and --resume can't be combined with --state-table");
+        notify::exit(1);
+    }
+    if options.via_ttl.is_some() && !options.delete {
+        log!("--via-ttl requires --delete");
+        notify::exit(1);
+    }
+    let yes = options.yes
+        || std::env::var(YES_VAR).is_ok_and(|v| !matches!(v.as_str(), "" | "0" | "false"));
+    if options.stream {
+        // These review, check, or resume the whole plan, which a stream never
+        // has before writing.
+        let whole_plan = [
+            ("--dry-run", options.dry_run),
+            ("--preview", options.preview.is_some()),
+            ("--plan-format", options.plan_format.is_some()),
+            ("--diff-out", options.diff_out.is_some()),
+            ("--plan-out", options.edit.plan_out.is_some()),
+            ("--drop-unknown", options.edit.drop_unknown),
+            ("--max-changes", options.max_changes.is_some()),
+            ("--max-growth", options.max_growth.is_some()),
+            ("--strict-map", options.edit.strict_map),
+            ("--traffic-check", options.traffic_check.is_some()),
+            ("--partition-by", options.partition_by.is_some()),
+            ("--boost-wcu", options.boost_wcu.is_some()),
+            ("--stats", options.stats),
+            ("--state-table", options.state_table.is_some()),
+            ("--checkpoint", options.checkpoint.is_some()),
+            ("--resume", options.resume.is_some()),
+        ];
+        if let Some((option, _)) = whole_plan.iter().find(|(_, given)| *given) {
+            log!("--stream can't be combined with {}", option);
+            notify::exit(1);
+        }
+        if options.command.is_some() {
+            log!("--stream can't be combined with subcommands");
+            notify::exit(1);
+        }
+        if !audit_sinks.is_empty() {
+            log!("--stream can't be combined with audit sinks");
+            notify::exit(1);
+        }
+        if !yes && payload.as_ref().is_none_or(|payload| !payload.confirm) {
+            log!("--stream writes without a plan to review, so it requires --yes");
+            notify::exit(1);
+        }
+    }
+
+    let validator = match &options.command {
+        Some(Command::Validate { schema, .. }) => match Validator::load(schema) {
+            Ok(validator) => Some(validator),
+            Err(e) => {
+                log!("{}", e);
+                notify::exit(1);
+            }
+        },
+        _ => None,
+    };
+    if matches!(
+        options.command,
+        Some(
+            Command::Coverage { .. }
+                | Command::CheckRefs { .. }
+                | Command::CheckNames { .. }
+                | Command::Stats { .. }
+                | Command::View { .. }
+        )
+    ) && (!options.edits().is_empty() || options.delete)
+    {
+        log!("coverage, check-refs, check-names, stats, and view only report, so they can't be combined with edits");
+        notify::exit(1);
+    }
+    if matches!(options.command, Some(Command::Seed { .. }))
+        && (!options.edits().is_empty() || options.delete)
+    {
+        log!("seed only writes new items, so it can't be combined with edits");
+        notify::exit(1);
+    }
+    if matches!(options.command, Some(Command::Bench { .. }))
+        && (!options.edits().is_empty() || options.delete)
+    {
+        log!("bench doesn't modify anything, so it can't be combined with edits");
+        notify::exit(1);
+    }
+    if matches!(options.command, Some(Command::BackfillIndex { .. })) {
+        if options.delete {
+            log!("backfill-index can't be combined with --delete");
+            notify::exit(1);
+        }
+        if options.edits().is_empty() {
+            log!("backfill-index needs edits that compute the index's key attributes (like --derive or --set)");
+            notify::exit(1);
+        }
+    }
+    if matches!(options.command, Some(Command::Compare { .. }))
+        && (options.edits().iter().any(|&edit| edit != "rename") || options.delete)
+    {
+        log!("compare only reports, so it can't be combined with edits other than rules (which limit it to their attributes)");
+        notify::exit(1);
+    }
+
+    let progress = match Progress::open(options.progress_fd, options.progress_file.as_deref()) {
+        Ok(progress) => progress,
+        Err(e) => {
+            log!("error opening progress output: {}", e);
+            notify::exit(1);
+        }
+    };
+
+    let mut credentials_builder = DefaultCredentialsChain::builder();
+
+    if let Some(region) = &options.region {
+        credentials_builder = credentials_builder.region(Region::new(Cow::Owned(region.clone())));
+    }
+    if let Some(profile) = &options.profile {
+        credentials_builder = credentials_builder.profile_name(profile);
+    }
+
+    let credentials_provider = credentials_builder.build().await;
+
+    let region = match &options.region {
+        Some(region) => Some(Region::new(Cow::Owned(region.clone()))),
+        None => {
+            let mut region_builder = DefaultRegionChain::builder();
+            if let Some(profile) = &options.profile {
+                region_builder = region_builder.profile_name(profile);
+            }
+            region_builder.build().region().await
+        }
+    };
+
+    let mut shared_config_loader = aws_config::from_env();
+    if let Some(arn) = &options.role_arn {
+        let role = Role {
+            arn: arn.clone(),
+            external_id: options.external_id.clone(),
+            mfa_serial: options.mfa_serial.clone(),
+            mfa_token: options.mfa_token.clone(),
+        };
+        let role_provider = match role.provider(credentials_provider, region.clone()).await {
+            Ok(role_provider) => role_provider,
+            Err(e) => {
+                log!("{}", e);
+                notify::exit(1);
+            }
+        };
+        // The `aws` CLI (for S3 and SNS) gets the role's credentials through
+        // the environment, instead of `--profile`.
+        match role_provider.provide_credentials().await {
+            Ok(credentials) => {
+                std::env::set_var("AWS_ACCESS_KEY_ID", credentials.access_key_id());
+                std::env::set_var("AWS_SECRET_ACCESS_KEY", credentials.secret_access_key());
+                match credentials.session_token() {
+                    Some(token) => std::env::set_var("AWS_SESSION_TOKEN", token),
+                    None => std::env::remove_var("AWS_SESSION_TOKEN"),
+                }
+            }
+            Err(e) => {
+                log!("error assuming role: {}", e);
+                notify::exit(1);
+            }
+        }
+        shared_config_loader = shared_config_loader.credentials_provider(role_provider);
+    } else {
+        shared_config_loader = shared_config_loader.credentials_provider(credentials_provider);
+    }
+    if let Some(region) = region {
+        shared_config_loader = shared_config_loader.region(region);
+    }
+
+    if let Some(timeout) = options.timeout {
+        let timeout = Duration::from_secs(timeout);
+        shared_config_loader = shared_config_loader.timeout_config(
+            timeout::Config::new().with_api_timeouts(
+                Api::new()
+                    .with_call_timeout(TriState::Set(timeout))
+                    .with_call_attempt_timeout(TriState::Set(timeout)),
+            ),
+        )
+    }
+
+    let shared_config = shared_config_loader.load().await;
+
+    let endpoint = options
+        .endpoint_url
+        .as_ref()
+        .map(|endpoint_url| match endpoint_url.parse() {
+            Ok(uri) => Endpoint::immutable(uri),
+            Err(e) => {
+                log!("--endpoint-url is invalid: {}", e);
+                notify::exit(1);
+            }
+        });
+    let dynamodb_config = || {
+        let builder = aws_sdk_dynamodb::config::Builder::from(&shared_config);
+        match &endpoint {
+            Some(endpoint) => builder.endpoint_resolver(endpoint.clone()),
+            None => builder,
+        }
+    };
+    let client = Client::from_conf(dynamodb_config().build());
+    // Items are read and written with `Retry` (up to --max-retries) instead,
+    // which needs to see every failed attempt at a write to tell when one was
+    // applied anyway.
+    let items_config = dynamodb_config()
+        .retry_config(RetryConfig::disabled())
+        .build();
+    let items_client = match options.inject_faults {
+        Some(rate) => {
+            log!(
+                "injecting faults into {}% of item reads and writes.",
+                rate.p * 100.0
+            );
+            let connector = DynConnector::new(Adapter::builder().build(conns::https()));
+            Client::from_conf_conn(items_config, Faults::new(connector, rate))
+        }
+        None => Client::from_conf(items_config),
+    };
+    // Aborting before this point (e.g. for invalid options) doesn't notify,
+    // since the operator is still there to see it.
+    notify::init(notify::Notifier::new(
+        options.notify_webhook.clone(),
+        options.notify_sns_topic.clone(),
+        aws_cli_args(&options),
+        options.table.clone(),
+    ));
+    cost::init(cost::Ledger::new(
+        options.table.clone(),
+        options.cost_tag.clone(),
+        options
+            .cost_report
+            .clone()
+            .map(|path| (path, options.cost_format)),
+    ));
+    let kms = aws_sdk_kms::Client::new(&shared_config);
+
+    let description = match describe_table(&client, &options.table).await {
+        Ok(description) => description,
+        Err(e) => {
+            log!("error describing table: {}", e);
+            progress.error("describe", &e.to_string());
+            notify::exit(1);
+        }
+    };
+
+    let key_names = key_names(&description);
+    if let Some(Command::Seed { template, count }) = &options.command {
+        let template = match Template::load(template) {
+            Ok(template) => template,
+            Err(e) => {
+                log!("{}", e);
+                notify::exit(1);
+            }
+        };
+        let mut rng = rand::thread_rng();
+        let mut items = Vec::new();
+        let mut positions = HashMap::new();
+        for seq in 0..*count {
+            let item = match template.generate(&mut rng, seq) {
+                Ok(item) => item,
+                Err(e) => {
+                    log!("{}", e);
+                    notify::exit(1);
+                }
+            };
+            if let Some(name) = key_names.iter().find(|name| !item.contains_key(*name)) {
+                log!("template doesn't set the key attribute '{}'", name);
+                notify::exit(1);
             }
-            ReplaceParseError::Unsupported => {
-                f.write_str("replacements that that move values are not yet supported")
+            // A batch can't write the same key twice, so later items win.
+            match positions.entry(json::item_to_json(&plan::key(&key_names, &item)).to_string()) {
+                Entry::Occupied(position) => items[*position.get()] = item,
+                Entry::Vacant(position) => {
+                    position.insert(items.len());
+                    items.push(item);
+                }
+            }
+        }
+        if items.len() < *count {
+            log!(
+                "the template generated {} duplicate key(s), so only {} item(s) are distinct.",
+                count - items.len(),
+                items.len()
+            );
+        }
+        if options.dry_run {
+            for item in &items {
+                output!("{}", render::item_text(item));
+            }
+            log!("not writing anything, since --dry-run was passed.");
+            return;
+        }
+        progress.phase("seed");
+        let total = items.len();
+        if let Err(e) = seed::write(&client, &options.table, items, |written| {
+            progress.emit("written", json!({ "written": written }));
+        })
+        .await
+        {
+            log!("error writing items: {}", e);
+            progress.error("seed", &e.to_string());
+            notify::exit(1);
+        }
+        log!("wrote {} synthetic item(s) to {}.", total, options.table);
+        progress.emit("done", json!({ "written": total }));
+        return;
+    }
+    if let Some(Command::Bench {
+        duration,
+        scratch_table,
+        max_concurrency,
+    }) = &options.command
+    {
+        // Retries would hide the onset of throttling.
+        let client = Client::from_conf(
+            dynamodb_config()
+                .retry_config(RetryConfig::disabled())
+                .build(),
+        );
+        let key_types = match scratch_table {
+            Some(scratch_table) if *scratch_table == options.table => {
+                log!("--scratch-table must not be the table being benchmarked");
+                notify::exit(1);
+            }
+            Some(scratch_table) => match describe_table(&client, scratch_table).await {
+                Ok(scratch) => Some(
+                    self::key_names(&scratch)
+                        .into_iter()
+                        .map(|name| {
+                            let kind = scratch
+                                .attribute_definitions
+                                .iter()
+                                .flatten()
+                                .find(|a| a.attribute_name.as_deref() == Some(name.as_str()))
+                                .and_then(|a| a.attribute_type.clone())
+                                .unwrap_or(ScalarAttributeType::S);
+                            (name, kind)
+                        })
+                        .collect::<Vec<_>>(),
+                ),
+                Err(e) => {
+                    log!("error describing scratch table: {}", e);
+                    progress.error("describe", &e.to_string());
+                    notify::exit(1);
+                }
+            },
+            None => None,
+        };
+        let levels = bench::levels(*max_concurrency);
+        let phases = if key_types.is_some() { 2 } else { 1 };
+        let step_duration =
+            (duration.0 / (levels.len() * phases) as u32).max(Duration::from_secs(1));
+        progress.phase("bench");
+
+        let mut scans = Vec::new();
+        for &segments in &levels {
+            log!(
+                "scanning with {} segment(s) for {}s...",
+                segments,
+                step_duration.as_secs()
+            );
+            let step = match bench::scans(
+                &client,
+                &options.table,
+                segments,
+                options.consistent_read,
+                step_duration,
+            )
+            .await
+            {
+                Ok(step) => step,
+                Err(e) => {
+                    log!("{}", e);
+                    progress.error("bench", &e.to_string());
+                    notify::exit(1);
+                }
+            };
+            output!(
+                "scan-parallelism {}: {:.0} items/s, {:.1} RCU/s, {} throttled request(s)",
+                step.concurrency,
+                step.items_per_sec(),
+                step.units_per_sec(),
+                step.throttled
+            );
+            let throttled = step.throttled > 0;
+            scans.push(step);
+            if throttled {
+                break;
+            }
+        }
+        report_bench("--scan-parallelism", "items", "RCU", &scans);
+
+        if let (Some(scratch_table), Some(key_types)) = (scratch_table, &key_types) {
+            let mut writes = Vec::new();
+            for &concurrency in &levels {
+                log!(
+                    "writing with {} in flight for {}s...",
+                    concurrency,
+                    step_duration.as_secs()
+                );
+                let step = match bench::writes(
+                    &client,
+                    scratch_table,
+                    key_types,
+                    concurrency,
+                    step_duration,
+                )
+                .await
+                {
+                    Ok(step) => step,
+                    Err(e) => {
+                        log!("{}", e);
+                        progress.error("bench", &e.to_string());
+                        notify::exit(1);
+                    }
+                };
+                output!(
+                    "write-concurrency {}: {:.0} writes/s, {:.1} WCU/s, {} throttled request(s)",
+                    step.concurrency,
+                    step.items_per_sec(),
+                    step.units_per_sec(),
+                    step.throttled
+                );
+                let throttled = step.throttled > 0;
+                writes.push(step);
+                if throttled {
+                    break;
+                }
+            }
+            report_bench("--write-concurrency", "writes", "WCU", &writes);
+        }
+        progress.phase("done");
+        return;
+    }
+    if let Some(Command::CheckRefs {
+        references_table,
+        references_key,
+        ..
+    }) = &options.command
+    {
+        let referenced = match describe_table(&client, references_table).await {
+            Ok(description) => description,
+            Err(e) => {
+                log!("error describing referenced table: {}", e);
+                progress.error("describe", &e.to_string());
+                notify::exit(1);
+            }
+        };
+        let referenced_key_names = self::key_names(&referenced);
+        if referenced_key_names != [references_key.as_str()] {
+            log!(
+                "--references-key must be the whole primary key of {} ({})",
+                references_table,
+                referenced_key_names.join(", ")
+            );
+            notify::exit(1);
+        }
+    }
+    let index = match &options.command {
+        Some(Command::BackfillIndex { index, .. }) => {
+            match backfill::Index::find(&description, index) {
+                Ok(index) => Some(index),
+                Err(names) => {
+                    log!(
+                        "table has no secondary index '{}' (it has: {})",
+                        index,
+                        names.join(", ")
+                    );
+                    notify::exit(1);
+                }
+            }
+        }
+        _ => None,
+    };
+    if index.as_ref().is_some_and(|index| index.keys.is_empty()) {
+        log!("the index's key attributes are all the table's, so every item is already in it.");
+        progress.phase("done");
+        return;
+    }
+    for name in &key_names {
+        allow_list.allow(name);
+    }
+
+    let mut exclusions = options.exclude_key.clone();
+    if let Some(path) = &options.exclude_keys_file {
+        match read_keys(path) {
+            Ok(keys) => exclusions.extend(keys),
+            Err(e) => {
+                log!("error in excluded keys: {}", e);
+                notify::exit(1);
+            }
+        }
+    }
+    if let Some(exclusion) = exclusions.iter().find(|e| !e.is_full_key(&key_names)) {
+        log!(
+            "excluded key '{}' must name exactly the table's key attributes ({})",
+            exclusion,
+            key_names.join(", ")
+        );
+        notify::exit(1);
+    }
+
+    let mut keys = options.key.clone();
+    if let Some(path) = &options.keys_file {
+        match read_keys(path) {
+            Ok(more) => keys.extend(more),
+            Err(e) => {
+                log!("error in keys: {}", e);
+                notify::exit(1);
+            }
+        }
+    }
+    // Typed like the table's key attributes, to fetch.
+    let mut typed_keys = Vec::new();
+    for key in &keys {
+        if !key.is_full_key(&key_names) {
+            log!(
+                "key '{}' must name exactly the table's key attributes ({})",
+                key,
+                key_names.join(", ")
+            );
+            notify::exit(1);
+        }
+        match key.to_item(&description) {
+            Ok(key) => typed_keys.push(key),
+            Err(name) => {
+                log!("key '{}' has an invalid value for '{}'", key, name);
+                notify::exit(1);
+            }
+        }
+    }
+    // Replayed edits of each item (by key), in the order they were made.
+    let mut replays = HashMap::<String, Vec<&replay::Record>>::new();
+    for record in &replayed {
+        match record.key.to_item(&description) {
+            Ok(key) => replays
+                .entry(json::item_to_json(&key).to_string())
+                .or_default()
+                .push(record),
+            Err(name) => {
+                log!("key '{}' has an invalid value for '{}'", record.key, name);
+                notify::exit(1);
+            }
+        }
+    }
+    // With `rollback`, what to restore of each item (by key).
+    let mut restoring = HashMap::new();
+    if let Some(Command::Rollback { journal, .. }) = &options.command {
+        let restores = match undo::load(journal, &options.table, &key_names) {
+            Ok(restores) => restores,
+            Err(e) => {
+                log!("{}", e);
+                notify::exit(1);
+            }
+        };
+        if restores.is_empty() {
+            log!("the undo journal has no records, so there's nothing to roll back.");
+            return;
+        }
+        log!("rolling back {} journaled item(s).", restores.len());
+        // The items are fetched by key, to compare with what was written.
+        for (id, restore) in restores {
+            typed_keys.push(plan::key(&key_names, &restore.written));
+            restoring.insert(id, restore);
+        }
+    }
+    if !keys.is_empty() {
+        if options.filter.is_some() || options.since.is_some() || options.until.is_some() {
+            // BatchGetItem has no filter expression.
+            log!("--key and --keys-file can't be combined with --filter, --since, or --until");
+            notify::exit(1);
+        }
+        if options.partition_key.is_some() {
+            log!("--key and --keys-file can't be combined with --partition-key");
+            notify::exit(1);
+        }
+    }
+    if (!keys.is_empty() || options.partition_key.is_some()) && options.scan_parallelism > 1 {
+        log!("--scan-parallelism only applies to scans");
+        notify::exit(1);
+    }
+    if (!keys.is_empty() || options.partition_key.is_some()) && options.stream {
+        log!("--stream only applies to scans");
+        notify::exit(1);
+    }
+    let key_condition = options.partition_key.as_ref().map(|partition_key| {
+        if !partition_key.is_full_key(&key_names[..1]) {
+            log!(
+                "--partition-key must name the table's partition key ({})",
+                key_names[0]
+            );
+            notify::exit(1);
+        }
+        let partition_key = match partition_key.to_item(&description) {
+            Ok(key) => key.into_iter().next().unwrap(),
+            Err(name) => {
+                log!("--partition-key has an invalid value for '{}'", name);
+                notify::exit(1);
+            }
+        };
+        let sort_key_prefix =
+            options
+                .sort_key_begins_with
+                .as_ref()
+                .map(|prefix| match key_names.get(1) {
+                    Some(sort_key) if is_string_attribute(&description, sort_key) => {
+                        (sort_key.clone(), prefix.clone())
+                    }
+                    _ => {
+                        log!("--sort-key-begins-with requires a string sort key");
+                        notify::exit(1);
+                    }
+                });
+        KeyCondition {
+            partition_key,
+            sort_key_prefix,
+        }
+    });
+
+    let mut maps = Vec::new();
+    for source in &options.edit.map {
+        match ValueMap::load(source) {
+            Ok(map) => maps.push(map),
+            Err(e) => {
+                log!("error in mapping {}: {}", source.path.display(), e);
+                notify::exit(1);
+            }
+        }
+    }
+
+    let mut filter = ScanFilter::default();
+    for pattern in &options.key_pattern {
+        if !key_names.contains(&pattern.name) {
+            log!(
+                "key pattern attribute '{}' is not a key attribute ({})",
+                pattern.name,
+                key_names.join(", ")
+            );
+            notify::exit(1);
+        }
+        if let Some(prefix) = pattern.literal_prefix() {
+            // A query's filter expression can't name key attributes.
+            if is_string_attribute(&description, &pattern.name) && key_condition.is_none() {
+                filter.and(&pattern.name, AttributeValue::S(prefix), |n, v| {
+                    format!("begins_with({}, {})", n, v)
+                });
+            }
+        }
+    }
+
+    if options.since.is_some() || options.until.is_some() {
+        match &options.time_attr {
+            Some(time_attr) => filter.time_range(
+                time_attr,
+                options.since.as_ref(),
+                options.until.as_ref().map(Time::end_of_day).as_ref(),
+            ),
+            None => {
+                log!("--since and --until require --time-attr");
+                notify::exit(1);
+            }
+        }
+    }
+
+    if let Some(index) = &index {
+        // Only items missing a key attribute can be added to the index. This
+        // comes before --filter, whose placeholders are checked against it.
+        let (expression, names) = index.missing_expression();
+        if let Err(placeholder) = filter.and_expression(&expression, names, HashMap::new()) {
+            unreachable!("backfill placeholder {} is already in use", placeholder);
+        }
+    }
+
+    if let Some(expression) = &options.filter {
+        let parse = |option: &str, json: Option<&String>| {
+            let object = match json.map(|json| serde_json::from_str::<serde_json::Value>(json)) {
+                Some(Ok(serde_json::Value::Object(object))) => object,
+                Some(_) => {
+                    log!("--{} must be a JSON object", option);
+                    notify::exit(1);
+                }
+                None => Default::default(),
+            };
+            object.into_iter()
+        };
+        let names = parse("filter-names", options.filter_names.as_ref())
+            .map(|(placeholder, name)| match name {
+                serde_json::Value::String(name) => (placeholder, name),
+                _ => {
+                    log!("--filter-names values must be attribute names");
+                    notify::exit(1);
+                }
+            })
+            .collect();
+        let values = parse("filter-values", options.filter_values.as_ref())
+            .map(|(placeholder, value)| match json::from_json(&value) {
+                Some(value) => (placeholder, value),
+                None => {
+                    log!(
+                        "--filter-values value of {} must be DynamoDB JSON, like {{\"S\": \"active\"}}",
+                        placeholder
+                    );
+                    notify::exit(1);
+                }
+            })
+            .collect();
+        if let Err(placeholder) = filter.and_expression(expression, names, values) {
+            log!(
+                "--filter placeholder {} is also used by another option; rename it",
+                placeholder
+            );
+            notify::exit(1);
+        }
+        if let Some(key_condition) = &key_condition {
+            let (_, names, values) = key_condition.expression();
+            if let Some(placeholder) = names
+                .keys()
+                .filter(|p| filter.names.contains_key(*p))
+                .chain(values.keys().filter(|p| filter.values.contains_key(*p)))
+                .next()
+            {
+                log!(
+                    "--filter placeholder {} is also used by --partition-key; rename it",
+                    placeholder
+                );
+                notify::exit(1);
+            }
+        }
+    }
+
+    let ttl = if options.skip_expired || options.via_ttl.is_some() {
+        match ttl_attribute(&client, &options.table).await {
+            Ok(attribute) => attribute,
+            Err(e) => {
+                log!("error describing TTL: {}", e);
+                progress.error("describe", &e.to_string());
+                notify::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+
+    let expired_attribute = if options.skip_expired {
+        if ttl.is_none() {
+            log!("TTL is not enabled on table, so no items are expired.");
+        }
+        ttl.clone()
+    } else {
+        None
+    };
+    // The TTL attribute and the epoch seconds to set it to.
+    let soft_delete = match (options.via_ttl, &ttl) {
+        (Some(period), Some(attribute)) => Some((attribute, now + period.0.as_secs() as i64)),
+        (Some(_), None) => {
+            log!("--via-ttl requires TTL to be enabled on the table");
+            notify::exit(1);
+        }
+        (None, _) => None,
+    };
+
+    let state = match &options.state_table {
+        Some(state_table) => {
+            let description = match describe_table(&client, state_table).await {
+                Ok(description) => description,
+                Err(e) => {
+                    log!("error describing state table: {}", e);
+                    progress.error("describe", &e.to_string());
+                    notify::exit(1);
+                }
+            };
+            let state_key_names = self::key_names(&description);
+            if state_key_names.len() != 1 || !is_string_attribute(&description, &state_key_names[0])
+            {
+                log!("--state-table must have a string partition key and no sort key");
+                notify::exit(1);
+            }
+            Some(State::new(
+                &client,
+                state_table,
+                state_key_names[0].clone(),
+                options
+                    .job_id
+                    .clone()
+                    .unwrap_or_else(|| options.table.clone()),
+                args[1..].join(" "),
+            ))
+        }
+        None => None,
+    };
+    let audit = if audit_sinks.is_empty() {
+        None
+    } else {
+        let run = format!(
+            "{}-{}-{:08x}",
+            options.table,
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            rand::random::<u32>()
+        );
+        let sinks = audit_sinks.iter().map(ToString::to_string).collect();
+        match Audit::new(&client, run, audit_sinks, aws_cli_args(&options)).await {
+            Ok(audit) => {
+                notify::audit(audit.run(), sinks);
+                Some(audit)
+            }
+            Err(e) => {
+                log!("{}", e);
+                progress.error("audit", &e.to_string());
+                notify::exit(1);
+            }
+        }
+    };
+    // Other arguments than the checkpoint file's, which a resumed run must
+    // repeat (except for injected faults, which don't change what's written).
+    let checkpoint_args = || {
+        let mut rest = Vec::new();
+        let mut args = args[1..].iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--checkpoint" | "--resume" | "--inject-faults" => {
+                    args.next();
+                }
+                arg if ["--checkpoint=", "--resume=", "--inject-faults="]
+                    .iter()
+                    .any(|prefix| arg.starts_with(prefix)) => {}
+                arg => rest.push(arg),
+            }
+        }
+        rest.join(" ")
+    };
+    let checkpoint = match (&options.checkpoint, &options.resume) {
+        (Some(path), _) => Some(Checkpoint::create(path, &checkpoint_args())),
+        (_, Some(path)) => Some(Checkpoint::resume(path, &checkpoint_args())),
+        (None, None) => None,
+    }
+    .map(|checkpoint| match checkpoint {
+        Ok(checkpoint) => checkpoint,
+        Err(e) => {
+            log!("{}", e);
+            progress.error("checkpoint", &e.to_string());
+            notify::exit(1);
+        }
+    });
+    let journal = options
+        .undo_journal
+        .as_ref()
+        .map(|path| match undo::Journal::open(path) {
+            Ok(journal) => journal,
+            Err(e) => {
+                log!("error opening undo journal: {}", e);
+                notify::exit(1);
+            }
+        });
+    // How many items a previous run wrote, and the key of the last one.
+    let mut resumed = (0, None);
+    if let Some(checkpoint) = checkpoint.as_ref().filter(|c| c.written_count() > 0) {
+        log!(
+            "resuming after {} item(s) written by a previous run.",
+            checkpoint.written_count()
+        );
+        resumed.0 = checkpoint.written_count();
+    }
+    if let Some(state) = &state {
+        match state.load().await {
+            Ok(Some(saved)) if saved.status != Status::Done => {
+                if saved.args != state.args() {
+                    log!(
+                        "the state table has an unfinished run against {} with different arguments:\n  {}\nrepeat them to resume it.",
+                        options.table, saved.args
+                    );
+                    notify::exit(1);
+                }
+                resumed = (saved.written, saved.last_written);
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log!("{}", e);
+                progress.error("state", &e.to_string());
+                notify::exit(1);
+            }
+        }
+        if let Err(e) = state
+            .save(Status::Scanning, resumed.0, resumed.1.as_ref())
+            .await
+        {
+            log!("{}", e);
+            progress.error("state", &e.to_string());
+            notify::exit(1);
+        }
+    }
+    let heartbeat = state.as_ref().map(State::spawn_heartbeat);
+
+    let reads = Reads {
+        consistent_read: options.consistent_read,
+        budget: options.max_rcu.map(|rcu| Arc::new(Budget::new(rcu))),
+        retry: Retry {
+            max_retries: options.max_retries,
+        },
+    };
+    // With `--stream`, the scan's pages, which are planned and written as they
+    // arrive rather than read up front.
+    let mut pages = None;
+    let rows = if !applying.is_empty() {
+        applying.iter().map(|(old, _)| old.clone()).collect()
+    } else if let Some(key_condition) = &key_condition {
+        progress.phase("query");
+        match query(
+            &items_client,
+            &options.table,
+            &filter,
+            key_condition,
+            &reads,
+            &progress,
+        )
+        .await
+        {
+            Ok(rows) => rows,
+            Err(e) => {
+                log!("error querying: {}", e);
+                progress.error("query", &e.to_string());
+                notify::exit(1);
+            }
+        }
+    } else if options.stream {
+        backup_before(&client, &options, &progress, now).await;
+        progress.phase("stream");
+        let starts = vec![Some(None); options.scan_parallelism as usize];
+        pages = Some(Pages::scan(
+            &items_client,
+            &options.table,
+            &filter,
+            starts,
+            &reads,
+            &progress,
+            None,
+        ));
+        Vec::new()
+    } else if let (
+        true,
+        Some(Command::Stats {
+            sample: Some(sample),
+            ..
+        }),
+    ) = (typed_keys.is_empty(), &options.command)
+    {
+        progress.phase("scan");
+        match sample_scan(
+            &items_client,
+            &options.table,
+            &filter,
+            *sample,
+            description.item_count,
+            &reads,
+            &progress,
+        )
+        .await
+        {
+            Ok(rows) => rows,
+            Err(e) => {
+                log!("error scanning: {}", e);
+                progress.error("scan", &e.to_string());
+                notify::exit(1);
+            }
+        }
+    } else if typed_keys.is_empty() {
+        progress.phase("scan");
+        match scan(
+            &items_client,
+            &options.table,
+            &filter,
+            options.scan_parallelism,
+            &reads,
+            &progress,
+            checkpoint.as_ref(),
+        )
+        .await
+        {
+            Ok(rows) => rows,
+            Err(e) => {
+                log!("error scanning: {}", e);
+                progress.error("scan", &e.to_string());
+                log_resume_hint(&options);
+                notify::exit(1);
+            }
+        }
+    } else {
+        progress.phase("get");
+        match get::get(
+            &items_client,
+            &options.table,
+            &key_names,
+            &typed_keys,
+            &reads,
+        )
+        .await
+        {
+            Ok(rows) => rows,
+            Err(e) => {
+                log!("error getting items: {}", e);
+                progress.error("get", &e.to_string());
+                notify::exit(1);
+            }
+        }
+    };
+
+    // Overlapping segments and retried pages can return an item more than
+    // once, and planning two versions of it would fail the second write's
+    // condition, so only the last version read is kept (where it was first
+    // read, to keep the order, which resuming relies on).
+    let mut positions = HashMap::new();
+    let mut duplicates = 0;
+    let mut rows = rows.into_iter().fold(Vec::new(), |mut rows, row| {
+        match positions.entry(json::item_to_json(&plan::key(&key_names, &row)).to_string()) {
+            Entry::Occupied(position) => {
+                rows[*position.get()] = row;
+                duplicates += 1;
+            }
+            Entry::Vacant(position) => {
+                position.insert(rows.len());
+                rows.push(row);
+            }
+        }
+        rows
+    });
+    if !applying.is_empty() {
+        log!("read {} planned change(s)...", rows.len());
+    } else if key_condition.is_some() {
+        log!("queried {} row(s) in table...", rows.len());
+    } else if options.stream {
+        log!("scanning and writing a page at a time...");
+    } else if typed_keys.is_empty() {
+        log!("scanned {} row(s) in table...", rows.len());
+    } else {
+        log!(
+            "fetched {} of {} key(s) in table...",
+            rows.len(),
+            typed_keys.len()
+        );
+    }
+    if duplicates > 0 {
+        log!(
+            "ignored {} duplicate read(s) of the same item(s).",
+            duplicates
+        );
+    }
+    if let Some(Command::Stats {
+        sample: Some(sample),
+        ..
+    }) = &options.command
+    {
+        if *sample < rows.len() {
+            rows.partial_shuffle(&mut rand::thread_rng(), *sample);
+            rows.truncate(*sample);
+        }
+    }
+    // Items up to the last one a previous run wrote were already processed,
+    // since scans return items in the same order each time.
+    let skip = match &resumed.1 {
+        Some(last_written) => {
+            match rows.iter().position(|row| {
+                key_names
+                    .iter()
+                    .all(|name| row.get(name) == last_written.get(name))
+            }) {
+                Some(i) => {
+                    log!(
+                        "resuming after {} item(s) written by a previous run.",
+                        resumed.0
+                    );
+                    i + 1
+                }
+                // Deleted items are no longer scanned, so there's nothing to skip.
+                None if options.delete && soft_delete.is_none() => {
+                    log!(
+                        "resuming after {} item(s) deleted by a previous run.",
+                        resumed.0
+                    );
+                    0
+                }
+                // Without it, there's no telling which items were written, and
+                // editing them again could apply edits twice (like `a` to `aa`).
+                None => {
+                    log!(
+                        "the last item written by a previous run is gone, so it can't be resumed without editing again the items it wrote. to start over anyway, pass a new --job-id."
+                    );
+                    progress.error("state", "the last item written by a previous run is gone");
+                    notify::exit(1);
+                }
+            }
+        }
+        None => 0,
+    };
+
+    if pages.is_none() {
+        progress.phase("plan");
+    }
+    let mut tallies = Tallies::default();
+    let mut dirty = Vec::new();
+    let inverse = options
+        .edit
+        .rename
+        .iter()
+        .rev()
+        .map(Replace::inverse)
+        .collect::<Vec<_>>();
+    // Items that the inverse rules would change before the run has even
+    // happened, meaning that they would not be a clean rollback.
+    let mut not_invertible = 0;
+    // Items skipped for values outside a remap, with a few examples.
+    let mut unremapped = (0, Vec::new());
+    // Items whose edits panicked or timed out, and why.
+    let mut quarantined = Vec::new();
+    // Keys of the items missing each required attribute, and how many have it.
+    let mut coverage = match &options.command {
+        Some(Command::Coverage { require, .. }) => require
+            .iter()
+            .map(|path| (path.as_str(), 0, Vec::new()))
+            .collect(),
+        _ => Vec::new(),
+    };
+    // Attribute names, to check.
+    let mut census = names::Census::default();
+    // Rows of the view.
+    let mut viewed = Vec::new();
+    // Keys of items with a reference, and the referenced value.
+    let mut references = Vec::new();
+    // With `stats`, what's reported about the items.
+    let mut summaries = match &options.command {
+        Some(Command::Stats {
+            attr,
+            group_by,
+            sizes,
+            key_distribution,
+            top,
+            ..
+        }) => report::Summaries {
+            numbers: attr.iter().map(|a| report::NumberSummary::new(a)).collect(),
+            frequencies: group_by
+                .iter()
+                .map(|a| report::Frequencies::new(a))
+                .collect(),
+            sizes: sizes.then(|| report::SizeSummary::new(&key_names)),
+            partitions: key_distribution.then(|| report::Frequencies::new(&key_names[0]).top(*top)),
+            count: 0,
+        },
+        _ => report::Summaries::default(),
+    };
+    // With `compare`, the items to compare.
+    let mut compared = Vec::new();
+    let now_string = aws_smithy_types::DateTime::from_secs(now)
+        .fmt(aws_smithy_types::date_time::Format::DateTime)
+        .unwrap_or_default();
+    // With `apply`, what to write in place of each planned item (by key).
+    let mut planned = applying
+        .into_iter()
+        .map(|(old, new)| {
+            (
+                json::item_to_json(&plan::key(&key_names, &old)).to_string(),
+                new,
+            )
+        })
+        .collect::<HashMap<_, _>>();
+    // Replayed items changed since they were audited, or rolled back items
+    // changed since they were journaled, with a few examples.
+    let mut conflicts = (0, Vec::new());
+    // Rolled back items that already are as they were.
+    let mut restored = 0;
+    let mut excluded = 0;
+    let mut expired = 0;
+    // Items skipped because they are already in the index being backfilled
+    // (only fetching by key doesn't filter them out).
+    let mut indexed = 0;
+    let replan = (options.conflict_retries > 0).then(|| Replan {
+        retries: options.conflict_retries,
+        rules: options
+            .edit
+            .rename
+            .iter()
+            .cloned()
+            .map(Rule::Rename)
+            .chain(options.edit.remove.iter().cloned().map(Rule::Remove))
+            .collect(),
+        conditions: &options.conditions,
+        expired: expired_attribute
+            .as_deref()
+            .map(|attribute| (attribute, now)),
+        diff_ignore: &options.diff_ignore,
+    });
+    let table = Table {
+        client: &items_client,
+        name: &options.table,
+        key_names: &key_names,
+        batch: options.batch,
+        update_mode: options.update_mode,
+        transactional: options.transactional,
+        concurrency: options.write_concurrency,
+        checkpoint: checkpoint.as_ref(),
+        journal: journal.as_ref(),
+        retry: Retry {
+            max_retries: options.max_retries,
+        },
+        replan: replan.as_ref(),
+    };
+    let verb = if options.delete && soft_delete.is_none() {
+        "deleted"
+    } else {
+        "updated"
+    };
+    // With `--stream`, whether a rollback file was written (once it's known,
+    // before the first page is written).
+    let mut rollback = None;
+    // With `--stream`, the pages still to plan, how writing them is paced, and
+    // how many items were written.
+    let mut stream = pages.map(|pages| {
+        let throttle = Throttle::new(
+            Duration::from_secs(options.throttle_pause_after),
+            options.slow_start,
+            options.max_wcu,
+        );
+        (pages, throttle, Stats::default(), Written::default())
+    });
+    rows.drain(..skip);
+    let mut rows = rows.into_iter();
+    'rows: loop {
+        let mut row = match (rows.next(), &mut stream) {
+            (Some(row), _) => row,
+            (None, None) => break,
+            // A page is written once it's planned, while the scan reads ahead.
+            (None, Some((pages, throttle, stats, written))) => {
+                // Problems stop the run before the page with them is written,
+                // but after the pages before it were.
+                refuse_invalid(&tallies.invalid, &progress);
+                refuse_collisions(&options.edit.rename, &tallies.collisions, &progress);
+                refuse_overwritten(&tallies.overwritten, &progress);
+                // Written before the first page is, as without `--stream`, from
+                // what's known so far.
+                if rollback.is_none() {
+                    rollback = Some(write_rollback(
+                        &options,
+                        &inverse,
+                        &tallies.edits,
+                        tallies.result.overwrites,
+                        not_invertible,
+                    ));
+                }
+                let outcome = tokio::select! {
+                    result = write(&table, std::mem::take(&mut dirty), throttle, stats, &progress, None, written) => Some(result),
+                    _ = tokio::signal::ctrl_c() => None,
+                };
+                // Only the count is reported, so the edits needn't be kept.
+                written.edits.clear();
+                if !matches!(outcome, Some(Ok(()))) {
+                    exit_after_write(
+                        outcome.and_then(Result::err),
+                        written.count,
+                        verb,
+                        &options,
+                        &progress,
+                    );
+                }
+                match pages.next().await {
+                    Ok(Some((_, page))) => {
+                        // Items aren't deduplicated across pages, which would
+                        // take remembering every key; a repeated one would
+                        // fail its conditional write, stopping the run.
+                        rows = page.into_iter();
+                        continue;
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        log!(
+                            "after {} successfully {} item(s), error scanning: {}",
+                            written.count,
+                            verb,
+                            e
+                        );
+                        progress.error("scan", &e.to_string());
+                        notify::exit(1);
+                    }
+                }
+            }
+        };
+        if checkpoint
+            .as_ref()
+            .is_some_and(|c| c.was_written(&plan::key(&key_names, &row)))
+        {
+            continue;
+        }
+        if !planned.is_empty() {
+            if let Some(new) =
+                planned.remove(&json::item_to_json(&plan::key(&key_names, &row)).to_string())
+            {
+                dirty.push((row, new));
+            }
+            continue;
+        }
+        if let Some(attribute) = &expired_attribute {
+            if is_expired(&row, attribute, now) {
+                expired += 1;
+                continue;
+            }
+        }
+        if exclusions.iter().any(|e| e.matches(&row)) {
+            excluded += 1;
+            continue;
+        }
+        if let Some(index) = &index {
+            if matches!(index.entry(&row), backfill::Entry::Indexed) {
+                indexed += 1;
+                continue;
+            }
+        }
+        if !options.key_pattern.iter().all(|p| p.matches(&row))
+            || !options.conditions.iter().all(|c| c.matches(&row))
+        {
+            continue;
+        }
+        if !replayed.is_empty() {
+            let id = json::item_to_json(&plan::key(&key_names, &row)).to_string();
+            let old = row.clone();
+            let conflict = replays
+                .remove(&id)
+                .unwrap_or_default()
+                .iter()
+                .find_map(|record| record.apply(&mut row).err());
+            match conflict {
+                Some(path) => {
+                    conflicts.0 += 1;
+                    if conflicts.1.len() < 3 {
+                        conflicts
+                            .1
+                            .push(format!("{} ({})", format_key(&old, &key_names), path));
+                    }
+                }
+                None if row.is_empty() => dirty.push((old, None)),
+                None if row != old => dirty.push((old, Some(row))),
+                None => {}
+            }
+            continue;
+        }
+        if !restoring.is_empty() {
+            let id = json::item_to_json(&plan::key(&key_names, &row)).to_string();
+            match restoring.remove(&id) {
+                Some(restore) if row == restore.original => restored += 1,
+                Some(restore) if row == restore.written => {
+                    dirty.push((row, Some(restore.original)))
+                }
+                Some(_) => {
+                    conflicts.0 += 1;
+                    if conflicts.1.len() < 3 {
+                        conflicts.1.push(format_key(&row, &key_names));
+                    }
+                }
+                None => {}
+            }
+            continue;
+        }
+        if !coverage.is_empty() {
+            for (path, present, missing) in &mut coverage {
+                if has_path(&row, path) {
+                    *present += 1;
+                } else {
+                    missing.push(format_key(&row, &key_names));
+                }
+            }
+            continue;
+        }
+        if let Some(Command::CheckNames { .. }) = &options.command {
+            census.add(&row);
+            continue;
+        }
+        if let Some(Command::View { select, .. }) = &options.command {
+            viewed.push(select.row(&row));
+            continue;
+        }
+        if let Some(Command::Stats { .. }) = &options.command {
+            summaries.add(&row);
+            continue;
+        }
+        if let Some(Command::Compare { .. }) = &options.command {
+            compared.push(row);
+            continue;
+        }
+        if let Some(Command::CheckRefs { attr, .. }) = &options.command {
+            if let Some(value) = row.get(attr) {
+                references.push((format_key(&row, &key_names), value.clone()));
+            }
+            continue;
+        }
+        if remap_unknown == UnknownPolicy::Skip {
+            let unknown = options
+                .edit
+                .remap
+                .iter()
+                .find_map(|m| Some((&m.attribute, m.unknown(&row)?)));
+            if let Some((attribute, value)) = unknown {
+                unremapped.0 += 1;
+                if unremapped.1.len() < 3 {
+                    unremapped.1.push(format!(
+                        "{} ({}={})",
+                        format_key(&row, &key_names),
+                        attribute,
+                        value
+                    ));
+                }
+                continue;
+            }
+        }
+        let violations = match &validator {
+            Some(validator) => {
+                let violations = validator.violations(&row);
+                if violations.is_empty() {
+                    continue;
+                }
+                violations
+            }
+            None => Vec::new(),
+        };
+        let old = row.clone();
+        if options.delete {
+            if !violations.is_empty() {
+                // Deleting the item repairs it, so to speak.
+                tallies.repaired += 1;
+                tallies.violating.push(validate::report(
+                    &format_key(&old, &key_names),
+                    &violations,
+                    true,
+                ));
+            }
+            match soft_delete {
+                Some((attribute, expiry)) => {
+                    // Leave items that TTL will already delete sooner alone.
+                    if !is_expired(&row, attribute, expiry) {
+                        row.insert(attribute.clone(), AttributeValue::N(expiry.to_string()));
+                        dirty.push((old, Some(row)));
+                    }
+                }
+                None => dirty.push((old, None)),
+            }
+            continue;
+        }
+        if would_change(&old, &inverse) {
+            not_invertible += 1;
+        }
+        let started = Instant::now();
+        // Edits are isolated, so that one pathological item (e.g. one that
+        // panics) is quarantined instead of taking down the whole run, and
+        // only counted once they're all done.
+        let mut staged = Tallies::default();
+        let outcome = quarantinable(|| {
+            let result = &mut staged.result;
+            replace(String::new(), &mut row, &options.edit.rename, result);
+            for (a, b, path) in std::mem::take(&mut result.collisions) {
+                let (count, examples) = staged.collisions.entry((a, b)).or_default();
+                *count += 1;
+                examples.push(format!("{} ({})", format_key(&old, &key_names), path));
+            }
+            if !result.overwritten.is_empty() {
+                staged.overwritten.push((
+                    format_key(&old, &key_names),
+                    std::mem::take(&mut result.overwritten),
+                ));
+            }
+            if options.edit.soft_delete && transform::soft_delete(&mut row, &now_string) {
+                *staged.edits.entry("soft-delete").or_default() += 1;
+            }
+            if options.edit.undelete && transform::undelete(&mut row) {
+                *staged.edits.entry("undelete").or_default() += 1;
+            }
+        });
+        if let Err(panic) = outcome {
+            quarantined.push((format_key(&old, &key_names), panic_message(panic)));
+            continue;
+        }
+        if let Some((from_key, to_key)) = reencrypt_keys {
+            let mut reencrypted = false;
+            for attribute in &options.edit.reencrypt {
+                let reencrypt = transform::reencrypt(&kms, &mut row, attribute, from_key, to_key);
+                let outcome = match options.item_timeout {
+                    Some(timeout) => {
+                        let remaining =
+                            Duration::from_secs(timeout).saturating_sub(started.elapsed());
+                        tokio::time::timeout(remaining, reencrypt).await.ok()
+                    }
+                    None => Some(reencrypt.await),
+                };
+                match outcome {
+                    Some(Ok(true)) => reencrypted = true,
+                    Some(Ok(false)) => {}
+                    None => {
+                        quarantined.push((
+                            format_key(&old, &key_names),
+                            String::from("re-encrypting timed out"),
+                        ));
+                        continue 'rows;
+                    }
+                    Some(Err(e)) => {
+                        let message = format!("item {}: {}", format_key(&old, &key_names), e);
+                        log!("{}", message);
+                        progress.error("plan", &message);
+                        notify::exit(1);
+                    }
+                }
+            }
+            if reencrypted {
+                *staged.edits.entry("reencrypt").or_default() += 1;
+            }
+        }
+        let outcome = quarantinable(|| {
+            // Records which edits changed the item, and why any couldn't be made.
+            let mut record = |edit: &'static str, results: Vec<Result<bool, String>>| {
+                let mut changed = false;
+                for result in results {
+                    match result {
+                        Ok(c) => changed |= c,
+                        Err(e) => staged.invalid.push((format_key(&old, &key_names), e)),
+                    }
+                }
+                if changed {
+                    *staged.edits.entry(edit).or_default() += 1;
+                }
+            };
+            record(
+                "encode",
+                options
+                    .edit
+                    .encode
+                    .iter()
+                    .map(|e| e.apply(&mut row))
+                    .collect(),
+            );
+            record(
+                "parse-json",
+                options
+                    .edit
+                    .parse_json
+                    .iter()
+                    .map(|a| transform::parse_json(&mut row, a))
+                    .collect(),
+            );
+            record(
+                "stringify",
+                options
+                    .edit
+                    .stringify
+                    .iter()
+                    .map(|a| transform::stringify(&mut row, a))
+                    .collect(),
+            );
+            record(
+                "split",
+                options
+                    .edit
+                    .split
+                    .iter()
+                    .map(|s| s.apply(&mut row, options.allow_overwrite))
+                    .collect(),
+            );
+            record(
+                "regex-replace",
+                options
+                    .edit
+                    .regex_replace
+                    .iter()
+                    .map(|r| r.apply(&mut row))
+                    .collect(),
+            );
+            let mut rendered = |option: String, result: Result<Derived, String>| match result {
+                Ok(Derived::Changed) => Ok(true),
+                Ok(Derived::Unchanged) => Ok(false),
+                Ok(Derived::Missing(attribute)) => {
+                    let (count, examples) = staged.unrendered.entry(option).or_default();
+                    *count += 1;
+                    examples.push(format!("{} ({})", format_key(&old, &key_names), attribute));
+                    Ok(false)
+                }
+                Err(e) => Err(e),
+            };
+            let derived = options
+                .edit
+                .derive
+                .iter()
+                .map(|d| {
+                    rendered(
+                        format!("--derive '{}'", d),
+                        d.apply(&mut row, options.allow_overwrite),
+                    )
+                })
+                .collect();
+            let set = options
+                .edit
+                .set
+                .iter()
+                .map(|s| rendered(format!("--set '{}'", s), s.apply(&mut row)))
+                .collect();
+            let default = options
+                .edit
+                .default
+                .iter()
+                .map(|s| rendered(format!("--default '{}'", s), s.apply_default(&mut row)))
+                .collect();
+            record("derive", derived);
+            record("set", set);
+            record("default", default);
+            record(
+                "replace-value",
+                options
+                    .edit
+                    .replace_value
+                    .iter()
+                    .map(|r| r.apply(&mut row))
+                    .collect(),
+            );
+            let mapped = maps
+                .iter()
+                .enumerate()
+                .map(|(i, map)| match map.apply(&mut row) {
+                    Ok(Mapped::Changed) => Ok(true),
+                    Ok(Mapped::Unchanged) => Ok(false),
+                    Ok(Mapped::Unknown(value)) => {
+                        *staged
+                            .unmapped
+                            .entry(i)
+                            .or_default()
+                            .entry(value)
+                            .or_default() += 1;
+                        Ok(false)
+                    }
+                    Err(e) => Err(e),
+                })
+                .collect();
+            record("map", mapped);
+            let remapped = options
+                .edit
+                .remap
+                .iter()
+                .map(|map| match map.apply(&mut row) {
+                    Ok(Mapped::Changed) => Ok(true),
+                    Ok(Mapped::Unchanged) => Ok(false),
+                    Ok(Mapped::Unknown(value)) if remap_unknown == UnknownPolicy::Fail => {
+                        Err(format!(
+                            "attribute '{}' has value '{}' outside --remap",
+                            map.attribute, value
+                        ))
+                    }
+                    Ok(Mapped::Unknown(_)) => Ok(false),
+                    Err(e) => Err(e),
+                })
+                .collect();
+            record("remap", remapped);
+            record(
+                "convert",
+                options
+                    .edit
+                    .convert
+                    .iter()
+                    .map(|c| c.apply(&mut row))
+                    .collect(),
+            );
+            record(
+                "round",
+                options
+                    .edit
+                    .round
+                    .iter()
+                    .map(|r| r.apply(&mut row))
+                    .collect(),
+            );
+            // Deduping first, so that equal elements don't have to be sorted.
+            record(
+                "list-dedupe",
+                options
+                    .edit
+                    .list_dedupe
+                    .iter()
+                    .map(|a| transform::list_dedupe(&mut row, a))
+                    .collect(),
+            );
+            record(
+                "list-sort",
+                options
+                    .edit
+                    .list_sort
+                    .iter()
+                    .map(|a| transform::list_sort(&mut row, a))
+                    .collect(),
+            );
+            record(
+                "to-set",
+                options
+                    .edit
+                    .to_set
+                    .iter()
+                    .map(|a| transform::to_set(&mut row, a))
+                    .collect(),
+            );
+            record(
+                "to-list",
+                options
+                    .edit
+                    .to_list
+                    .iter()
+                    .map(|a| transform::to_list(&mut row, a))
+                    .collect(),
+            );
+            if !options.edit.remove.is_empty() {
+                record(
+                    "remove",
+                    vec![Ok(remove("", &mut row, &options.edit.remove))],
+                );
+            }
+            if options.edit.drop_unknown {
+                let paths = allow_list.drop_unknown(&mut row);
+                record("drop-unknown", vec![Ok(!paths.is_empty())]);
+                for path in paths {
+                    *staged.dropped.entry(path).or_default() += 1;
+                }
+            }
+            if let Some(validator) = validator.as_ref().filter(|_| !violations.is_empty()) {
+                let fixed = validator.violations(&row).is_empty();
+                staged.repaired += fixed as usize;
+                staged.violating.push(validate::report(
+                    &format_key(&old, &key_names),
+                    &violations,
+                    fixed,
+                ));
+            }
+        });
+        if let Err(panic) = outcome {
+            quarantined.push((format_key(&old, &key_names), panic_message(panic)));
+            continue;
+        }
+        if let Some(timeout) = options.item_timeout {
+            // Synchronous edits can't be interrupted, but their result can be
+            // discarded.
+            if started.elapsed() > Duration::from_secs(timeout) {
+                quarantined.push((
+                    format_key(&old, &key_names),
+                    format!("editing took longer than {}s", timeout),
+                ));
+                continue;
+            }
+        }
+        tallies.add(staged);
+        if !diff::changes_ignoring(&old, &row, &options.diff_ignore).is_empty() {
+            dirty.push((old, Some(row)));
+        }
+    }
+
+    let Tallies {
+        result,
+        collisions,
+        overwritten,
+        edits,
+        invalid,
+        unrendered,
+        unmapped,
+        violating,
+        repaired,
+        dropped,
+    } = tallies;
+    if excluded > 0 {
+        log!("excluded {} item(s) by key.", excluded);
+    }
+    if !quarantined.is_empty() {
+        log!(
+            "quarantined {} item(s) whose edits failed, leaving them alone:",
+            quarantined.len()
+        );
+        for (key, reason) in quarantined.iter().take(LISTED) {
+            log!("  {}: {}", key, reason);
+        }
+        if quarantined.len() > LISTED {
+            log!("  ...and {} more item(s)", quarantined.len() - LISTED);
+        }
+        if let Some(path) = &options.quarantine_file {
+            let lines = quarantined
+                .iter()
+                .map(|(key, reason)| format!("{}\n", json!({ "key": key, "reason": reason })))
+                .collect::<String>();
+            if let Err(e) = std::fs::write(path, lines) {
+                log!("error writing quarantined items: {}", e);
+                notify::exit(1);
+            }
+        }
+        progress.emit("quarantined", json!({ "items": quarantined.len() }));
+    }
+    if expired > 0 {
+        log!("skipped {} expired item(s).", expired);
+    }
+    if indexed > 0 {
+        log!("skipped {} item(s) already in the index.", indexed);
+    }
+    if let Some(Command::Coverage { report, .. }) = &options.command {
+        let lines = coverage
+            .iter()
+            .map(|(path, present, missing)| {
+                log!(
+                    "{} item(s) have {} and {} item(s) don't.",
+                    present,
+                    path,
+                    missing.len()
+                );
+                let line = json!({
+                    "attribute": path,
+                    "present": present,
+                    "missing": missing.len(),
+                    "missing_keys": missing,
+                });
+                format!("{}\n", line)
+            })
+            .collect::<String>();
+        let written = match report {
+            Some(path) => std::fs::write(path, lines),
+            None => {
+                print!("{}", lines);
+                Ok(())
+            }
+        };
+        if let Err(e) = written {
+            log!("error writing report: {}", e);
+            notify::exit(1);
+        }
+        progress.phase("done");
+        notify::exit(0);
+    }
+    if let Some(Command::View { select, format }) = &options.command {
+        let rows = viewed.len();
+        for line in view::lines(*format, select.headers(), viewed) {
+            output!("{}", line);
+        }
+        log!("viewed {} item(s).", rows);
+        progress.phase("done");
+        notify::exit(0);
+    }
+    if let Some(Command::CheckNames { report }) = &options.command {
+        let records = census.report();
+        for record in &records {
+            log!(
+                "{} item(s) have '{}' ({}){}: {}",
+                record["items"],
+                record["escaped"].as_str().unwrap_or_default(),
+                match record["parent"].as_str() {
+                    Some("") | None => String::from("at the root"),
+                    Some(parent) => format!("in {}", parent),
+                },
+                match (record["rule"].as_str(), record["existing"].as_u64()) {
+                    (Some(rule), Some(0)) => format!(", fixed by --rename '{}'", rule),
+                    (Some(rule), existing) => format!(
+                        ", fixed by --rename '{}' (but {} item(s) already have '{}')",
+                        rule,
+                        existing.unwrap_or_default(),
+                        record["fixed"].as_str().unwrap_or_default()
+                    ),
+                    (None, _) => String::new(),
+                },
+                record["problems"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(serde_json::Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        log!("{} suspicious attribute name(s).", records.len());
+        let lines = records
+            .iter()
+            .map(|record| format!("{}\n", record))
+            .collect::<String>();
+        let written = match report {
+            Some(path) => std::fs::write(path, lines),
+            None => {
+                print!("{}", lines);
+                Ok(())
+            }
+        };
+        if let Err(e) = written {
+            log!("error writing report: {}", e);
+            notify::exit(1);
+        }
+        progress.phase("done");
+        notify::exit(if records.is_empty() { 0 } else { 1 });
+    }
+    if let Some(Command::Stats { .. }) = &options.command {
+        log!("summarized {} item(s).", summaries.count);
+        for summary in &summaries.numbers {
+            output!("{}", summary);
+        }
+        for summary in &summaries.frequencies {
+            output!("{}", summary);
+        }
+        if let Some(summary) = &summaries.sizes {
+            output!("{}", summary);
+        }
+        if let Some(summary) = &summaries.partitions {
+            output!("{}", summary);
+        }
+        progress.phase("done");
+        notify::exit(0);
+    }
+    if let Some(Command::Compare {
+        as_of,
+        s3_bucket,
+        s3_prefix,
+        report,
+    }) = &options.command
+    {
+        progress.phase("export");
+        log!("exporting {} as of {}...", options.table, as_of);
+        let table_arn = description.table_arn.as_deref().unwrap_or_default();
+        let exported = match export::export(
+            &client,
+            table_arn,
+            as_of.secs(),
+            s3_bucket,
+            s3_prefix.as_deref(),
+        )
+        .await
+        {
+            Ok(manifest) => export::load(s3_bucket, &manifest, &aws_cli_args(&options)).await,
+            Err(e) => Err(e),
+        };
+        let exported = match exported {
+            Ok(items) => items,
+            Err(e) => {
+                log!("{}", e);
+                progress.error("export", &e.to_string());
+                notify::exit(1);
+            }
+        };
+        // Limited to the rules' attributes, if they name specific ones.
+        let attributes = affected_root_attributes(&options.edit.rename).filter(|a| !a.is_empty());
+        let limit = |item: &HashMap<String, AttributeValue>| match &attributes {
+            Some(attributes) => item
+                .iter()
+                .filter(|(name, _)| key_names.contains(name) || attributes.contains(&name.as_str()))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            None => item.clone(),
+        };
+        let mut before = exported
+            .iter()
+            .filter(|item| {
+                !exclusions.iter().any(|e| e.matches(item))
+                    && (keys.is_empty() || keys.iter().any(|k| k.matches(item)))
+                    && key_condition.as_ref().is_none_or(|k| k.matches(item))
+                    && options.key_pattern.iter().all(|p| p.matches(item))
+                    && options.conditions.iter().all(|c| c.matches(item))
+            })
+            .map(|item| (format_key(item, &key_names), limit(item)))
+            .collect::<BTreeMap<_, _>>();
+        let mut lines = String::new();
+        let (mut changed, mut added) = (0, 0);
+        let deleted = HashMap::new();
+        for item in &compared {
+            let key = format_key(item, &key_names);
+            let after = limit(item);
+            let (status, changes) = match before.remove(&key) {
+                Some(old) => {
+                    let changes = diff::changes(&old, &after)
+                        .iter()
+                        .map(diff::Change::to_json)
+                        .collect::<Vec<_>>();
+                    if changes.is_empty() {
+                        continue;
+                    }
+                    changed += 1;
+                    ("changed", changes)
+                }
+                None => {
+                    added += 1;
+                    let changes = diff::changes(&deleted, &after)
+                        .iter()
+                        .map(diff::Change::to_json)
+                        .collect();
+                    ("added", changes)
+                }
+            };
+            lines.push_str(&format!(
+                "{}\n",
+                json!({ "key": key, "status": status, "changes": changes })
+            ));
+        }
+        for (key, old) in &before {
+            let changes = diff::changes(old, &deleted)
+                .iter()
+                .map(diff::Change::to_json)
+                .collect::<Vec<_>>();
+            lines.push_str(&format!(
+                "{}\n",
+                json!({ "key": key, "status": "removed", "changes": changes })
+            ));
+        }
+        log!(
+            "since {}, {} item(s) changed, {} were added, and {} were removed.",
+            as_of,
+            changed,
+            added,
+            before.len()
+        );
+        let written = match report {
+            Some(path) => std::fs::write(path, lines),
+            None => {
+                print!("{}", lines);
+                Ok(())
+            }
+        };
+        if let Err(e) = written {
+            log!("error writing report: {}", e);
+            notify::exit(1);
+        }
+        progress.phase("done");
+        notify::exit(0);
+    }
+    if let Some(Command::CheckRefs {
+        attr,
+        references_table,
+        references_key,
+        report,
+    }) = &options.command
+    {
+        let mut seen = HashSet::new();
+        let values = references
+            .iter()
+            .map(|(_, value)| value)
+            .filter(|value| seen.insert(refs::reference_id(value)))
+            .cloned()
+            .collect::<Vec<_>>();
+        progress.phase("check_refs");
+        log!(
+            "looking up {} distinct reference(s) in {}...",
+            values.len(),
+            references_table
+        );
+        let existing = match refs::existing(&client, references_table, references_key, values).await
+        {
+            Ok(existing) => existing,
+            Err(e) => {
+                log!("error looking up references: {}", e);
+                progress.error("check_refs", &e.to_string());
+                notify::exit(1);
+            }
+        };
+        let dangling = references
+            .iter()
+            .filter(|(_, value)| !existing.contains(&refs::reference_id(value)))
+            .map(|(key, value)| {
+                format!(
+                    "{}\n",
+                    json!({
+                        "key": key,
+                        "attribute": attr,
+                        "value": render::value(value),
+                    })
+                )
+            })
+            .collect::<Vec<_>>();
+        let written = match report {
+            Some(path) => std::fs::write(path, dangling.concat()),
+            None => {
+                print!("{}", dangling.concat());
+                Ok(())
+            }
+        };
+        if let Err(e) = written {
+            log!("error writing report: {}", e);
+            notify::exit(1);
+        }
+        log!(
+            "{} of {} item(s) with {} reference a nonexistent item.",
+            dangling.len(),
+            references.len(),
+            attr
+        );
+        progress.phase("done");
+        notify::exit(if dangling.is_empty() { 0 } else { 1 });
+    }
+    if let Some(Command::Validate { report, .. }) = &options.command {
+        let lines = violating
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect::<String>();
+        let written = match report {
+            Some(path) => std::fs::write(path, lines),
+            None => {
+                print!("{}", lines);
+                Ok(())
+            }
+        };
+        if let Err(e) = written {
+            log!("error writing report: {}", e);
+            notify::exit(1);
+        }
+        log!("{} item(s) violate the schema.", violating.len());
+        if options.edits().is_empty() && !options.delete {
+            progress.phase("done");
+            notify::exit(if violating.is_empty() { 0 } else { 1 });
+        }
+        if repaired < violating.len() {
+            log!(
+                "{} of them would still violate it after the edits.",
+                violating.len() - repaired
+            );
+        }
+    }
+    if unremapped.0 > 0 {
+        log!(
+            "skipped {} item(s) with values outside --remap, e.g. {}",
+            unremapped.0,
+            unremapped.1.join(", ")
+        );
+    }
+    if conflicts.0 > 0 && matches!(options.command, Some(Command::Rollback { .. })) {
+        log!(
+            "skipped {} rolled back item(s) changed since they were journaled, e.g. {}",
+            conflicts.0,
+            conflicts.1.join(", ")
+        );
+    } else if conflicts.0 > 0 {
+        log!(
+            "skipped {} replayed item(s) changed since they were audited, e.g. {}",
+            conflicts.0,
+            conflicts.1.join(", ")
+        );
+    }
+    if restored > 0 {
+        log!(
+            "skipped {} rolled back item(s) that already are as they were.",
+            restored
+        );
+    }
+    if !restoring.is_empty() {
+        let mut missing = restoring
+            .values()
+            .map(|restore| format_key(&restore.written, &key_names))
+            .collect::<Vec<_>>();
+        missing.sort_unstable();
+        log!(
+            "skipped {} rolled back item(s) that don't exist anymore, e.g. {}",
+            missing.len(),
+            missing[..missing.len().min(3)].join(", ")
+        );
+    }
+    // Items that are gone were already deleted, if that's what was audited.
+    let mut missing = replays
+        .values()
+        .filter(|records| !records.last().is_some_and(|r| r.deleted))
+        .map(|records| records[0].key.to_string())
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        missing.sort_unstable();
+        log!(
+            "skipped {} replayed item(s) that don't exist, e.g. {}",
+            missing.len(),
+            missing[..missing.len().min(3)].join(", ")
+        );
+    }
+    for (i, values) in &unmapped {
+        log!(
+            "{} item(s) have {} distinct value(s) of '{}' absent from {}:",
+            values.values().sum::<usize>(),
+            values.len(),
+            maps[*i].attribute,
+            options.edit.map[*i].path.display()
+        );
+        for (value, count) in values.iter().take(LISTED) {
+            log!("  {}: {} item(s)", value, count);
+        }
+        if values.len() > LISTED {
+            log!("  ...and {} more value(s)", values.len() - LISTED);
+        }
+    }
+    if options.edit.strict_map && !unmapped.is_empty() {
+        log!("refusing to proceed with unmapped values, since --strict-map was passed.");
+        progress.error("plan", "values absent from mapping");
+        notify::exit(1);
+    }
+    for (option, (count, examples)) in &unrendered {
+        log!(
+            "skipped {} item(s) missing attributes for {}, e.g. {}",
+            count,
+            option,
+            examples.join(", ")
+        );
+    }
+    if let Some((_, _, stats, written)) = stream {
+        log_fallbacks(&stats);
+        log!("successfully {} {} items.", verb, written.count);
+        if rollback == Some(true)
+            && (!edits.is_empty() || result.overwrites > 0 || not_invertible > 0)
+        {
+            log!(
+                "{} was written before the first page, but later pages made this run irreversible, so it wouldn't cleanly roll it back.",
+                options.rollback_file.display()
+            );
+        }
+        progress.emit("done", json!({ "written": written.count }));
+        return;
+    }
+    // How many of the items to write will be added to the index.
+    let mut backfilled = 0;
+    if let Some(index) = &index {
+        let mut unindexed = (0, Vec::new());
+        let mut mistyped = Vec::new();
+        for (old, new) in &dirty {
+            match index.entry(new.as_ref().unwrap_or(old)) {
+                backfill::Entry::Indexed => backfilled += 1,
+                backfill::Entry::Missing(attribute) => {
+                    unindexed.0 += 1;
+                    if unindexed.1.len() < 3 {
+                        unindexed.1.push(format!(
+                            "{} ({})",
+                            format_key(old, &key_names),
+                            attribute
+                        ));
+                    }
+                }
+                backfill::Entry::Mistyped(attribute, type_name, expected) => {
+                    mistyped.push((format_key(old, &key_names), attribute, type_name, expected))
+                }
+            }
+        }
+        if unindexed.0 > 0 {
+            log!(
+                "{} item(s) would still be missing a key attribute of the index, so stay out of it, e.g. {}",
+                unindexed.0,
+                unindexed.1.join(", ")
+            );
+        }
+        if !mistyped.is_empty() {
+            log!(
+                "{} item(s) would have a key attribute of the index with the wrong type, which DynamoDB rejects:",
+                mistyped.len()
+            );
+            for (key, attribute, type_name, expected) in mistyped.iter().take(LISTED) {
+                log!(
+                    "  {}: {} is {}, not {}",
+                    key,
+                    attribute,
+                    type_name,
+                    expected
+                );
+            }
+            if mistyped.len() > LISTED {
+                log!("  ...and {} more item(s)", mistyped.len() - LISTED);
+            }
+            progress.error("plan", "some items would have mistyped index keys");
+            notify::exit(1);
+        }
+    }
+
+    progress.emit(
+        "planned",
+        json!({
+            "replacements": result.replacements,
+            "items": dirty.len(),
+            "overwrites": result.overwrites,
+        }),
+    );
+
+    refuse_invalid(&invalid, &progress);
+
+    if dirty.is_empty() {
+        if options.delete {
+            log!("no matching items found.");
+        } else if options.edit.rename.is_empty() {
+            log!("no changes found.");
+        } else {
+            log!("no replacements found.");
+        }
+        progress.phase("done");
+        return;
+    }
+
+    refuse_collisions(&options.edit.rename, &collisions, &progress);
+    refuse_overwritten(&overwritten, &progress);
+
+    if let Some(max_changes) = options.max_changes {
+        if dirty.len() > max_changes {
+            let message = format!(
+                "{} item(s) would be modified, exceeding --max-changes {}",
+                dirty.len(),
+                max_changes
+            );
+            log!("{}. aborting.", message);
+            progress.error("plan", &message);
+            notify::exit(1);
+        }
+    }
+
+    if let Some(max_growth) = options.max_growth {
+        let grown = dirty
+            .iter()
+            .filter_map(|(old, new)| {
+                let (old_size, new_size) =
+                    (report::item_size(old), report::item_size(new.as_ref()?));
+                let growth = (new_size as f64 / old_size.max(1) as f64 - 1.0) * 100.0;
+                (growth > max_growth.0).then(|| (format_key(old, &key_names), old_size, new_size))
+            })
+            .collect::<Vec<_>>();
+        if !grown.is_empty() {
+            log!(
+                "{} item(s) would grow by more than --max-growth {}:",
+                grown.len(),
+                max_growth
+            );
+            for (key, old_size, new_size) in grown.iter().take(LISTED) {
+                log!("  {}: {}B to {}B", key, old_size, new_size);
+            }
+            if grown.len() > LISTED {
+                log!("  ...and {} more item(s)", grown.len() - LISTED);
+            }
+            progress.error("plan", "items would grow by more than --max-growth");
+            notify::exit(1);
+        }
+    }
+
+    match soft_delete {
+        Some((attribute, expiry)) => log!(
+            "prepared to set TTL attribute '{}' to {} on {} item(s)...",
+            attribute,
+            expiry,
+            dirty.len()
+        ),
+        None if options.delete => log!("prepared to delete {} item(s)...", dirty.len()),
+        None if options.edit.rename.is_empty() => {}
+        None => log!(
+            "prepared to make {} replacement(s) across {} item(s) with {} overwritten key(s)...",
+            result.replacements,
+            dirty.len(),
+            result.overwrites
+        ),
+    }
+    for (edit, count) in &edits {
+        log!("prepared to {} {} item(s)...", edit, count);
+    }
+    // The `--partition-by` value of an item, by which changes are written
+    // (and confirmed) one group at a time.
+    let partition = |item: &HashMap<String, AttributeValue>| {
+        options
+            .partition_by
+            .as_ref()
+            .and_then(|attribute| item.get(attribute))
+            .map(render::label)
+            .unwrap_or_default()
+    };
+    if let Some(attribute) = &options.partition_by {
+        let mut counts = BTreeMap::<String, usize>::new();
+        for (old, _) in &dirty {
+            *counts.entry(partition(old)).or_default() += 1;
+        }
+        log!("by {}:", attribute);
+        for (value, count) in &counts {
+            log!("  {}={}: {} item(s)", attribute, value, count);
+        }
+    }
+
+    let account = match aws_sdk_sts::Client::new(&shared_config)
+        .get_caller_identity()
+        .send()
+        .await
+    {
+        Ok(identity) => identity.account.unwrap_or_else(|| String::from("?")),
+        Err(e) => {
+            log!("could not resolve account: {}", e);
+            String::from("?")
+        }
+    };
+    log!(
+        "account: {}, region: {}, table: {}",
+        account,
+        shared_config.region().map(|r| r.as_ref()).unwrap_or("?"),
+        options.table
+    );
+    if !options.edit.rename.is_empty() {
+        print_breakdown(&options.edit.rename, &result);
+    }
+
+    if let Some(plan_format) = options.plan_format {
+        for (old, new) in &dirty {
+            output!(
+                "{}",
+                plan_format.render(&options.table, &key_names, old, new.as_ref())
+            );
+        }
+    }
+
+    if !dropped.is_empty() {
+        log!("unknown attribute(s) to drop:");
+        for (path, count) in &dropped {
+            log!("  {}: {} item(s)", path, count);
+        }
+    }
+
+    // Dropping unknown attributes is destructive enough to always review.
+    let sample = if options.edit.drop_unknown {
+        log!(
+            "all {} change(s), since --drop-unknown was passed:",
+            dirty.len()
+        );
+        Some(dirty.iter().collect::<Vec<_>>())
+    } else if let Some(n) = options.preview {
+        let mut rng = rand::thread_rng();
+        let sample = dirty.choose_multiple(&mut rng, n).collect::<Vec<_>>();
+        log!("preview of {} random change(s):", sample.len());
+        Some(sample)
+    } else if options.dry_run {
+        log!("all {} change(s), since --dry-run was passed:", dirty.len());
+        Some(dirty.iter().collect())
+    } else {
+        None
+    };
+    if let Some(sample) = sample {
+        let deleted = HashMap::new();
+        for (old, new) in sample {
+            log!("{}", format_key(old, &key_names));
+            let new = new.as_ref().unwrap_or(&deleted);
+            for change in diff::changes_ignoring(old, new, &options.diff_ignore) {
+                for line in change.to_string().lines() {
+                    log!("  {}", line);
+                }
+            }
+        }
+    }
+
+    if let Some(path) = &options.diff_out {
+        let deleted = HashMap::new();
+        let lines = dirty
+            .iter()
+            .map(|(old, new)| {
+                let after = new.as_ref().unwrap_or(&deleted);
+                let changes = diff::changes_ignoring(old, after, &options.diff_ignore)
+                    .iter()
+                    .map(diff::Change::to_json)
+                    .collect::<Vec<_>>();
+                format!(
+                    "{}\n",
+                    json!({
+                        "key": format_key(old, &key_names),
+                        "deleted": new.is_none(),
+                        "changes": changes,
+                    })
+                )
+            })
+            .collect::<String>();
+        if let Err(e) = std::fs::write(path, lines) {
+            log!("error writing diffs: {}", e);
+            notify::exit(1);
+        }
+        log!("wrote {} change(s) to {}.", dirty.len(), path.display());
+    }
+
+    if let Some(period) = options.traffic_check {
+        progress.phase("traffic_check");
+        check_traffic(&options, &description, &key_names, &dirty, period.0).await;
+    }
+
+    if let Some(path) = &options.edit.plan_out {
+        if let Err(e) = plan::save(path, &options.table, &dirty) {
+            log!("error writing plan: {}", e);
+            notify::exit(1);
+        }
+        log!(
+            "wrote {} change(s) to {}; to make them, run: dynamodb_bulk_edit --table {} apply {}",
+            dirty.len(),
+            path.display(),
+            options.table,
+            path.display()
+        );
+        progress.phase("done");
+        return;
+    }
+    if options.dry_run {
+        log!("not writing anything, since --dry-run was passed.");
+        progress.phase("done");
+        return;
+    }
+
+    progress.phase("confirm");
+    match &payload {
+        _ if yes => log!("confirmed by --yes."),
+        Some(payload) => {
+            if !payload.confirm {
+                log!("stopping before writing, since the payload doesn't set \"confirm\": true.");
+                progress.phase("canceled");
+                notify::exit(0);
+            }
+        }
+        None => {
+            if !confirm("confirm") {
+                println!("canceled.");
+                progress.phase("canceled");
+                notify::exit(1);
+            }
+        }
+    }
+
+    backup_before(&client, &options, &progress, now).await;
+
+    write_rollback(
+        &options,
+        &inverse,
+        &edits,
+        result.overwrites,
+        not_invertible,
+    );
+
+    // Counted before writing, to know how many items the index should end up
+    // with, and before boosting, so that failing to count doesn't leave the
+    // capacity boosted.
+    let index_count = match &index {
+        Some(index) => {
+            match backfill::count(&items_client, &options.table, &index.name, &reads).await {
+                Ok(count) => Some(count),
+                Err(e) => {
+                    log!("error counting index: {}", e);
+                    progress.error("verify", &e.to_string());
+                    notify::exit(1);
+                }
+            }
+        }
+        None => None,
+    };
+
+    let boost = match options.boost_wcu {
+        Some(wcu) => {
+            let attributes = match soft_delete {
+                Some((attribute, _)) => Some(vec![attribute.as_str()]),
+                // Deleting an item removes it from every index.
+                None if options.delete => None,
+                None => affected_root_attributes(&options.edit.rename)
+                    .filter(|_| {
+                        options
+                            .edit
+                            .replace_value
+                            .iter()
+                            .all(|r| r.root_attribute().is_some())
+                            && options
+                                .edit
+                                .remove
+                                .iter()
+                                .all(|r| r.root_attribute().is_some())
+                    })
+                    .map(|mut attributes| {
+                        if options.edit.soft_delete || options.edit.undelete {
+                            attributes.extend([transform::DELETED, transform::DELETED_AT]);
+                        }
+                        attributes.extend(options.edit.reencrypt.iter().map(String::as_str));
+                        attributes.extend(options.edit.encode.iter().map(|e| e.attribute.as_str()));
+                        attributes.extend(options.edit.parse_json.iter().map(String::as_str));
+                        attributes.extend(options.edit.stringify.iter().map(String::as_str));
+                        attributes.extend(options.edit.derive.iter().map(|d| d.target.as_str()));
+                        attributes.extend(options.edit.set.iter().map(Set::root_attribute));
+                        attributes.extend(options.edit.default.iter().map(Set::root_attribute));
+                        attributes.extend(
+                            options
+                                .edit
+                                .regex_replace
+                                .iter()
+                                .filter_map(|r| r.path.split('.').next()),
+                        );
+                        attributes.extend(
+                            options
+                                .edit
+                                .replace_value
+                                .iter()
+                                .filter_map(ReplaceValue::root_attribute),
+                        );
+                        attributes.extend(
+                            options
+                                .edit
+                                .remove
+                                .iter()
+                                .filter_map(Remove::root_attribute),
+                        );
+                        attributes.extend(options.edit.map.iter().map(|m| m.attribute.as_str()));
+                        attributes.extend(options.edit.remap.iter().map(|m| m.attribute.as_str()));
+                        attributes
+                            .extend(options.edit.convert.iter().map(|c| c.attribute.as_str()));
+                        attributes.extend(options.edit.list_sort.iter().map(String::as_str));
+                        attributes.extend(options.edit.list_dedupe.iter().map(String::as_str));
+                        attributes.extend(options.edit.to_set.iter().map(String::as_str));
+                        attributes.extend(options.edit.to_list.iter().map(String::as_str));
+                        for round in &options.edit.round {
+                            attributes.extend(round.0.iter().map(|(a, _)| a.as_str()));
+                        }
+                        for split in &options.edit.split {
+                            attributes.push(&split.source);
+                            attributes.extend(split.targets.iter().map(String::as_str));
+                        }
+                        attributes
+                    }),
+            };
+            match capacity::boost(&client, &options.table, wcu, attributes.as_deref()).await {
+                Ok(boost) => boost,
+                Err(e) => {
+                    log!("error boosting write capacity: {}", e);
+                    progress.error("boost", &e.to_string());
+                    notify::exit(1);
+                }
+            }
+        }
+        None => None,
+    };
+
+    let mut throttle = Throttle::new(
+        Duration::from_secs(options.throttle_pause_after),
+        options.slow_start,
+        options.max_wcu,
+    );
+    let stats = Arc::new(Stats::default());
+    let reporter = options
+        .stats
+        .then(|| stats.spawn_reporter(dirty.len(), Duration::from_secs(options.stats_interval)));
+    let mut partitions = BTreeMap::<String, Vec<Edit>>::new();
+    for (old, new) in dirty {
+        partitions
+            .entry(partition(&old))
+            .or_default()
+            .push((old, new));
+    }
+    progress.phase("write");
+    let mut written = Written {
+        count: resumed.0,
+        prefix: resumed.0,
+        edits: Vec::new(),
+    };
+    let mut outcome = Some(Ok(()));
+    // Partitions left alone, after the operator declined to continue.
+    let mut declined = 0;
+    let partition_count = partitions.len();
+    for (i, (value, dirty)) in partitions.into_iter().enumerate() {
+        let attribute = options.partition_by.as_deref().unwrap_or_default();
+        if i > 0 && !yes && payload.is_none() {
+            let prompt = format!(
+                "continue with {}={} ({} item(s))?",
+                attribute,
+                value,
+                dirty.len()
+            );
+            if !confirm(&prompt) {
+                declined = partition_count - i;
+                break;
+            }
+        }
+        let before = written.count;
+        outcome = tokio::select! {
+            result = write(&table, dirty, &mut throttle, &stats, &progress, state.as_ref(), &mut written) => Some(result),
+            _ = tokio::signal::ctrl_c() => None,
+        };
+        if options.partition_by.is_some() {
+            log!(
+                "{}={}: {} {} item(s).",
+                attribute,
+                value,
+                verb,
+                written.count - before
+            );
+            progress.emit(
+                "partition",
+                json!({ "attribute": attribute, "value": value, "written": written.count - before }),
+            );
+        }
+        if !matches!(outcome, Some(Ok(()))) {
+            break;
+        }
+    }
+    if let Some(reporter) = reporter {
+        reporter.abort();
+    }
+
+    if let Some(boost) = boost {
+        log!("restoring write capacity...");
+        if let Err(e) = boost.restore(&client).await {
+            log!("{}. restore {} manually!", e, boost);
+            progress.error("restore", &e.to_string());
+        }
+    }
+
+    log_fallbacks(&stats);
+
+    if let Some(audit) = &audit {
+        // Only the edits that were written, in the order they were.
+        let deleted = HashMap::new();
+        let records = written
+            .edits
+            .iter()
+            .map(|(old, new)| {
+                let changes = diff::changes(old, new.as_ref().unwrap_or(&deleted))
+                    .iter()
+                    .map(diff::Change::to_json)
+                    .collect::<Vec<_>>();
+                json!({
+                    "run": audit.run(),
+                    "time": now_string,
+                    "table": options.table,
+                    "key": format_key(old, &key_names),
+                    "deleted": new.is_none(),
+                    "changes": changes,
+                })
+            })
+            .collect::<Vec<_>>();
+        for (sink, e) in audit.write(&records).await {
+            log!("{} (audit trail not saved to {})", e, sink);
+            progress.error("audit", &e.to_string());
+        }
+    }
+
+    if !matches!(outcome, Some(Ok(()))) {
+        exit_after_write(
+            outcome.and_then(Result::err),
+            written.count,
+            verb,
+            &options,
+            &progress,
+        );
+    }
+
+    if let Some(heartbeat) = heartbeat {
+        heartbeat.abort();
+    }
+    if let Some(state) = &state {
+        if let Err(e) = state.save(Status::Done, written.prefix, None).await {
+            log!("{}", e);
+            progress.error("state", &e.to_string());
+        }
+    }
+    if let Some(checkpoint) = &checkpoint {
+        if declined == 0 {
+            if let Err(e) = checkpoint.done() {
+                log!("error journaling to checkpoint: {}", e);
+                progress.error("checkpoint", &e.to_string());
+            }
+        } else {
+            log_resume_hint(&options);
+        }
+    }
+    if declined > 0 {
+        log!(
+            "after {} successfully {} item(s), stopped, leaving {} partition(s) alone.",
+            written.count,
+            verb,
+            declined
+        );
+        progress.phase("canceled");
+        notify::exit(1);
+    }
+    log!("successfully {} {} items.", verb, written.count);
+    if !options.delete {
+        let attribute_changes = report::AttributeChanges::new(
+            written
+                .edits
+                .iter()
+                .filter_map(|(old, new)| Some((old, new.as_ref()?))),
+        );
+        log!("{}", attribute_changes);
+    }
+    if let (Some(index), Some(before), Some(Command::BackfillIndex { verify_timeout, .. })) =
+        (&index, index_count, &options.command)
+    {
+        progress.phase("verify");
+        let expected = before + backfilled;
+        log!(
+            "waiting for index {} to reach {} item(s)...",
+            index.name,
+            expected
+        );
+        match backfill::converge(
+            &items_client,
+            &options.table,
+            &index.name,
+            expected,
+            verify_timeout.0,
+            &reads,
+        )
+        .await
+        {
+            Ok(count) if count >= expected => {
+                log!("index {} has {} item(s), as expected.", index.name, count);
+            }
+            Ok(count) => {
+                log!(
+                    "index {} only has {} of the expected {} item(s) after {}s; it may still be catching up, or items may have been changed concurrently.",
+                    index.name,
+                    count,
+                    expected,
+                    verify_timeout.0.as_secs()
+                );
+                progress.error("verify", "index didn't reach the expected item count");
+                notify::exit(1);
+            }
+            Err(e) => {
+                log!("error counting index: {}", e);
+                progress.error("verify", &e.to_string());
+                notify::exit(1);
+            }
+        }
+    }
+    progress.emit("done", json!({ "written": written.count }));
+}
+
+/// An item as scanned, and what to replace it with (or `None` to delete it).
+type Edit = (
+    HashMap<String, AttributeValue>,
+    Option<HashMap<String, AttributeValue>>,
+);
+
+/// What writing has done so far.
+#[derive(Debug, Default)]
+struct Written {
+    /// How many items were written, including any before resuming.
+    count: usize,
+    /// How many items are done before the first one that isn't (including any
+    /// before resuming), which is as far as `--state-table` can resume from.
+    prefix: usize,
+    /// The edits written (not including any before resuming), as written,
+    /// unless taken since.
+    edits: Vec<Edit>,
+}
+
+#[derive(Debug)]
+enum WriteError {
+    Dynamo(aws_sdk_dynamodb::Error),
+    State(state::StateError),
+    Checkpoint(io::Error),
+    Journal(io::Error),
+    /// The operator aborted while a batch had this many unprocessed items.
+    Unprocessed(usize),
+}
+
+impl Display for WriteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteError::Dynamo(e) => e.fmt(f),
+            WriteError::State(e) => e.fmt(f),
+            WriteError::Checkpoint(e) => {
+                f.write_fmt(format_args!("error journaling to checkpoint: {}", e))
+            }
+            WriteError::Journal(e) => {
+                f.write_fmt(format_args!("error journaling to undo journal: {}", e))
+            }
+            WriteError::Unprocessed(n) => f.write_fmt(format_args!(
+                "aborted while throttled, with {} item(s) of the batch unprocessed",
+                n
+            )),
+        }
+    }
+}
+
+/// The table being written to.
+struct Table<'a> {
+    client: &'a Client,
+    name: &'a str,
+    key_names: &'a [String],
+    /// Whether to write unconditionally, with `BatchWriteItem`.
+    batch: bool,
+    /// Whether to update only the changed attributes (unless `batch`).
+    update_mode: bool,
+    /// Whether to write items in transactions (unless `batch`).
+    transactional: bool,
+    /// How many items to write at once (unless `batch` or `transactional`).
+    concurrency: usize,
+    /// Where to journal each item written.
+    checkpoint: Option<&'a Checkpoint>,
+    /// Where to journal each item replaced, before it is.
+    journal: Option<&'a undo::Journal>,
+    retry: Retry,
+    /// How to plan an item again if it changed since it was scanned, if at all.
+    replan: Option<&'a Replan<'a>>,
+}
+
+/// How to plan an item again, as it is now, after its conditional write failed,
+/// for `--conflict-retries`.
+struct Replan<'a> {
+    /// How many times to, for each item.
+    retries: usize,
+    /// `--rename`, then `--remove`, in the order planning applies them.
+    rules: Vec<Rule>,
+    conditions: &'a [Where],
+    /// The TTL attribute and the time, with `--skip-expired`.
+    expired: Option<(&'a str, i64)>,
+    diff_ignore: &'a [String],
+}
+
+/// What planning an item again came to.
+enum Replanned {
+    /// Write this instead.
+    Write(Edit),
+    /// Leave the item alone, for this reason.
+    Skip(&'static str),
+    /// Stop, since the rules can't be applied to the item as it is now, for
+    /// this reason.
+    Refuse(&'static str),
+}
+
+impl Replan<'_> {
+    /// Plans `current` (the item as read again, if it still exists) the way
+    /// the scan planned it.
+    fn plan(&self, current: Option<HashMap<String, AttributeValue>>) -> Replanned {
+        let old = match current {
+            Some(item) => item,
+            None => return Replanned::Skip("deleted"),
+        };
+        if self
+            .expired
+            .is_some_and(|(attribute, now)| is_expired(&old, attribute, now))
+        {
+            return Replanned::Skip("expired");
+        }
+        if !self.conditions.iter().all(|c| c.matches(&old)) {
+            return Replanned::Skip("no longer matches --where");
+        }
+        let mut new = old.clone();
+        let result = rule::apply(&self.rules, &mut new);
+        if !result.collisions.is_empty() {
+            return Replanned::Refuse("rules would now write the same attribute");
+        }
+        if !result.overwritten.is_empty() {
+            return Replanned::Refuse("replacements would now overwrite existing attributes");
+        }
+        if diff::changes_ignoring(&old, &new, self.diff_ignore).is_empty() {
+            return Replanned::Skip("no longer needs changes");
+        }
+        Replanned::Write((old, Some(new)))
+    }
+}
+
+/// Puts each new item in place of its old one, or deletes the old one if there
+/// is no new one, adding the edits written to `written`.
+async fn write(
+    table: &Table<'_>,
+    dirty: Vec<Edit>,
+    throttle: &mut Throttle,
+    stats: &Stats,
+    progress: &Progress,
+    state: Option<&State<'_>>,
+    written: &mut Written,
+) -> Result<(), WriteError> {
+    if table.batch || table.transactional {
+        // These write items in order, so the ones written come first.
+        let before = written.count;
+        let result = if table.batch {
+            write_batches(table, &dirty, throttle, stats, progress, state, written).await
+        } else {
+            write_transactions(table, &dirty, throttle, stats, progress, state, written).await
+        };
+        written
+            .edits
+            .extend(dirty.into_iter().take(written.count - before));
+        return result;
+    }
+    let throttle = &tokio::sync::Mutex::new(throttle);
+    let mut pending = dirty.iter().enumerate();
+    let mut in_flight = FuturesUnordered::new();
+    // What became of each item that is done, since any state only covers the
+    // items before the first one that isn't, which resuming relies on.
+    let mut done = (0..dirty.len()).map(|_| None).collect::<Vec<_>>();
+    let mut prefix = 0;
+    let mut error = None;
+    loop {
+        // After an error, the writes in flight finish, but no more start.
+        while error.is_none() && in_flight.len() < table.concurrency {
+            match pending.next() {
+                Some((i, (old, new))) => in_flight.push(async move {
+                    (
+                        i,
+                        write_item(table, old, new.as_ref(), throttle, stats).await,
+                    )
+                }),
+                None => break,
+            }
+        }
+        let (i, result) = match in_flight.next().await {
+            Some(next) => next,
+            None => break,
+        };
+        let outcome = match result {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                error.get_or_insert(e);
+                continue;
+            }
+        };
+        if !matches!(outcome, Outcome::LeftAlone) {
+            written.count += 1;
+            progress.emit("written", json!({ "written": written.count }));
+        }
+        done[i] = Some(outcome);
+        // Errors journaling also let the writes in flight finish, so that
+        // every item written is accounted for.
+        if let Some(checkpoint) = table.checkpoint {
+            if let Err(e) = checkpoint.written(&plan::key(table.key_names, &dirty[i].0)) {
+                error.get_or_insert(WriteError::Checkpoint(e));
+            }
+        }
+        let before = prefix;
+        while done.get(prefix).is_some_and(Option::is_some) {
+            prefix += 1;
+        }
+        if prefix > before {
+            written.prefix += prefix - before;
+            if let Some(state) = state {
+                let key = plan::key(table.key_names, &dirty[prefix - 1].0);
+                if let Err(e) = state
+                    .save(Status::Writing, written.prefix, Some(&key))
+                    .await
+                {
+                    error.get_or_insert(WriteError::State(e));
+                }
+            }
+        }
+    }
+    drop(in_flight);
+    drop(pending);
+    written.edits.extend(dirty.into_iter().zip(done).filter_map(
+        |(edit, outcome)| match outcome? {
+            Outcome::Planned => Some(edit),
+            Outcome::Replanned(edit) => Some(edit),
+            Outcome::LeftAlone => None,
+        },
+    ));
+    error.map_or(Ok(()), Err)
+}
+
+/// What became of an item that [`write_item`] wrote.
+enum Outcome {
+    /// It was written as planned.
+    Planned,
+    /// It changed since it was scanned, so this was written instead.
+    Replanned(Edit),
+    /// It changed since it was scanned, and was left alone after all.
+    LeftAlone,
+}
+
+/// Puts `new` in place of `old`, or deletes `old` if there is no `new`, and
+/// (with `--conflict-retries`) plans the item again whenever it changed since
+/// it was planned.
+async fn write_item(
+    table: &Table<'_>,
+    old: &HashMap<String, AttributeValue>,
+    new: Option<&HashMap<String, AttributeValue>>,
+    throttle: &tokio::sync::Mutex<&mut Throttle>,
+    stats: &Stats,
+) -> Result<Outcome, WriteError> {
+    let mut replanned = None::<Edit>;
+    let mut conflicts = 0;
+    loop {
+        let (old, new) = match &replanned {
+            Some((old, new)) => (old, new.as_ref()),
+            None => (old, new),
+        };
+        let e = match write_planned(table, old, new, throttle, stats).await {
+            Err(WriteError::Dynamo(
+                e @ aws_sdk_dynamodb::Error::ConditionalCheckFailedException(_),
+            )) => e,
+            Err(e) => return Err(e),
+            Ok(()) => {
+                return Ok(match replanned {
+                    Some(edit) => {
+                        stats.replanned(
+                            format_key(&edit.0, table.key_names),
+                            "written as it is now",
+                        );
+                        Outcome::Replanned(edit)
+                    }
+                    None => Outcome::Planned,
+                })
+            }
+        };
+        let replan = match table.replan {
+            Some(replan) if conflicts < replan.retries && new.is_some() => replan,
+            _ => return Err(WriteError::Dynamo(e)),
+        };
+        conflicts += 1;
+        stats.retry();
+        let current = table
+            .retry
+            .run(|| current(table, old))
+            .await
+            .map_err(|e| WriteError::Dynamo(e.into()))?;
+        match replan.plan(current) {
+            Replanned::Write(edit) => replanned = Some(edit),
+            Replanned::Skip(reason) => {
+                stats.replanned(format_key(old, table.key_names), reason);
+                return Ok(Outcome::LeftAlone);
+            }
+            Replanned::Refuse(reason) => {
+                log!(
+                    "{} changed since it was scanned, and {}",
+                    format_key(old, table.key_names),
+                    reason
+                );
+                return Err(WriteError::Dynamo(e));
+            }
+        }
+    }
+}
+
+/// Puts `new` in place of `old`, or deletes `old` if there is no `new`,
+/// retrying while throttled.
+async fn write_planned(
+    table: &Table<'_>,
+    old: &HashMap<String, AttributeValue>,
+    new: Option<&HashMap<String, AttributeValue>>,
+    throttle: &tokio::sync::Mutex<&mut Throttle>,
+    stats: &Stats,
+) -> Result<(), WriteError> {
+    let update = new.and_then(|new| update_for(table, old, new, stats));
+    let guard = Guard::new(table.key_names, old);
+    if guard.partial && update.is_none() {
+        stats.fell_back(format_key(old, table.key_names));
+    }
+    if let (Some(journal), Some(new)) = (table.journal, new) {
+        journal
+            .replacing(table.name, old, new)
+            .map_err(WriteError::Journal)?;
+    }
+    let mut retries = 0;
+    // Whether an attempt failed in a way that it may have been applied anyway.
+    let mut ambiguous = false;
+    loop {
+        throttle.lock().await.pace().await;
+        let start = Instant::now();
+        // Why the write failed, along with the error.
+        let result = async {
+            if let Some(update) = &update {
+                return update_item(table, update)
+                    .await
+                    .map_err(|e| (failure(&e), e.into()));
+            }
+            if guard.partial {
+                match unchanged(table, old).await {
+                    Ok(true) => {}
+                    Ok(false) => return Err((Failure::Permanent, changed_since_scan())),
+                    Err(e) => return Err((failure(&e), e.into())),
+                }
+            }
+            match new {
+                Some(new) => items::put(table.client, table.name, &guard, new)
+                    .await
+                    .map_err(|e| (failure(&e), e.into())),
+                None => items::delete(table.client, table.name, &guard)
+                    .await
+                    .map_err(|e| (failure(&e), e.into())),
+            }
+        }
+        .await;
+        match result {
+            Ok(consumed_wcu) => {
+                throttle.lock().await.success(start.elapsed(), consumed_wcu);
+                stats.written(consumed_wcu);
+                cost::write(consumed_wcu);
+                return Ok(());
+            }
+            Err((Failure::Throttled, e)) => {
+                stats.retry();
+                if !throttle.lock().await.throttled().await {
+                    return Err(WriteError::Dynamo(e));
+                }
+            }
+            Err((Failure::Transient, _)) if retries < table.retry.max_retries => {
+                retries += 1;
+                ambiguous = true;
+                stats.retry();
+                tokio::time::sleep(Retry::backoff(retries)).await;
+            }
+            Err((_, e)) => {
+                if matches!(
+                    e,
+                    aws_sdk_dynamodb::Error::ConditionalCheckFailedException(_)
+                ) {
+                    // The condition may have failed because an earlier attempt
+                    // was applied after all, leaving the item as intended.
+                    let applied = ambiguous
+                        && matches!(
+                            table.retry.run(|| current(table, old)).await,
+                            Ok(item) if item.as_ref() == new
+                        );
+                    if applied {
+                        stats.written(0.0);
+                        return Ok(());
+                    }
+                    stats.conflict();
+                }
+                return Err(WriteError::Dynamo(e));
+            }
+        }
+    }
+}
+
+/// With `--update-mode`, the update that replaces `old` with `new`, unless it
+/// has to be put whole.
+fn update_for(
+    table: &Table<'_>,
+    old: &HashMap<String, AttributeValue>,
+    new: &HashMap<String, AttributeValue>,
+    stats: &Stats,
+) -> Option<plan::Update> {
+    if !table.update_mode {
+        return None;
+    }
+    let update = plan::Update::new(table.key_names, old, new);
+    if plan::has_dotted_names(old)
+        || plan::has_dotted_names(new)
+        || update.update_expression.len() > items::MAX_EXPRESSION_LENGTH
+        || update.condition_expression.len() > items::MAX_EXPRESSION_LENGTH
+    {
+        stats.put_whole(format_key(old, table.key_names));
+        return None;
+    }
+    Some(update)
+}
+
+/// Splits `dirty` into ranges to write in one transaction each, of at most
+/// [`TRANSACTION_SIZE`] items (and [`MAX_TRANSACTION_BYTES`]), with no key
+/// twice. A partition that would fit in a transaction, but not in the rest of
+/// the current one, starts a new one.
+fn transactions(dirty: &[Edit], key_names: &[String]) -> Vec<Range<usize>> {
+    let bytes_of =
+        |(old, new): &Edit| report::item_size(old) + new.as_ref().map_or(0, report::item_size);
+    let partition_key = key_names.first();
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut bytes = 0;
+    let mut keys = HashSet::new();
+    let mut i = 0;
+    while i < dirty.len() {
+        let partition = partition_key.and_then(|name| dirty[i].0.get(name));
+        let end = i + dirty[i..]
+            .iter()
+            .take_while(|(old, _)| partition_key.and_then(|name| old.get(name)) == partition)
+            .count();
+        let partition_bytes = dirty[i..end].iter().map(bytes_of).sum::<usize>();
+        let fits_alone = end - i <= TRANSACTION_SIZE && partition_bytes <= MAX_TRANSACTION_BYTES;
+        let fits =
+            end - start <= TRANSACTION_SIZE && bytes + partition_bytes <= MAX_TRANSACTION_BYTES;
+        if i > start && fits_alone && !fits {
+            ranges.push(start..i);
+            start = i;
+            bytes = 0;
+            keys.clear();
+        }
+        for (j, edit) in dirty.iter().enumerate().take(end).skip(i) {
+            let edit_bytes = bytes_of(edit);
+            let key = checkpoint::id(&plan::key(key_names, &edit.0));
+            if j > start
+                && (j - start == TRANSACTION_SIZE
+                    || bytes + edit_bytes > MAX_TRANSACTION_BYTES
+                    || keys.contains(&key))
+            {
+                ranges.push(start..j);
+                start = j;
+                bytes = 0;
+                keys.clear();
+            }
+            bytes += edit_bytes;
+            keys.insert(key);
+        }
+        i = end;
+    }
+    if start < dirty.len() {
+        ranges.push(start..dirty.len());
+    }
+    ranges
+}
+
+/// How a `TransactWriteItems` request failed, including why DynamoDB canceled
+/// the transaction, if it did.
+fn transaction_failure(e: &SdkError<TransactWriteItemsError>) -> Failure {
+    let kind = match e {
+        SdkError::ServiceError { err, .. } => &err.kind,
+        _ => return failure(e),
+    };
+    match kind {
+        TransactWriteItemsErrorKind::TransactionCanceledException(canceled) => {
+            let codes = canceled
+                .cancellation_reasons()
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|reason| reason.code.as_deref())
+                .collect::<Vec<_>>();
+            if codes
+                .iter()
+                .any(|code| matches!(*code, "ThrottlingError" | "ProvisionedThroughputExceeded"))
+            {
+                Failure::Throttled
+            } else if codes.contains(&"TransactionConflict") {
+                Failure::Transient
+            } else {
+                Failure::Permanent
             }
         }
+        TransactWriteItemsErrorKind::TransactionInProgressException(_) => Failure::Transient,
+        _ => failure(e),
     }
 }
 
-impl FromStr for Replace {
-    type Err = ReplaceParseError;
+/// The items of a transaction (as in `transaction`) whose condition failed.
+fn conditions_failed<'a>(
+    e: &SdkError<TransactWriteItemsError>,
+    transaction: &'a [Edit],
+) -> Vec<&'a Edit> {
+    match e {
+        SdkError::ServiceError { err, .. } => match &err.kind {
+            TransactWriteItemsErrorKind::TransactionCanceledException(canceled) => canceled
+                .cancellation_reasons()
+                .unwrap_or_default()
+                .iter()
+                .zip(transaction)
+                .filter(|(reason, _)| reason.code.as_deref() == Some("ConditionalCheckFailed"))
+                .map(|(_, edit)| edit)
+                .collect(),
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    }
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let Some((mut before, mut after)) = s.split_once('>') {
-            let root = if before.starts_with("*") {
-                before = &before[1..];
-                if after.starts_with("*") {
-                    after = &after[1..];
-                } else {
-                    return Err(ReplaceParseError::Unsupported);
+/// Like [`write`], but [`TRANSACTION_SIZE`] items (as split by
+/// [`transactions`]) per `TransactWriteItems`, each still conditionally, so
+/// either all of a transaction's items are written or none are. The count (and
+/// any state) advances a transaction at a time.
+async fn write_transactions(
+    table: &Table<'_>,
+    dirty: &[Edit],
+    throttle: &mut Throttle,
+    stats: &Stats,
+    progress: &Progress,
+    state: Option<&State<'_>>,
+    written: &mut Written,
+) -> Result<(), WriteError> {
+    for range in transactions(dirty, table.key_names) {
+        let transaction = &dirty[range];
+        // Items whose condition can't check all of their attributes, which are
+        // read back and compared before each attempt instead.
+        let mut partial = Vec::new();
+        let mut requests = Vec::new();
+        for (old, new) in transaction {
+            if let (Some(journal), Some(new)) = (table.journal, new) {
+                journal
+                    .replacing(table.name, old, new)
+                    .map_err(WriteError::Journal)?;
+            }
+            let update = new
+                .as_ref()
+                .and_then(|new| update_for(table, old, new, stats));
+            let guard = Guard::new(table.key_names, old);
+            if guard.partial && update.is_none() {
+                stats.fell_back(format_key(old, table.key_names));
+                partial.push(old);
+            }
+            let names = Some(guard.names).filter(|names| !names.is_empty());
+            let values = Some(guard.values).filter(|values| !values.is_empty());
+            let condition = Some(guard.condition_expression).filter(|c| !c.is_empty());
+            let request = match (update, new) {
+                (Some(update), _) => TransactWriteItem::builder()
+                    .update(
+                        model::Update::builder()
+                            .table_name(table.name)
+                            .set_key(Some(update.key))
+                            .update_expression(update.update_expression)
+                            .condition_expression(update.condition_expression)
+                            .set_expression_attribute_names(Some(update.names))
+                            .set_expression_attribute_values(
+                                Some(update.values).filter(|values| !values.is_empty()),
+                            )
+                            .build(),
+                    )
+                    .build(),
+                (None, Some(new)) => TransactWriteItem::builder()
+                    .put(
+                        model::Put::builder()
+                            .table_name(table.name)
+                            .set_item(Some(new.clone()))
+                            .set_condition_expression(condition)
+                            .set_expression_attribute_names(names)
+                            .set_expression_attribute_values(values)
+                            .build(),
+                    )
+                    .build(),
+                (None, None) => TransactWriteItem::builder()
+                    .delete(
+                        model::Delete::builder()
+                            .table_name(table.name)
+                            .set_key(Some(guard.key))
+                            .set_condition_expression(condition)
+                            .set_expression_attribute_names(names)
+                            .set_expression_attribute_values(values)
+                            .build(),
+                    )
+                    .build(),
+            };
+            requests.push(request);
+        }
+        // Retries with the same token are only applied once, even if an
+        // earlier attempt was applied after all.
+        let token = format!("{:032x}", rand::random::<u128>());
+        let mut retries = 0;
+        loop {
+            for _ in transaction {
+                throttle.pace().await;
+            }
+            for old in &partial {
+                match table.retry.run(|| unchanged(table, old)).await {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        log!(
+                            "{} changed since it was scanned, so its transaction wasn't written",
+                            format_key(old, table.key_names)
+                        );
+                        stats.conflict();
+                        return Err(WriteError::Dynamo(changed_since_scan()));
+                    }
+                    Err(e) => return Err(WriteError::Dynamo(e.into())),
                 }
-                false
-            } else {
-                true
+            }
+            let start = Instant::now();
+            let result = table
+                .client
+                .transact_write_items()
+                .set_transact_items(Some(requests.clone()))
+                .client_request_token(&token)
+                .return_consumed_capacity(ReturnConsumedCapacity::Total)
+                .send()
+                .await;
+            let e = match result {
+                Ok(output) => {
+                    let consumed_wcu = output
+                        .consumed_capacity
+                        .iter()
+                        .flatten()
+                        .filter_map(|c| c.capacity_units)
+                        .sum::<f64>();
+                    cost::write(consumed_wcu);
+                    for _ in transaction {
+                        throttle.success(start.elapsed(), consumed_wcu / transaction.len() as f64);
+                        stats.written(consumed_wcu / transaction.len() as f64);
+                    }
+                    break;
+                }
+                Err(e) => e,
             };
-
-            fn validate_attribute_name(name: &str) -> Result<(), ReplaceParseError> {
-                lazy_static! {
-                    static ref NAME_REGEX: Regex = Regex::new("[a-zA-Z0-9_\\-.]+").unwrap();
+            match transaction_failure(&e) {
+                Failure::Throttled => {
+                    stats.retry();
+                    if !throttle.throttled().await {
+                        return Err(WriteError::Dynamo(e.into()));
+                    }
                 }
-
-                if NAME_REGEX
-                    .find(name)
-                    .map(|m: Match| m.start() == 0 && m.end() == name.len())
-                    .unwrap_or(false)
-                {
-                    Ok(())
-                } else {
-                    Err(ReplaceParseError::InvalidAttribute(name.to_string()))
+                Failure::Transient if retries < table.retry.max_retries => {
+                    retries += 1;
+                    stats.retry();
+                    tokio::time::sleep(Retry::backoff(retries)).await;
+                }
+                _ => {
+                    let failed = conditions_failed(&e, transaction);
+                    if failed.is_empty() {
+                        return Err(WriteError::Dynamo(e.into()));
+                    }
+                    log!(
+                        "{} of the transaction's {} item(s) changed since they were scanned, so none were written:",
+                        failed.len(),
+                        transaction.len()
+                    );
+                    for (old, _) in failed.iter().take(LISTED) {
+                        log!("  {}", format_key(old, table.key_names));
+                    }
+                    if failed.len() > LISTED {
+                        log!("  ...and {} more item(s)", failed.len() - LISTED);
+                    }
+                    stats.conflict();
+                    return Err(WriteError::Dynamo(changed_since_scan()));
                 }
             }
+        }
+        written.count += transaction.len();
+        written.prefix += transaction.len();
+        if let Some(checkpoint) = table.checkpoint {
+            for (old, _) in transaction {
+                checkpoint
+                    .written(&plan::key(table.key_names, old))
+                    .map_err(WriteError::Checkpoint)?;
+            }
+        }
+        if let Some(state) = state {
+            let key = plan::key(table.key_names, &transaction[transaction.len() - 1].0);
+            state
+                .save(Status::Writing, written.prefix, Some(&key))
+                .await
+                .map_err(WriteError::State)?;
+        }
+        progress.emit("written", json!({ "written": written.count }));
+    }
+    Ok(())
+}
 
-            validate_attribute_name(before)?;
-            validate_attribute_name(after)?;
-
-            let (prefix, from, to) = if before.contains('.') {
-                let from = before.split('.').last().unwrap().to_string();
-                let prefix = before
-                    .strip_suffix(&format!(".{}", from))
-                    .unwrap()
-                    .to_string();
-                let to = after
-                    .strip_prefix(&format!("{}.", prefix))
-                    .ok_or(ReplaceParseError::Unsupported)?
-                    .to_string();
-                (prefix, from, to)
-            } else {
-                if after.contains('.') {
-                    return Err(ReplaceParseError::Unsupported);
+/// Like [`write`], but unconditionally, [`BATCH_SIZE`] items per
+/// `BatchWriteItem`. The count (and any state) only advances once a whole batch
+/// is written, so a resumed run rewrites (with the same items) any batch that
+/// was interrupted.
+async fn write_batches(
+    table: &Table<'_>,
+    dirty: &[Edit],
+    throttle: &mut Throttle,
+    stats: &Stats,
+    progress: &Progress,
+    state: Option<&State<'_>>,
+    written: &mut Written,
+) -> Result<(), WriteError> {
+    for batch in dirty.chunks(BATCH_SIZE) {
+        if let Some(journal) = table.journal {
+            for (old, new) in batch {
+                if let Some(new) = new {
+                    journal
+                        .replacing(table.name, old, new)
+                        .map_err(WriteError::Journal)?;
                 }
-                (String::new(), before.to_string(), after.to_string())
-            };
-
-            Ok(Self {
-                root,
-                prefix,
-                from,
-                to,
+            }
+        }
+        let mut requests = batch
+            .iter()
+            .map(|(old, new)| match new {
+                Some(new) => WriteRequest::builder()
+                    .put_request(PutRequest::builder().set_item(Some(new.clone())).build())
+                    .build(),
+                None => WriteRequest::builder()
+                    .delete_request(
+                        DeleteRequest::builder()
+                            .set_key(Some(plan::key(table.key_names, old)))
+                            .build(),
+                    )
+                    .build(),
             })
-        } else {
-            Err(ReplaceParseError::MissingArrow)
+            .collect::<Vec<_>>();
+        let mut retries = 0;
+        while !requests.is_empty() {
+            for _ in &requests {
+                throttle.pace().await;
+            }
+            let start = Instant::now();
+            let sent = requests.len();
+            let result = table
+                .client
+                .batch_write_item()
+                .request_items(table.name, requests.clone())
+                .return_consumed_capacity(ReturnConsumedCapacity::Total)
+                .send()
+                .await;
+            let output = match result {
+                Ok(output) => output,
+                Err(e) if is_throttling(&e) => {
+                    stats.retry();
+                    if !throttle.throttled().await {
+                        return Err(WriteError::Dynamo(e.into()));
+                    }
+                    continue;
+                }
+                // Unconditional writes can simply be repeated.
+                Err(e)
+                    if failure(&e) == Failure::Transient && retries < table.retry.max_retries =>
+                {
+                    retries += 1;
+                    stats.retry();
+                    tokio::time::sleep(Retry::backoff(retries)).await;
+                    continue;
+                }
+                Err(e) => return Err(WriteError::Dynamo(e.into())),
+            };
+            requests = output
+                .unprocessed_items
+                .and_then(|mut unprocessed| unprocessed.remove(table.name))
+                .unwrap_or_default();
+            let written = sent - requests.len();
+            let consumed_wcu = output
+                .consumed_capacity
+                .iter()
+                .flatten()
+                .filter_map(|c| c.capacity_units)
+                .sum::<f64>();
+            cost::write(consumed_wcu);
+            for _ in 0..written {
+                throttle.success(start.elapsed(), consumed_wcu / written as f64);
+                stats.written(consumed_wcu / written as f64);
+            }
+            if !requests.is_empty() {
+                // DynamoDB returns unprocessed items when throttling the batch.
+                stats.retry();
+                if !throttle.throttled().await {
+                    return Err(WriteError::Unprocessed(requests.len()));
+                }
+            }
+        }
+        written.count += batch.len();
+        written.prefix += batch.len();
+        if let Some(checkpoint) = table.checkpoint {
+            for (old, _) in batch {
+                checkpoint
+                    .written(&plan::key(table.key_names, old))
+                    .map_err(WriteError::Checkpoint)?;
+            }
         }
+        if let Some(state) = state {
+            let key = plan::key(table.key_names, &batch[batch.len() - 1].0);
+            state
+                .save(Status::Writing, written.prefix, Some(&key))
+                .await
+                .map_err(WriteError::State)?;
+        }
+        progress.emit("written", json!({ "written": written.count }));
     }
+    Ok(())
 }
 
-#[tokio::main]
-async fn main() {
-    let options: Options = Options::from_args();
-    let mut credentials_builder = DefaultCredentialsChain::builder();
+/// Prints a table of how many replacements and overwrites each rule made.
+fn print_breakdown(replacements: &[Replace], result: &ReplaceResult) {
+    let rows = replacements
+        .iter()
+        .zip(&result.rules)
+        .map(|(replacement, rule_result)| {
+            [
+                replacement.to_string(),
+                rule_result.replacements.to_string(),
+                rule_result.overwrites.to_string(),
+                rule_result.skips.to_string(),
+                rule_result.attributes.len().to_string(),
+                rule_result
+                    .types
+                    .iter()
+                    .map(|(t, n)| format!("{}={}", t, n))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let header = [
+        "rule",
+        "matches",
+        "overwrites",
+        "skips",
+        "attributes",
+        "types",
+    ]
+    .map(String::from);
 
-    if let Some(region) = options.region {
-        credentials_builder = credentials_builder.region(Region::new(Cow::Owned(region)));
+    let mut widths = [0; 6];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
     }
-    if let Some(profile) = options.profile {
-        credentials_builder = credentials_builder.profile_name(&profile);
+    for row in std::iter::once(&header).chain(&rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        log!("{}", line.trim_end());
     }
+}
 
-    let credentials_provider = credentials_builder.build().await;
+/// Refuses to proceed if any items couldn't be edited.
+fn refuse_invalid(invalid: &[(String, String)], progress: &Progress) {
+    if invalid.is_empty() {
+        return;
+    }
+    log!("{} item(s) couldn't be edited:", invalid.len());
+    for (key, e) in invalid.iter().take(LISTED) {
+        log!("  {}: {}", key, e);
+    }
+    if invalid.len() > LISTED {
+        log!("  ...and {} more item(s)", invalid.len() - LISTED);
+    }
+    progress.error("plan", "some items couldn't be edited");
+    notify::exit(1);
+}
 
-    let mut shared_config_loader =
-        aws_config::from_env().credentials_provider(credentials_provider);
+/// Refuses to proceed if any rules (by index) wrote the same attribute.
+fn refuse_collisions(
+    rename: &[Replace],
+    collisions: &BTreeMap<(usize, usize), (usize, Vec<String>)>,
+    progress: &Progress,
+) {
+    if collisions.is_empty() {
+        return;
+    }
+    for ((a, b), (count, examples)) in collisions {
+        log!(
+            "rules '{}' and '{}' wrote the same attribute in {} item(s), e.g. {}",
+            rename[*a],
+            rename[*b],
+            count,
+            examples.join(", ")
+        );
+    }
+    progress.error("plan", "multiple rules wrote the same attribute");
+    notify::exit(1);
+}
 
-    if let Some(timeout) = options.timeout {
-        let timeout = Duration::from_secs(timeout);
-        shared_config_loader =
-            shared_config_loader.timeout_config(timeout::Config::new().with_api_timeouts(
-                Api::new()
-                    .with_call_timeout(TriState::Set(timeout))
-                    .with_call_attempt_timeout(TriState::Set(timeout)),
-            ))
+/// Refuses to proceed if replacements would overwrite existing attributes.
+fn refuse_overwritten(overwritten: &[(String, Vec<String>)], progress: &Progress) {
+    if overwritten.is_empty() {
+        return;
     }
+    log!(
+        "{} overwritten key(s) across {} item(s). pass --allow-overwrite, or suffix rules with ':skip' or ':overwrite', to proceed anyway:",
+        overwritten.iter().map(|(_, paths)| paths.len()).sum::<usize>(),
+        overwritten.len()
+    );
+    for (key, paths) in overwritten.iter().take(LISTED) {
+        log!("  {}: {}", key, paths.join(", "));
+    }
+    if overwritten.len() > LISTED {
+        log!("  ...and {} more item(s)", overwritten.len() - LISTED);
+    }
+    progress.error("plan", "replacements would overwrite existing attributes");
+    notify::exit(1);
+}
 
-    let shared_config = shared_config_loader.load().await;
+/// Reports that writing stopped after `count` items, with an error or (if
+/// `None`) interrupted, and exits.
+fn exit_after_write(
+    error: Option<WriteError>,
+    count: usize,
+    verb: &str,
+    options: &Options,
+    progress: &Progress,
+) -> ! {
+    match error {
+        Some(e) => {
+            let e_string = e.to_string();
+            progress.error("write", &e_string);
+            if matches!(
+                e,
+                WriteError::Dynamo(aws_sdk_dynamodb::Error::ConditionalCheckFailedException(_))
+            ) {
+                log!("after {} successfully {} items(s), concurrent modification detected. retry if desired.", count, verb);
+            } else {
+                log!(
+                    "after {} successfully {} item(s), error writing item: {}",
+                    count,
+                    verb,
+                    e_string
+                );
+            }
+        }
+        None => {
+            log!(
+                "after {} successfully {} item(s), interrupted.",
+                count,
+                verb
+            );
+            progress.error("write", "interrupted");
+        }
+    }
+    log_resume_hint(options);
+    notify::exit(1);
+}
 
-    let client = Client::new(&shared_config);
+/// Lists the items that were read back before writing, since their condition
+/// expression would have been too long, and so weren't fully protected.
+fn log_fallbacks(stats: &Stats) {
+    let fallbacks = stats.fallbacks();
+    if !fallbacks.is_empty() {
+        log!(
+"{} item(s) had too many attributes to check in one condition expression, so were read back and compared before writing, then only written on the condition that their key and some attributes were unchanged. a concurrent change to their other attributes in between would have been overwritten, unnoticed:",
+            fallbacks.len()
+        );
+        for key in fallbacks.iter().take(LISTED) {
+            log!("  {}", key);
+        }
+        if fallbacks.len() > LISTED {
+            log!("  ...and {} more item(s)", fallbacks.len() - LISTED);
+        }
+    }
+    let puts = stats.puts();
+    if !puts.is_empty() {
+        log!(
+            "{} item(s) were put whole despite --update-mode, since their attribute names contain '.' or their update would be longer than DynamoDB allows:",
+            puts.len()
+        );
+        for key in puts.iter().take(LISTED) {
+            log!("  {}", key);
+        }
+        if puts.len() > LISTED {
+            log!("  ...and {} more item(s)", puts.len() - LISTED);
+        }
+    }
+    let replans = stats.replans();
+    if !replans.is_empty() {
+        log!(
+            "{} item(s) changed since they were scanned, so were read again and planned as they are now:",
+            replans.len()
+        );
+        for (key, outcome) in replans.iter().take(LISTED) {
+            log!("  {}: {}", key, outcome);
+        }
+        if replans.len() > LISTED {
+            log!("  ...and {} more item(s)", replans.len() - LISTED);
+        }
+    }
+}
 
-    let rows = match scan(&client, &options.table).await {
-        Ok(rows) => rows,
+/// Runs the command line once in each account of `--accounts`, one after
+/// another (each confirming its own plan), then reports how each run went.
+fn fan_out(path: &Path, options: &Options, args: &[String]) -> ! {
+    if headless::enabled() {
+        log!("--accounts can't be used headless");
+        notify::exit(1);
+    }
+    if options.role_arn.is_some() || options.mfa_token.is_some() {
+        log!("--accounts can't be combined with --role-arn or --mfa-token, since each account has its own role");
+        notify::exit(1);
+    }
+    let given = |option: &str| {
+        args.iter()
+            .any(|arg| arg == option || arg.starts_with(&format!("{}=", option)))
+    };
+    // Files that would be mixed up between accounts if they shared them.
+    let mut files = vec![
+        ("--checkpoint", options.checkpoint.as_deref()),
+        ("--resume", options.resume.as_deref()),
+        ("--undo-journal", options.undo_journal.as_deref()),
+        ("--plan-out", options.edit.plan_out.as_deref()),
+        ("--diff-out", options.diff_out.as_deref()),
+        ("--quarantine-file", options.quarantine_file.as_deref()),
+        ("--cost-report", options.cost_report.as_deref()),
+    ];
+    if given("--rollback-file") {
+        files.push(("--rollback-file", Some(&options.rollback_file)));
+    }
+    match &options.command {
+        Some(Command::Apply { plan }) => files.push(("apply's plan", Some(plan))),
+        Some(Command::Rollback { journal, .. }) => {
+            files.push(("rollback's journal", Some(journal)))
+        }
+        _ => {}
+    }
+    for (option, file) in files {
+        if file.is_some_and(|file| !file.to_string_lossy().contains("{account}")) {
+            log!(
+                "with --accounts, {} must contain {{account}} (replaced by each account's name), so that accounts don't share it",
+                option
+            );
+            notify::exit(1);
+        }
+    }
+    let accounts = match accounts::load(path) {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            log!("{}", e);
+            notify::exit(1);
+        }
+    };
+    let labels = accounts
+        .iter()
+        .enumerate()
+        .map(|(i, account)| account.label(i + 1))
+        .collect::<Vec<_>>();
+    // Names for `{account}`, which may end up in file names.
+    let names = labels
+        .iter()
+        .map(|label| {
+            label
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                        c
+                    } else {
+                        '-'
+                    }
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>();
+    for (i, name) in names.iter().enumerate() {
+        if let Some(j) = names[..i].iter().position(|other| other == name) {
+            log!(
+                "accounts {} and {} are both named {}, so give them distinct names",
+                j + 1,
+                i + 1,
+                name
+            );
+            notify::exit(1);
+        }
+    }
+    let progress = match Progress::open(options.progress_fd, options.progress_file.as_deref()) {
+        Ok(progress) => progress,
+        Err(e) => {
+            log!("error opening progress output: {}", e);
+            notify::exit(1);
+        }
+    };
+    let program = match std::env::current_exe() {
+        Ok(program) => program,
         Err(e) => {
-            eprintln!("error scanning: {}", e.to_string());
-            process::exit(1);
+            log!(
+                "error finding this program to run it in each account: {}",
+                e
+            );
+            notify::exit(1);
         }
     };
+    let mut rest = args[1..].to_vec();
+    for option in ["--accounts", "--progress-fd", "--progress-file"] {
+        rest = accounts::without_option(&rest, option);
+    }
+
+    // What happened in each account, and whether it went as planned.
+    let mut results = Vec::<(String, bool)>::new();
+    for (i, account) in accounts.iter().enumerate() {
+        let label = &labels[i];
+        if i > 0 && !options.yes && !results.last().is_some_and(|(_, ok)| *ok) {
+            let prompt = format!(
+                "continue with the remaining {} account(s)?",
+                accounts.len() - i
+            );
+            if !confirm(&prompt) {
+                break;
+            }
+        }
+        log!("running in {} ({}/{})...", label, i + 1, accounts.len());
+        let events = std::env::temp_dir().join(format!(
+            "dynamodb_bulk_edit-{}-{}.jsonl",
+            std::process::id(),
+            i
+        ));
+        let mut child_args = account.args();
+        child_args.push(String::from("--progress-file"));
+        child_args.push(events.display().to_string());
+        if !given("--rollback-file") {
+            child_args.push(String::from("--rollback-file"));
+            child_args.push(format!("rollback-{}-{{time}}.toml", names[i]));
+        }
+        let rest = match account.region {
+            Some(_) => accounts::without_option(&rest, "--region"),
+            None => rest.clone(),
+        };
+        child_args.extend(rest.iter().map(|arg| arg.replace("{account}", &names[i])));
+        let status = match std::process::Command::new(&program)
+            .args(&child_args)
+            .status()
+        {
+            Ok(status) => status,
+            Err(e) => {
+                log!("error running in {}: {}", label, e);
+                notify::exit(1);
+            }
+        };
+        let outcome = accounts::Outcome::read(&events);
+        let _ = std::fs::remove_file(&events);
+        let (result, ok) = match (status.success(), outcome.written) {
+            (true, Some(written)) => (format!("wrote {} item(s)", written), true),
+            (true, None) => (String::from("finished without writing"), true),
+            (false, _) if outcome.canceled => (String::from("canceled"), false),
+            (false, written) => {
+                let reason = outcome.error.unwrap_or_else(|| match status.code() {
+                    Some(code) => format!("exit code {}", code),
+                    None => String::from("killed"),
+                });
+                match written {
+                    Some(written) if written > 0 => (
+                        format!("failed after writing {} item(s): {}", written, reason),
+                        false,
+                    ),
+                    _ => (format!("failed: {}", reason), false),
+                }
+            }
+        };
+        progress.emit(
+            "account",
+            json!({ "account": label, "ok": ok, "written": outcome.written, "result": result }),
+        );
+        results.push((result, ok));
+    }
 
-    eprintln!("scanned {} row(s) in table...", rows.len());
+    log!("accounts:");
+    for (i, label) in labels.iter().enumerate() {
+        match results.get(i) {
+            Some((result, _)) => log!("  {}: {}", label, result),
+            None => log!("  {}: not run", label),
+        }
+    }
+    let ok = results.len() == accounts.len() && results.iter().all(|(_, ok)| *ok);
+    progress.phase("done");
+    notify::exit(if ok { 0 } else { 1 });
+}
 
-    let mut result = ReplaceResult::default();
-    let mut dirty = Vec::new();
-    for mut row in rows {
-        let old = row.clone();
-        replace(String::new(), &mut row, &options.rename, &mut result);
-        if old != row {
-            dirty.push((old, row));
+/// Writes a config with the `inverse` rules to `--rollback-file`, unless the
+/// run can't be undone by them (because of its `edits`, `overwrites`, or items
+/// that already matched the inverse rules).
+fn write_rollback(
+    options: &Options,
+    inverse: &[Replace],
+    edits: &BTreeMap<&'static str, usize>,
+    overwrites: usize,
+    not_invertible: usize,
+) -> bool {
+    if matches!(options.command, Some(Command::Replay { .. })) {
+        log!("not writing a rollback file, since rules can't undo a replay.");
+    } else if matches!(options.command, Some(Command::Apply { .. })) {
+        log!("not writing a rollback file, since rules can't undo a plan file.");
+    } else if matches!(options.command, Some(Command::Rollback { .. })) {
+        log!("not writing a rollback file, since rules can't undo a rollback.");
+    } else if options.delete {
+        log!("not writing a rollback file, since rules can't undo deletions.");
+    } else if !edits.is_empty() {
+        log!(
+            "not writing a rollback file, since rules can't undo --{}.",
+            edits.keys().copied().collect::<Vec<_>>().join(" or --")
+        );
+    } else if overwrites == 0 && not_invertible == 0 {
+        let config = Config {
+            rename: inverse.iter().map(Replace::to_string).collect(),
+            ..Default::default()
+        };
+        let mut command = String::from("dynamodb_bulk_edit");
+        if let Some(region) = &options.region {
+            command.push_str(&format!(" --region {}", region));
+        }
+        if let Some(profile) = &options.profile {
+            command.push_str(&format!(" --profile {}", profile));
+        }
+        if let Some(role_arn) = &options.role_arn {
+            command.push_str(&format!(" --role-arn {}", role_arn));
+        }
+        if let Some(external_id) = &options.external_id {
+            command.push_str(&format!(" --external-id {}", external_id));
         }
+        if let Some(mfa_serial) = &options.mfa_serial {
+            command.push_str(&format!(" --mfa-serial {}", mfa_serial));
+        }
+        command.push_str(&format!(
+            " --table {} --config {}",
+            options.table,
+            options.rollback_file.display()
+        ));
+        match config.save(
+            &options.rollback_file,
+            &format!(
+                "Rolls back a run against table '{}' with:\n  {}",
+                options.table, command
+            ),
+        ) {
+            Ok(()) => log!("to roll back, run: {}", command),
+            Err(e) => {
+                log!("error writing rollback file: {}", e);
+                notify::exit(1);
+            }
+        }
+        return true;
+    } else {
+        log!(
+            "not writing a rollback file, since {} overwritten key(s) and {} item(s) that already match the inverse rules make this run irreversible.",
+            overwrites, not_invertible
+        );
+    }
+    false
+}
+
+/// Tells the operator how to continue the run, if it has a checkpoint file.
+fn log_resume_hint(options: &Options) {
+    if let Some(path) = options.checkpoint.as_ref().or(options.resume.as_ref()) {
+        log!(
+            "progress is saved; to continue, repeat the command with --resume {} (instead of any --checkpoint).",
+            path.display()
+        );
+    }
+}
+
+/// Arguments passing `--region` and `--profile` on to the `aws` CLI. With
+/// `--role-arn`, its credentials are in the environment instead of a profile.
+fn aws_cli_args(options: &Options) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(region) = &options.region {
+        args.extend([String::from("--region"), region.clone()]);
+    }
+    if let Some(profile) = options
+        .profile
+        .as_ref()
+        .filter(|_| options.role_arn.is_none())
+    {
+        args.extend([String::from("--profile"), profile.clone()]);
     }
+    args
+}
 
-    if result.replacements == 0 {
-        eprintln!("no replacements found.");
+/// Backs up the table with `--backup-before`, exiting if that fails, since
+/// writing without the backup asked for isn't safe.
+async fn backup_before(client: &Client, options: &Options, progress: &Progress, now: i64) {
+    if !options.backup_before {
         return;
     }
+    progress.phase("backup");
+    let name = format!("{}-dynamodb_bulk_edit-{}", options.table, now);
+    log!("backing up table {} as {}...", options.table, name);
+    match backup::backup(client, &options.table, &name).await {
+        Ok(arn) => log!("backed up table {} to {}.", options.table, arn),
+        Err(e) => {
+            log!("{}", e);
+            progress.error("backup", &e.to_string());
+            notify::exit(1);
+        }
+    }
+}
 
-    eprintln!(
-        "prepared to make {} replacement(s) across {} item(s) with {} overwritten key(s)...",
-        result.replacements,
-        dirty.len(),
-        result.overwrites
+/// Reports the write traffic on the table over `duration`, and how much of it
+/// hits the items about to be edited (in `dirty`), warning when enough does
+/// that conditional writes are likely to fail.
+async fn check_traffic(
+    options: &Options,
+    description: &TableDescription,
+    key_names: &[String],
+    dirty: &[Edit],
+    duration: Duration,
+) {
+    let cli_args = aws_cli_args(options);
+    let edited = dirty
+        .iter()
+        .map(|(old, _)| json::item_to_json(&plan::key(key_names, old)).to_string())
+        .collect::<HashSet<_>>();
+    let stream = description.latest_stream_arn.as_ref().filter(|_| {
+        description
+            .stream_specification
+            .as_ref()
+            .and_then(|s| s.stream_enabled)
+            .unwrap_or_default()
+    });
+    let stream = match stream {
+        Some(stream) => stream,
+        None => {
+            log!("table has no stream, so estimating write traffic from CloudWatch...");
+            let wcu = match traffic::consumed_wcu(&options.table, duration, &cli_args).await {
+                Ok(wcu) => wcu,
+                Err(e) => {
+                    log!("error checking write traffic: {}", e);
+                    return;
+                }
+            };
+            let items = description.item_count;
+            log!("the table consumed {:.1} WCU/s.", wcu);
+            if items > 0 && wcu > 0.0 {
+                // Assuming writes of up to 1 KB, spread evenly over the items.
+                let per_minute = wcu * 60.0 * dirty.len() as f64 / items as f64;
+                log!(
+                    "if spread evenly over the table, about {:.1} write(s) per minute would hit the {} item(s) to edit, each failing its conditional write.",
+                    per_minute,
+                    dirty.len()
+                );
+            }
+            return;
+        }
+    };
+    // The root attributes that the edits change, or `None` if any (deleting).
+    let affected = dirty
+        .iter()
+        .map(|(old, new)| {
+            let new = new.as_ref()?;
+            Some(
+                old.keys()
+                    .chain(new.keys())
+                    .filter(|name| old.get(*name) != new.get(*name))
+                    .cloned()
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect::<Option<Vec<_>>>()
+        .map(|attributes| attributes.into_iter().flatten().collect::<HashSet<_>>());
+    let images = matches!(
+        description
+            .stream_specification
+            .as_ref()
+            .and_then(|s| s.stream_view_type.as_ref()),
+        Some(StreamViewType::NewAndOldImages)
+    );
+    log!(
+        "sampling write traffic from the table's stream for {}s...",
+        duration.as_secs()
+    );
+    let sample = match traffic::sample(
+        stream,
+        duration,
+        &edited,
+        affected.as_ref(),
+        images,
+        &cli_args,
+    )
+    .await
+    {
+        Ok(sample) => sample,
+        Err(e) => {
+            log!("error checking write traffic: {}", e);
+            return;
+        }
+    };
+    let minutes = duration.as_secs_f64() / 60.0;
+    log!(
+        "the table took {:.1} write(s)/s, of which {} hit {} of the {} item(s) to edit.",
+        sample.writes as f64 / duration.as_secs_f64(),
+        sample.to_edited,
+        sample.edited.len(),
+        dirty.len()
+    );
+    if sample.edited.is_empty() {
+        return;
+    }
+    log!(
+        "at this rate, about {:.1} item(s) to edit per minute would change between being scanned and written, failing their conditional writes.",
+        sample.edited.len() as f64 / minutes
     );
+    if let Some(to_affected) = sample.to_affected {
+        log!(
+            "{} of those write(s) changed the attributes being edited; the other {} would only fail a whole-item conditional put, not an update of just those attributes.",
+            to_affected,
+            sample.to_edited - to_affected
+        );
+    }
+}
+
+/// Prints where throttling started in `steps` (at increasing concurrency),
+/// and the `option` value to recommend.
+fn report_bench(option: &str, noun: &str, unit: &str, steps: &[bench::Step]) {
+    if let Some(onset) = steps.iter().find(|step| step.throttled > 0) {
+        output!("throttling started at {} {}.", option, onset.concurrency);
+    }
+    if let Some(step) = bench::recommend(steps) {
+        output!(
+            "recommended: {} {} (about {:.0} {}/s, at {:.1} {}/s).",
+            option,
+            step.concurrency,
+            step.items_per_sec(),
+            noun,
+            step.units_per_sec(),
+            unit
+        );
+    }
+}
+
+/// Reads one key per line of `path`, skipping blank lines and `#` comments.
+fn read_keys(path: &Path) -> Result<Vec<Key>, String> {
+    let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.parse().map_err(|e: KeyParseError| e.to_string()))
+        .collect()
+}
+
+/// Whether the table defines `name` (which must be a key attribute) as a string.
+fn is_string_attribute(description: &TableDescription, name: &str) -> bool {
+    description.attribute_definitions.iter().flatten().any(|a| {
+        a.attribute_name.as_deref() == Some(name)
+            && matches!(a.attribute_type, Some(ScalarAttributeType::S))
+    })
+}
+
+/// The table's TTL attribute, if TTL is enabled.
+async fn ttl_attribute(
+    client: &Client,
+    table: &str,
+) -> Result<Option<String>, SdkError<DescribeTimeToLiveError>> {
+    let output = client
+        .describe_time_to_live()
+        .table_name(table)
+        .send()
+        .await?;
+    Ok(output
+        .time_to_live_description
+        .filter(|d| matches!(d.time_to_live_status, Some(TimeToLiveStatus::Enabled)))
+        .and_then(|d| d.attribute_name))
+}
+
+async fn describe_table(
+    client: &Client,
+    table: &str,
+) -> Result<TableDescription, SdkError<DescribeTableError>> {
+    let output = client.describe_table().table_name(table).send().await?;
+    Ok(output
+        .table
+        .unwrap_or_else(|| TableDescription::builder().build()))
+}
 
-    eprint!("confirm (type 'Y' and press 'Enter'): ");
+/// Asks the operator to type `Y`, returning whether they did.
+fn confirm(prompt: &str) -> bool {
+    eprint!("{} (type 'Y' and press 'Enter'): ", prompt);
 
     let mut line = String::new();
     let stdin = io::stdin();
@@ -197,140 +5451,452 @@ async fn main() {
         .read_line(&mut line)
         .expect("could not read line from stdin");
 
-    if line.trim() != "Y" {
-        println!("canceled.");
-        process::exit(1);
-    }
+    line.trim() == "Y"
+}
 
-    let mut count = 0;
-    for (old, new) in dirty {
-        if let Err(e) = put(&client, old, new, &options.table).await {
-            let e_string = e.to_string();
-            let compat = e.into();
-            if matches!(
-                compat,
-                aws_sdk_dynamodb::Error::ConditionalCheckFailedException(_)
-            ) {
-                eprintln!("after {} successfully updated items(s), concurrent modification detected. retry if desired.", count);
-            } else {
-                eprintln!(
-                    "after {} successfully updated item(s), error putting item: {}",
-                    count, e_string
-                );
+/// What planning items added up to.
+#[derive(Default)]
+struct Tallies {
+    result: ReplaceResult,
+    /// Pairs of rules (by index) that wrote the same path, in how many items,
+    /// with a few examples.
+    collisions: BTreeMap<(usize, usize), (usize, Vec<String>)>,
+    /// Items in which replacements would overwrite attributes, and their paths.
+    overwritten: Vec<(String, Vec<String>)>,
+    /// Items changed by each canned edit.
+    edits: BTreeMap<&'static str, usize>,
+    /// Items that couldn't be edited, and why.
+    invalid: Vec<(String, String)>,
+    /// Items skipped by each template (described as its option), with a few
+    /// examples.
+    unrendered: BTreeMap<String, (usize, Vec<String>)>,
+    /// Occurrences of values absent from each mapping (by index).
+    unmapped: BTreeMap<usize, BTreeMap<String, usize>>,
+    /// One line per item that violates the schema, and how many of them the
+    /// edits repair.
+    violating: Vec<serde_json::Value>,
+    repaired: usize,
+    /// Items from which each unknown attribute path would be dropped.
+    dropped: BTreeMap<String, usize>,
+}
+
+impl Tallies {
+    /// Adds those of another item.
+    fn add(&mut self, other: Tallies) {
+        self.result.add(other.result);
+        let merge = |tally: &mut (usize, Vec<String>), (count, examples): (usize, Vec<String>)| {
+            tally.0 += count;
+            let room = 3usize.saturating_sub(tally.1.len());
+            tally.1.extend(examples.into_iter().take(room));
+        };
+        for (rules, tally) in other.collisions {
+            merge(self.collisions.entry(rules).or_default(), tally);
+        }
+        for (option, tally) in other.unrendered {
+            merge(self.unrendered.entry(option).or_default(), tally);
+        }
+        self.overwritten.extend(other.overwritten);
+        for (edit, count) in other.edits {
+            *self.edits.entry(edit).or_default() += count;
+        }
+        self.invalid.extend(other.invalid);
+        for (i, values) in other.unmapped {
+            let unmapped = self.unmapped.entry(i).or_default();
+            for (value, count) in values {
+                *unmapped.entry(value).or_default() += count;
             }
-            process::exit(1);
-        } else {
-            count += 1;
+        }
+        self.violating.extend(other.violating);
+        self.repaired += other.repaired;
+        for (path, count) in other.dropped {
+            *self.dropped.entry(path).or_default() += count;
+        }
+    }
+}
+
+/// Runs an item's edits, catching any panic (to quarantine the item) without
+/// the default hook's report on stderr, which would break headless output.
+fn quarantinable<R>(edits: impl FnOnce() -> R) -> std::thread::Result<R> {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let ret = panic::catch_unwind(AssertUnwindSafe(edits));
+    panic::set_hook(hook);
+    ret
+}
+
+/// The message a panic was started with.
+fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+    let message = match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => match panic.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => String::from("unknown"),
+        },
+    };
+    format!("panicked: {}", message)
+}
+
+/// Formats the primary key of `item` like `pk=USER#42,sk=PROFILE`.
+fn format_key(item: &HashMap<String, AttributeValue>, key_names: &[String]) -> String {
+    key_names
+        .iter()
+        .map(|name| {
+            let value = match item.get(name) {
+                Some(value) => render::label(value),
+                None => String::new(),
+            };
+            format!("{}={}", name, value)
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// How to read items: whether consistently, and within what read capacity.
+#[derive(Clone)]
+struct Reads {
+    consistent_read: bool,
+    /// Read capacity to stay under, with `--max-rcu` (shared by all segments).
+    budget: Option<Arc<Budget>>,
+    retry: Retry,
+}
+
+impl Reads {
+    /// Waits until a read is within budget.
+    async fn wait(&self) {
+        if let Some(budget) = &self.budget {
+            budget.wait().await;
         }
     }
 
-    eprintln!("successfully updated {} items.", count);
+    /// Records the capacity that a read consumed.
+    fn consumed(&self, capacity: Option<&ConsumedCapacity>) {
+        let units = capacity.and_then(|c| c.capacity_units).unwrap_or_default();
+        cost::read(units);
+        if let Some(budget) = &self.budget {
+            budget.consumed(units);
+        }
+    }
 }
 
-#[derive(Debug, Default)]
-struct ReplaceResult {
-    replacements: usize,
-    overwrites: usize,
+/// Sends a scan page's `request` with the filter, paced and retried.
+async fn send_scan(
+    request: fluent_builders::Scan,
+    filter: &ScanFilter,
+    reads: &Reads,
+) -> Result<ScanOutput, SdkError<ScanError>> {
+    reads.wait().await;
+    let request = request
+        .set_filter_expression(filter.expression())
+        .set_expression_attribute_names(Some(filter.names.clone()).filter(|n| !n.is_empty()))
+        .set_expression_attribute_values(Some(filter.values.clone()).filter(|v| !v.is_empty()))
+        .return_consumed_capacity(ReturnConsumedCapacity::Total);
+    let output = reads.retry.run(|| request.clone().send()).await?;
+    reads.consumed(output.consumed_capacity.as_ref());
+    Ok(output)
 }
 
-fn replace(
-    path: String,
-    attribute: &mut HashMap<String, AttributeValue>,
-    replacements: &[Replace],
-    result: &mut ReplaceResult,
+/// Journals a page of `segment` (if the scan is segmented) to the checkpoint,
+/// if any, and reports it, with how many items are `scanned` including it.
+fn scanned_page(
+    progress: &Progress,
+    checkpoint: Option<&Checkpoint>,
+    segment: Option<u32>,
+    (items, lek): &Page,
+    scanned: usize,
 ) {
-    for replacement in replacements {
-        if path == replacement.prefix || (!replacement.root && path.ends_with(&replacement.prefix))
-        {
-            if let Some(value) = attribute.remove(&replacement.from) {
-                result.replacements += 1;
-                result.overwrites +=
-                    attribute.insert(replacement.to.clone(), value).is_some() as usize;
-            }
+    if let Some(checkpoint) = checkpoint {
+        if let Err(e) = checkpoint.page(segment.unwrap_or_default(), items, lek.as_ref()) {
+            log!("error journaling to checkpoint: {}", e);
+            progress.error("checkpoint", &e.to_string());
+            notify::exit(1);
         }
     }
+    progress.emit(
+        "scan_page",
+        json!({
+            "items": items.len(),
+            "scanned": scanned,
+            "segment": segment,
+            "last_evaluated_key": lek.as_ref().map(json::item_to_json),
+        }),
+    );
+}
 
-    for (key, value) in attribute {
-        if let AttributeValue::M(map) = value {
-            let new_path = if path.is_empty() {
-                key.clone()
-            } else {
-                path.clone() + "." + key
+/// A scan page, or the error reading it.
+type ScannedPage = Result<Page, SdkError<ScanError>>;
+
+/// Scan pages as they arrive from concurrent segmented scans, each segment
+/// reading at most [`PAGES_AHEAD`] pages ahead of them.
+struct Pages<'a> {
+    receiver: tokio::sync::mpsc::Receiver<(Option<(u32, u32)>, ScannedPage)>,
+    progress: &'a Progress,
+    checkpoint: Option<&'a Checkpoint>,
+    /// Items scanned so far.
+    scanned: usize,
+}
+
+impl<'a> Pages<'a> {
+    /// Starts scanning the table with `starts.len()` segments (if more than
+    /// one), each from its key (`Some(None)` from the beginning), skipping any
+    /// that are `None`. With a `checkpoint`, each page is journaled.
+    fn scan(
+        client: &Client,
+        table: &str,
+        filter: &ScanFilter,
+        starts: Vec<Option<Option<HashMap<String, AttributeValue>>>>,
+        reads: &Reads,
+        progress: &'a Progress,
+        checkpoint: Option<&'a Checkpoint>,
+    ) -> Self {
+        let segments = starts.len() as u32;
+        let (sender, receiver) = tokio::sync::mpsc::channel(starts.len() * PAGES_AHEAD);
+        for (segment, start) in starts.into_iter().enumerate() {
+            let start = match start {
+                Some(start) => start,
+                None => continue,
             };
-            replace(new_path, map, replacements, result);
+            let client = client.clone();
+            let table = table.to_string();
+            let filter = filter.clone();
+            let reads = reads.clone();
+            let sender = sender.clone();
+            let segment = (segments > 1).then_some((segment as u32, segments));
+            tokio::spawn(async move {
+                let mut last_evaluated_key = start;
+                loop {
+                    let request = items::scan_request(
+                        &client,
+                        &table,
+                        segment,
+                        reads.consistent_read,
+                        last_evaluated_key,
+                    );
+                    let result = send_scan(request, &filter, &reads).await.map(|output| {
+                        (output.items.unwrap_or_default(), output.last_evaluated_key)
+                    });
+                    let done = match &result {
+                        Ok((_, lek)) => lek.is_none(),
+                        Err(_) => true,
+                    };
+                    last_evaluated_key = result.as_ref().ok().and_then(|(_, lek)| lek.clone());
+                    // The receiver only hangs up after an error.
+                    if sender.send((segment, result)).await.is_err() || done {
+                        break;
+                    }
+                }
+            });
         }
+        Self {
+            receiver,
+            progress,
+            checkpoint,
+            scanned: 0,
+        }
+    }
+
+    /// The next page to arrive, with the segment it's from (0 without
+    /// segments), or `None` once every segment is scanned.
+    async fn next(
+        &mut self,
+    ) -> Result<Option<(u32, Vec<HashMap<String, AttributeValue>>)>, SdkError<ScanError>> {
+        let (segment, result) = match self.receiver.recv().await {
+            Some(next) => next,
+            None => return Ok(None),
+        };
+        let page = result?;
+        let segment = segment.map(|(segment, _)| segment);
+        self.scanned += page.0.len();
+        scanned_page(self.progress, self.checkpoint, segment, &page, self.scanned);
+        Ok(Some((segment.unwrap_or_default(), page.0)))
     }
 }
 
-async fn scan_inner(
+/// Scans the table with `segments` concurrent segmented scans (if more than
+/// one), returning the items of each segment in turn, so that the order is the
+/// same each time. With a `checkpoint`, each page is journaled, and segments
+/// continue from where a previous run got to.
+async fn scan(
     client: &Client,
     table: &str,
-    last_evaluated_key: Option<HashMap<String, AttributeValue>>,
-) -> Result<
-    (
-        Vec<HashMap<String, AttributeValue>>,
-        Option<HashMap<String, AttributeValue>>,
-    ),
-    SdkError<ScanError>,
-> {
-    let scan_output = match client
-        .scan()
-        .table_name(table)
-        .set_exclusive_start_key(last_evaluated_key)
-        .send()
-        .await
-    {
-        Ok(output) => output,
-        Err(e) => return Err(e),
-    };
+    filter: &ScanFilter,
+    segments: u32,
+    reads: &Reads,
+    progress: &Progress,
+    checkpoint: Option<&Checkpoint>,
+) -> Result<Vec<HashMap<String, AttributeValue>>, SdkError<ScanError>> {
+    let saved = (0..segments)
+        .map(|segment| checkpoint.map(|c| c.segment(segment)).unwrap_or_default())
+        .collect::<Vec<_>>();
+    let starts = saved
+        .iter()
+        .map(|saved| (!saved.finished).then(|| saved.last_evaluated_key.clone()))
+        .collect();
+    let scanned = Cell::new(saved.iter().map(|saved| saved.items.len()).sum::<usize>());
+    if scanned.get() > 0 {
+        log!(
+            "continuing after {} item(s) scanned by a previous run.",
+            scanned.get()
+        );
+    }
+    let read = items::scan_with(
+        client,
+        table,
+        starts,
+        reads.consistent_read,
+        |request| send_scan(request, filter, reads),
+        |segment, page| {
+            scanned.set(scanned.get() + page.0.len());
+            let segment = (segments > 1).then_some(segment);
+            scanned_page(progress, checkpoint, segment, page, scanned.get());
+        },
+    )
+    .await?;
 
-    Ok((
-        scan_output.items.unwrap_or_default(),
-        scan_output.last_evaluated_key,
-    ))
+    Ok(saved
+        .into_iter()
+        .zip(read)
+        .flat_map(|(saved, read)| saved.items.into_iter().chain(read))
+        .collect())
 }
 
-async fn scan(
+/// How many segments [`sample_scan`] expects to read.
+const SAMPLE_SEGMENTS: u32 = 4;
+
+/// Reads at least `sample` items (unless the table has fewer) from randomly
+/// chosen segments, rather than scanning the whole table: it's split into
+/// enough segments, by its approximate `item_count`, that [`SAMPLE_SEGMENTS`]
+/// of them should hold that many, which are scanned concurrently, and then
+/// more in turn until that many items are read.
+async fn sample_scan(
     client: &Client,
     table: &str,
+    filter: &ScanFilter,
+    sample: usize,
+    item_count: i64,
+    reads: &Reads,
+    progress: &Progress,
 ) -> Result<Vec<HashMap<String, AttributeValue>>, SdkError<ScanError>> {
+    // DynamoDB allows at most a million segments.
+    let segments = (item_count.max(0) as u64 * SAMPLE_SEGMENTS as u64 / sample.max(1) as u64)
+        .clamp(1, 1_000_000) as u32;
+    let mut order = (0..segments).collect::<Vec<_>>();
+    order.shuffle(&mut rand::thread_rng());
     let mut ret = Vec::new();
-    let mut last_evaluated_key = None;
-    loop {
-        match scan_inner(client, table, last_evaluated_key).await {
-            Err(e) => return Err(e),
-            Ok((mut items, lek)) => {
-                ret.append(&mut items);
-                last_evaluated_key = lek;
-
-                if last_evaluated_key.is_none() {
-                    break;
-                }
-            }
+    let mut scanned = 0;
+    for chosen in order.chunks(SAMPLE_SEGMENTS as usize) {
+        if ret.len() >= sample {
+            break;
+        }
+        let mut starts = vec![None; segments as usize];
+        for &segment in chosen {
+            starts[segment as usize] = Some(None);
+        }
+        let mut pages = Pages::scan(client, table, filter, starts, reads, progress, None);
+        pages.scanned = ret.len();
+        while let Some((_, mut items)) = pages.next().await? {
+            ret.append(&mut items);
         }
+        scanned += chosen.len();
+    }
+    if (scanned as u32) < segments {
+        log!(
+            "sampled {} of the table's {} segment(s).",
+            scanned,
+            segments
+        );
     }
-
     Ok(ret)
 }
 
-async fn put(
+/// Queries the items in `key_condition`'s range, a page at a time.
+async fn query(
     client: &Client,
-    old: HashMap<String, AttributeValue>,
-    item: HashMap<String, AttributeValue>,
     table: &str,
-) -> Result<(), SdkError<PutItemError>> {
-    let mut req = client.put_item().table_name(table).set_item(Some(item));
-    let mut expr = Vec::new();
-    let mut i = 0;
-    for (key, value) in old {
-        expr.push(format!("#a{} = :a{}", i, i));
-        req = req
-            .expression_attribute_names(format!("#a{}", i), key)
-            .expression_attribute_values(format!(":a{}", i), value);
-        i += 1;
-    }
-    if !expr.is_empty() {
-        req = req.condition_expression(expr.join(" AND "));
+    filter: &ScanFilter,
+    key_condition: &KeyCondition,
+    reads: &Reads,
+    progress: &Progress,
+) -> Result<Vec<HashMap<String, AttributeValue>>, SdkError<QueryError>> {
+    let (expression, mut names, mut values) = key_condition.expression();
+    names.extend(filter.names.clone());
+    values.extend(filter.values.clone());
+    let mut ret = Vec::new();
+    let mut last_evaluated_key = None;
+    loop {
+        reads.wait().await;
+        let request = client
+            .query()
+            .table_name(table)
+            .key_condition_expression(&expression)
+            .set_filter_expression(filter.expression())
+            .set_expression_attribute_names(Some(names.clone()))
+            .set_expression_attribute_values(Some(values.clone()))
+            .consistent_read(reads.consistent_read)
+            .return_consumed_capacity(ReturnConsumedCapacity::Total)
+            .set_exclusive_start_key(last_evaluated_key);
+        let output = reads.retry.run(|| request.clone().send()).await?;
+        reads.consumed(output.consumed_capacity.as_ref());
+        let mut items = output.items.unwrap_or_default();
+        progress.emit(
+            "scan_page",
+            json!({
+                "items": items.len(),
+                "scanned": ret.len() + items.len(),
+                "last_evaluated_key": output.last_evaluated_key.as_ref().map(json::item_to_json),
+            }),
+        );
+        ret.append(&mut items);
+        last_evaluated_key = output.last_evaluated_key;
+        if last_evaluated_key.is_none() {
+            return Ok(ret);
+        }
     }
-    req.send().await.map(|_| ())
+}
+
+/// Whether `old` is still as scanned, reading it back with a consistent read.
+async fn unchanged(
+    table: &Table<'_>,
+    old: &HashMap<String, AttributeValue>,
+) -> Result<bool, SdkError<GetItemError>> {
+    Ok(current(table, old).await?.as_ref() == Some(old))
+}
+
+/// Makes the changes of `update`, as long as its condition holds, returning the
+/// write capacity units consumed.
+async fn update_item(
+    table: &Table<'_>,
+    update: &plan::Update,
+) -> Result<f64, SdkError<UpdateItemError>> {
+    let output = table
+        .client
+        .update_item()
+        .table_name(table.name)
+        .set_key(Some(update.key.clone()))
+        .update_expression(&update.update_expression)
+        .condition_expression(&update.condition_expression)
+        .set_expression_attribute_names(Some(update.names.clone()))
+        .set_expression_attribute_values((!update.values.is_empty()).then(|| update.values.clone()))
+        .return_consumed_capacity(ReturnConsumedCapacity::Total)
+        .send()
+        .await?;
+    Ok(output
+        .consumed_capacity
+        .and_then(|c| c.capacity_units)
+        .unwrap_or_default())
+}
+
+/// Reads the item with the key of `item` back, with a consistent read.
+async fn current(
+    table: &Table<'_>,
+    item: &HashMap<String, AttributeValue>,
+) -> Result<Option<HashMap<String, AttributeValue>>, SdkError<GetItemError>> {
+    let output = table
+        .client
+        .get_item()
+        .table_name(table.name)
+        .set_key(Some(plan::key(table.key_names, item)))
+        .consistent_read(true)
+        .send()
+        .await?;
+    Ok(output.item)
 }
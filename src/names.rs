@@ -0,0 +1,211 @@
+use crate::EVERY_ELEMENT;
+use aws_sdk_dynamodb::model::AttributeValue;
+use dynamodb_bulk_edit::Replace;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+
+/// Characters that render as nothing (zero-width spaces and joiners,
+/// direction marks, byte order marks, and the like).
+const INVISIBLE: &[char] = &[
+    '\u{00AD}', '\u{034F}', '\u{061C}', '\u{115F}', '\u{1160}', '\u{17B4}', '\u{17B5}', '\u{180E}',
+    '\u{200B}', '\u{200C}', '\u{200D}', '\u{200E}', '\u{200F}', '\u{202A}', '\u{202B}', '\u{202C}',
+    '\u{202D}', '\u{202E}', '\u{2060}', '\u{2061}', '\u{2062}', '\u{2063}', '\u{2064}', '\u{2066}',
+    '\u{2067}', '\u{2068}', '\u{2069}', '\u{206A}', '\u{206B}', '\u{206C}', '\u{206D}', '\u{206E}',
+    '\u{206F}', '\u{3164}', '\u{FEFF}', '\u{FFA0}',
+];
+
+/// Cyrillic and Greek letters that look like Latin ones, and the Latin ones.
+const HOMOGLYPHS: &[(char, char)] = &[
+    ('а', 'a'),
+    ('е', 'e'),
+    ('о', 'o'),
+    ('р', 'p'),
+    ('с', 'c'),
+    ('у', 'y'),
+    ('х', 'x'),
+    ('і', 'i'),
+    ('ј', 'j'),
+    ('ѕ', 's'),
+    ('ԁ', 'd'),
+    ('ԛ', 'q'),
+    ('ԝ', 'w'),
+    ('А', 'A'),
+    ('В', 'B'),
+    ('Е', 'E'),
+    ('К', 'K'),
+    ('М', 'M'),
+    ('Н', 'H'),
+    ('О', 'O'),
+    ('Р', 'P'),
+    ('С', 'C'),
+    ('Т', 'T'),
+    ('У', 'Y'),
+    ('Х', 'X'),
+    ('І', 'I'),
+    ('Ј', 'J'),
+    ('Ѕ', 'S'),
+    ('α', 'a'),
+    ('ο', 'o'),
+    ('ν', 'v'),
+    ('Α', 'A'),
+    ('Β', 'B'),
+    ('Ε', 'E'),
+    ('Ζ', 'Z'),
+    ('Η', 'H'),
+    ('Ι', 'I'),
+    ('Κ', 'K'),
+    ('Μ', 'M'),
+    ('Ν', 'N'),
+    ('Ο', 'O'),
+    ('Ρ', 'P'),
+    ('Τ', 'T'),
+    ('Υ', 'Y'),
+    ('Χ', 'X'),
+];
+
+/// What's suspicious about an attribute name, if anything, and the name it
+/// was probably meant to be.
+pub fn check(name: &str) -> (Vec<&'static str>, String) {
+    let mut problems = Vec::new();
+    if name.chars().any(|c| INVISIBLE.contains(&c)) {
+        problems.push("invisible characters");
+    }
+    if name.chars().any(|c| c.is_control()) {
+        problems.push("control characters");
+    }
+    if name.starts_with(char::is_whitespace) || name.ends_with(char::is_whitespace) {
+        problems.push("leading or trailing whitespace");
+    }
+    if name.chars().any(|c| c.is_whitespace() && c != ' ') {
+        problems.push("unusual whitespace");
+    }
+    // Only in names that are otherwise Latin, since the letters are fine on
+    // their own.
+    let homoglyph = |c: char| HOMOGLYPHS.iter().find(|(from, _)| *from == c);
+    let mixed = name.chars().any(|c| c.is_ascii_alphabetic());
+    if mixed && name.chars().any(|c| homoglyph(c).is_some()) {
+        problems.push("look-alike letters from another script");
+    }
+    if name.chars().any(|c| ('\u{FF01}'..='\u{FF5E}').contains(&c)) {
+        problems.push("fullwidth characters");
+    }
+    let mut fixed = name
+        .chars()
+        .filter(|c| !INVISIBLE.contains(c) && !c.is_control())
+        .map(|c| match homoglyph(c) {
+            Some((_, to)) if mixed => *to,
+            _ if ('\u{FF01}'..='\u{FF5E}').contains(&c) => {
+                char::from_u32(c as u32 - 0xFF01 + 0x21).unwrap_or(c)
+            }
+            _ if c.is_whitespace() => ' ',
+            _ => c,
+        })
+        .collect::<String>();
+    fixed = fixed.trim().to_string();
+    (problems, fixed)
+}
+
+/// The attribute names at each path, and how many items (or list elements)
+/// have them.
+#[derive(Default)]
+pub struct Census {
+    /// Items with each name, by the path (with a `[*]` suffix per list) of
+    /// the map it's in.
+    names: BTreeMap<Vec<String>, BTreeMap<String, usize>>,
+}
+
+impl Census {
+    pub fn add(&mut self, item: &HashMap<String, AttributeValue>) {
+        self.add_map(&mut Vec::new(), item);
+    }
+
+    fn add_map(&mut self, path: &mut Vec<String>, map: &HashMap<String, AttributeValue>) {
+        for (name, value) in map {
+            *self
+                .names
+                .entry(path.clone())
+                .or_default()
+                .entry(name.clone())
+                .or_default() += 1;
+            path.push(name.clone());
+            self.add_value(path, value);
+            path.pop();
+        }
+    }
+
+    fn add_value(&mut self, path: &mut Vec<String>, value: &AttributeValue) {
+        match value {
+            AttributeValue::M(map) => self.add_map(path, map),
+            AttributeValue::L(list) => {
+                let last = path.len() - 1;
+                path[last].push_str(EVERY_ELEMENT);
+                for element in list {
+                    self.add_value(path, element);
+                }
+                let name = path[last].len() - EVERY_ELEMENT.len();
+                path[last].truncate(name);
+            }
+            _ => {}
+        }
+    }
+
+    /// One JSON record per suspicious name, like `{"parent": "meta", "name":
+    /// "status ", "escaped": "status\\u{20}", "problems": [...], "items": 3,
+    /// "fixed": "status", "existing": 10, "rule": "meta.\"status \">meta.status"}`,
+    /// where `parent` is the path of the map it's in, `existing` counts the
+    /// items already having the fixed name there (which the rule would collide
+    /// with), and `rule` is null if no rule can fix the name.
+    pub fn report(&self) -> Vec<Value> {
+        let mut records = Vec::new();
+        for (prefix, names) in &self.names {
+            for (name, items) in names {
+                let (problems, fixed) = check(name);
+                if problems.is_empty() {
+                    continue;
+                }
+                // Rules can't name attributes with dots.
+                let rule = (!fixed.is_empty()
+                    && !fixed.contains('.')
+                    && !name.contains('.')
+                    && !prefix.iter().any(|segment| segment.contains('.')))
+                .then(|| {
+                    Replace {
+                        root: true,
+                        prefix: prefix.join("."),
+                        from: name.clone(),
+                        to: fixed.clone(),
+                        overwrite: None,
+                        min_depth: None,
+                        max_depth: None,
+                    }
+                    .to_string()
+                });
+                records.push(json!({
+                    "parent": prefix.join("."),
+                    "name": name,
+                    "escaped": escape(name),
+                    "problems": problems,
+                    "items": items,
+                    "fixed": fixed,
+                    "existing": names.get(&fixed).copied().unwrap_or_default(),
+                    "rule": rule,
+                }));
+            }
+        }
+        records
+    }
+}
+
+/// `name` with every character but visible ASCII (so also spaces) escaped,
+/// like `status\u{20}`.
+pub fn escape(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_graphic() {
+                c.to_string()
+            } else {
+                c.escape_unicode().to_string()
+            }
+        })
+        .collect()
+}
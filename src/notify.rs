@@ -0,0 +1,179 @@
+use serde_json::{json, Value};
+use std::io::Write;
+use std::process::{self, Command, Stdio};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+static NOTIFIER: OnceLock<Notifier> = OnceLock::new();
+
+/// Where to send a summary of the run when it finishes or aborts, with
+/// `curl` (for a webhook) and the `aws` CLI (for an SNS topic).
+pub struct Notifier {
+    pub webhook: Option<String>,
+    pub sns_topic: Option<String>,
+    /// Like `--region us-east-1`, passed to the `aws` CLI.
+    pub cli_args: Vec<String>,
+    pub table: String,
+    start: Instant,
+    summary: Mutex<Summary>,
+}
+
+/// What the run's progress events said so far.
+#[derive(Default)]
+struct Summary {
+    scanned: usize,
+    planned: Option<usize>,
+    written: usize,
+    canceled: bool,
+    failures: Vec<Value>,
+    audit: Option<Value>,
+}
+
+impl Notifier {
+    pub fn new(
+        webhook: Option<String>,
+        sns_topic: Option<String>,
+        cli_args: Vec<String>,
+        table: String,
+    ) -> Self {
+        Self {
+            webhook,
+            sns_topic,
+            cli_args,
+            table,
+            start: Instant::now(),
+            summary: Mutex::default(),
+        }
+    }
+}
+
+/// Starts summarizing progress events, to send when the run ends, if there
+/// is anywhere to send them.
+pub fn init(notifier: Notifier) {
+    if notifier.webhook.is_some() || notifier.sns_topic.is_some() {
+        let _ = NOTIFIER.set(notifier);
+    }
+}
+
+/// Updates the summary with a progress event.
+pub fn record(event: &str, fields: &Value) {
+    let notifier = match NOTIFIER.get() {
+        Some(notifier) => notifier,
+        None => return,
+    };
+    let mut summary = notifier.summary.lock().unwrap();
+    let count = |name: &str| fields.get(name).and_then(Value::as_u64).map(|n| n as usize);
+    match event {
+        "scan_page" => summary.scanned = count("scanned").unwrap_or(summary.scanned),
+        "planned" => summary.planned = count("items"),
+        "written" | "done" => summary.written = count("written").unwrap_or(summary.written),
+        "error" => summary.failures.push(fields.clone()),
+        "phase" if fields.get("phase") == Some(&json!("canceled")) => summary.canceled = true,
+        _ => {}
+    }
+}
+
+/// Records where the audit trail is kept, for the summary.
+pub fn audit(run: &str, sinks: Vec<String>) {
+    if let Some(notifier) = NOTIFIER.get() {
+        notifier.summary.lock().unwrap().audit = Some(json!({ "run": run, "sinks": sinks }));
+    }
+}
+
+/// Sends the summary (if there is anywhere to send it), then exits with `code`.
+pub fn exit(code: i32) -> ! {
+    send(code);
+    process::exit(code)
+}
+
+/// Sends the summary of a run ending with exit code `code`, if there is
+/// anywhere to send it, after reporting the capacity it consumed. Failing to
+/// send it is reported, but not fatal.
+pub fn send(code: i32) {
+    crate::cost::report();
+    let notifier = match NOTIFIER.get() {
+        Some(notifier) => notifier,
+        None => return,
+    };
+    let summary = notifier.summary.lock().unwrap();
+    let status = if summary.canceled {
+        "canceled"
+    } else if code != 0 {
+        "failed"
+    } else {
+        "finished"
+    };
+    let body = json!({
+        "table": notifier.table,
+        "status": status,
+        "exit_code": code,
+        "duration_secs": notifier.start.elapsed().as_secs(),
+        "scanned": summary.scanned,
+        "planned": summary.planned,
+        "written": summary.written,
+        "failures": summary.failures,
+        "audit": summary.audit,
+    })
+    .to_string();
+    if let Some(url) = &notifier.webhook {
+        let mut command = Command::new("curl");
+        command.args([
+            "--silent",
+            "--show-error",
+            "--fail",
+            "--max-time",
+            "30",
+            "--header",
+            "Content-Type: application/json",
+            "--data-binary",
+            "@-",
+            url,
+        ]);
+        if let Err(e) = run(&mut command, Some(&body)) {
+            log!("error notifying webhook: {}", e);
+        }
+    }
+    if let Some(topic) = &notifier.sns_topic {
+        let subject = format!("dynamodb_bulk_edit {} on {}", status, notifier.table);
+        let mut command = Command::new("aws");
+        command.args(&notifier.cli_args).args([
+            "sns",
+            "publish",
+            "--topic-arn",
+            topic,
+            // SNS limits subjects to 100 characters.
+            "--subject",
+            &subject.chars().take(100).collect::<String>(),
+            "--message",
+            &body,
+        ]);
+        if let Err(e) = run(&mut command, None) {
+            log!("error notifying SNS topic: {}", e);
+        }
+    }
+}
+
+/// Runs `command`, with `stdin` as its input, ignoring its output.
+fn run(command: &mut Command, stdin: Option<&str>) -> Result<(), String> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    let mut pipe = child.stdin.take().unwrap();
+    if let Some(stdin) = stdin {
+        pipe.write_all(stdin.as_bytes())
+            .map_err(|e| e.to_string())?;
+    }
+    drop(pipe);
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} ({})",
+            String::from_utf8_lossy(&output.stderr).trim(),
+            output.status
+        ));
+    }
+    Ok(())
+}
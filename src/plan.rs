@@ -0,0 +1,573 @@
+use crate::diff::{changes, Change};
+use crate::json::{item_from_json, item_to_json, to_json};
+use crate::Edit;
+use aws_sdk_dynamodb::model::AttributeValue;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use dynamodb_bulk_edit::items::{Guard, MAX_EXPRESSION_LENGTH};
+
+pub use dynamodb_bulk_edit::items::key;
+
+/// How to print pending changes for review.
+#[derive(Debug, Clone, Copy)]
+pub enum PlanFormat {
+    /// One PartiQL `UPDATE` (or `DELETE`) statement per item.
+    Partiql,
+    /// One `aws dynamodb update-item` (or `delete-item`) command per item.
+    AwsCli,
+}
+
+#[derive(Debug)]
+pub struct PlanFormatParseError(String);
+
+impl Display for PlanFormatParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "plan format '{}' is invalid (expected partiql or aws-cli)",
+            self.0
+        ))
+    }
+}
+
+impl FromStr for PlanFormat {
+    type Err = PlanFormatParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "partiql" => Ok(Self::Partiql),
+            "aws-cli" => Ok(Self::AwsCli),
+            _ => Err(PlanFormatParseError(s.to_string())),
+        }
+    }
+}
+
+impl PlanFormat {
+    /// Renders replacing `old` with `new`, or deleting it if `new` is `None`.
+    pub fn render(
+        self,
+        table: &str,
+        key_names: &[String],
+        old: &HashMap<String, AttributeValue>,
+        new: Option<&HashMap<String, AttributeValue>>,
+    ) -> String {
+        match (self, new) {
+            (PlanFormat::Partiql, Some(new)) if has_dotted_names(old) || has_dotted_names(new) => {
+                format!(
+                    "-- {}: can't be shown as an UPDATE, since an attribute name contains a '.' (the item is replaced whole)",
+                    crate::format_key(old, key_names)
+                )
+            }
+            (PlanFormat::Partiql, Some(new)) => partiql(table, key_names, old, new),
+            (PlanFormat::Partiql, None) => partiql_delete(table, key_names, old),
+            (PlanFormat::AwsCli, Some(new)) => aws_cli(table, key_names, old, new),
+            (PlanFormat::AwsCli, None) => aws_cli_delete(table, key_names, old),
+        }
+    }
+}
+
+/// An `UpdateItem` request that makes the same change as replacing `old` with
+/// `new`, guarded by a condition that the item still exists and the changed
+/// attributes still have their old values.
+pub struct Update {
+    pub key: HashMap<String, AttributeValue>,
+    pub update_expression: String,
+    pub condition_expression: String,
+    pub names: HashMap<String, String>,
+    pub values: HashMap<String, AttributeValue>,
+}
+
+impl Update {
+    pub fn new(
+        key_names: &[String],
+        old: &HashMap<String, AttributeValue>,
+        new: &HashMap<String, AttributeValue>,
+    ) -> Self {
+        let mut ret = Self {
+            key: key(key_names, old),
+            update_expression: String::new(),
+            condition_expression: String::new(),
+            names: HashMap::new(),
+            values: HashMap::new(),
+        };
+        let mut name_placeholders = HashMap::<String, String>::new();
+        let mut sets = Vec::new();
+        let mut removes = Vec::new();
+        let mut conditions = Vec::new();
+        // Otherwise, an update would create the item again if it was deleted.
+        if let Some(partition_key) = key_names.first() {
+            let name = ret.name(partition_key, &mut name_placeholders);
+            conditions.push(format!("attribute_exists({})", name));
+        }
+
+        for change in changes(old, new) {
+            match change {
+                Change::Removed(path, old) => {
+                    let path = ret.path(&path, &mut name_placeholders);
+                    let old = ret.value(old);
+                    removes.push(path.clone());
+                    conditions.push(format!("{} = {}", path, old));
+                }
+                Change::Added(path, new) => {
+                    let path = ret.path(&path, &mut name_placeholders);
+                    let new = ret.value(new);
+                    sets.push(format!("{} = {}", path, new));
+                    conditions.push(format!("attribute_not_exists({})", path));
+                }
+                Change::Changed(path, old, new) => {
+                    let path = ret.path(&path, &mut name_placeholders);
+                    let old = ret.value(old);
+                    let new = ret.value(new);
+                    sets.push(format!("{} = {}", path, new));
+                    conditions.push(format!("{} = {}", path, old));
+                }
+            }
+        }
+
+        let mut clauses = Vec::new();
+        if !sets.is_empty() {
+            clauses.push(format!("SET {}", sets.join(", ")));
+        }
+        if !removes.is_empty() {
+            clauses.push(format!("REMOVE {}", removes.join(", ")));
+        }
+        ret.update_expression = clauses.join(" ");
+        ret.condition_expression = conditions.join(" AND ");
+        ret
+    }
+
+    /// Placeholder path like `#n0.#n1` for a dot-separated attribute path.
+    fn path(&mut self, path: &str, placeholders: &mut HashMap<String, String>) -> String {
+        path.split('.')
+            .map(|segment| self.name(segment, placeholders))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// Placeholder like `#n0` for an attribute name.
+    fn name(&mut self, name: &str, placeholders: &mut HashMap<String, String>) -> String {
+        placeholders
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                let placeholder = format!("#n{}", self.names.len());
+                self.names.insert(placeholder.clone(), name.to_string());
+                placeholder
+            })
+            .clone()
+    }
+
+    fn value(&mut self, value: &AttributeValue) -> String {
+        let placeholder = format!(":v{}", self.values.len());
+        self.values.insert(placeholder.clone(), value.clone());
+        placeholder
+    }
+}
+
+/// Whether any attribute name in `item`, at any level, contains a `.`, which
+/// makes the dot-separated paths of its changes ambiguous.
+pub fn has_dotted_names(item: &HashMap<String, AttributeValue>) -> bool {
+    fn within(value: &AttributeValue) -> bool {
+        match value {
+            AttributeValue::M(map) => has_dotted_names(map),
+            AttributeValue::L(list) => list.iter().any(within),
+            _ => false,
+        }
+    }
+    item.iter()
+        .any(|(name, value)| name.contains('.') || within(value))
+}
+
+#[derive(Debug)]
+pub enum PlanFileError {
+    Io(io::Error),
+    /// The file isn't a plan written by `--plan-out`.
+    Malformed,
+    /// The plan is for this other table.
+    Table(String),
+}
+
+impl Display for PlanFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlanFileError::Io(e) => f.write_fmt(format_args!("error reading plan file: {}", e)),
+            PlanFileError::Malformed => {
+                f.write_str("plan file is not a plan written by --plan-out")
+            }
+            PlanFileError::Table(table) => {
+                f.write_fmt(format_args!("plan file is for table {}", table))
+            }
+        }
+    }
+}
+
+/// Writes the planned changes to `table` as a JSON plan file, like
+/// `{"table": "users", "changes": [{"old": {...}, "new": {...}}]}`, with items
+/// as DynamoDB JSON (and `"new": null` for deletions).
+pub fn save(path: &Path, table: &str, changes: &[Edit]) -> io::Result<()> {
+    let changes = changes
+        .iter()
+        .map(
+            |(old, new)| json!({ "old": item_to_json(old), "new": new.as_ref().map(item_to_json) }),
+        )
+        .collect::<Vec<_>>();
+    let plan = json!({ "table": table, "changes": changes });
+    std::fs::write(path, serde_json::to_string_pretty(&plan).unwrap())
+}
+
+/// Reads the changes planned to `table` from a plan file written by [`save`].
+pub fn load(path: &Path, table: &str) -> Result<Vec<Edit>, PlanFileError> {
+    let contents = std::fs::read_to_string(path).map_err(PlanFileError::Io)?;
+    let plan = serde_json::from_str::<Value>(&contents).map_err(|_| PlanFileError::Malformed)?;
+    match plan["table"].as_str() {
+        Some(planned) if planned == table => {}
+        Some(planned) => return Err(PlanFileError::Table(planned.to_string())),
+        None => return Err(PlanFileError::Malformed),
+    }
+    plan["changes"]
+        .as_array()
+        .ok_or(PlanFileError::Malformed)?
+        .iter()
+        .map(|change| {
+            let old = item_from_json(&change["old"]).ok_or(PlanFileError::Malformed)?;
+            let new = match &change["new"] {
+                Value::Null => None,
+                new => Some(item_from_json(new).ok_or(PlanFileError::Malformed)?),
+            };
+            Ok((old, new))
+        })
+        .collect()
+}
+
+/// A `DeleteItem` request for `old`, guarded by a condition that its
+/// attributes still have their old values.
+pub struct Delete {
+    pub key: HashMap<String, AttributeValue>,
+    pub condition_expression: String,
+    pub names: HashMap<String, String>,
+    pub values: HashMap<String, AttributeValue>,
+}
+
+impl Delete {
+    pub fn new(key_names: &[String], old: &HashMap<String, AttributeValue>) -> Self {
+        let mut names = HashMap::new();
+        let mut values = HashMap::new();
+        let mut attributes = old.iter().collect::<Vec<_>>();
+        attributes.sort_unstable_by_key(|(name, _)| *name);
+        let conditions = attributes
+            .into_iter()
+            .enumerate()
+            .map(|(i, (name, value))| {
+                names.insert(format!("#n{}", i), name.clone());
+                values.insert(format!(":v{}", i), value.clone());
+                format!("#n{} = :v{}", i, i)
+            })
+            .collect::<Vec<_>>();
+        Self {
+            key: key(key_names, old),
+            condition_expression: conditions.join(" AND "),
+            names,
+            values,
+        }
+    }
+}
+
+fn aws_cli_delete(
+    table: &str,
+    key_names: &[String],
+    old: &HashMap<String, AttributeValue>,
+) -> String {
+    let delete = Delete::new(key_names, old);
+    format!(
+        "aws dynamodb delete-item --table-name {} --key {} --condition-expression {} --expression-attribute-names {} --expression-attribute-values {}",
+        shell_quote(table),
+        shell_quote(&item_to_json(&delete.key).to_string()),
+        shell_quote(&delete.condition_expression),
+        shell_quote(&serde_json::to_string(&delete.names).unwrap()),
+        shell_quote(&item_to_json(&delete.values).to_string()),
+    )
+}
+
+fn aws_cli(
+    table: &str,
+    key_names: &[String],
+    old: &HashMap<String, AttributeValue>,
+    new: &HashMap<String, AttributeValue>,
+) -> String {
+    let update = Update::new(key_names, old, new);
+    // Like the run itself, which replaces such items whole.
+    if has_dotted_names(old)
+        || has_dotted_names(new)
+        || update.update_expression.len() > MAX_EXPRESSION_LENGTH
+        || update.condition_expression.len() > MAX_EXPRESSION_LENGTH
+    {
+        return aws_cli_put(table, key_names, old, new);
+    }
+    let mut command = format!(
+        "aws dynamodb update-item --table-name {} --key {} --update-expression {} --condition-expression {} --expression-attribute-names {}",
+        shell_quote(table),
+        shell_quote(&item_to_json(&update.key).to_string()),
+        shell_quote(&update.update_expression),
+        shell_quote(&update.condition_expression),
+        shell_quote(&serde_json::to_string(&update.names).unwrap()),
+    );
+    if !update.values.is_empty() {
+        command.push_str(&format!(
+            " --expression-attribute-values {}",
+            shell_quote(&item_to_json(&update.values).to_string())
+        ));
+    }
+    command
+}
+
+/// A `put-item` command replacing `old` with `new`, guarded by a condition
+/// that `old` still has its attributes (or, if checking all of them would be
+/// too long, some of them, noted in a comment).
+fn aws_cli_put(
+    table: &str,
+    key_names: &[String],
+    old: &HashMap<String, AttributeValue>,
+    new: &HashMap<String, AttributeValue>,
+) -> String {
+    let guard = Guard::new(key_names, old);
+    let mut command = format!(
+        "aws dynamodb put-item --table-name {} --item {} --condition-expression {} --expression-attribute-names {} --expression-attribute-values {}",
+        shell_quote(table),
+        shell_quote(&item_to_json(new).to_string()),
+        shell_quote(&guard.condition_expression),
+        shell_quote(&serde_json::to_string(&guard.names).unwrap()),
+        shell_quote(&item_to_json(&guard.values).to_string()),
+    );
+    if guard.partial {
+        command.push_str(" # the condition only checks some of the attributes");
+    }
+    command
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// An `UPDATE` statement that makes the same change, guarded by a check that
+/// the changed attributes still have their old values.
+fn partiql(
+    table: &str,
+    key_names: &[String],
+    old: &HashMap<String, AttributeValue>,
+    new: &HashMap<String, AttributeValue>,
+) -> String {
+    // Placeholders are numbered by position, so the clauses and the conditions
+    // (which come after them) collect their parameters separately.
+    let mut clause_parameters = Vec::new();
+    let mut condition_parameters = Vec::new();
+    let mut clauses = Vec::new();
+    let mut conditions = key_names
+        .iter()
+        .filter_map(|name| {
+            let value = old.get(name)?;
+            Some(format!(
+                "{} = {}",
+                partiql_identifier(name),
+                partiql_value(value, &mut condition_parameters)
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    for change in changes(old, new) {
+        match change {
+            Change::Removed(path, old) => {
+                let path = partiql_path(&path);
+                clauses.push(format!("REMOVE {}", path));
+                conditions.push(format!(
+                    "{} = {}",
+                    path,
+                    partiql_value(old, &mut condition_parameters)
+                ));
+            }
+            Change::Added(path, new) => {
+                let path = partiql_path(&path);
+                clauses.push(format!(
+                    "SET {} = {}",
+                    path,
+                    partiql_value(new, &mut clause_parameters)
+                ));
+                conditions.push(format!("{} IS MISSING", path));
+            }
+            Change::Changed(path, old, new) => {
+                let path = partiql_path(&path);
+                clauses.push(format!(
+                    "SET {} = {}",
+                    path,
+                    partiql_value(new, &mut clause_parameters)
+                ));
+                conditions.push(format!(
+                    "{} = {}",
+                    path,
+                    partiql_value(old, &mut condition_parameters)
+                ));
+            }
+        }
+    }
+
+    let mut statement = format!(
+        "UPDATE {} {} WHERE {};",
+        partiql_identifier(table),
+        clauses.join(" "),
+        conditions.join(" AND ")
+    );
+    clause_parameters.append(&mut condition_parameters);
+    if !clause_parameters.is_empty() {
+        statement.push_str(&format!(
+            " -- parameters: {}",
+            serde_json::Value::Array(clause_parameters)
+        ));
+    }
+    statement
+}
+
+/// A `DELETE` statement, guarded by a check that the item's attributes still
+/// have their old values.
+fn partiql_delete(
+    table: &str,
+    key_names: &[String],
+    old: &HashMap<String, AttributeValue>,
+) -> String {
+    let mut parameters = Vec::new();
+    // Key attributes first, since they identify the item.
+    let mut attributes = old.iter().collect::<Vec<_>>();
+    attributes.sort_unstable_by_key(|(name, _)| (!key_names.contains(name), *name));
+    let conditions = attributes
+        .into_iter()
+        .map(|(name, value)| {
+            format!(
+                "{} = {}",
+                partiql_identifier(name),
+                partiql_value(value, &mut parameters)
+            )
+        })
+        .collect::<Vec<_>>();
+    let mut statement = format!(
+        "DELETE FROM {} WHERE {};",
+        partiql_identifier(table),
+        conditions.join(" AND ")
+    );
+    if !parameters.is_empty() {
+        statement.push_str(&format!(
+            " -- parameters: {}",
+            serde_json::Value::Array(parameters)
+        ));
+    }
+    statement
+}
+
+fn partiql_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn partiql_path(path: &str) -> String {
+    path.split('.')
+        .map(partiql_identifier)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn partiql_string(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// PartiQL has no binary literals, so binary values become `?` placeholders
+/// whose DynamoDB JSON is collected in `parameters`.
+fn partiql_value(value: &AttributeValue, parameters: &mut Vec<serde_json::Value>) -> String {
+    match value {
+        AttributeValue::B(_) | AttributeValue::Bs(_) => {
+            parameters.push(to_json(value));
+            String::from("?")
+        }
+        AttributeValue::Bool(b) => b.to_string(),
+        AttributeValue::L(l) => format!(
+            "[{}]",
+            l.iter()
+                .map(|v| partiql_value(v, parameters))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        AttributeValue::M(m) => {
+            let mut entries = m.iter().collect::<Vec<_>>();
+            entries.sort_unstable_by_key(|(k, _)| *k);
+            format!(
+                "{{{}}}",
+                entries
+                    .into_iter()
+                    .map(|(k, v)| format!(
+                        "{}: {}",
+                        partiql_string(k),
+                        partiql_value(v, parameters)
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        }
+        AttributeValue::N(n) => n.clone(),
+        AttributeValue::Ns(ns) => format!("<<{}>>", ns.join(", ")),
+        AttributeValue::Null(_) => String::from("NULL"),
+        AttributeValue::S(s) => partiql_string(s),
+        AttributeValue::Ss(ss) => format!(
+            "<<{}>>",
+            ss.iter()
+                .map(|s| partiql_string(s))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        _ => String::from("NULL"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(attributes: &[(&str, &str)]) -> HashMap<String, AttributeValue> {
+        attributes
+            .iter()
+            .map(|(name, value)| (name.to_string(), AttributeValue::S(value.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn dotted_names() {
+        let key_names = [String::from("pk")];
+        let old = item(&[("pk", "1"), ("first.name", "a")]);
+        let new = item(&[("pk", "1"), ("first.name", "b")]);
+        let partiql = PlanFormat::Partiql.render("t", &key_names, &old, Some(&new));
+        assert!(
+            partiql.starts_with("-- pk=1: can't be shown"),
+            "{}",
+            partiql
+        );
+        let aws_cli = PlanFormat::AwsCli.render("t", &key_names, &old, Some(&new));
+        assert!(aws_cli.starts_with("aws dynamodb put-item"), "{}", aws_cli);
+        assert!(aws_cli.contains(r#""first.name":{"S":"b"}"#), "{}", aws_cli);
+    }
+
+    #[test]
+    fn plain_names() {
+        let key_names = [String::from("pk")];
+        let old = item(&[("pk", "1"), ("a", "x")]);
+        let new = item(&[("pk", "1"), ("b", "x")]);
+        let partiql = PlanFormat::Partiql.render("t", &key_names, &old, Some(&new));
+        assert!(
+            partiql.starts_with(r#"UPDATE "t" REMOVE "a" SET "b" = 'x'"#),
+            "{}",
+            partiql
+        );
+        let aws_cli = PlanFormat::AwsCli.render("t", &key_names, &old, Some(&new));
+        assert!(
+            aws_cli.starts_with("aws dynamodb update-item"),
+            "{}",
+            aws_cli
+        );
+    }
+}
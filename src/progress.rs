@@ -0,0 +1,75 @@
+use crate::{headless, notify};
+use serde_json::{json, Value};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Newline-delimited JSON progress events, for orchestration tools that would
+/// otherwise have to parse stderr.
+#[derive(Default)]
+pub struct Progress {
+    out: Option<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl Progress {
+    pub fn open(fd: Option<i32>, file: Option<&Path>) -> io::Result<Self> {
+        let out: Box<dyn Write + Send> = match (fd, file) {
+            (Some(fd), _) => Box::new(from_fd(fd)?),
+            (None, Some(path)) => Box::new(File::create(path)?),
+            (None, None) if headless::enabled() => Box::new(io::stdout()),
+            (None, None) => return Ok(Self::default()),
+        };
+        Ok(Self {
+            out: Some(Mutex::new(out)),
+        })
+    }
+
+    /// Writes one event, tagged with its `event` type and a millisecond timestamp.
+    ///
+    /// Errors writing events are not fatal to the run, so they are reported
+    /// and otherwise ignored.
+    pub fn emit(&self, event: &str, mut fields: Value) {
+        notify::record(event, &fields);
+        let out = match &self.out {
+            Some(out) => out,
+            None => return,
+        };
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        if let Value::Object(map) = &mut fields {
+            map.insert(String::from("event"), json!(event));
+            map.insert(String::from("time"), json!(time));
+        }
+        let mut out = out.lock().unwrap();
+        if let Err(e) = writeln!(out, "{}", fields).and_then(|_| out.flush()) {
+            eprintln!("error writing progress event: {}", e);
+        }
+    }
+
+    pub fn phase(&self, phase: &str) {
+        self.emit("phase", json!({ "phase": phase }));
+    }
+
+    pub fn error(&self, phase: &str, message: &str) {
+        self.emit("error", json!({ "phase": phase, "message": message }));
+    }
+}
+
+#[cfg(unix)]
+fn from_fd(fd: i32) -> io::Result<File> {
+    use std::os::unix::io::FromRawFd;
+    // SAFETY: the caller hands the descriptor over to us on the command line.
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+#[cfg(not(unix))]
+fn from_fd(_fd: i32) -> io::Result<File> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--progress-fd is only supported on unix",
+    ))
+}
@@ -0,0 +1,70 @@
+use crate::json;
+use aws_sdk_dynamodb::error::BatchGetItemError;
+use aws_sdk_dynamodb::model::{AttributeValue, KeysAndAttributes};
+use aws_sdk_dynamodb::types::SdkError;
+use aws_sdk_dynamodb::Client;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+/// The most keys that `BatchGetItem` accepts at once.
+const BATCH_SIZE: usize = 100;
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Identifies a referenced value, including its type (so that `N` `1` and `S`
+/// `1` are different references).
+pub fn reference_id(value: &AttributeValue) -> String {
+    json::to_json(value).to_string()
+}
+
+/// Looks up `values` as the partition key `key` of `table`, returning the
+/// [`reference_id`]s of those that exist.
+pub async fn existing(
+    client: &Client,
+    table: &str,
+    key: &str,
+    values: Vec<AttributeValue>,
+) -> Result<HashSet<String>, SdkError<BatchGetItemError>> {
+    let mut ret = HashSet::new();
+    for chunk in values.chunks(BATCH_SIZE) {
+        let mut keys = chunk
+            .iter()
+            .map(|value| HashMap::from([(key.to_string(), value.clone())]))
+            .collect::<Vec<_>>();
+        let mut backoff = Duration::from_millis(100);
+        while !keys.is_empty() {
+            let request = KeysAndAttributes::builder()
+                .set_keys(Some(keys))
+                .projection_expression("#k")
+                .expression_attribute_names("#k", key)
+                .build();
+            let output = client
+                .batch_get_item()
+                .request_items(table, request)
+                .send()
+                .await?;
+            let found = output
+                .responses
+                .and_then(|mut responses| responses.remove(table))
+                .unwrap_or_default();
+            ret.extend(
+                found
+                    .iter()
+                    .filter_map(|item| item.get(key))
+                    .map(reference_id),
+            );
+            keys = output
+                .unprocessed_keys
+                .and_then(|mut unprocessed| unprocessed.remove(table))
+                .and_then(|k| k.keys)
+                .unwrap_or_default();
+            if !keys.is_empty() {
+                // DynamoDB returns unprocessed keys when throttling the batch.
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            } else {
+                backoff = Duration::from_millis(100);
+            }
+        }
+    }
+    Ok(ret)
+}
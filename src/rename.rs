@@ -0,0 +1,610 @@
+use aws_sdk_dynamodb::model::AttributeValue;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// A rule that renames attributes, like `meta."first name">meta."given name"`,
+/// or (with `*`) the attributes ending in a path at any level, like
+/// `*userName>*username`.
+#[derive(Debug, Clone)]
+pub struct Replace {
+    /// Whether the path starts at the root, rather than at any level.
+    pub root: bool,
+    /// The path of the maps holding the attribute, like `meta` (or empty).
+    pub prefix: String,
+    pub from: String,
+    pub to: String,
+    /// What to do if `to` already exists, or `None` to follow `--allow-overwrite`.
+    pub overwrite: Option<OverwritePolicy>,
+    /// Inclusive bounds on how many path segments (including its own) the
+    /// matched attribute may have, e.g. 1 for a root-level attribute.
+    pub min_depth: Option<usize>,
+    pub max_depth: Option<usize>,
+}
+
+/// What a rule does if the attribute it renames to already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    /// Fail planning.
+    Error,
+    /// Leave both attributes as they are.
+    Skip,
+    /// Replace the existing attribute.
+    Overwrite,
+}
+
+impl OverwritePolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OverwritePolicy::Error => "error",
+            OverwritePolicy::Skip => "skip",
+            OverwritePolicy::Overwrite => "overwrite",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplaceParseError {
+    MissingArrow,
+    InvalidAttribute(String),
+    InvalidOverwritePolicy(String),
+    InvalidDepth(String),
+    Unsupported,
+}
+
+impl Display for ReplaceParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplaceParseError::MissingArrow => f.write_str("replacement missing arrow ('>')"),
+            ReplaceParseError::InvalidAttribute(a) => {
+                f.write_fmt(format_args!(
+                    "attribute '{}' is invalid (double-quote names with other characters than letters, digits, _, and -, like \"first name\")",
+                    a
+                ))
+            }
+            ReplaceParseError::InvalidOverwritePolicy(p) => f.write_fmt(format_args!(
+                "overwrite policy '{}' is invalid (expected error, skip, or overwrite)",
+                p
+            )),
+            ReplaceParseError::InvalidDepth(d) => f.write_fmt(format_args!(
+                "depth '{}' is invalid (expected e.g. 2, 1-3, -3, or 2-)",
+                d
+            )),
+            ReplaceParseError::Unsupported => {
+                f.write_str("replacements that that move values are not yet supported")
+            }
+        }
+    }
+}
+
+impl Replace {
+    /// The rule that undoes this one, as long as it never overwrote anything.
+    pub fn inverse(&self) -> Self {
+        Self {
+            root: self.root,
+            prefix: self.prefix.clone(),
+            from: self.to.clone(),
+            to: self.from.clone(),
+            overwrite: Some(OverwritePolicy::Error),
+            min_depth: self.min_depth,
+            max_depth: self.max_depth,
+        }
+    }
+}
+
+impl Display for Replace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let wildcard = if self.root { "" } else { "*" };
+        let (from, to) = (quote_name(&self.from), quote_name(&self.to));
+        if self.prefix.is_empty() {
+            f.write_fmt(format_args!("{}{}>{}{}", wildcard, from, wildcard, to))?;
+        } else {
+            let prefix = self
+                .prefix
+                .split('.')
+                .map(quote_name)
+                .collect::<Vec<_>>()
+                .join(".");
+            f.write_fmt(format_args!(
+                "{}{}.{}>{}{}.{}",
+                wildcard, prefix, from, wildcard, prefix, to
+            ))?;
+        }
+        match (self.min_depth, self.max_depth) {
+            (None, None) => {}
+            (Some(min), Some(max)) if min == max => f.write_fmt(format_args!("@{}", min))?,
+            (min, max) => f.write_fmt(format_args!(
+                "@{}-{}",
+                min.map(|d| d.to_string()).unwrap_or_default(),
+                max.map(|d| d.to_string()).unwrap_or_default()
+            ))?,
+        }
+        if let Some(overwrite) = self.overwrite {
+            f.write_fmt(format_args!(":{}", overwrite.as_str()))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Replace {
+    type Err = ReplaceParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (s, overwrite) = match rfind_unquoted(s, ':') {
+            Some(i) => (
+                &s[..i],
+                Some(match &s[i + 1..] {
+                    "error" => OverwritePolicy::Error,
+                    "skip" => OverwritePolicy::Skip,
+                    "overwrite" => OverwritePolicy::Overwrite,
+                    policy => {
+                        return Err(ReplaceParseError::InvalidOverwritePolicy(
+                            policy.to_string(),
+                        ))
+                    }
+                }),
+            ),
+            None => (s, None),
+        };
+
+        let (s, min_depth, max_depth) = match rfind_unquoted(s, '@') {
+            Some(i) => {
+                let depth = &s[i + 1..];
+                let invalid = || ReplaceParseError::InvalidDepth(depth.to_string());
+                let bound = |b: &str| {
+                    if b.is_empty() {
+                        Ok(None)
+                    } else {
+                        b.parse::<usize>().map(Some).map_err(|_| invalid())
+                    }
+                };
+                let (min, max) = match depth.split_once('-') {
+                    Some((min, max)) => (bound(min)?, bound(max)?),
+                    None => {
+                        let exact = bound(depth)?.ok_or_else(invalid)?;
+                        (Some(exact), Some(exact))
+                    }
+                };
+                (&s[..i], min, max)
+            }
+            None => (s, None, None),
+        };
+
+        let arrow = find_unquoted(s, '>').ok_or(ReplaceParseError::MissingArrow)?;
+        let (mut before, mut after) = (&s[..arrow], &s[arrow + 1..]);
+        let root = if before.starts_with("*") {
+            before = &before[1..];
+            if after.starts_with("*") {
+                after = &after[1..];
+            } else {
+                return Err(ReplaceParseError::Unsupported);
+            }
+            false
+        } else {
+            true
+        };
+
+        let mut before = parse_path(before)?;
+        let mut after = parse_path(after)?;
+        let from = before.pop().unwrap();
+        let to = after.pop().unwrap();
+        for name in [&from, &to] {
+            if name.ends_with(EVERY_ELEMENT) {
+                return Err(ReplaceParseError::InvalidAttribute(name.clone()));
+            }
+        }
+        if before != after {
+            return Err(ReplaceParseError::Unsupported);
+        }
+
+        Ok(Self {
+            root,
+            prefix: before.join("."),
+            from,
+            to,
+            overwrite,
+            min_depth,
+            max_depth,
+        })
+    }
+}
+
+/// Byte indices of `c` in `s`, other than within double quotes.
+fn unquoted_indices(s: &str, c: char) -> impl Iterator<Item = usize> + '_ {
+    let mut quoted = false;
+    s.char_indices().filter_map(move |(i, next)| {
+        if next == '"' {
+            quoted = !quoted;
+        }
+        (next == c && !quoted).then_some(i)
+    })
+}
+
+fn find_unquoted(s: &str, c: char) -> Option<usize> {
+    unquoted_indices(s, c).next()
+}
+
+fn rfind_unquoted(s: &str, c: char) -> Option<usize> {
+    unquoted_indices(s, c).last()
+}
+
+/// Whether `name` can appear in a rule without quotes.
+pub fn is_plain_name(name: &str) -> bool {
+    lazy_static! {
+        static ref NAME_REGEX: Regex = Regex::new("^[a-zA-Z0-9_\\-]+$").unwrap();
+    }
+    NAME_REGEX.is_match(name)
+}
+
+/// Marks a path segment as the maps in a list, like `orders[*]`.
+pub const EVERY_ELEMENT: &str = "[*]";
+
+/// Splits a rule's path, like `meta."first name"` or `orders[*].name`, into
+/// attribute names (with a `[*]` suffix for each level of list).
+///
+/// Names with other characters than letters, digits, `_`, and `-` (like
+/// spaces, `#`, or `:`) must be double-quoted, with `""` for a literal quote.
+/// They are always sent to DynamoDB as expression attribute name
+/// placeholders, so reserved words need no quoting. No name may contain `.`,
+/// which always separates path segments, or `[*]`.
+pub fn parse_path(path: &str) -> Result<Vec<String>, ReplaceParseError> {
+    let invalid = || ReplaceParseError::InvalidAttribute(path.to_string());
+    let mut names = Vec::new();
+    let mut chars = path.chars().peekable();
+    loop {
+        let mut name = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next().ok_or_else(invalid)? {
+                    '"' if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        name.push('"');
+                    }
+                    '"' => break,
+                    '.' => return Err(invalid()),
+                    c => name.push(c),
+                }
+            }
+            if name.is_empty() || name.contains(EVERY_ELEMENT) {
+                return Err(invalid());
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c == '.' || c == '[' {
+                    break;
+                }
+                name.push(c);
+                chars.next();
+            }
+            if !is_plain_name(&name) {
+                return Err(invalid());
+            }
+        }
+        while chars.peek() == Some(&'[') {
+            for expected in EVERY_ELEMENT.chars() {
+                if chars.next() != Some(expected) {
+                    return Err(invalid());
+                }
+            }
+            name.push_str(EVERY_ELEMENT);
+        }
+        names.push(name);
+        match chars.next() {
+            Some('.') => {}
+            Some(_) => return Err(invalid()),
+            None => return Ok(names),
+        }
+    }
+}
+
+/// Quotes a path segment for a rule, if necessary.
+pub(crate) fn quote_name(segment: &str) -> String {
+    let mut name = segment;
+    while let Some(list) = name.strip_suffix(EVERY_ELEMENT) {
+        name = list;
+    }
+    let lists = &segment[name.len()..];
+    if is_plain_name(name) {
+        segment.to_string()
+    } else {
+        format!("\"{}\"{}", name.replace('"', "\"\""), lists)
+    }
+}
+
+/// Whether applying `replacements` would modify `row` at all.
+pub fn would_change(row: &HashMap<String, AttributeValue>, replacements: &[Replace]) -> bool {
+    let mut probe = row.clone();
+    replace(
+        String::new(),
+        &mut probe,
+        replacements,
+        &mut ReplaceResult::default(),
+    );
+    probe != *row
+}
+
+/// Root-level attributes that `replacements` may modify, or `None` if they may
+/// modify any of them.
+pub fn affected_root_attributes(replacements: &[Replace]) -> Option<Vec<&str>> {
+    let mut ret = Vec::new();
+    for replacement in replacements {
+        if !replacement.root {
+            return None;
+        }
+        if replacement.prefix.is_empty() {
+            ret.push(replacement.from.as_str());
+            ret.push(replacement.to.as_str());
+        } else {
+            ret.push(replacement.prefix.split(['.', '[']).next().unwrap());
+        }
+    }
+    Some(ret)
+}
+
+/// What applying rules did, accumulated across items.
+#[derive(Debug, Default)]
+pub struct ReplaceResult {
+    pub replacements: usize,
+    pub overwrites: usize,
+    /// Breakdown by replacement, in the same order.
+    pub rules: Vec<RuleResult>,
+    /// Paths of attributes overwritten, against their rule's policy, since this
+    /// was last cleared.
+    pub overwritten: Vec<String>,
+    /// Pairs of rules (by index) that wrote the same path, and that path,
+    /// since this was last cleared.
+    pub collisions: Vec<(usize, usize, String)>,
+}
+
+/// What applying one rule did.
+#[derive(Debug, Default)]
+pub struct RuleResult {
+    pub replacements: usize,
+    pub overwrites: usize,
+    /// Replacements not made because the target existed.
+    pub skips: usize,
+    /// Distinct root-level attributes under which replacements were made.
+    pub attributes: HashSet<String>,
+    /// Number of replaced values of each type.
+    pub types: BTreeMap<&'static str, usize>,
+}
+
+impl ReplaceResult {
+    /// Adds what applying the same rules elsewhere did.
+    pub fn add(&mut self, other: ReplaceResult) {
+        self.replacements += other.replacements;
+        self.overwrites += other.overwrites;
+        if self.rules.len() < other.rules.len() {
+            self.rules.resize_with(other.rules.len(), Default::default);
+        }
+        for (rule, other) in self.rules.iter_mut().zip(other.rules) {
+            rule.replacements += other.replacements;
+            rule.overwrites += other.overwrites;
+            rule.skips += other.skips;
+            rule.attributes.extend(other.attributes);
+            for (type_name, count) in other.types {
+                *rule.types.entry(type_name).or_default() += count;
+            }
+        }
+        self.overwritten.extend(other.overwritten);
+        self.collisions.extend(other.collisions);
+    }
+}
+
+/// Whether a rule's `prefix` (from the root, or at any level) is the path of
+/// the map at `path`, segment by segment.
+fn matches_prefix(path: &str, root: bool, prefix: &str) -> bool {
+    path == prefix
+        || (!root
+            && (prefix.is_empty()
+                || path
+                    .strip_suffix(prefix)
+                    .is_some_and(|rest| rest.ends_with('.'))))
+}
+
+/// Applies `replacements` to `attribute`, a map at `path` (empty for an
+/// item), and everything in it, recording what they did in `result`.
+pub fn replace(
+    path: String,
+    attribute: &mut HashMap<String, AttributeValue>,
+    replacements: &[Replace],
+    result: &mut ReplaceResult,
+) {
+    if result.rules.len() < replacements.len() {
+        result
+            .rules
+            .resize_with(replacements.len(), Default::default);
+    }
+
+    // Which rule wrote each target at this level, to detect collisions.
+    let mut targets = HashMap::<&str, usize>::new();
+
+    // Path segments of attributes at this level, including their own.
+    let depth = if path.is_empty() {
+        1
+    } else {
+        path.split('.').count() + 1
+    };
+
+    for (i, (replacement, rule_result)) in replacements.iter().zip(&mut result.rules).enumerate() {
+        if replacement.min_depth.is_some_and(|min| depth < min)
+            || replacement.max_depth.is_some_and(|max| depth > max)
+        {
+            continue;
+        }
+        if matches_prefix(&path, replacement.root, &replacement.prefix) {
+            let policy = replacement.overwrite.unwrap_or(OverwritePolicy::Error);
+            if policy == OverwritePolicy::Skip
+                && attribute.contains_key(&replacement.from)
+                && attribute.contains_key(&replacement.to)
+            {
+                rule_result.skips += 1;
+                continue;
+            }
+            if let Some(value) = attribute.remove(&replacement.from) {
+                let root_attribute = path.split(['.', '[']).next().filter(|a| !a.is_empty());
+                rule_result
+                    .attributes
+                    .insert(root_attribute.unwrap_or(&replacement.from).to_string());
+                *rule_result
+                    .types
+                    .entry(crate::type_name(&value))
+                    .or_default() += 1;
+
+                let target_path = if path.is_empty() {
+                    replacement.to.clone()
+                } else {
+                    format!("{}.{}", path, replacement.to)
+                };
+                let overwrite = attribute.insert(replacement.to.clone(), value).is_some() as usize;
+                if let Some(other) = targets.insert(&replacement.to, i) {
+                    result.collisions.push((other, i, target_path));
+                } else if overwrite > 0 && policy == OverwritePolicy::Error {
+                    result.overwritten.push(target_path);
+                }
+                result.replacements += 1;
+                result.overwrites += overwrite;
+                rule_result.replacements += 1;
+                rule_result.overwrites += overwrite;
+            }
+        }
+    }
+
+    // No rule can match any deeper.
+    if replacements
+        .iter()
+        .all(|r| r.max_depth.is_some_and(|max| depth >= max))
+    {
+        return;
+    }
+
+    for (key, value) in attribute {
+        let new_path = if path.is_empty() {
+            key.clone()
+        } else {
+            path.clone() + "." + key
+        };
+        replace_within(new_path, value, replacements, result);
+    }
+}
+
+/// Applies `replacements` within `value`, at `path`, if it is a map or a list
+/// (whose elements are at `path[*]`).
+pub fn replace_within(
+    path: String,
+    value: &mut AttributeValue,
+    replacements: &[Replace],
+    result: &mut ReplaceResult,
+) {
+    match value {
+        AttributeValue::M(map) => replace(path, map, replacements, result),
+        AttributeValue::L(list) => {
+            let element_path = path + EVERY_ELEMENT;
+            for element in list {
+                replace_within(element_path.clone(), element, replacements, result);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// An attribute to remove wherever it appears at a path, like `meta.legacyId`,
+/// or (with `*`) at any level ending in that path, like `*legacyId`.
+#[derive(Debug, Clone)]
+pub struct Remove {
+    /// Whether the path starts at the root, rather than at any level.
+    pub root: bool,
+    /// The path of the maps holding the attribute, like `meta` (or empty).
+    pub prefix: String,
+    pub name: String,
+}
+
+impl FromStr for Remove {
+    type Err = ReplaceParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (root, path) = match s.strip_prefix('*') {
+            Some(path) => (false, path),
+            None => (true, s),
+        };
+        let mut path = parse_path(path)?;
+        let name = path.pop().unwrap();
+        if name.ends_with(EVERY_ELEMENT) {
+            return Err(ReplaceParseError::InvalidAttribute(name));
+        }
+        Ok(Self {
+            root,
+            prefix: path.join("."),
+            name,
+        })
+    }
+}
+
+impl Display for Remove {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if !self.root {
+            f.write_str("*")?;
+        }
+        for segment in self.prefix.split('.').filter(|s| !s.is_empty()) {
+            f.write_fmt(format_args!("{}.", quote_name(segment)))?;
+        }
+        f.write_str(&quote_name(&self.name))
+    }
+}
+
+impl Remove {
+    /// The root-level attribute this may remove (or remove from), or `None` if
+    /// it may affect any of them.
+    pub fn root_attribute(&self) -> Option<&str> {
+        if !self.root {
+            None
+        } else if self.prefix.is_empty() {
+            Some(&self.name)
+        } else {
+            self.prefix.split(['.', '[']).next()
+        }
+    }
+}
+
+/// Removes the attributes named by `removals` from `attribute` (at `path`) and
+/// anything in it, returning whether any were removed.
+pub fn remove(
+    path: &str,
+    attribute: &mut HashMap<String, AttributeValue>,
+    removals: &[Remove],
+) -> bool {
+    let mut removed = false;
+    for removal in removals {
+        if matches_prefix(path, removal.root, &removal.prefix) {
+            removed |= attribute.remove(&removal.name).is_some();
+        }
+    }
+    for (key, value) in attribute {
+        let new_path = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", path, key)
+        };
+        removed |= remove_within(new_path, value, removals);
+    }
+    removed
+}
+
+/// Like [`replace_within`], for [`remove`].
+pub fn remove_within(path: String, value: &mut AttributeValue, removals: &[Remove]) -> bool {
+    match value {
+        AttributeValue::M(map) => remove(&path, map, removals),
+        AttributeValue::L(list) => {
+            let element_path = path + EVERY_ELEMENT;
+            let mut removed = false;
+            for element in list {
+                removed |= remove_within(element_path.clone(), element, removals);
+            }
+            removed
+        }
+        _ => false,
+    }
+}
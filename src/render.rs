@@ -0,0 +1,157 @@
+use crate::filter::scalar_string;
+use crate::json::{item_to_json, item_to_plain_json, to_json, to_plain_json};
+use aws_sdk_dynamodb::model::AttributeValue;
+use aws_smithy_types::base64;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+static RENDER: OnceLock<Render> = OnceLock::new();
+
+/// How to write items and values in previews, diffs, reports, audit records,
+/// and messages (but not where DynamoDB or the `aws` CLI reads them back).
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Render {
+    /// DynamoDB JSON, like `{"S": "x"}` (the format used by the AWS CLI).
+    #[default]
+    Dynamodb,
+    /// Plain JSON, like `"x"`, with binary as base64 and sets as sorted arrays.
+    Plain,
+    /// For reading, like `{name: "x", age: 42, tags: <<"a", "b">>}`, with
+    /// binary like `B:3q2+7w==`. In JSON output, this is a string.
+    Compact,
+}
+
+#[derive(Debug)]
+pub struct RenderParseError(String);
+
+impl Display for RenderParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "render format '{}' is invalid (expected dynamodb, plain, or compact)",
+            self.0
+        ))
+    }
+}
+
+impl FromStr for Render {
+    type Err = RenderParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dynamodb" => Ok(Self::Dynamodb),
+            "plain" => Ok(Self::Plain),
+            "compact" => Ok(Self::Compact),
+            _ => Err(RenderParseError(s.to_string())),
+        }
+    }
+}
+
+/// Makes all further output use `render`.
+pub fn set(render: Render) {
+    let _ = RENDER.set(render);
+}
+
+fn get() -> Render {
+    RENDER.get().copied().unwrap_or_default()
+}
+
+/// A value, for JSON output.
+pub fn value(value: &AttributeValue) -> Value {
+    match get() {
+        Render::Dynamodb => to_json(value),
+        Render::Plain => to_plain_json(value),
+        Render::Compact => Value::String(compact(value)),
+    }
+}
+
+/// An item, for JSON output.
+pub fn item(item: &HashMap<String, AttributeValue>) -> Value {
+    match get() {
+        Render::Dynamodb => item_to_json(item),
+        Render::Plain => item_to_plain_json(item),
+        Render::Compact => Value::String(compact_map(item)),
+    }
+}
+
+/// A value, for text output.
+pub fn text(value: &AttributeValue) -> String {
+    match get() {
+        Render::Compact => compact(value),
+        _ => self::value(value).to_string(),
+    }
+}
+
+/// An item, for text output (like one line of `seed --dry-run`).
+pub fn item_text(item: &HashMap<String, AttributeValue>) -> String {
+    match get() {
+        Render::Compact => compact_map(item),
+        _ => self::item(item).to_string(),
+    }
+}
+
+/// A value in a label (like a key, or a group in a report), where strings,
+/// numbers, and binary (as base64) are written as themselves.
+pub fn label(value: &AttributeValue) -> String {
+    scalar_string(value).unwrap_or_else(|| text(value))
+}
+
+fn compact(value: &AttributeValue) -> String {
+    let string = |s: &String| Value::String(s.clone()).to_string();
+    let binary = |b: &aws_smithy_types::Blob| format!("B:{}", base64::encode(b.as_ref()));
+    let set = |mut elements: Vec<String>| {
+        elements.sort_unstable();
+        format!("<<{}>>", elements.join(", "))
+    };
+    match value {
+        AttributeValue::S(s) => string(s),
+        AttributeValue::N(n) => n.clone(),
+        AttributeValue::B(b) => binary(b),
+        AttributeValue::Bool(b) => b.to_string(),
+        AttributeValue::Null(_) => String::from("null"),
+        AttributeValue::Ss(ss) => set(ss.iter().map(string).collect()),
+        AttributeValue::Ns(ns) => {
+            let mut numbers = ns.iter().collect::<Vec<_>>();
+            numbers.sort_unstable_by(|a, b| {
+                let (a, b) = (a.parse::<f64>(), b.parse::<f64>());
+                a.ok()
+                    .partial_cmp(&b.ok())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            format!(
+                "<<{}>>",
+                numbers
+                    .into_iter()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        }
+        AttributeValue::Bs(bs) => set(bs.iter().map(binary).collect()),
+        AttributeValue::L(l) => {
+            format!("[{}]", l.iter().map(compact).collect::<Vec<_>>().join(", "))
+        }
+        AttributeValue::M(m) => compact_map(m),
+        _ => String::from("?"),
+    }
+}
+
+/// Writes a map with its attributes sorted by name, quoting names like rules do.
+fn compact_map(map: &HashMap<String, AttributeValue>) -> String {
+    let mut attributes = map.iter().collect::<Vec<_>>();
+    attributes.sort_unstable_by_key(|(name, _)| name.as_str());
+    let attributes = attributes
+        .into_iter()
+        .map(|(name, value)| {
+            let name = if crate::is_plain_name(name) {
+                name.clone()
+            } else {
+                format!("\"{}\"", name.replace('"', "\"\""))
+            };
+            format!("{}: {}", name, compact(value))
+        })
+        .collect::<Vec<_>>();
+    format!("{{{}}}", attributes.join(", "))
+}
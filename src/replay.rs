@@ -0,0 +1,133 @@
+use crate::filter::{get_path, get_path_mut, Key};
+use crate::json::from_json;
+use aws_sdk_dynamodb::model::AttributeValue;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+/// An audited edit of one item, to make again.
+pub struct Record {
+    pub table: String,
+    pub key: Key,
+    /// Whether the item was deleted.
+    pub deleted: bool,
+    changes: Vec<Change>,
+}
+
+/// An audited change of the attribute at a dot-separated path, from its old
+/// value to its new one (either of which may be absent).
+struct Change {
+    path: String,
+    old: Option<AttributeValue>,
+    new: Option<AttributeValue>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    /// This line (from 1) isn't an audit record.
+    Malformed(usize),
+    /// This line's values aren't DynamoDB JSON, since the audit trail was
+    /// written with another `--render`.
+    Rendered(usize),
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Io(e) => f.write_fmt(format_args!("error reading audit file: {}", e)),
+            ReplayError::Malformed(line) => f.write_fmt(format_args!(
+                "audit file line {} is not an audit record",
+                line
+            )),
+            ReplayError::Rendered(line) => f.write_fmt(format_args!(
+                "audit file line {} doesn't have DynamoDB JSON values (only audit trails written with --render dynamodb can be replayed)",
+                line
+            )),
+        }
+    }
+}
+
+/// Reads the records of an audit file, in order.
+pub fn load(path: &Path) -> Result<Vec<Record>, ReplayError> {
+    let file = File::open(path).map_err(ReplayError::Io)?;
+    let mut records = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(ReplayError::Io)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let malformed = || ReplayError::Malformed(i + 1);
+        let record = serde_json::from_str::<Value>(&line).map_err(|_| malformed())?;
+        let string = |field: &str| {
+            record[field]
+                .as_str()
+                .map(String::from)
+                .ok_or_else(malformed)
+        };
+        let value = |value: &Value| match value {
+            Value::Null => Ok(None),
+            value => from_json(value)
+                .map(Some)
+                .ok_or(ReplayError::Rendered(i + 1)),
+        };
+        let changes = record["changes"]
+            .as_array()
+            .ok_or_else(malformed)?
+            .iter()
+            .map(|change| {
+                Ok(Change {
+                    path: change["path"].as_str().ok_or_else(malformed)?.to_string(),
+                    old: value(&change["old"])?,
+                    new: value(&change["new"])?,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        records.push(Record {
+            table: string("table")?,
+            key: string("key")?.parse().map_err(|_| malformed())?,
+            deleted: record["deleted"].as_bool().ok_or_else(malformed)?,
+            changes,
+        });
+    }
+    Ok(records)
+}
+
+impl Record {
+    /// Makes the record's changes to `item`, as long as each changed attribute
+    /// still has its old value, or already has its new one (so replaying is
+    /// idempotent). Otherwise, returns the path of the first that has neither.
+    ///
+    /// A deleted item leaves `item` empty, as long as it had nothing else.
+    pub fn apply(&self, item: &mut HashMap<String, AttributeValue>) -> Result<(), String> {
+        for change in &self.changes {
+            let current = get_path(item, &change.path);
+            if current == change.new.as_ref() {
+                continue;
+            }
+            if current != change.old.as_ref() {
+                return Err(change.path.clone());
+            }
+            let (parent, name) = match change.path.rsplit_once('.') {
+                Some((parent, name)) => match get_path_mut(item, parent) {
+                    Some(AttributeValue::M(map)) => (map, name),
+                    _ => return Err(change.path.clone()),
+                },
+                None => (&mut *item, change.path.as_str()),
+            };
+            match &change.new {
+                Some(new) => parent.insert(name.to_string(), new.clone()),
+                None => parent.remove(name),
+            };
+        }
+        if self.deleted {
+            if let Some(name) = item.keys().min() {
+                return Err(name.clone());
+            }
+        }
+        Ok(())
+    }
+}
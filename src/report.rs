@@ -0,0 +1,421 @@
+use crate::decimal::Decimal;
+use crate::diff::{self, Change};
+use crate::filter::get_path;
+use crate::render;
+use aws_sdk_dynamodb::model::AttributeValue;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Percentiles included in a [`NumberSummary`].
+const PERCENTILES: [u32; 4] = [50, 90, 95, 99];
+
+/// Distribution of a numeric attribute over some items.
+#[derive(Debug)]
+pub struct NumberSummary {
+    attribute: String,
+    /// How many items hold each value.
+    values: BTreeMap<Decimal, usize>,
+    /// Items with a number.
+    count: usize,
+    /// Items with the attribute, but not as a number.
+    other: usize,
+    /// Items without the attribute.
+    missing: usize,
+}
+
+impl NumberSummary {
+    pub fn new(attribute: &str) -> Self {
+        Self {
+            attribute: attribute.to_string(),
+            values: BTreeMap::new(),
+            count: 0,
+            other: 0,
+            missing: 0,
+        }
+    }
+
+    pub fn add(&mut self, item: &HashMap<String, AttributeValue>) {
+        match item.get(&self.attribute) {
+            Some(AttributeValue::N(n)) => match n.parse() {
+                Ok(value) => {
+                    *self.values.entry(value).or_default() += 1;
+                    self.count += 1;
+                }
+                Err(_) => self.other += 1,
+            },
+            Some(_) => self.other += 1,
+            None => self.missing += 1,
+        }
+    }
+
+    /// The smallest value that at least `p` percent of values are at or below.
+    fn percentile(&self, p: u32) -> &Decimal {
+        let rank = (self.count * p as usize).div_ceil(100).max(1);
+        let mut seen = 0;
+        self.values
+            .iter()
+            .find(|(_, count)| {
+                seen += *count;
+                seen >= rank
+            })
+            .map(|(value, _)| value)
+            .unwrap()
+    }
+
+    /// Arithmetic mean, which (unlike the other statistics) is approximate.
+    fn mean(&self) -> f64 {
+        let sum = self
+            .values
+            .iter()
+            .map(|(v, count)| v.to_string().parse::<f64>().unwrap_or(f64::NAN) * *count as f64)
+            .sum::<f64>();
+        sum / self.count as f64
+    }
+}
+
+impl Display for NumberSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}: count {}", self.attribute, self.count))?;
+        if let (Some((min, _)), Some((max, _))) =
+            (self.values.first_key_value(), self.values.last_key_value())
+        {
+            f.write_fmt(format_args!(
+                ", min {}, max {}, mean {}",
+                min,
+                max,
+                self.mean()
+            ))?;
+            for p in PERCENTILES {
+                f.write_fmt(format_args!(", p{} {}", p, self.percentile(p)))?;
+            }
+        }
+        if self.other > 0 {
+            f.write_fmt(format_args!(" ({} not numbers)", self.other))?;
+        }
+        if self.missing > 0 {
+            f.write_fmt(format_args!(" ({} missing)", self.missing))?;
+        }
+        Ok(())
+    }
+}
+
+/// How many items hold each distinct value of an attribute.
+#[derive(Debug)]
+pub struct Frequencies {
+    attribute: String,
+    counts: HashMap<String, usize>,
+    missing: usize,
+    /// How many of the most frequent values to list.
+    listed: Option<usize>,
+}
+
+impl Frequencies {
+    pub fn new(attribute: &str) -> Self {
+        Self {
+            attribute: attribute.to_string(),
+            counts: HashMap::new(),
+            missing: 0,
+            listed: None,
+        }
+    }
+
+    pub fn add(&mut self, item: &HashMap<String, AttributeValue>) {
+        match item.get(&self.attribute) {
+            Some(value) => *self.counts.entry(render::label(value)).or_default() += 1,
+            None => self.missing += 1,
+        }
+    }
+
+    /// Only lists the `n` most frequent values, each with its share of the
+    /// items (e.g. to find hot partitions).
+    pub fn top(mut self, n: usize) -> Self {
+        self.listed = Some(n);
+        self
+    }
+}
+
+impl Display for Frequencies {
+    /// One line for the attribute, then one indented line per value.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "{}: {} distinct value(s)",
+            self.attribute,
+            self.counts.len()
+        ))?;
+        if self.missing > 0 {
+            f.write_fmt(format_args!(" ({} missing)", self.missing))?;
+        }
+        // By descending count, then value.
+        let mut counts = self.counts.iter().collect::<Vec<_>>();
+        counts.sort_unstable_by(|(a, a_count), (b, b_count)| {
+            b_count.cmp(a_count).then_with(|| a.cmp(b))
+        });
+        let width = counts
+            .first()
+            .map(|(_, count)| count.to_string().len())
+            .unwrap_or_default();
+        let listed = self.listed.unwrap_or(counts.len());
+        let truncated = listed < counts.len();
+        let total = self.counts.values().sum::<usize>();
+        for (value, count) in counts.iter().take(listed) {
+            f.write_fmt(format_args!("\n  {:>width$}  ", count, width = width))?;
+            if truncated {
+                f.write_fmt(format_args!(
+                    "{:>5.1}%  ",
+                    **count as f64 * 100.0 / total as f64
+                ))?;
+            }
+            f.write_str(value)?;
+        }
+        if truncated {
+            f.write_fmt(format_args!("\n  ... and {} more", counts.len() - listed))?;
+        }
+        Ok(())
+    }
+}
+
+/// The largest item DynamoDB can store, in bytes.
+pub const MAX_ITEM_SIZE: usize = 400 * 1024;
+/// How many of the largest items a [`SizeSummary`] lists.
+const LARGEST: usize = 10;
+
+/// Approximately how many bytes DynamoDB counts `item` as, for its size limit
+/// and capacity units.
+pub fn item_size(item: &HashMap<String, AttributeValue>) -> usize {
+    item.iter()
+        .map(|(name, value)| name.len() + value_size(value))
+        .sum()
+}
+
+fn value_size(value: &AttributeValue) -> usize {
+    let number_size = |n: &str| match n.parse::<Decimal>() {
+        Ok(n) => n.significant_digits().div_ceil(2) + 1,
+        Err(_) => n.len(),
+    };
+    match value {
+        AttributeValue::S(s) => s.len(),
+        AttributeValue::N(n) => number_size(n),
+        AttributeValue::B(b) => b.as_ref().len(),
+        AttributeValue::Ss(ss) => ss.iter().map(String::len).sum(),
+        AttributeValue::Ns(ns) => ns.iter().map(|n| number_size(n)).sum(),
+        AttributeValue::Bs(bs) => bs.iter().map(|b| b.as_ref().len()).sum(),
+        // Each element has a byte of overhead, and the container three.
+        AttributeValue::L(l) => 3 + l.iter().map(|v| 1 + value_size(v)).sum::<usize>(),
+        AttributeValue::M(m) => {
+            3 + m
+                .iter()
+                .map(|(name, v)| 1 + name.len() + value_size(v))
+                .sum::<usize>()
+        }
+        _ => 1,
+    }
+}
+
+/// A percentage, like `20%`.
+#[derive(Debug, Clone, Copy)]
+pub struct Percent(pub f64);
+
+#[derive(Debug)]
+pub struct PercentParseError(String);
+
+impl Display for PercentParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "percentage '{}' is invalid (expected e.g. 20%)",
+            self.0
+        ))
+    }
+}
+
+impl FromStr for Percent {
+    type Err = PercentParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.strip_suffix('%')
+            .and_then(|n| n.parse::<f64>().ok())
+            .filter(|n| n.is_finite() && *n >= 0.0)
+            .map(Self)
+            .ok_or_else(|| PercentParseError(s.to_string()))
+    }
+}
+
+impl Display for Percent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}%", self.0))
+    }
+}
+
+/// Distribution of item sizes, with the keys of the largest items.
+#[derive(Debug)]
+pub struct SizeSummary {
+    key_names: Vec<String>,
+    /// How many items are each size in bytes.
+    sizes: BTreeMap<usize, usize>,
+    count: usize,
+    /// The [`LARGEST`] items' sizes, with their formatted keys, largest first.
+    largest: Vec<(usize, String)>,
+}
+
+impl SizeSummary {
+    pub fn new(key_names: &[String]) -> Self {
+        Self {
+            key_names: key_names.to_vec(),
+            sizes: BTreeMap::new(),
+            count: 0,
+            largest: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, item: &HashMap<String, AttributeValue>) {
+        let size = item_size(item);
+        *self.sizes.entry(size).or_default() += 1;
+        self.count += 1;
+        if self.largest.len() < LARGEST || self.largest.last().is_some_and(|(s, _)| size > *s) {
+            let entry = (size, crate::format_key(item, &self.key_names));
+            let position = self.largest.partition_point(|e| *e > entry);
+            self.largest.insert(position, entry);
+            self.largest.truncate(LARGEST);
+        }
+    }
+
+    fn percentile(&self, p: u32) -> usize {
+        let rank = (self.count * p as usize).div_ceil(100).max(1);
+        let mut seen = 0;
+        self.sizes
+            .iter()
+            .find(|(_, count)| {
+                seen += *count;
+                seen >= rank
+            })
+            .map(|(&size, _)| size)
+            .unwrap_or_default()
+    }
+}
+
+impl Display for SizeSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("item size: count {}", self.count))?;
+        let max = match self.largest.first() {
+            Some(&(max, _)) => max,
+            None => return Ok(()),
+        };
+        for p in [50, 95, 99] {
+            f.write_fmt(format_args!(", p{} {}B", p, self.percentile(p)))?;
+        }
+        f.write_fmt(format_args!(
+            ", max {}B ({:.1}% of the limit)",
+            max,
+            max as f64 * 100.0 / MAX_ITEM_SIZE as f64
+        ))?;
+        f.write_str("\nlargest:")?;
+        let width = max.to_string().len();
+        for (size, key) in &self.largest {
+            f.write_fmt(format_args!(
+                "\n  {:>width$}B  {}",
+                size,
+                key,
+                width = width
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+/// How the edits changed each attribute path, over the items they changed.
+#[derive(Debug, Default)]
+pub struct AttributeChanges {
+    paths: BTreeMap<String, PathChanges>,
+}
+
+#[derive(Debug, Default)]
+struct PathChanges {
+    created: usize,
+    overwritten: usize,
+    removed: usize,
+    unchanged: usize,
+    /// Smallest and largest change in the value's size, in bytes.
+    size_delta: Option<(i64, i64)>,
+}
+
+impl AttributeChanges {
+    pub fn new<'a>(
+        edits: impl IntoIterator<
+                Item = (
+                    &'a HashMap<String, AttributeValue>,
+                    &'a HashMap<String, AttributeValue>,
+                ),
+            > + Clone,
+    ) -> Self {
+        let mut ret = Self::default();
+        for (old, new) in edits.clone() {
+            for change in diff::changes(old, new) {
+                let (path, old_size, new_size) = match &change {
+                    Change::Added(path, value) => (path, 0, value_size(value)),
+                    Change::Removed(path, value) => (path, value_size(value), 0),
+                    Change::Changed(path, old, new) => (path, value_size(old), value_size(new)),
+                };
+                let stats = ret.paths.entry(path.clone()).or_default();
+                match change {
+                    Change::Added(..) => stats.created += 1,
+                    Change::Removed(..) => stats.removed += 1,
+                    Change::Changed(..) => stats.overwritten += 1,
+                }
+                let delta = new_size as i64 - old_size as i64;
+                stats.size_delta = Some(match stats.size_delta {
+                    Some((min, max)) => (min.min(delta), max.max(delta)),
+                    None => (delta, delta),
+                });
+            }
+        }
+        for (old, new) in edits {
+            for (path, stats) in &mut ret.paths {
+                if let Some(value) = get_path(old, path) {
+                    if get_path(new, path) == Some(value) {
+                        stats.unchanged += 1;
+                    }
+                }
+            }
+        }
+        ret
+    }
+}
+
+impl Display for AttributeChanges {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("changes by attribute:")?;
+        for (path, stats) in &self.paths {
+            f.write_fmt(format_args!(
+                "\n  {}: {} created, {} overwritten, {} removed, {} unchanged",
+                path, stats.created, stats.overwritten, stats.removed, stats.unchanged
+            ))?;
+            if let Some((min, max)) = stats.size_delta {
+                f.write_fmt(format_args!(", size change {:+}B to {:+}B", min, max))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What the `stats` command reports, with each item added as it's read, so
+/// the items needn't be kept.
+#[derive(Debug, Default)]
+pub struct Summaries {
+    pub numbers: Vec<NumberSummary>,
+    pub frequencies: Vec<Frequencies>,
+    pub sizes: Option<SizeSummary>,
+    /// Of the partition key, to find hot partitions.
+    pub partitions: Option<Frequencies>,
+    /// Items added.
+    pub count: usize,
+}
+
+impl Summaries {
+    pub fn add(&mut self, item: &HashMap<String, AttributeValue>) {
+        self.numbers.iter_mut().for_each(|s| s.add(item));
+        self.frequencies.iter_mut().for_each(|s| s.add(item));
+        self.sizes.iter_mut().for_each(|s| s.add(item));
+        self.partitions.iter_mut().for_each(|s| s.add(item));
+        self.count += 1;
+    }
+}
@@ -0,0 +1,144 @@
+use aws_config::sts::AssumeRoleProvider;
+use aws_sdk_dynamodb::types::SdkError;
+use aws_sdk_dynamodb::{Credentials, Region};
+use aws_sdk_sts::error::AssumeRoleError;
+use aws_types::credentials::{ProvideCredentials, SharedCredentialsProvider};
+use std::fmt::{Display, Formatter};
+use std::io::{self, BufRead};
+use std::time::SystemTime;
+
+/// Identifies our sessions in the role account's CloudTrail.
+const SESSION_NAME: &str = "dynamodb_bulk_edit";
+
+/// A role to assume (with STS) in front of the other credentials, such as for
+/// a table in another account.
+pub struct Role {
+    pub arn: String,
+    pub external_id: Option<String>,
+    /// The serial number (or ARN) of the MFA device the role requires.
+    pub mfa_serial: Option<String>,
+    /// The MFA code, which is asked for if needed and not given.
+    pub mfa_token: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum RoleError {
+    /// Asking for the MFA code failed.
+    Token(io::Error),
+    /// The MFA code wasn't given and can't be asked for.
+    NoToken,
+    AssumeRole(Box<SdkError<AssumeRoleError>>),
+    /// STS didn't return credentials.
+    NoCredentials,
+}
+
+impl Display for RoleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoleError::Token(e) => f.write_fmt(format_args!("error reading MFA code: {}", e)),
+            RoleError::NoToken => f.write_str("--mfa-token is required when running headless"),
+            RoleError::AssumeRole(e) => f.write_fmt(format_args!("error assuming role: {}", e)),
+            RoleError::NoCredentials => f.write_str("error assuming role: no credentials returned"),
+        }
+    }
+}
+
+impl Role {
+    /// Credentials for the role, obtained with the `base` credentials.
+    ///
+    /// Without MFA, the credentials are refreshed before they expire. MFA
+    /// codes can only be used once, so with MFA, the role is assumed once, and
+    /// its credentials last for the role's maximum session duration (one hour
+    /// unless the role allows longer).
+    pub async fn provider(
+        &self,
+        base: impl ProvideCredentials + 'static,
+        region: Option<Region>,
+    ) -> Result<SharedCredentialsProvider, RoleError> {
+        let base = SharedCredentialsProvider::new(base);
+        let mfa_serial = match &self.mfa_serial {
+            Some(mfa_serial) => mfa_serial,
+            None => {
+                let mut builder = AssumeRoleProvider::builder(&self.arn).session_name(SESSION_NAME);
+                if let Some(external_id) = &self.external_id {
+                    builder = builder.external_id(external_id);
+                }
+                if let Some(region) = region {
+                    builder = builder.region(region);
+                }
+                return Ok(SharedCredentialsProvider::new(builder.build(base)));
+            }
+        };
+        let token = match &self.mfa_token {
+            Some(token) => token.clone(),
+            None => read_token(mfa_serial)?,
+        };
+        let config = aws_sdk_sts::Config::builder()
+            .credentials_provider(base)
+            .region(region)
+            .build();
+        let sts = aws_sdk_sts::Client::from_conf(config);
+        // Asks for the longest session the role allows, falling back to the
+        // default hour if the role's maximum is lower than that.
+        let mut output = None;
+        for duration in [Some(12 * 60 * 60), None] {
+            match sts
+                .assume_role()
+                .role_arn(&self.arn)
+                .role_session_name(SESSION_NAME)
+                .set_external_id(self.external_id.clone())
+                .serial_number(mfa_serial)
+                .token_code(&token)
+                .set_duration_seconds(duration)
+                .send()
+                .await
+            {
+                Ok(o) => {
+                    output = Some(o);
+                    break;
+                }
+                Err(SdkError::ServiceError { err, .. })
+                    if duration.is_some()
+                        && err.code() == Some("ValidationError")
+                        && err.message().is_some_and(|m| m.contains("DurationSeconds")) => {}
+                Err(e) => return Err(RoleError::AssumeRole(Box::new(e))),
+            }
+        }
+        let credentials = output
+            .and_then(|o| o.credentials)
+            .ok_or(RoleError::NoCredentials)?;
+        let expiration = credentials
+            .expiration
+            .and_then(|e| SystemTime::try_from(e).ok());
+        match (
+            credentials.access_key_id,
+            credentials.secret_access_key,
+            credentials.session_token,
+        ) {
+            (Some(access_key_id), Some(secret_access_key), session_token) => {
+                Ok(SharedCredentialsProvider::new(Credentials::new(
+                    access_key_id,
+                    secret_access_key,
+                    session_token,
+                    expiration,
+                    "AssumeRole",
+                )))
+            }
+            _ => Err(RoleError::NoCredentials),
+        }
+    }
+}
+
+/// Asks the operator to type the current code of their MFA device.
+fn read_token(mfa_serial: &str) -> Result<String, RoleError> {
+    if crate::headless::enabled() {
+        return Err(RoleError::NoToken);
+    }
+    eprint!("MFA code for {}: ", mfa_serial);
+    let mut line = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .map_err(RoleError::Token)?;
+    Ok(line.trim().to_string())
+}
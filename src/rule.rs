@@ -0,0 +1,241 @@
+//! Rules built in code, rather than parsed from strings, and what they mean.
+//!
+//! ```
+//! use dynamodb_bulk_edit::rule::{Path, Rule};
+//! use dynamodb_bulk_edit::OverwritePolicy;
+//!
+//! let rules = [
+//!     Rule::rename(Path::root("meta").field("first name"), "given name")?,
+//!     Rule::rename(Path::any("userName"), "username")?.overwrite(OverwritePolicy::Skip),
+//!     Rule::remove(Path::root("orders").every().field("legacyId"))?,
+//! ];
+//! assert_eq!(rules[0].to_string(), "meta.\"first name\">meta.\"given name\"");
+//! assert_eq!(rules[2].to_string(), "orders[*].legacyId");
+//! # Ok::<(), dynamodb_bulk_edit::ReplaceParseError>(())
+//! ```
+//!
+//! # Semantics
+//!
+//! An item is a tree of values, where maps (`M`) hold named values and lists
+//! (`L`) hold unnamed ones. A [`Path`] names the attributes at the end of it in
+//! every map it reaches: starting at the item ([`Path::root`]), or at any map
+//! in it, however deep ([`Path::any`]), each segment goes into the map held by
+//! the attribute of that name, or (with [`Path::every`]) into every element of
+//! the list it holds. Paths that reach something else (like a string, or a
+//! list where a map is expected) name nothing, so rules never fail on the
+//! shape of an item.
+//!
+//! A rename moves the value of each attribute its path names to a new name in
+//! the same map, without looking into or changing the value itself beyond
+//! applying the same rules within it. If the new name is taken, its
+//! [`OverwritePolicy`] decides: [`Error`](OverwritePolicy::Error) (the default)
+//! moves it anyway but reports the overwrite (so the binary refuses to write),
+//! [`Skip`](OverwritePolicy::Skip) leaves both, and
+//! [`Overwrite`](OverwritePolicy::Overwrite) replaces the existing value.
+//! Optional depth bounds only match attributes with that many path segments
+//! (including their own). A removal deletes each attribute its path names.
+//!
+//! [`apply`] applies rules in order, where consecutive renames are applied in
+//! one pass, from the item inwards, and report when two of them write the same
+//! attribute. Paths are matched by whole names, as renamed so far, so a map
+//! renamed into a rule's path is then renamed within. Renames are (as checked
+//! by this crate's property tests):
+//!
+//! - Lossless: unless it overwrites, a rename keeps every value in the item,
+//!   exactly once, and only renames attributes.
+//! - Idempotent: applying a rename again changes nothing.
+//! - Independent of nesting: without depth bounds, a rename at any level does
+//!   the same to a map wherever it is, as long as what holds it isn't named in
+//!   its path.
+//! - Invertible: unless it overwrites, or its new name is also in its path,
+//!   applying the [`Replace::inverse`] afterwards restores the item, as long as
+//!   the inverse wouldn't have changed it beforehand.
+//! - Round-tripping: a rule's string parses back to the same rule.
+
+use crate::rename::{
+    quote_name, remove, replace, Remove, Replace, ReplaceParseError, ReplaceResult, EVERY_ELEMENT,
+};
+use crate::OverwritePolicy;
+use aws_sdk_dynamodb::model::AttributeValue;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// The attributes a rule applies to, like `meta."first name"` or (with `*`,
+/// at any level) `*orders[*].total`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path {
+    root: bool,
+    /// Attribute names, with a `[*]` suffix for each level of list.
+    segments: Vec<String>,
+}
+
+impl Path {
+    /// The root-level attribute `name`.
+    pub fn root(name: impl Into<String>) -> Self {
+        Self {
+            root: true,
+            segments: vec![name.into()],
+        }
+    }
+
+    /// The attributes named `name` in any map, at any level.
+    pub fn any(name: impl Into<String>) -> Self {
+        Self {
+            root: false,
+            segments: vec![name.into()],
+        }
+    }
+
+    /// The attribute `name` of the map at this path.
+    pub fn field(mut self, name: impl Into<String>) -> Self {
+        self.segments.push(name.into());
+        self
+    }
+
+    /// Every element of the list at this path.
+    pub fn every(mut self) -> Self {
+        if let Some(last) = self.segments.last_mut() {
+            last.push_str(EVERY_ELEMENT);
+        }
+        self
+    }
+
+    /// The path's prefix and the name at its end, as long as they can be
+    /// written in a rule: no name may be empty, contain `.` or `[*]`, and the
+    /// path must end in a name rather than a list's elements.
+    fn split(&self) -> Result<(String, String), ReplaceParseError> {
+        for segment in &self.segments {
+            let mut name = segment.as_str();
+            while let Some(list) = name.strip_suffix(EVERY_ELEMENT) {
+                name = list;
+            }
+            if name.is_empty() || name.contains('.') || name.contains(EVERY_ELEMENT) {
+                return Err(ReplaceParseError::InvalidAttribute(segment.clone()));
+            }
+        }
+        let (name, prefix) = self.segments.split_last().unwrap();
+        if name.ends_with(EVERY_ELEMENT) {
+            return Err(ReplaceParseError::InvalidAttribute(name.clone()));
+        }
+        Ok((prefix.join("."), name.clone()))
+    }
+}
+
+impl Display for Path {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if !self.root {
+            f.write_str("*")?;
+        }
+        let segments = self
+            .segments
+            .iter()
+            .map(|segment| quote_name(segment))
+            .collect::<Vec<_>>();
+        f.write_str(&segments.join("."))
+    }
+}
+
+/// A rule, like those given with `--rename` and `--remove`.
+#[derive(Debug, Clone)]
+pub enum Rule {
+    Rename(Replace),
+    Remove(Remove),
+}
+
+impl Rule {
+    /// Renames the attributes at `path` to `to`, in the same map.
+    pub fn rename(path: Path, to: impl Into<String>) -> Result<Self, ReplaceParseError> {
+        let (prefix, from) = path.split()?;
+        let to = to.into();
+        if to.is_empty() || to.contains('.') || to.contains(EVERY_ELEMENT) {
+            return Err(ReplaceParseError::InvalidAttribute(to));
+        }
+        Ok(Self::Rename(Replace {
+            root: path.root,
+            prefix,
+            from,
+            to,
+            overwrite: None,
+            min_depth: None,
+            max_depth: None,
+        }))
+    }
+
+    /// Removes the attributes at `path`.
+    pub fn remove(path: Path) -> Result<Self, ReplaceParseError> {
+        let (prefix, name) = path.split()?;
+        Ok(Self::Remove(Remove {
+            root: path.root,
+            prefix,
+            name,
+        }))
+    }
+
+    /// What a rename does if its new name is taken (a removal has nothing to
+    /// overwrite, so is left as is).
+    pub fn overwrite(mut self, policy: OverwritePolicy) -> Self {
+        if let Self::Rename(replace) = &mut self {
+            replace.overwrite = Some(policy);
+        }
+        self
+    }
+
+    /// Inclusive bounds on how many path segments (including its own) an
+    /// attribute a rename matches may have (a removal is left as is).
+    pub fn depth(mut self, min: Option<usize>, max: Option<usize>) -> Self {
+        if let Self::Rename(replace) = &mut self {
+            replace.min_depth = min;
+            replace.max_depth = max;
+        }
+        self
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rule::Rename(replace) => replace.fmt(f),
+            Rule::Remove(remove) => remove.fmt(f),
+        }
+    }
+}
+
+/// Applies `rules` to `item` in order, returning what the renames did (with an
+/// empty breakdown for each removal).
+pub fn apply(rules: &[Rule], item: &mut HashMap<String, AttributeValue>) -> ReplaceResult {
+    let mut result = ReplaceResult::default();
+    let mut i = 0;
+    while i < rules.len() {
+        match &rules[i] {
+            Rule::Rename(_) => {
+                let renames = rules[i..]
+                    .iter()
+                    .map_while(|rule| match rule {
+                        Rule::Rename(replace) => Some(replace.clone()),
+                        Rule::Remove(_) => None,
+                    })
+                    .collect::<Vec<_>>();
+                let mut renamed = ReplaceResult::default();
+                replace(String::new(), item, &renames, &mut renamed);
+                let start = i;
+                i += renames.len();
+                result.replacements += renamed.replacements;
+                result.overwrites += renamed.overwrites;
+                result.overwritten.append(&mut renamed.overwritten);
+                result.collisions.extend(
+                    renamed
+                        .collisions
+                        .into_iter()
+                        .map(|(a, b, path)| (start + a, start + b, path)),
+                );
+                result.rules.append(&mut renamed.rules);
+            }
+            Rule::Remove(removal) => {
+                remove("", item, std::slice::from_ref(removal));
+                result.rules.push(Default::default());
+                i += 1;
+            }
+        }
+    }
+    result
+}
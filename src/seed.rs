@@ -0,0 +1,343 @@
+use crate::filter::Time;
+use crate::json::item_from_json;
+use aws_sdk_dynamodb::error::BatchWriteItemError;
+use aws_sdk_dynamodb::model::{AttributeValue, PutRequest, WriteRequest};
+use aws_sdk_dynamodb::types::SdkError;
+use aws_sdk_dynamodb::Client;
+use aws_smithy_types::date_time::Format;
+use aws_smithy_types::DateTime;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// The most requests that `BatchWriteItem` accepts at once.
+const BATCH_SIZE: usize = 25;
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+const LOREM: [&str; 24] = [
+    "lorem",
+    "ipsum",
+    "dolor",
+    "sit",
+    "amet",
+    "consectetur",
+    "adipiscing",
+    "elit",
+    "sed",
+    "do",
+    "eiusmod",
+    "tempor",
+    "incididunt",
+    "ut",
+    "labore",
+    "et",
+    "dolore",
+    "magna",
+    "aliqua",
+    "enim",
+    "ad",
+    "minim",
+    "veniam",
+    "quis",
+];
+
+/// Makes up part of a string in a [`Template`], written in braces, like
+/// `{int:18..90}`.
+#[derive(Debug, Clone)]
+pub enum Generator {
+    /// `{uuid}`: a random (version 4) UUID.
+    Uuid,
+    /// `{seq}`: the item's position, from 0 (e.g. for unique keys).
+    Seq,
+    /// `{int:MIN..MAX}`: a random integer, inclusive.
+    Int(i64, i64),
+    /// `{lorem:WORDS}`: that many words of placeholder text.
+    Lorem(usize),
+    /// `{choice:a|b|c}`: one of the options.
+    Choice(Vec<String>),
+    /// `{timestamp:START..END}`: a random ISO 8601 UTC time in the range.
+    Timestamp(i64, i64),
+    /// `{epoch:START..END}`: a random time in the range, in epoch seconds.
+    Epoch(i64, i64),
+}
+
+#[derive(Debug)]
+pub struct GeneratorParseError(String);
+
+impl Display for GeneratorParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "generator '{{{}}}' is invalid (expected uuid, seq, int:1..10, lorem:5, choice:a|b, timestamp:2023-01-01..2024-01-01, or epoch:2023-01-01..2024-01-01)",
+            self.0
+        ))
+    }
+}
+
+impl FromStr for Generator {
+    type Err = GeneratorParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || GeneratorParseError(s.to_string());
+        let (name, argument) = s.split_once(':').unwrap_or((s, ""));
+        let range = || argument.split_once("..").ok_or_else(err);
+        let time_range = || {
+            let (start, end) = range()?;
+            let start = start.parse::<Time>().map_err(|_| err())?.secs();
+            let end = end.parse::<Time>().map_err(|_| err())?.secs();
+            Some((start, end)).filter(|(s, e)| s <= e).ok_or_else(err)
+        };
+        Ok(match (name, argument.is_empty()) {
+            ("uuid", true) => Self::Uuid,
+            ("seq", true) => Self::Seq,
+            ("int", false) => {
+                let (min, max) = range()?;
+                let min = min.parse().map_err(|_| err())?;
+                let max = max.parse().map_err(|_| err())?;
+                if min > max {
+                    return Err(err());
+                }
+                Self::Int(min, max)
+            }
+            ("lorem", false) => Self::Lorem(argument.parse().map_err(|_| err())?),
+            ("choice", false) => Self::Choice(argument.split('|').map(String::from).collect()),
+            ("timestamp", false) => {
+                let (start, end) = time_range()?;
+                Self::Timestamp(start, end)
+            }
+            ("epoch", false) => {
+                let (start, end) = time_range()?;
+                Self::Epoch(start, end)
+            }
+            _ => return Err(err()),
+        })
+    }
+}
+
+impl Generator {
+    fn generate(&self, rng: &mut impl Rng, seq: usize) -> String {
+        match self {
+            Self::Uuid => {
+                let mut bytes = rng.gen::<[u8; 16]>();
+                bytes[6] = (bytes[6] & 0x0f) | 0x40;
+                bytes[8] = (bytes[8] & 0x3f) | 0x80;
+                let hex = bytes
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<String>();
+                format!(
+                    "{}-{}-{}-{}-{}",
+                    &hex[..8],
+                    &hex[8..12],
+                    &hex[12..16],
+                    &hex[16..20],
+                    &hex[20..]
+                )
+            }
+            Self::Seq => seq.to_string(),
+            Self::Int(min, max) => rng.gen_range(*min..=*max).to_string(),
+            Self::Lorem(words) => (0..*words)
+                .map(|_| *LOREM.choose(rng).unwrap())
+                .collect::<Vec<_>>()
+                .join(" "),
+            Self::Choice(options) => options.choose(rng).cloned().unwrap_or_default(),
+            Self::Timestamp(start, end) => DateTime::from_secs(rng.gen_range(*start..=*end))
+                .fmt(Format::DateTime)
+                .unwrap_or_default(),
+            Self::Epoch(start, end) => rng.gen_range(*start..=*end).to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TemplateError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Generator(GeneratorParseError),
+    /// A brace isn't part of a generator, or `{{`/`}}`.
+    Brace(String),
+    /// The template (with its generators run) isn't an item in DynamoDB JSON.
+    Malformed,
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateError::Io(e) => f.write_fmt(format_args!("error reading template: {}", e)),
+            TemplateError::Json(e) => f.write_fmt(format_args!("error parsing template: {}", e)),
+            TemplateError::Generator(e) => e.fmt(f),
+            TemplateError::Brace(s) => f.write_fmt(format_args!(
+                "template string '{}' has an unmatched brace (write {{{{ or }}}} for a literal one)",
+                s
+            )),
+            TemplateError::Malformed => f.write_str(
+                "template must be an item in DynamoDB JSON, like {\"pk\": {\"S\": \"USER#{uuid}\"}}",
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Piece {
+    Literal(String),
+    Generator(Generator),
+}
+
+/// A JSON value, with the strings parsed into pieces.
+#[derive(Debug, Clone)]
+enum Node {
+    String(Vec<Piece>),
+    Array(Vec<Node>),
+    Object(Vec<(String, Node)>),
+    Other(Value),
+}
+
+/// An item in DynamoDB JSON, whose strings may include [`Generator`]s, like
+/// `{"pk": {"S": "USER#{uuid}"}, "age": {"N": "{int:18..90}"}}`.
+#[derive(Debug, Clone)]
+pub struct Template(Node);
+
+impl Template {
+    pub fn load(path: &Path) -> Result<Self, TemplateError> {
+        let contents = std::fs::read_to_string(path).map_err(TemplateError::Io)?;
+        let value = serde_json::from_str(&contents).map_err(TemplateError::Json)?;
+        let template = Self(parse(&value)?);
+        // Checks the shape (which doesn't depend on the generated values).
+        template.generate(&mut rand::thread_rng(), 0)?;
+        Ok(template)
+    }
+
+    /// Runs the generators to make the item at position `seq`.
+    pub fn generate(
+        &self,
+        rng: &mut impl Rng,
+        seq: usize,
+    ) -> Result<HashMap<String, AttributeValue>, TemplateError> {
+        item_from_json(&render(&self.0, rng, seq)).ok_or(TemplateError::Malformed)
+    }
+}
+
+fn parse(value: &Value) -> Result<Node, TemplateError> {
+    Ok(match value {
+        Value::String(s) => Node::String(parse_string(s)?),
+        Value::Array(values) => Node::Array(values.iter().map(parse).collect::<Result<_, _>>()?),
+        Value::Object(fields) => Node::Object(
+            fields
+                .iter()
+                .map(|(name, value)| Ok((name.clone(), parse(value)?)))
+                .collect::<Result<_, _>>()?,
+        ),
+        other => Node::Other(other.clone()),
+    })
+}
+
+fn parse_string(s: &str) -> Result<Vec<Piece>, TemplateError> {
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let mut generator = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    generator.push(c);
+                }
+                if !closed {
+                    return Err(TemplateError::Brace(s.to_string()));
+                }
+                if !literal.is_empty() {
+                    pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                }
+                pieces.push(Piece::Generator(
+                    generator.parse().map_err(TemplateError::Generator)?,
+                ));
+            }
+            '}' => return Err(TemplateError::Brace(s.to_string())),
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        pieces.push(Piece::Literal(literal));
+    }
+    Ok(pieces)
+}
+
+fn render(node: &Node, rng: &mut impl Rng, seq: usize) -> Value {
+    match node {
+        Node::String(pieces) => Value::String(
+            pieces
+                .iter()
+                .map(|piece| match piece {
+                    Piece::Literal(s) => s.clone(),
+                    Piece::Generator(generator) => generator.generate(rng, seq),
+                })
+                .collect(),
+        ),
+        Node::Array(nodes) => Value::Array(nodes.iter().map(|n| render(n, rng, seq)).collect()),
+        Node::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, n)| (name.clone(), render(n, rng, seq)))
+                .collect(),
+        ),
+        Node::Other(value) => value.clone(),
+    }
+}
+
+/// Writes `items` to `table` with `BatchWriteItem`, calling `written` with the
+/// running total after each batch.
+pub async fn write(
+    client: &Client,
+    table: &str,
+    items: Vec<HashMap<String, AttributeValue>>,
+    mut written: impl FnMut(usize),
+) -> Result<(), SdkError<BatchWriteItemError>> {
+    let mut total = 0;
+    for chunk in items.chunks(BATCH_SIZE) {
+        let mut requests = chunk
+            .iter()
+            .map(|item| {
+                WriteRequest::builder()
+                    .put_request(PutRequest::builder().set_item(Some(item.clone())).build())
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        let mut backoff = Duration::from_millis(100);
+        while !requests.is_empty() {
+            let output = client
+                .batch_write_item()
+                .request_items(table, requests)
+                .send()
+                .await?;
+            requests = output
+                .unprocessed_items
+                .and_then(|mut unprocessed| unprocessed.remove(table))
+                .unwrap_or_default();
+            if !requests.is_empty() {
+                // DynamoDB returns unprocessed items when throttling the batch.
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+        total += chunk.len();
+        written(total);
+    }
+    Ok(())
+}
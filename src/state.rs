@@ -0,0 +1,264 @@
+use aws_sdk_dynamodb::error::{GetItemError, PutItemError};
+use aws_sdk_dynamodb::model::AttributeValue;
+use aws_sdk_dynamodb::types::SdkError;
+use aws_sdk_dynamodb::Client;
+use aws_smithy_types::date_time::Format;
+use aws_smithy_types::DateTime;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+
+/// How long a run's lease lasts without a heartbeat, after which another
+/// invocation may take the run over.
+const LEASE: Duration = Duration::from_secs(60);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(20);
+
+/// A run's progress, saved as one item in a `--state-table` so that another
+/// invocation (possibly on another machine) can resume it.
+///
+/// Only one invocation at a time holds the run's lease, which it renews with
+/// heartbeats, and every save is conditional on still holding it.
+pub struct State<'a> {
+    client: &'a Client,
+    table: &'a str,
+    /// The state table's partition key, and this run's value of it.
+    key: (String, String),
+    /// The command line arguments, which a resumed run must repeat.
+    args: String,
+    /// Identifies this invocation as the holder of the lease.
+    owner: String,
+}
+
+/// What a previous run saved.
+#[derive(Debug)]
+pub struct Saved {
+    pub status: Status,
+    pub args: String,
+    pub written: usize,
+    /// Key of the last item written, in scan order.
+    pub last_written: Option<HashMap<String, AttributeValue>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Scanning,
+    Writing,
+    Done,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Scanning => "scanning",
+            Self::Writing => "writing",
+            Self::Done => "done",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum StateError {
+    Get(Box<SdkError<GetItemError>>),
+    Put(Box<SdkError<PutItemError>>),
+    /// Another invocation holds the lease.
+    Leased,
+    /// The saved item is missing or has unexpected attributes.
+    Malformed(String),
+}
+
+impl Display for StateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StateError::Get(e) => f.write_fmt(format_args!("error reading state: {}", e)),
+            StateError::Put(e) => f.write_fmt(format_args!("error saving state: {}", e)),
+            StateError::Leased => f.write_str(
+                "another invocation is running (or less than a minute has passed since it stopped heartbeating)",
+            ),
+            StateError::Malformed(attribute) => f.write_fmt(format_args!(
+                "saved state has a missing or invalid '{}'",
+                attribute
+            )),
+        }
+    }
+}
+
+impl<'a> State<'a> {
+    /// `key_name` is the state table's partition key, and `run` identifies
+    /// this run within it.
+    pub fn new(
+        client: &'a Client,
+        table: &'a str,
+        key_name: String,
+        run: String,
+        args: String,
+    ) -> Self {
+        Self {
+            client,
+            table,
+            key: (key_name, run),
+            args,
+            owner: format!("{}-{:016x}", std::process::id(), rand::random::<u64>()),
+        }
+    }
+
+    pub fn args(&self) -> &str {
+        &self.args
+    }
+
+    /// Reads what a previous run saved, if anything.
+    pub async fn load(&self) -> Result<Option<Saved>, StateError> {
+        let output = self
+            .client
+            .get_item()
+            .table_name(self.table)
+            .key(&self.key.0, AttributeValue::S(self.key.1.clone()))
+            .consistent_read(true)
+            .send()
+            .await
+            .map_err(|e| StateError::Get(Box::new(e)))?;
+        let item = match output.item {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+        let string = |name: &str| match item.get(name) {
+            Some(AttributeValue::S(s)) => Ok(s.clone()),
+            _ => Err(StateError::Malformed(name.to_string())),
+        };
+        let status = match string("status")?.as_str() {
+            "scanning" => Status::Scanning,
+            "writing" => Status::Writing,
+            "done" => Status::Done,
+            _ => return Err(StateError::Malformed(String::from("status"))),
+        };
+        let written = match item.get("written") {
+            Some(AttributeValue::N(n)) => n
+                .parse()
+                .map_err(|_| StateError::Malformed(String::from("written")))?,
+            _ => return Err(StateError::Malformed(String::from("written"))),
+        };
+        let last_written = match item.get("last_written") {
+            Some(AttributeValue::M(key)) => Some(key.clone()),
+            None => None,
+            Some(_) => return Err(StateError::Malformed(String::from("last_written"))),
+        };
+        Ok(Some(Saved {
+            status,
+            args: string("args")?,
+            written,
+            last_written,
+        }))
+    }
+
+    /// Overwrites the saved state, taking the lease if it is free.
+    pub async fn save(
+        &self,
+        status: Status,
+        written: usize,
+        last_written: Option<&HashMap<String, AttributeValue>>,
+    ) -> Result<(), StateError> {
+        let (now, lease_expires) = lease();
+        let mut item = HashMap::from([
+            (self.key.0.clone(), AttributeValue::S(self.key.1.clone())),
+            (
+                String::from("status"),
+                AttributeValue::S(status.as_str().to_string()),
+            ),
+            (String::from("args"), AttributeValue::S(self.args.clone())),
+            (
+                String::from("written"),
+                AttributeValue::N(written.to_string()),
+            ),
+            (String::from("updated_at"), AttributeValue::S(now.clone())),
+            (String::from("heartbeat_at"), AttributeValue::S(now)),
+            (String::from("owner"), AttributeValue::S(self.owner.clone())),
+            (String::from("lease_expires"), lease_expires),
+        ]);
+        if status == Status::Done {
+            // A finished run releases its lease.
+            item.insert(
+                String::from("lease_expires"),
+                AttributeValue::N(0.to_string()),
+            );
+        }
+        if let Some(key) = last_written {
+            item.insert(String::from("last_written"), AttributeValue::M(key.clone()));
+        }
+        self.client
+            .put_item()
+            .table_name(self.table)
+            .set_item(Some(item))
+            .condition_expression(
+                "attribute_not_exists(#k) OR #owner = :owner OR #lease_expires < :now",
+            )
+            .expression_attribute_names("#k", &self.key.0)
+            .expression_attribute_names("#owner", "owner")
+            .expression_attribute_names("#lease_expires", "lease_expires")
+            .expression_attribute_values(":owner", AttributeValue::S(self.owner.clone()))
+            .expression_attribute_values(":now", AttributeValue::N(epoch_secs().to_string()))
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|e| match e {
+                SdkError::ServiceError { err, .. }
+                    if err.is_conditional_check_failed_exception() =>
+                {
+                    StateError::Leased
+                }
+                e => StateError::Put(Box::new(e)),
+            })
+    }
+
+    /// Renews the lease every [`HEARTBEAT_INTERVAL`], updating `heartbeat_at`
+    /// for supervisors, until the returned task is aborted. Once the lease is
+    /// lost, the next save fails.
+    pub fn spawn_heartbeat(&self) -> JoinHandle<()> {
+        let client = self.client.clone();
+        let table = self.table.to_string();
+        let key = self.key.clone();
+        let owner = self.owner.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(HEARTBEAT_INTERVAL);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let (now, lease_expires) = lease();
+                let result = client
+                    .update_item()
+                    .table_name(&table)
+                    .key(&key.0, AttributeValue::S(key.1.clone()))
+                    .update_expression("SET #heartbeat_at = :now, #lease_expires = :lease_expires")
+                    .condition_expression("#owner = :owner")
+                    .expression_attribute_names("#heartbeat_at", "heartbeat_at")
+                    .expression_attribute_names("#lease_expires", "lease_expires")
+                    .expression_attribute_names("#owner", "owner")
+                    .expression_attribute_values(":now", AttributeValue::S(now))
+                    .expression_attribute_values(":lease_expires", lease_expires)
+                    .expression_attribute_values(":owner", AttributeValue::S(owner.clone()))
+                    .send()
+                    .await;
+                if let Err(e) = result {
+                    log!("error renewing lease on run state: {}", e);
+                }
+            }
+        })
+    }
+}
+
+fn epoch_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// The current time, and when a lease taken now expires (in epoch seconds).
+fn lease() -> (String, AttributeValue) {
+    let now = DateTime::from_secs(epoch_secs() as i64)
+        .fmt(Format::DateTime)
+        .unwrap_or_default();
+    (
+        now,
+        AttributeValue::N((epoch_secs() + LEASE.as_secs()).to_string()),
+    )
+}
@@ -0,0 +1,117 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+/// Counters for the write phase, shared with the periodic reporter.
+#[derive(Debug, Default)]
+pub struct Stats {
+    counters: Mutex<Counters>,
+    /// Formatted keys of items read back and compared before writing, since
+    /// checking all of their attributes wouldn't fit in one condition (so they
+    /// are only partly protected against concurrent changes).
+    fallbacks: Mutex<Vec<String>>,
+    /// Formatted keys of items put whole with `--update-mode`, since an update
+    /// couldn't make their changes.
+    puts: Mutex<Vec<String>>,
+    /// Formatted keys of items planned again with `--conflict-retries`, since
+    /// they changed since they were scanned, and what came of it.
+    replans: Mutex<Vec<(String, &'static str)>>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Counters {
+    written: usize,
+    consumed_wcu: f64,
+    conflicts: usize,
+    retries: usize,
+}
+
+impl Stats {
+    pub fn written(&self, consumed_wcu: f64) {
+        let mut counters = self.counters.lock().unwrap();
+        counters.written += 1;
+        counters.consumed_wcu += consumed_wcu;
+    }
+
+    pub fn conflict(&self) {
+        self.counters.lock().unwrap().conflicts += 1;
+    }
+
+    pub fn retry(&self) {
+        self.counters.lock().unwrap().retries += 1;
+    }
+
+    pub fn fell_back(&self, key: String) {
+        self.fallbacks.lock().unwrap().push(key);
+    }
+
+    pub fn fallbacks(&self) -> Vec<String> {
+        self.fallbacks.lock().unwrap().clone()
+    }
+
+    pub fn put_whole(&self, key: String) {
+        self.puts.lock().unwrap().push(key);
+    }
+
+    pub fn puts(&self) -> Vec<String> {
+        self.puts.lock().unwrap().clone()
+    }
+
+    pub fn replanned(&self, key: String, outcome: &'static str) {
+        self.replans.lock().unwrap().push((key, outcome));
+    }
+
+    pub fn replans(&self) -> Vec<(String, &'static str)> {
+        self.replans.lock().unwrap().clone()
+    }
+
+    /// Prints a stats line to stderr every `interval` until the returned task
+    /// is aborted.
+    pub fn spawn_reporter(self: &Arc<Self>, total: usize, interval: Duration) -> JoinHandle<()> {
+        let stats = Arc::clone(self);
+        tokio::spawn(async move {
+            let start = Instant::now();
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            let mut last = Counters::default();
+            let mut last_time = start;
+            loop {
+                ticker.tick().await;
+                let now = Instant::now();
+                let current = *stats.counters.lock().unwrap();
+                let secs = now.duration_since(last_time).as_secs_f64();
+                let average = current.written as f64 / now.duration_since(start).as_secs_f64();
+                let eta = if average > 0.0 {
+                    format_duration(Duration::from_secs_f64(
+                        (total - current.written) as f64 / average,
+                    ))
+                } else {
+                    String::from("?")
+                };
+                log!(
+                    "stats: {}/{} item(s), {:.1} item(s)/s, {:.1} WCU/s, {} conflict(s), {} retries, ETA {}",
+                    current.written,
+                    total,
+                    (current.written - last.written) as f64 / secs,
+                    (current.consumed_wcu - last.consumed_wcu) / secs,
+                    current.conflicts,
+                    current.retries,
+                    eta
+                );
+                last = current;
+                last_time = now;
+            }
+        })
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h{:02}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
@@ -0,0 +1,312 @@
+use aws_sdk_dynamodb::types::SdkError;
+use aws_smithy_types::retry::{ErrorKind, ProvideErrorKind};
+use rand::Rng;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+/// How often the measured write rate is sampled (and, in slow start, raised).
+const WINDOW: Duration = Duration::from_secs(1);
+/// Lowest rate that reducing the rate will go to, in writes per second.
+const MIN_RATE: f64 = 1.0;
+
+/// Paces writes (to a rate, and to a write capacity budget), and backs off
+/// while DynamoDB throttles them, asking the operator what to do once
+/// throttling has persisted for longer than `pause_after`.
+pub struct Throttle {
+    pause_after: Duration,
+    backoff: Duration,
+    throttled_since: Option<Instant>,
+    /// Target writes per second, or `None` if unlimited.
+    rate: Option<f64>,
+    /// When the next write may be issued, if `rate` is set.
+    next: Instant,
+    slow_start: Option<SlowStart>,
+    window_start: Instant,
+    window_writes: u32,
+    /// Writes per second measured over the last full window.
+    measured_rate: f64,
+    /// Write capacity to stay under, if limited.
+    budget: Option<Budget>,
+}
+
+/// Paces requests to consume at most a number of capacity units per second,
+/// on average. Since a request's consumption is only known once it returns,
+/// each request delays the ones after it by as long as its units take to
+/// earn, and idle time doesn't accumulate a burst.
+pub struct Budget {
+    units_per_sec: f64,
+    /// When the units consumed so far will have been earned.
+    next: Mutex<Instant>,
+}
+
+impl Budget {
+    pub fn new(units_per_sec: f64) -> Self {
+        Self {
+            units_per_sec,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits until the units consumed so far have been earned.
+    pub async fn wait(&self) {
+        let next = *self.next.lock().unwrap();
+        if next > Instant::now() {
+            tokio::time::sleep_until(next.into()).await;
+        }
+    }
+
+    /// Records that a request consumed `units`.
+    pub fn consumed(&self, units: f64) {
+        let mut next = self.next.lock().unwrap();
+        *next = (*next).max(Instant::now()) + Duration::from_secs_f64(units / self.units_per_sec);
+    }
+}
+
+/// Like TCP slow start: the rate doubles every [`WINDOW`] until the first sign
+/// of trouble, after which it only grows additively.
+struct SlowStart {
+    initial: f64,
+    /// Rate beyond which growth is additive, set once trouble is seen.
+    threshold: Option<f64>,
+    /// Lowest write latency seen, as a baseline for detecting a struggling table.
+    baseline_latency: Option<Duration>,
+    /// Whether the window so far has been free of throttles and latency spikes.
+    healthy: bool,
+}
+
+impl Throttle {
+    pub fn new(pause_after: Duration, slow_start: Option<f64>, max_wcu: Option<f64>) -> Self {
+        let now = Instant::now();
+        Self {
+            pause_after,
+            backoff: INITIAL_BACKOFF,
+            throttled_since: None,
+            rate: slow_start,
+            next: now,
+            slow_start: slow_start.map(|initial| SlowStart {
+                initial,
+                threshold: None,
+                baseline_latency: None,
+                healthy: true,
+            }),
+            window_start: now,
+            window_writes: 0,
+            measured_rate: 0.0,
+            budget: max_wcu.map(Budget::new),
+        }
+    }
+
+    /// Waits until the next write may be issued.
+    pub async fn pace(&mut self) {
+        if let Some(budget) = &self.budget {
+            budget.wait().await;
+        }
+        if let Some(rate) = self.rate {
+            let now = Instant::now();
+            if self.next > now {
+                tokio::time::sleep_until(self.next.into()).await;
+            }
+            self.next = self.next.max(now) + Duration::from_secs_f64(1.0 / rate);
+        }
+    }
+
+    /// Records that a write went through, having taken `latency` and consumed
+    /// `units` of write capacity.
+    pub fn success(&mut self, latency: Duration, units: f64) {
+        if let Some(budget) = &self.budget {
+            budget.consumed(units);
+        }
+        self.backoff = INITIAL_BACKOFF;
+        self.throttled_since = None;
+        self.window_writes += 1;
+
+        if let Some(slow_start) = &mut self.slow_start {
+            let baseline = *slow_start.baseline_latency.get_or_insert(latency);
+            if latency < baseline {
+                slow_start.baseline_latency = Some(latency);
+            } else if latency > baseline * 3 {
+                slow_start.healthy = false;
+            }
+        }
+
+        let elapsed = self.window_start.elapsed();
+        if elapsed < WINDOW {
+            return;
+        }
+        self.measured_rate = self.window_writes as f64 / elapsed.as_secs_f64();
+        self.window_start = Instant::now();
+        self.window_writes = 0;
+
+        if let (Some(slow_start), Some(rate)) = (&mut self.slow_start, &mut self.rate) {
+            // Only speed up if the rate is what is actually holding writes back.
+            if slow_start.healthy && self.measured_rate >= *rate * 0.9 {
+                *rate = match slow_start.threshold {
+                    Some(threshold) if *rate >= threshold => *rate + slow_start.initial,
+                    _ => *rate * 2.0,
+                };
+            } else if !slow_start.healthy {
+                slow_start.threshold = Some(slow_start.threshold.map_or(*rate, |t| t.min(*rate)));
+            }
+            slow_start.healthy = true;
+        }
+    }
+
+    /// Records that a write was throttled, waiting or prompting as appropriate.
+    ///
+    /// Returns `false` if the operator chose to abort.
+    pub async fn throttled(&mut self) -> bool {
+        // Back off once per window, not once per throttled attempt.
+        let reduce = match &mut self.slow_start {
+            Some(slow_start) if slow_start.healthy => {
+                slow_start.healthy = false;
+                true
+            }
+            _ => false,
+        };
+        if reduce {
+            self.reduce_rate();
+        }
+
+        let since = *self.throttled_since.get_or_insert_with(Instant::now);
+        if since.elapsed() < self.pause_after {
+            tokio::time::sleep(jitter(self.backoff)).await;
+            self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+            return true;
+        }
+
+        if crate::headless::enabled() {
+            log!("table is throttling heavily, so aborting, since there is no one to ask.");
+            return false;
+        }
+        let mut stdin = BufReader::new(tokio::io::stdin());
+        loop {
+            eprint!(
+                "table is throttling heavily — continue at reduced rate, abort, or wait? (c/a/w): "
+            );
+            let mut line = String::new();
+            if stdin.read_line(&mut line).await.unwrap_or(0) == 0 {
+                return false;
+            }
+            match line.trim() {
+                "c" => {
+                    self.reduce_rate();
+                    log!(
+                        "continuing at {:.1} write(s) per second...",
+                        self.rate.unwrap_or_default()
+                    );
+                }
+                "a" => return false,
+                "w" => {
+                    log!("waiting {}s...", self.pause_after.as_secs());
+                    tokio::time::sleep(self.pause_after).await;
+                }
+                _ => continue,
+            }
+            self.backoff = INITIAL_BACKOFF;
+            self.throttled_since = None;
+            return true;
+        }
+    }
+
+    /// Halves the target rate (or, if unlimited, the measured rate).
+    fn reduce_rate(&mut self) {
+        let current = self.rate.unwrap_or(self.measured_rate);
+        self.rate = Some((current / 2.0).max(MIN_RATE));
+    }
+}
+
+/// Retries requests that failed transiently (or were throttled), with
+/// jittered exponential backoff.
+#[derive(Debug, Clone, Copy)]
+pub struct Retry {
+    pub max_retries: u32,
+}
+
+/// Why a request failed, for deciding whether to retry it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// Rejected due to throughput limits, so it wasn't applied.
+    Throttled,
+    /// A timeout, a dropped connection, or a server error, so it may or may
+    /// not have been applied.
+    Transient,
+    Permanent,
+}
+
+impl Retry {
+    /// How long to wait before retry number `retry` (from 1).
+    pub fn backoff(retry: u32) -> Duration {
+        jitter(
+            INITIAL_BACKOFF
+                .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+                .min(MAX_BACKOFF),
+        )
+    }
+
+    /// Sends `request` until it succeeds, fails permanently, or has been
+    /// retried `max_retries` times.
+    pub async fn run<T, E: ProvideErrorKind, F: Future<Output = Result<T, SdkError<E>>>>(
+        &self,
+        mut request: impl FnMut() -> F,
+    ) -> Result<T, SdkError<E>> {
+        let mut retries = 0;
+        loop {
+            match request().await {
+                Err(e) if retries < self.max_retries && failure(&e) != Failure::Permanent => {
+                    retries += 1;
+                    tokio::time::sleep(Self::backoff(retries)).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// A random duration between half of `limit` and `limit`, so that concurrent
+/// requests backing off don't all retry at once.
+fn jitter(limit: Duration) -> Duration {
+    limit.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+}
+
+/// Why `e` failed.
+pub fn failure<E: ProvideErrorKind>(e: &SdkError<E>) -> Failure {
+    if is_throttling(e) {
+        return Failure::Throttled;
+    }
+    let transient = match e {
+        SdkError::TimeoutError(_) | SdkError::ResponseError { .. } => true,
+        SdkError::DispatchFailure(e) => e.is_io() || e.is_timeout(),
+        SdkError::ServiceError { err, raw } => {
+            raw.http().status().is_server_error()
+                || matches!(
+                    err.retryable_error_kind(),
+                    Some(ErrorKind::TransientError | ErrorKind::ServerError)
+                )
+        }
+        _ => false,
+    };
+    if transient {
+        Failure::Transient
+    } else {
+        Failure::Permanent
+    }
+}
+
+/// Whether `e` means the request was rejected due to throughput limits.
+pub fn is_throttling<E: ProvideErrorKind>(e: &SdkError<E>) -> bool {
+    match e {
+        SdkError::ServiceError { err, .. } => matches!(
+            err.code(),
+            Some(
+                "ProvisionedThroughputExceededException"
+                    | "RequestLimitExceeded"
+                    | "ThrottlingException"
+            )
+        ),
+        _ => false,
+    }
+}
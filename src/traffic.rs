@@ -0,0 +1,241 @@
+use crate::json::{item_from_json, item_to_json};
+use aws_smithy_types::date_time::Format;
+use aws_smithy_types::DateTime;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::process::Command;
+
+/// How often each shard is read (DynamoDB Streams allows a few reads per second).
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub enum TrafficError {
+    /// The `aws` CLI couldn't be run, or failed.
+    Command(String),
+    /// The `aws` CLI's output wasn't as expected.
+    Malformed(String),
+}
+
+impl Display for TrafficError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrafficError::Command(e) => f.write_fmt(format_args!("error running aws: {}", e)),
+            TrafficError::Malformed(what) => {
+                f.write_fmt(format_args!("unexpected output from aws {}", what))
+            }
+        }
+    }
+}
+
+/// Writes seen on the table's stream while sampling it.
+#[derive(Debug, Default)]
+pub struct Sample {
+    /// Every write (insert, modification, or removal).
+    pub writes: u64,
+    /// The writes to items about to be edited.
+    pub to_edited: u64,
+    /// The items about to be edited that were written.
+    pub edited: HashSet<String>,
+    /// The writes to items about to be edited that changed the attributes being
+    /// edited, if the stream has old and new images to tell.
+    pub to_affected: Option<u64>,
+}
+
+/// Reads the new records of every open shard of `stream_arn` for `duration`,
+/// with the `aws` CLI (given `cli_args` like `--region us-east-1`).
+///
+/// `edited` has the keys (as [`item_to_json`] strings) of the items about to be
+/// edited, and `affected` the root attributes the edits change (or `None` for
+/// all of them). `images` is whether the stream has old and new images.
+pub async fn sample(
+    stream_arn: &str,
+    duration: Duration,
+    edited: &HashSet<String>,
+    affected: Option<&HashSet<String>>,
+    images: bool,
+    cli_args: &[String],
+) -> Result<Sample, TrafficError> {
+    let mut iterators = Vec::new();
+    for shard in open_shards(stream_arn, cli_args).await? {
+        let output = aws(
+            cli_args,
+            &[
+                "dynamodbstreams",
+                "get-shard-iterator",
+                "--stream-arn",
+                stream_arn,
+                "--shard-id",
+                &shard,
+                "--shard-iterator-type",
+                "LATEST",
+            ],
+        )
+        .await?;
+        let iterator = output["ShardIterator"].as_str().ok_or_else(|| {
+            TrafficError::Malformed(String::from("dynamodbstreams get-shard-iterator"))
+        })?;
+        iterators.push(iterator.to_string());
+    }
+
+    let mut sample = Sample {
+        to_affected: images.then_some(0),
+        ..Sample::default()
+    };
+    let deadline = Instant::now() + duration;
+    while Instant::now() < deadline && !iterators.is_empty() {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        let mut next = Vec::new();
+        for iterator in &iterators {
+            let output = aws(
+                cli_args,
+                &[
+                    "dynamodbstreams",
+                    "get-records",
+                    "--shard-iterator",
+                    iterator,
+                ],
+            )
+            .await?;
+            for record in output["Records"].as_array().into_iter().flatten() {
+                sample.record(record, edited, affected);
+            }
+            // A shard that was closed (e.g. split) has no next iterator.
+            if let Some(iterator) = output["NextShardIterator"].as_str() {
+                next.push(iterator.to_string());
+            }
+        }
+        iterators = next;
+    }
+    Ok(sample)
+}
+
+impl Sample {
+    fn record(
+        &mut self,
+        record: &Value,
+        edited: &HashSet<String>,
+        affected: Option<&HashSet<String>>,
+    ) {
+        self.writes += 1;
+        let record = &record["dynamodb"];
+        let key = match item_from_json(&record["Keys"]) {
+            Some(key) => item_to_json(&key).to_string(),
+            None => return,
+        };
+        if !edited.contains(&key) {
+            return;
+        }
+        self.to_edited += 1;
+        self.edited.insert(key);
+        if let Some(to_affected) = &mut self.to_affected {
+            let changed = match (&record["OldImage"], &record["NewImage"]) {
+                (Value::Object(old), Value::Object(new)) => affected.is_none_or(|affected| {
+                    affected
+                        .iter()
+                        .any(|attribute| old.get(attribute) != new.get(attribute))
+                }),
+                // An insert or removal changes every attribute.
+                _ => true,
+            };
+            *to_affected += changed as u64;
+        }
+    }
+}
+
+/// The IDs of the shards of `stream_arn` that are still being written to.
+async fn open_shards(stream_arn: &str, cli_args: &[String]) -> Result<Vec<String>, TrafficError> {
+    let mut shards = Vec::new();
+    let mut start = None::<String>;
+    loop {
+        let mut args = vec![
+            "dynamodbstreams",
+            "describe-stream",
+            "--stream-arn",
+            stream_arn,
+        ];
+        if let Some(start) = &start {
+            args.extend(["--exclusive-start-shard-id", start]);
+        }
+        let output = aws(cli_args, &args).await?;
+        let description = &output["StreamDescription"];
+        for shard in description["Shards"].as_array().into_iter().flatten() {
+            if shard["SequenceNumberRange"]["EndingSequenceNumber"].is_null() {
+                if let Some(id) = shard["ShardId"].as_str() {
+                    shards.push(id.to_string());
+                }
+            }
+        }
+        match description["LastEvaluatedShardId"].as_str() {
+            Some(last) => start = Some(last.to_string()),
+            None => return Ok(shards),
+        }
+    }
+}
+
+/// The table's consumed write capacity units per second over the last
+/// `duration` (at least a minute), from CloudWatch.
+pub async fn consumed_wcu(
+    table: &str,
+    duration: Duration,
+    cli_args: &[String],
+) -> Result<f64, TrafficError> {
+    let duration = duration.max(Duration::from_secs(60));
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    let time = |secs: i64| {
+        DateTime::from_secs(secs)
+            .fmt(Format::DateTime)
+            .unwrap_or_default()
+    };
+    let output = aws(
+        cli_args,
+        &[
+            "cloudwatch",
+            "get-metric-statistics",
+            "--namespace",
+            "AWS/DynamoDB",
+            "--metric-name",
+            "ConsumedWriteCapacityUnits",
+            "--dimensions",
+            &format!("Name=TableName,Value={}", table),
+            "--start-time",
+            &time(now - duration.as_secs() as i64),
+            "--end-time",
+            &time(now),
+            "--period",
+            "60",
+            "--statistics",
+            "Sum",
+        ],
+    )
+    .await?;
+    let sum = output["Datapoints"]
+        .as_array()
+        .ok_or_else(|| TrafficError::Malformed(String::from("cloudwatch get-metric-statistics")))?
+        .iter()
+        .filter_map(|point| point["Sum"].as_f64())
+        .sum::<f64>();
+    Ok(sum / duration.as_secs_f64())
+}
+
+async fn aws(cli_args: &[String], args: &[&str]) -> Result<Value, TrafficError> {
+    let output = Command::new("aws")
+        .args(cli_args)
+        .args(args)
+        .args(["--output", "json"])
+        .output()
+        .await
+        .map_err(|e| TrafficError::Command(e.to_string()))?;
+    if !output.status.success() {
+        return Err(TrafficError::Command(format!(
+            "{} ({})",
+            String::from_utf8_lossy(&output.stderr).trim(),
+            output.status
+        )));
+    }
+    serde_json::from_slice(&output.stdout).map_err(|_| TrafficError::Malformed(args[..2].join(" ")))
+}
@@ -0,0 +1,1506 @@
+use crate::decimal::Decimal;
+use crate::filter::{get_path_mut, has_path, scalar_string};
+use crate::json::{from_plain_json, to_plain_json};
+use aws_sdk_dynamodb::model::AttributeValue;
+use aws_sdk_kms::error::ReEncryptError;
+use aws_sdk_kms::types::SdkError;
+use aws_smithy_types::{base64, Blob};
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Attribute that `--soft-delete` sets to `true`.
+pub const DELETED: &str = "deleted";
+/// Attribute that `--soft-delete` sets to the time of deletion.
+pub const DELETED_AT: &str = "deletedAt";
+
+/// Marks `item` as deleted at `now` (an ISO 8601 string), unless it already is.
+pub fn soft_delete(item: &mut HashMap<String, AttributeValue>, now: &str) -> bool {
+    if matches!(item.get(DELETED), Some(AttributeValue::Bool(true))) {
+        return false;
+    }
+    item.insert(DELETED.to_string(), AttributeValue::Bool(true));
+    item.insert(DELETED_AT.to_string(), AttributeValue::S(now.to_string()));
+    true
+}
+
+/// Removes any soft-deletion marks from `item`.
+pub fn undelete(item: &mut HashMap<String, AttributeValue>) -> bool {
+    let deleted = item.remove(DELETED).is_some();
+    let deleted_at = item.remove(DELETED_AT).is_some();
+    deleted || deleted_at
+}
+
+#[derive(Debug)]
+pub enum ReencryptError {
+    /// The attribute is neither binary nor a base64 string.
+    NotCiphertext(String),
+    Kms(Box<SdkError<ReEncryptError>>),
+}
+
+impl Display for ReencryptError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReencryptError::NotCiphertext(attribute) => f.write_fmt(format_args!(
+                "attribute '{}' is not binary or base64 ciphertext",
+                attribute
+            )),
+            ReencryptError::Kms(e) => f.write_fmt(format_args!("error re-encrypting: {}", e)),
+        }
+    }
+}
+
+/// Re-encrypts the KMS ciphertext in `attribute` of `item` from one key to
+/// another, within KMS so the plaintext is never exposed. Returns whether the
+/// attribute was re-encrypted, or `false` if it is missing or was encrypted
+/// under a different key.
+///
+/// Ciphertext stored as a base64 string stays a base64 string.
+pub async fn reencrypt(
+    kms: &aws_sdk_kms::Client,
+    item: &mut HashMap<String, AttributeValue>,
+    attribute: &str,
+    from_key: &str,
+    to_key: &str,
+) -> Result<bool, ReencryptError> {
+    let not_ciphertext = || ReencryptError::NotCiphertext(attribute.to_string());
+    let ciphertext = match item.get(attribute) {
+        Some(AttributeValue::B(b)) => b.clone().into_inner(),
+        Some(AttributeValue::S(s)) => base64::decode(s).map_err(|_| not_ciphertext())?,
+        Some(_) => return Err(not_ciphertext()),
+        None => return Ok(false),
+    };
+    let output = match kms
+        .re_encrypt()
+        .ciphertext_blob(Blob::new(ciphertext))
+        .source_key_id(from_key)
+        .destination_key_id(to_key)
+        .send()
+        .await
+    {
+        Ok(output) => output,
+        Err(SdkError::ServiceError { err, .. }) if err.is_incorrect_key_exception() => {
+            return Ok(false)
+        }
+        Err(e) => return Err(ReencryptError::Kms(Box::new(e))),
+    };
+    let ciphertext = output.ciphertext_blob.ok_or_else(not_ciphertext)?;
+    let value = match item.get(attribute) {
+        Some(AttributeValue::S(_)) => AttributeValue::S(base64::encode(ciphertext.as_ref())),
+        _ => AttributeValue::B(ciphertext),
+    };
+    item.insert(attribute.to_string(), value);
+    Ok(true)
+}
+
+/// A conversion of a root-level attribute between base64 strings and binary,
+/// like `payload:base64-to-binary`.
+#[derive(Debug, Clone)]
+pub struct Encoding {
+    pub attribute: String,
+    pub to_binary: bool,
+}
+
+#[derive(Debug)]
+pub struct EncodingParseError(String);
+
+impl Display for EncodingParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "encoding '{}' is invalid (expected e.g. payload:base64-to-binary or payload:binary-to-base64)",
+            self.0
+        ))
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = EncodingParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (attribute, conversion) = s
+            .rsplit_once(':')
+            .filter(|(attribute, _)| !attribute.is_empty())
+            .ok_or_else(|| EncodingParseError(s.to_string()))?;
+        let to_binary = match conversion {
+            "base64-to-binary" => true,
+            "binary-to-base64" => false,
+            _ => return Err(EncodingParseError(s.to_string())),
+        };
+        Ok(Self {
+            attribute: attribute.to_string(),
+            to_binary,
+        })
+    }
+}
+
+impl Encoding {
+    /// Converts the attribute (or set) of `item`, returning whether it changed.
+    /// Attributes that are missing or already converted are left alone.
+    pub fn apply(&self, item: &mut HashMap<String, AttributeValue>) -> Result<bool, String> {
+        let invalid =
+            |s: &str| format!("attribute '{}' has invalid base64 '{}'", self.attribute, s);
+        let value = match item.get(&self.attribute) {
+            Some(value) => value,
+            None => return Ok(false),
+        };
+        let value = match (self.to_binary, value) {
+            (true, AttributeValue::S(s)) => {
+                AttributeValue::B(Blob::new(base64::decode(s).map_err(|_| invalid(s))?))
+            }
+            (true, AttributeValue::Ss(ss)) => AttributeValue::Bs(
+                ss.iter()
+                    .map(|s| base64::decode(s).map(Blob::new).map_err(|_| invalid(s)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            (false, AttributeValue::B(b)) => AttributeValue::S(base64::encode(b.as_ref())),
+            (false, AttributeValue::Bs(bs)) => {
+                AttributeValue::Ss(bs.iter().map(|b| base64::encode(b.as_ref())).collect())
+            }
+            (true, AttributeValue::B(_) | AttributeValue::Bs(_))
+            | (false, AttributeValue::S(_) | AttributeValue::Ss(_)) => return Ok(false),
+            (_, value) => {
+                return Err(format!(
+                    "attribute '{}' is {}, not a string or binary",
+                    self.attribute,
+                    crate::type_name(value)
+                ))
+            }
+        };
+        item.insert(self.attribute.clone(), value);
+        Ok(true)
+    }
+}
+
+/// Replaces a root-level string `attribute` of `item` holding a JSON object or
+/// array with the equivalent `M` or `L`, returning whether it changed.
+pub fn parse_json(
+    item: &mut HashMap<String, AttributeValue>,
+    attribute: &str,
+) -> Result<bool, String> {
+    let s = match item.get(attribute) {
+        Some(AttributeValue::S(s)) => s,
+        Some(AttributeValue::M(_) | AttributeValue::L(_)) | None => return Ok(false),
+        Some(value) => {
+            return Err(format!(
+                "attribute '{}' is {}, not a string",
+                attribute,
+                crate::type_name(value)
+            ))
+        }
+    };
+    let value = match serde_json::from_str::<serde_json::Value>(s) {
+        Ok(value @ (serde_json::Value::Object(_) | serde_json::Value::Array(_))) => value,
+        Ok(_) => {
+            return Err(format!(
+                "attribute '{}' is not a JSON object or array",
+                attribute
+            ))
+        }
+        Err(e) => return Err(format!("attribute '{}' is invalid JSON: {}", attribute, e)),
+    };
+    item.insert(attribute.to_string(), from_plain_json(&value));
+    Ok(true)
+}
+
+/// Replaces a root-level map or list `attribute` of `item` with its canonical
+/// JSON as a string, returning whether it changed.
+pub fn stringify(
+    item: &mut HashMap<String, AttributeValue>,
+    attribute: &str,
+) -> Result<bool, String> {
+    let json = match item.get(attribute) {
+        Some(value @ (AttributeValue::M(_) | AttributeValue::L(_))) => to_plain_json(value),
+        Some(AttributeValue::S(_)) | None => return Ok(false),
+        Some(value) => {
+            return Err(format!(
+                "attribute '{}' is {}, not a map or list",
+                attribute,
+                crate::type_name(value)
+            ))
+        }
+    };
+    item.insert(attribute.to_string(), AttributeValue::S(json.to_string()));
+    Ok(true)
+}
+
+/// Extraction of a root-level string attribute's regex captures into other
+/// attributes, like `fullName:/(\S+)\s+(\S+)/ -> firstName,lastName`, with a
+/// `:remove` suffix to also remove the source.
+#[derive(Debug, Clone)]
+pub struct Split {
+    pub source: String,
+    regex: Regex,
+    pub targets: Vec<String>,
+    pub remove_source: bool,
+}
+
+#[derive(Debug)]
+pub enum SplitParseError {
+    Invalid(String),
+    InvalidRegex(regex::Error),
+    /// The number of targets doesn't match the number of capture groups.
+    TargetCount(usize, usize),
+}
+
+impl Display for SplitParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SplitParseError::Invalid(s) => f.write_fmt(format_args!(
+                "split '{}' is invalid (expected e.g. fullName:/(\\S+)\\s+(\\S+)/ -> firstName,lastName)",
+                s
+            )),
+            SplitParseError::InvalidRegex(e) => e.fmt(f),
+            SplitParseError::TargetCount(targets, groups) => f.write_fmt(format_args!(
+                "split has {} target(s) but {} capture group(s)",
+                targets, groups
+            )),
+        }
+    }
+}
+
+impl FromStr for Split {
+    type Err = SplitParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || SplitParseError::Invalid(s.to_string());
+        let (source, rest) = s
+            .split_once(':')
+            .filter(|(source, _)| !source.is_empty())
+            .ok_or_else(invalid)?;
+        let (regex, targets) = rest.rsplit_once("->").ok_or_else(invalid)?;
+        let regex = regex
+            .trim()
+            .strip_prefix('/')
+            .and_then(|r| r.strip_suffix('/'))
+            .ok_or_else(invalid)?;
+        let regex = Regex::new(regex).map_err(SplitParseError::InvalidRegex)?;
+        let (targets, remove_source) = match targets.trim().strip_suffix(":remove") {
+            Some(targets) => (targets, true),
+            None => (targets.trim(), false),
+        };
+        let targets = targets
+            .split(',')
+            .map(|t| t.trim().to_string())
+            .collect::<Vec<_>>();
+        if targets.iter().any(String::is_empty) {
+            return Err(invalid());
+        }
+        let groups = regex.captures_len() - 1;
+        if targets.len() != groups {
+            return Err(SplitParseError::TargetCount(targets.len(), groups));
+        }
+        Ok(Self {
+            source: source.to_string(),
+            regex,
+            targets,
+            remove_source,
+        })
+    }
+}
+
+impl Split {
+    /// Sets each target to its capture (skipping groups that didn't
+    /// participate), returning whether `item` changed. Values that don't match
+    /// are errors, as are targets with other values unless `allow_overwrite`.
+    pub fn apply(
+        &self,
+        item: &mut HashMap<String, AttributeValue>,
+        allow_overwrite: bool,
+    ) -> Result<bool, String> {
+        let value = match item.get(&self.source) {
+            Some(AttributeValue::S(s)) => s,
+            Some(value) => {
+                return Err(format!(
+                    "attribute '{}' is {}, not a string",
+                    self.source,
+                    crate::type_name(value)
+                ))
+            }
+            None => return Ok(false),
+        };
+        let captures = self.regex.captures(value).ok_or_else(|| {
+            format!(
+                "attribute '{}' doesn't match /{}/",
+                self.source,
+                self.regex.as_str()
+            )
+        })?;
+        let values = self
+            .targets
+            .iter()
+            .zip(captures.iter().skip(1))
+            .filter_map(|(target, capture)| {
+                Some((
+                    target.clone(),
+                    AttributeValue::S(capture?.as_str().to_string()),
+                ))
+            })
+            .collect::<Vec<_>>();
+        for (target, value) in &values {
+            if !allow_overwrite && item.get(target).is_some_and(|v| v != value) {
+                return Err(format!(
+                    "split would overwrite attribute '{}' (pass --allow-overwrite to proceed anyway)",
+                    target
+                ));
+            }
+        }
+        let mut changed = false;
+        if self.remove_source && !self.targets.contains(&self.source) {
+            item.remove(&self.source);
+            changed = true;
+        }
+        for (target, value) in values {
+            changed |= item.insert(target, value.clone()).as_ref() != Some(&value);
+        }
+        Ok(changed)
+    }
+}
+
+/// A regex substitution on a string attribute, like
+/// `links.home:/^http:/https:/`, whose replacement may reference captures like
+/// `$1` or `${name}`. A `/` in the pattern or replacement is escaped as `\/`.
+#[derive(Debug, Clone)]
+pub struct RegexReplace {
+    pub path: String,
+    regex: Regex,
+    replacement: String,
+}
+
+#[derive(Debug)]
+pub enum RegexReplaceParseError {
+    Invalid(String),
+    InvalidRegex(regex::Error),
+}
+
+impl Display for RegexReplaceParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegexReplaceParseError::Invalid(s) => f.write_fmt(format_args!(
+                "regex replacement '{}' is invalid (expected e.g. url:/^http:/https:/)",
+                s
+            )),
+            RegexReplaceParseError::InvalidRegex(e) => e.fmt(f),
+        }
+    }
+}
+
+impl FromStr for RegexReplace {
+    type Err = RegexReplaceParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || RegexReplaceParseError::Invalid(s.to_string());
+        let (path, rest) = s
+            .split_once(':')
+            .filter(|(path, _)| !path.split('.').any(str::is_empty))
+            .ok_or_else(invalid)?;
+        let rest = rest
+            .strip_prefix('/')
+            .and_then(|r| r.strip_suffix('/'))
+            .ok_or_else(invalid)?;
+        // Splits at the first `/` that isn't escaped, unescaping the others.
+        let mut parts = vec![String::new()];
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('/') => parts.last_mut().unwrap().push('/'),
+                    Some(c) => parts.last_mut().unwrap().extend(['\\', c]),
+                    None => parts.last_mut().unwrap().push('\\'),
+                },
+                '/' => parts.push(String::new()),
+                c => parts.last_mut().unwrap().push(c),
+            }
+        }
+        let [pattern, replacement] = <[String; 2]>::try_from(parts).map_err(|_| invalid())?;
+        Ok(Self {
+            path: path.to_string(),
+            regex: Regex::new(&pattern).map_err(RegexReplaceParseError::InvalidRegex)?,
+            replacement,
+        })
+    }
+}
+
+impl RegexReplace {
+    /// Replaces every match in the attribute (if present), returning whether
+    /// it changed.
+    pub fn apply(&self, item: &mut HashMap<String, AttributeValue>) -> Result<bool, String> {
+        let value = match get_path_mut(item, &self.path) {
+            Some(AttributeValue::S(s)) => s,
+            Some(value) => {
+                return Err(format!(
+                    "attribute '{}' is {}, not a string",
+                    self.path,
+                    crate::type_name(value)
+                ))
+            }
+            None => return Ok(false),
+        };
+        let replaced = self
+            .regex
+            .replace_all(value, self.replacement.as_str())
+            .into_owned();
+        if replaced == *value {
+            return Ok(false);
+        }
+        *value = replaced;
+        Ok(true)
+    }
+}
+
+/// Text with `{attribute}` placeholders for root-level attributes of an item,
+/// like `{firstName} {lastName}`. Braces are escaped by doubling them.
+#[derive(Debug, Clone)]
+pub struct Template(Vec<Part>);
+
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(String),
+    Attribute(String),
+}
+
+#[derive(Debug)]
+pub struct TemplateParseError(String);
+
+impl Display for TemplateParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "template '{}' is invalid (expected e.g. {{firstName}} {{lastName}}, with literal braces doubled)",
+            self.0
+        ))
+    }
+}
+
+impl FromStr for Template {
+    type Err = TemplateParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || TemplateParseError(s.to_string());
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some('{') | None => return Err(invalid()),
+                            Some(c) => name.push(c),
+                        }
+                    }
+                    if name.is_empty() {
+                        return Err(invalid());
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Attribute(name));
+                }
+                '}' => return Err(invalid()),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Self(parts))
+    }
+}
+
+impl Display for Template {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for part in &self.0 {
+            match part {
+                Part::Literal(s) => f.write_str(&s.replace('{', "{{").replace('}', "}}"))?,
+                Part::Attribute(name) => f.write_fmt(format_args!("{{{}}}", name))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Template {
+    /// Fills in the placeholders from `item`, or returns the name of the first
+    /// referenced attribute that is missing (or isn't a string, number, or
+    /// binary).
+    pub fn render(&self, item: &HashMap<String, AttributeValue>) -> Result<String, String> {
+        let mut ret = String::new();
+        for part in &self.0 {
+            match part {
+                Part::Literal(s) => ret.push_str(s),
+                Part::Attribute(name) => match item.get(name).and_then(scalar_string) {
+                    Some(value) => ret.push_str(&value),
+                    None => return Err(name.clone()),
+                },
+            }
+        }
+        Ok(ret)
+    }
+}
+
+/// A root-level string attribute built from a template, like
+/// `displayName = "{firstName} {lastName}"`.
+#[derive(Debug, Clone)]
+pub struct Derive {
+    pub target: String,
+    pub template: Template,
+}
+
+#[derive(Debug)]
+pub enum DeriveParseError {
+    MissingEquals(String),
+    Template(TemplateParseError),
+}
+
+impl Display for DeriveParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeriveParseError::MissingEquals(s) => f.write_fmt(format_args!(
+                "derivation '{}' is invalid (expected e.g. displayName = \"{{firstName}} {{lastName}}\")",
+                s
+            )),
+            DeriveParseError::Template(e) => e.fmt(f),
+        }
+    }
+}
+
+impl FromStr for Derive {
+    type Err = DeriveParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (target, template) = s
+            .split_once('=')
+            .map(|(target, template)| (target.trim(), template.trim()))
+            .filter(|(target, _)| !target.is_empty())
+            .ok_or_else(|| DeriveParseError::MissingEquals(s.to_string()))?;
+        let template = template
+            .strip_prefix('"')
+            .and_then(|t| t.strip_suffix('"'))
+            .unwrap_or(template);
+        Ok(Self {
+            target: target.to_string(),
+            template: template.parse().map_err(DeriveParseError::Template)?,
+        })
+    }
+}
+
+impl Display for Derive {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{} = \"{}\"", self.target, self.template))
+    }
+}
+
+/// What deriving an attribute did to an item.
+pub enum Derived {
+    Changed,
+    Unchanged,
+    /// Not derived, because this referenced attribute is missing.
+    Missing(String),
+}
+
+impl Derive {
+    /// Sets the target to the rendered template. Targets with other values are
+    /// errors unless `allow_overwrite`.
+    pub fn apply(
+        &self,
+        item: &mut HashMap<String, AttributeValue>,
+        allow_overwrite: bool,
+    ) -> Result<Derived, String> {
+        let value = match self.template.render(item) {
+            Ok(value) => AttributeValue::S(value),
+            Err(missing) => return Ok(Derived::Missing(missing)),
+        };
+        match item.get(&self.target) {
+            Some(existing) if *existing == value => return Ok(Derived::Unchanged),
+            Some(_) if !allow_overwrite => {
+                return Err(format!(
+                    "derivation would overwrite attribute '{}' (pass --allow-overwrite to proceed anyway)",
+                    self.target
+                ))
+            }
+            _ => {}
+        }
+        item.insert(self.target.clone(), value);
+        Ok(Derived::Changed)
+    }
+}
+
+/// An attribute set to a typed value, which may be a template, like
+/// `gsi1pk=S:TENANT#{tenantId}`, `retries=N:0`, or `flags.active=BOOL:false`.
+/// Missing maps on the way to a nested attribute are created.
+#[derive(Debug, Clone)]
+pub struct Set {
+    /// Dot-separated path, like `flags.active`.
+    pub target: String,
+    kind: SetKind,
+    pub template: Template,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SetKind {
+    S,
+    N,
+    Bool,
+}
+
+#[derive(Debug)]
+pub enum SetParseError {
+    Invalid(String),
+    Template(TemplateParseError),
+}
+
+impl Display for SetParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SetParseError::Invalid(s) => f.write_fmt(format_args!(
+                "assignment '{}' is invalid (expected e.g. gsi1pk=S:TENANT#{{tenantId}}, version=N:2, or active=BOOL:false)",
+                s
+            )),
+            SetParseError::Template(e) => e.fmt(f),
+        }
+    }
+}
+
+impl FromStr for Set {
+    type Err = SetParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || SetParseError::Invalid(s.to_string());
+        let (target, value) = s
+            .split_once('=')
+            .filter(|(target, _)| !target.split('.').any(str::is_empty))
+            .ok_or_else(invalid)?;
+        let (kind, template) = value.split_once(':').ok_or_else(invalid)?;
+        let kind = match kind {
+            "S" => SetKind::S,
+            "N" => SetKind::N,
+            "BOOL" => SetKind::Bool,
+            _ => return Err(invalid()),
+        };
+        Ok(Self {
+            target: target.to_string(),
+            kind,
+            template: template.parse().map_err(SetParseError::Template)?,
+        })
+    }
+}
+
+impl Display for Set {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            SetKind::S => "S",
+            SetKind::N => "N",
+            SetKind::Bool => "BOOL",
+        };
+        f.write_fmt(format_args!("{}={}:{}", self.target, kind, self.template))
+    }
+}
+
+impl Set {
+    /// The root-level attribute this sets (or sets within).
+    pub fn root_attribute(&self) -> &str {
+        self.target.split('.').next().unwrap_or_default()
+    }
+
+    /// Sets the target only if it is missing.
+    pub fn apply_default(
+        &self,
+        item: &mut HashMap<String, AttributeValue>,
+    ) -> Result<Derived, String> {
+        if has_path(item, &self.target) {
+            return Ok(Derived::Unchanged);
+        }
+        self.apply(item)
+    }
+
+    /// Sets the target, overwriting any existing value.
+    pub fn apply(&self, item: &mut HashMap<String, AttributeValue>) -> Result<Derived, String> {
+        let value = match self.template.render(item) {
+            Ok(value) => value,
+            Err(missing) => return Ok(Derived::Missing(missing)),
+        };
+        let value = match self.kind {
+            SetKind::S => AttributeValue::S(value),
+            SetKind::N => {
+                if value.trim().parse::<f64>().map_or(true, |n| !n.is_finite()) {
+                    return Err(format!(
+                        "attribute '{}' would be set to '{}', which is not a number",
+                        self.target, value
+                    ));
+                }
+                AttributeValue::N(value.trim().to_string())
+            }
+            SetKind::Bool => match value.trim() {
+                "true" => AttributeValue::Bool(true),
+                "false" => AttributeValue::Bool(false),
+                _ => {
+                    return Err(format!(
+                        "attribute '{}' would be set to '{}', which is not true or false",
+                        self.target, value
+                    ))
+                }
+            },
+        };
+        let (parent, name) = match self.target.rsplit_once('.') {
+            Some((parent, name)) => {
+                let mut map = &mut *item;
+                for segment in parent.split('.') {
+                    let value = map
+                        .entry(segment.to_string())
+                        .or_insert_with(|| AttributeValue::M(HashMap::new()));
+                    map = match value {
+                        AttributeValue::M(m) => m,
+                        value => {
+                            return Err(format!(
+                                "attribute '{}' can't be set, since '{}' is {}, not a map",
+                                self.target,
+                                segment,
+                                crate::type_name(value)
+                            ))
+                        }
+                    };
+                }
+                (map, name)
+            }
+            None => (item, self.target.as_str()),
+        };
+        if parent.get(name) == Some(&value) {
+            return Ok(Derived::Unchanged);
+        }
+        parent.insert(name.to_string(), value);
+        Ok(Derived::Changed)
+    }
+}
+
+/// A string or number value to rewrite wherever it appears at a path, like
+/// `status:active>ACTIVE`, `*currency:usd>USD` (an attribute with that name
+/// at any level), or `*:N/A>n/a` (any attribute). Matches in lists count as
+/// being at the list's path.
+#[derive(Debug, Clone)]
+pub struct ReplaceValue {
+    selector: Selector,
+    old: String,
+    new: String,
+}
+
+#[derive(Debug, Clone)]
+enum Selector {
+    Any,
+    Named(String),
+    /// A dot-separated path, like `address.country`.
+    Path(String),
+}
+
+#[derive(Debug)]
+pub struct ReplaceValueParseError(String);
+
+impl Display for ReplaceValueParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "value replacement '{}' is invalid (expected e.g. status:active>ACTIVE, *currency:usd>USD, or *:N/A>n/a)",
+            self.0
+        ))
+    }
+}
+
+impl FromStr for ReplaceValue {
+    type Err = ReplaceValueParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ReplaceValueParseError(s.to_string());
+        let (selector, values) = s.split_once(':').ok_or_else(invalid)?;
+        let (old, new) = values.split_once('>').ok_or_else(invalid)?;
+        let selector = match selector.strip_prefix('*') {
+            Some("") => Selector::Any,
+            Some(name) if !name.contains('.') => Selector::Named(name.to_string()),
+            None if !selector.is_empty() && selector.split('.').all(|s| !s.is_empty()) => {
+                Selector::Path(selector.to_string())
+            }
+            _ => return Err(invalid()),
+        };
+        Ok(Self {
+            selector,
+            old: old.to_string(),
+            new: new.to_string(),
+        })
+    }
+}
+
+impl Display for ReplaceValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.selector {
+            Selector::Any => f.write_str("*")?,
+            Selector::Named(name) => f.write_fmt(format_args!("*{}", name))?,
+            Selector::Path(path) => f.write_str(path)?,
+        }
+        f.write_fmt(format_args!(":{}>{}", self.old, self.new))
+    }
+}
+
+impl ReplaceValue {
+    /// The root-level attribute this may change, or `None` if it may change
+    /// any of them.
+    pub fn root_attribute(&self) -> Option<&str> {
+        match &self.selector {
+            Selector::Path(path) => path.split('.').next(),
+            _ => None,
+        }
+    }
+
+    /// Rewrites every matching value in `item`, returning whether any changed.
+    pub fn apply(&self, item: &mut HashMap<String, AttributeValue>) -> Result<bool, String> {
+        if let Selector::Path(path) = &self.selector {
+            return match get_path_mut(item, path) {
+                Some(value) => self.rewrite(path, value),
+                None => Ok(false),
+            };
+        }
+        let mut changed = false;
+        for (name, value) in item {
+            changed |= self.visit(name, value)?;
+        }
+        Ok(changed)
+    }
+
+    /// Rewrites `value` (named `name`) and anything in it, if it matches.
+    fn visit(&self, name: &str, value: &mut AttributeValue) -> Result<bool, String> {
+        let mut changed = false;
+        match value {
+            AttributeValue::M(map) => {
+                for (name, value) in map {
+                    changed |= self.visit(name, value)?;
+                }
+            }
+            AttributeValue::L(list) => {
+                for value in list {
+                    changed |= self.visit(name, value)?;
+                }
+            }
+            value => {
+                if match &self.selector {
+                    Selector::Named(n) => n == name,
+                    _ => true,
+                } {
+                    changed = self.rewrite(name, value)?;
+                }
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Rewrites `value` at `path`, or the elements of a list there, if equal
+    /// to the old value.
+    fn rewrite(&self, path: &str, value: &mut AttributeValue) -> Result<bool, String> {
+        match value {
+            AttributeValue::S(s) if *s == self.old => {
+                *s = self.new.clone();
+                Ok(true)
+            }
+            AttributeValue::N(n) => {
+                let old = match self.old.parse::<Decimal>() {
+                    Ok(old) => old,
+                    Err(_) => return Ok(false),
+                };
+                if n.parse::<Decimal>().map_or(true, |n| n != old) {
+                    return Ok(false);
+                }
+                if self.new.parse::<Decimal>().is_err() {
+                    return Err(format!(
+                        "number '{}' in attribute '{}' would be replaced with '{}', which is not a number",
+                        n, path, self.new
+                    ));
+                }
+                *n = self.new.clone();
+                Ok(true)
+            }
+            AttributeValue::L(list) => {
+                let mut changed = false;
+                for value in list {
+                    changed |= self.rewrite(path, value)?;
+                }
+                Ok(changed)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+/// A root-level attribute and a CSV file of `old,new` value pairs to translate
+/// it with, like `countryCode<-mapping.csv`.
+#[derive(Debug, Clone)]
+pub struct MapSource {
+    pub attribute: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug)]
+pub struct MapSourceParseError(String);
+
+impl Display for MapSourceParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "mapping '{}' is invalid (expected e.g. countryCode<-mapping.csv)",
+            self.0
+        ))
+    }
+}
+
+impl FromStr for MapSource {
+    type Err = MapSourceParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split_once("<-")
+            .filter(|(attribute, path)| !attribute.is_empty() && !path.is_empty())
+            .map(|(attribute, path)| Self {
+                attribute: attribute.to_string(),
+                path: PathBuf::from(path),
+            })
+            .ok_or_else(|| MapSourceParseError(s.to_string()))
+    }
+}
+
+#[derive(Debug)]
+pub enum MapLoadError {
+    Csv(csv::Error),
+    /// The file has a row without exactly two columns, on this line.
+    Columns(u64),
+    /// The file maps this value to two different values.
+    Conflict(String),
+}
+
+impl Display for MapLoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapLoadError::Csv(e) => e.fmt(f),
+            MapLoadError::Columns(line) => f.write_fmt(format_args!(
+                "line {} doesn't have exactly two columns (old,new)",
+                line
+            )),
+            MapLoadError::Conflict(value) => {
+                f.write_fmt(format_args!("'{}' is mapped more than once", value))
+            }
+        }
+    }
+}
+
+/// A translation of a root-level string or number attribute's values.
+#[derive(Debug, Clone)]
+pub struct ValueMap {
+    pub attribute: String,
+    values: HashMap<String, String>,
+}
+
+/// What translating a value did to an item.
+pub enum Mapped {
+    Changed,
+    Unchanged,
+    /// The value is absent from the mapping.
+    Unknown(String),
+}
+
+impl ValueMap {
+    /// Reads a headerless CSV of `old,new` pairs, ignoring lines that start
+    /// with `#`.
+    pub fn load(source: &MapSource) -> Result<Self, MapLoadError> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .comment(Some(b'#'))
+            .flexible(true)
+            .from_path(&source.path)
+            .map_err(MapLoadError::Csv)?;
+        let mut values = HashMap::new();
+        for record in reader.records() {
+            let record = record.map_err(MapLoadError::Csv)?;
+            let (old, new) = match (record.len(), record.get(0), record.get(1)) {
+                (2, Some(old), Some(new)) => (old.to_string(), new.to_string()),
+                _ => {
+                    let line = record.position().map_or(0, |p| p.line());
+                    return Err(MapLoadError::Columns(line));
+                }
+            };
+            if values.get(&old).is_some_and(|existing| *existing != new) {
+                return Err(MapLoadError::Conflict(old));
+            }
+            values.insert(old, new);
+        }
+        Ok(Self {
+            attribute: source.attribute.clone(),
+            values,
+        })
+    }
+
+    /// Translates the attribute of `item`, keeping its type. Values absent from
+    /// the mapping, and missing attributes, are left alone.
+    pub fn apply(&self, item: &mut HashMap<String, AttributeValue>) -> Result<Mapped, String> {
+        let (value, number) = match item.get(&self.attribute) {
+            Some(AttributeValue::S(s)) => (s, false),
+            Some(AttributeValue::N(n)) => (n, true),
+            Some(value) => {
+                return Err(format!(
+                    "attribute '{}' is {}, not a string or number",
+                    self.attribute,
+                    crate::type_name(value)
+                ))
+            }
+            None => return Ok(Mapped::Unchanged),
+        };
+        let new = match self.values.get(value) {
+            Some(new) if new == value => return Ok(Mapped::Unchanged),
+            Some(new) => new.clone(),
+            None => return Ok(Mapped::Unknown(value.clone())),
+        };
+        let new = if number {
+            if new.parse::<f64>().map_or(true, |n| !n.is_finite()) {
+                return Err(format!(
+                    "attribute '{}' is a number, but '{}' maps to '{}'",
+                    self.attribute, value, new
+                ));
+            }
+            AttributeValue::N(new)
+        } else {
+            AttributeValue::S(new)
+        };
+        item.insert(self.attribute.clone(), new);
+        Ok(Mapped::Changed)
+    }
+}
+
+#[derive(Debug)]
+pub struct RemapParseError(String);
+
+impl Display for RemapParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "remap '{}' is invalid (expected e.g. status: active->ACTIVE, inactive->INACTIVE)",
+            self.0
+        ))
+    }
+}
+
+/// Parses an inline mapping, like `status: active->ACTIVE, inactive->INACTIVE`.
+impl FromStr for ValueMap {
+    type Err = RemapParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || RemapParseError(s.to_string());
+        let (attribute, pairs) = s
+            .split_once(':')
+            .map(|(attribute, pairs)| (attribute.trim(), pairs))
+            .filter(|(attribute, _)| !attribute.is_empty())
+            .ok_or_else(invalid)?;
+        let mut values = HashMap::new();
+        for pair in pairs.split(',') {
+            let (old, new) = pair.split_once("->").ok_or_else(invalid)?;
+            if values
+                .insert(old.trim().to_string(), new.trim().to_string())
+                .is_some()
+            {
+                return Err(invalid());
+            }
+        }
+        Ok(Self {
+            attribute: attribute.to_string(),
+            values,
+        })
+    }
+}
+
+impl ValueMap {
+    /// The attribute's value, if it is present but absent from the mapping.
+    pub fn unknown<'a>(&self, item: &'a HashMap<String, AttributeValue>) -> Option<&'a str> {
+        match item.get(&self.attribute) {
+            Some(AttributeValue::S(value) | AttributeValue::N(value))
+                if !self.values.contains_key(value) =>
+            {
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// What to do with values outside a `--remap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownPolicy {
+    /// Fail planning.
+    Fail,
+    /// Leave the whole item alone, and report it.
+    Skip,
+    /// Leave the value alone.
+    Pass,
+}
+
+#[derive(Debug)]
+pub struct UnknownPolicyParseError(String);
+
+impl Display for UnknownPolicyParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "unknown value policy '{}' is invalid (expected fail, skip, or pass)",
+            self.0
+        ))
+    }
+}
+
+impl FromStr for UnknownPolicy {
+    type Err = UnknownPolicyParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fail" => Ok(Self::Fail),
+            "skip" => Ok(Self::Skip),
+            "pass" => Ok(Self::Pass),
+            _ => Err(UnknownPolicyParseError(s.to_string())),
+        }
+    }
+}
+
+/// An exact conversion of a root-level number attribute, like
+/// `durationMs:ms-to-s` or `priceCents:cents-to-decimal("USD")`.
+#[derive(Debug, Clone)]
+pub struct Convert {
+    pub attribute: String,
+    conversion: Conversion,
+}
+
+#[derive(Debug, Clone)]
+enum Conversion {
+    MsToS,
+    SToMs,
+    /// From minor to major units of a currency.
+    CentsToDecimal(String),
+    /// From major to minor units of a currency, rounding ties to even.
+    DecimalToCents(String),
+}
+
+#[derive(Debug)]
+pub struct ConvertParseError(String);
+
+impl Display for ConvertParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "conversion '{}' is invalid (expected e.g. durationMs:ms-to-s, or priceCents:cents-to-decimal(\"USD\"); also s-to-ms and decimal-to-cents)",
+            self.0
+        ))
+    }
+}
+
+impl FromStr for Convert {
+    type Err = ConvertParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ConvertParseError(s.to_string());
+        let (attribute, conversion) = s
+            .rsplit_once(':')
+            .filter(|(attribute, _)| !attribute.is_empty())
+            .ok_or_else(invalid)?;
+        let currency = |name: &str| {
+            let currency = conversion
+                .strip_prefix(name)?
+                .strip_prefix('(')?
+                .strip_suffix(')')?
+                .trim_matches('"');
+            (currency.len() == 3 && currency.chars().all(|c| c.is_ascii_uppercase()))
+                .then(|| currency.to_string())
+        };
+        let conversion = match conversion {
+            "ms-to-s" => Conversion::MsToS,
+            "s-to-ms" => Conversion::SToMs,
+            _ => {
+                if let Some(currency) = currency("cents-to-decimal") {
+                    Conversion::CentsToDecimal(currency)
+                } else if let Some(currency) = currency("decimal-to-cents") {
+                    Conversion::DecimalToCents(currency)
+                } else {
+                    return Err(invalid());
+                }
+            }
+        };
+        Ok(Self {
+            attribute: attribute.to_string(),
+            conversion,
+        })
+    }
+}
+
+/// Digits after the decimal point in an ISO 4217 currency's major unit.
+fn minor_unit_places(currency: &str) -> i64 {
+    match currency {
+        "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF" | "UGX"
+        | "UYI" | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+        "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+        "CLF" | "UYW" => 4,
+        _ => 2,
+    }
+}
+
+impl Convert {
+    /// Converts the attribute of `item`, returning whether it changed.
+    pub fn apply(&self, item: &mut HashMap<String, AttributeValue>) -> Result<bool, String> {
+        let value = match item.get(&self.attribute) {
+            Some(AttributeValue::N(n)) => n,
+            Some(value) => {
+                return Err(format!(
+                    "attribute '{}' is {}, not a number",
+                    self.attribute,
+                    crate::type_name(value)
+                ))
+            }
+            None => return Ok(false),
+        };
+        let decimal = value.parse::<Decimal>().map_err(|e| e.to_string())?;
+        let converted = match &self.conversion {
+            Conversion::MsToS => decimal.shift(-3),
+            Conversion::SToMs => decimal.shift(3),
+            Conversion::CentsToDecimal(currency) => decimal.shift(-minor_unit_places(currency)),
+            Conversion::DecimalToCents(currency) => {
+                decimal.shift(minor_unit_places(currency)).round(0)
+            }
+        };
+        let converted = converted.to_string();
+        if converted == *value {
+            return Ok(false);
+        }
+        item.insert(self.attribute.clone(), AttributeValue::N(converted));
+        Ok(true)
+    }
+}
+
+/// Root-level number attributes to round to a number of decimal places, like
+/// `lat:6,lng:6`.
+#[derive(Debug, Clone)]
+pub struct Round(pub Vec<(String, i64)>);
+
+#[derive(Debug)]
+pub struct RoundParseError(String);
+
+impl Display for RoundParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "rounding '{}' is invalid (expected e.g. lat:6,lng:6)",
+            self.0
+        ))
+    }
+}
+
+impl FromStr for Round {
+    type Err = RoundParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(|pair| {
+                let (attribute, places) = pair
+                    .trim()
+                    .rsplit_once(':')
+                    .filter(|(attribute, _)| !attribute.is_empty())?;
+                Some((attribute.to_string(), places.parse::<u8>().ok()?.into()))
+            })
+            .collect::<Option<_>>()
+            .map(Self)
+            .ok_or_else(|| RoundParseError(s.to_string()))
+    }
+}
+
+impl Round {
+    /// Rounds each attribute of `item` (ties to even), returning whether any
+    /// changed.
+    pub fn apply(&self, item: &mut HashMap<String, AttributeValue>) -> Result<bool, String> {
+        let mut changed = false;
+        for (attribute, places) in &self.0 {
+            let value = match item.get(attribute) {
+                Some(AttributeValue::N(n)) => n,
+                Some(value) => {
+                    return Err(format!(
+                        "attribute '{}' is {}, not a number",
+                        attribute,
+                        crate::type_name(value)
+                    ))
+                }
+                None => continue,
+            };
+            let rounded = value
+                .parse::<Decimal>()
+                .map_err(|e| e.to_string())?
+                .round(*places)
+                .to_string();
+            if rounded != *value {
+                item.insert(attribute.clone(), AttributeValue::N(rounded));
+                changed = true;
+            }
+        }
+        Ok(changed)
+    }
+}
+
+/// The root-level list `attribute` of `item`, or `None` if it is missing or a
+/// set (which is already unordered and unique).
+fn list<'a>(
+    item: &'a mut HashMap<String, AttributeValue>,
+    attribute: &str,
+) -> Result<Option<&'a mut Vec<AttributeValue>>, String> {
+    match item.get_mut(attribute) {
+        Some(AttributeValue::L(l)) => Ok(Some(l)),
+        Some(AttributeValue::Ss(_) | AttributeValue::Ns(_) | AttributeValue::Bs(_)) | None => {
+            Ok(None)
+        }
+        Some(value) => Err(format!(
+            "attribute '{}' is {}, not a list",
+            attribute,
+            crate::type_name(value)
+        )),
+    }
+}
+
+/// Orders scalars by type (`NULL`, `BOOL`, `N`, `S`, then `B`), then by value,
+/// with numbers compared exactly.
+fn scalar_key(value: &AttributeValue) -> Option<(u8, Option<Decimal>, Vec<u8>)> {
+    Some(match value {
+        AttributeValue::Null(_) => (0, None, Vec::new()),
+        AttributeValue::Bool(b) => (1, None, vec![*b as u8]),
+        AttributeValue::N(n) => (2, Some(n.parse().ok()?), Vec::new()),
+        AttributeValue::S(s) => (3, None, s.as_bytes().to_vec()),
+        AttributeValue::B(b) => (4, None, b.as_ref().to_vec()),
+        _ => return None,
+    })
+}
+
+/// Sorts the root-level list `attribute` of `item`, which must only contain
+/// scalars, returning whether it changed.
+pub fn list_sort(
+    item: &mut HashMap<String, AttributeValue>,
+    attribute: &str,
+) -> Result<bool, String> {
+    let list = match list(item, attribute)? {
+        Some(list) => list,
+        None => return Ok(false),
+    };
+    let mut keyed = list
+        .iter()
+        .map(|value| Some((scalar_key(value)?, value.clone())))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| format!("list '{}' has non-scalar elements to sort", attribute))?;
+    keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let sorted = keyed
+        .into_iter()
+        .map(|(_, value)| value)
+        .collect::<Vec<_>>();
+    if sorted == *list {
+        return Ok(false);
+    }
+    *list = sorted;
+    Ok(true)
+}
+
+/// Removes all but the first of equal elements of the root-level list
+/// `attribute` of `item`, returning whether it changed.
+pub fn list_dedupe(
+    item: &mut HashMap<String, AttributeValue>,
+    attribute: &str,
+) -> Result<bool, String> {
+    let list = match list(item, attribute)? {
+        Some(list) => list,
+        None => return Ok(false),
+    };
+    let len = list.len();
+    let mut deduped = Vec::<AttributeValue>::with_capacity(len);
+    for value in list.drain(..) {
+        if !deduped.contains(&value) {
+            deduped.push(value);
+        }
+    }
+    *list = deduped;
+    Ok(list.len() != len)
+}
+
+/// Converts the root-level list `attribute` of `item`, of only strings,
+/// numbers, or binary, to the corresponding set, dropping duplicates. Empty
+/// lists are left alone, since sets can't be empty.
+pub fn to_set(item: &mut HashMap<String, AttributeValue>, attribute: &str) -> Result<bool, String> {
+    let list = match item.get(attribute) {
+        Some(AttributeValue::L(l)) if !l.is_empty() => l,
+        Some(
+            AttributeValue::L(_)
+            | AttributeValue::Ss(_)
+            | AttributeValue::Ns(_)
+            | AttributeValue::Bs(_),
+        )
+        | None => return Ok(false),
+        Some(value) => {
+            return Err(format!(
+                "attribute '{}' is {}, not a list",
+                attribute,
+                crate::type_name(value)
+            ))
+        }
+    };
+    let mixed = || format!("list '{}' isn't all strings, numbers, or binary", attribute);
+    // Elements are duplicates if their keys are equal.
+    fn unique<T, K: PartialEq>(
+        values: impl Iterator<Item = Option<T>>,
+        key: impl Fn(&T) -> K,
+    ) -> Option<Vec<T>> {
+        let mut ret = Vec::new();
+        let mut keys = Vec::new();
+        for value in values {
+            let value = value?;
+            let key = key(&value);
+            if !keys.contains(&key) {
+                keys.push(key);
+                ret.push(value);
+            }
+        }
+        Some(ret)
+    }
+    let set = match &list[0] {
+        AttributeValue::S(_) => AttributeValue::Ss(
+            unique(list.iter().map(|v| v.as_s().ok().cloned()), String::clone).ok_or_else(mixed)?,
+        ),
+        // By value, like DynamoDB, so that `1` and `1.0` are the same element.
+        AttributeValue::N(_) => AttributeValue::Ns(
+            unique(list.iter().map(|v| v.as_n().ok().cloned()), |n| {
+                n.parse::<Decimal>().map_err(|_| n.clone())
+            })
+            .ok_or_else(mixed)?,
+        ),
+        AttributeValue::B(_) => AttributeValue::Bs(
+            unique(list.iter().map(|v| v.as_b().ok().cloned()), Blob::clone).ok_or_else(mixed)?,
+        ),
+        _ => return Err(mixed()),
+    };
+    item.insert(attribute.to_string(), set);
+    Ok(true)
+}
+
+/// Converts the root-level set `attribute` of `item` to a list, in sorted order.
+pub fn to_list(
+    item: &mut HashMap<String, AttributeValue>,
+    attribute: &str,
+) -> Result<bool, String> {
+    let mut list: Vec<_> = match item.get(attribute) {
+        Some(AttributeValue::Ss(ss)) => ss.iter().cloned().map(AttributeValue::S).collect(),
+        Some(AttributeValue::Ns(ns)) => ns.iter().cloned().map(AttributeValue::N).collect(),
+        Some(AttributeValue::Bs(bs)) => bs.iter().cloned().map(AttributeValue::B).collect(),
+        Some(AttributeValue::L(_)) | None => return Ok(false),
+        Some(value) => {
+            return Err(format!(
+                "attribute '{}' is {}, not a set",
+                attribute,
+                crate::type_name(value)
+            ))
+        }
+    };
+    list.sort_by_key(scalar_key);
+    item.insert(attribute.to_string(), AttributeValue::L(list));
+    Ok(true)
+}
@@ -0,0 +1,132 @@
+use crate::checkpoint::id;
+use crate::json::{item_from_json, item_to_json};
+use crate::plan;
+use aws_sdk_dynamodb::model::AttributeValue;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// A journal of the items that runs replaced, as newline-delimited JSON, so
+/// that `rollback` can restore them without a separate backup.
+///
+/// Before each item is put, a line with the table, the item as it was, and the
+/// item as it's about to be written (both as DynamoDB JSON) is appended, so
+/// nothing is ever rewritten, and several runs can share a journal.
+pub struct Journal {
+    file: Mutex<File>,
+}
+
+impl Journal {
+    /// Opens a journal to append to, creating it if it doesn't exist.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Records that `original` in `table` is about to be replaced by `written`.
+    pub fn replacing(
+        &self,
+        table: &str,
+        original: &HashMap<String, AttributeValue>,
+        written: &HashMap<String, AttributeValue>,
+    ) -> io::Result<()> {
+        let record = json!({
+            "table": table,
+            "original": item_to_json(original),
+            "written": item_to_json(written),
+        });
+        let mut file = self.file.lock().unwrap();
+        writeln!(file, "{}", record)?;
+        file.flush()
+    }
+}
+
+/// An item to restore, to how it was the first time it was journaled (since it
+/// was last changed otherwise), if it's still as it was last written.
+pub struct Restore {
+    pub original: HashMap<String, AttributeValue>,
+    pub written: HashMap<String, AttributeValue>,
+}
+
+#[derive(Debug)]
+pub enum UndoError {
+    Io(io::Error),
+    /// This line (from 1) isn't a journal record.
+    Malformed(usize),
+    /// The journal has records for this other table.
+    Table(String),
+}
+
+impl Display for UndoError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UndoError::Io(e) => f.write_fmt(format_args!("error reading undo journal: {}", e)),
+            UndoError::Malformed(line) => f.write_fmt(format_args!(
+                "undo journal line {} is not a journal record",
+                line
+            )),
+            UndoError::Table(table) => f.write_fmt(format_args!(
+                "the undo journal has records for table {}, so it can't be rolled back here",
+                table
+            )),
+        }
+    }
+}
+
+/// Reads a journal of `table`, returning what to restore of each item (by
+/// [`id`] of its key), in the order they were first journaled.
+pub fn load(
+    path: &Path,
+    table: &str,
+    key_names: &[String],
+) -> Result<Vec<(String, Restore)>, UndoError> {
+    let file = File::open(path).map_err(UndoError::Io)?;
+    let mut restores = Vec::<(String, Restore)>::new();
+    let mut positions = HashMap::<String, usize>::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(UndoError::Io)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let malformed = || UndoError::Malformed(i + 1);
+        // An interrupted append leaves a partial last line, which is ignored.
+        let record = match serde_json::from_str::<Value>(&line) {
+            Ok(record) => record,
+            Err(e) if e.is_eof() => break,
+            Err(_) => return Err(malformed()),
+        };
+        match record["table"].as_str() {
+            Some(t) if t == table => {}
+            Some(t) => return Err(UndoError::Table(t.to_string())),
+            None => return Err(malformed()),
+        }
+        let original = item_from_json(&record["original"]).ok_or_else(malformed)?;
+        let written = item_from_json(&record["written"]).ok_or_else(malformed)?;
+        let key = id(&plan::key(key_names, &written));
+        // An item journaled again was replaced again, so it's restored to how
+        // it was first, if it's still as it was last written. Unless it had
+        // changed in between (someone else wrote it, or the earlier write
+        // never happened), in which case it's restored to how it was then,
+        // rather than undoing the other change too.
+        match positions.get(&key) {
+            Some(&position) => {
+                let restore = &mut restores[position].1;
+                if restore.written != original {
+                    restore.original = original;
+                }
+                restore.written = written;
+            }
+            None => {
+                positions.insert(key.clone(), restores.len());
+                restores.push((key, Restore { original, written }));
+            }
+        }
+    }
+    Ok(restores)
+}
@@ -0,0 +1,82 @@
+use crate::json::item_to_plain_json;
+use aws_sdk_dynamodb::model::AttributeValue;
+use jsonschema::JSONSchema;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::{fs, io};
+
+/// A JSON Schema that items, as plain JSON, must satisfy.
+pub struct Validator {
+    schema: JSONSchema,
+}
+
+#[derive(Debug)]
+pub enum SchemaError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Invalid(String),
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaError::Io(e) => f.write_fmt(format_args!("error reading schema: {}", e)),
+            SchemaError::Json(e) => f.write_fmt(format_args!("error parsing schema: {}", e)),
+            SchemaError::Invalid(e) => f.write_fmt(format_args!("invalid schema: {}", e)),
+        }
+    }
+}
+
+/// One failed constraint.
+#[derive(Debug)]
+pub struct Violation {
+    /// JSON pointer to the offending part of the item, like `/address/zip`.
+    pub path: String,
+    /// JSON pointer to the failed constraint in the schema.
+    pub constraint: String,
+    pub message: String,
+}
+
+impl Validator {
+    pub fn load(path: &Path) -> Result<Self, SchemaError> {
+        let contents = fs::read_to_string(path).map_err(SchemaError::Io)?;
+        let schema = serde_json::from_str::<Value>(&contents).map_err(SchemaError::Json)?;
+        let schema =
+            JSONSchema::compile(&schema).map_err(|e| SchemaError::Invalid(e.to_string()))?;
+        Ok(Self { schema })
+    }
+
+    /// How `item`, converted to plain JSON, violates the schema, if at all.
+    pub fn violations(&self, item: &HashMap<String, AttributeValue>) -> Vec<Violation> {
+        let instance = item_to_plain_json(item);
+        let violations = match self.schema.validate(&instance) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors
+                .map(|e| Violation {
+                    path: e.instance_path.to_string(),
+                    constraint: e.schema_path.to_string(),
+                    message: e.to_string(),
+                })
+                .collect(),
+        };
+        violations
+    }
+}
+
+/// A report line for the item with primary key `key`.
+pub fn report(key: &str, violations: &[Violation], repaired: bool) -> Value {
+    json!({
+        "key": key,
+        "violations": violations
+            .iter()
+            .map(|v| json!({
+                "path": v.path,
+                "constraint": v.constraint,
+                "message": v.message,
+            }))
+            .collect::<Vec<_>>(),
+        "repaired": repaired,
+    })
+}
@@ -0,0 +1,241 @@
+use crate::render;
+use crate::EVERY_ELEMENT;
+use aws_sdk_dynamodb::model::AttributeValue;
+use dynamodb_bulk_edit::rename::parse_path;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Columns to view, like `pk, sk, meta.version as v, orders[0].total`: paths
+/// like in rules (with `[*]` for every element of a list), which may also
+/// index lists, each optionally named with `as`.
+#[derive(Debug, Clone)]
+pub struct Select(Vec<Column>);
+
+#[derive(Debug, Clone)]
+struct Column {
+    header: String,
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    Name(String),
+    Index(usize),
+    Every,
+}
+
+#[derive(Debug)]
+pub struct SelectParseError(String);
+
+impl Display for SelectParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "column '{}' is invalid (expected e.g. meta.version, orders[0].total, or \"first name\" as name)",
+            self.0
+        ))
+    }
+}
+
+impl FromStr for Select {
+    type Err = SelectParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        split_unquoted(s, ',')
+            .into_iter()
+            .map(|column| {
+                let column = column.trim();
+                let err = || SelectParseError(column.to_string());
+                let (path, header) = match column.rsplit_once(" as ") {
+                    Some((path, alias)) if !alias.contains('"') => (path.trim(), alias.trim()),
+                    _ => (column, column),
+                };
+                if header.is_empty() {
+                    return Err(err());
+                }
+                Ok(Column {
+                    header: header.to_string(),
+                    steps: parse_steps(path).ok_or_else(err)?,
+                })
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+/// Splits `s` at each `c` not within double quotes.
+fn split_unquoted(s: &str, c: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, next) in s.char_indices() {
+        if next == '"' {
+            quoted = !quoted;
+        } else if next == c && !quoted {
+            parts.push(&s[start..i]);
+            start = i + next.len_utf8();
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// Parses a path with the rules' syntax, after taking out list indexes (which
+/// rules don't have), like `orders[0]`, to put back in order.
+fn parse_steps(path: &str) -> Option<Vec<Step>> {
+    let mut rule_path = String::new();
+    let mut indexes = Vec::new();
+    let mut quoted = false;
+    let mut chars = path.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        if c == '[' && !quoted && chars.peek().is_some_and(char::is_ascii_digit) {
+            let mut index = String::new();
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                index.push(digit);
+            }
+            if chars.next() != Some(']') {
+                return None;
+            }
+            indexes.push(Some(index.parse().ok()?));
+            rule_path.push_str(EVERY_ELEMENT);
+        } else {
+            if c == '[' && !quoted {
+                indexes.push(None);
+            }
+            rule_path.push(c);
+        }
+    }
+    let mut indexes = indexes.into_iter();
+    let mut steps = Vec::new();
+    for mut segment in parse_path(&rule_path).ok()? {
+        let mut lists = 0;
+        while let Some(name) = segment.strip_suffix(EVERY_ELEMENT) {
+            segment.truncate(name.len());
+            lists += 1;
+        }
+        steps.push(Step::Name(segment));
+        for _ in 0..lists {
+            steps.push(match indexes.next()? {
+                Some(index) => Step::Index(index),
+                None => Step::Every,
+            });
+        }
+    }
+    Some(steps)
+}
+
+impl Select {
+    pub fn headers(&self) -> Vec<String> {
+        self.0.iter().map(|column| column.header.clone()).collect()
+    }
+
+    /// The cells of `item`'s row, empty where it has nothing at a path.
+    pub fn row(&self, item: &HashMap<String, AttributeValue>) -> Vec<String> {
+        self.0
+            .iter()
+            .map(|column| {
+                let value = match column.steps.split_first() {
+                    Some((Step::Name(name), rest)) => {
+                        item.get(name).and_then(|value| select(value, rest))
+                    }
+                    _ => None,
+                };
+                value.as_ref().map(render::label).unwrap_or_default()
+            })
+            .collect()
+    }
+}
+
+/// The value at `steps` within `value`, with a list of the values found in
+/// each element for [`Step::Every`].
+fn select(value: &AttributeValue, steps: &[Step]) -> Option<AttributeValue> {
+    let (step, rest) = match steps.split_first() {
+        Some(split) => split,
+        None => return Some(value.clone()),
+    };
+    match (step, value) {
+        (Step::Name(name), AttributeValue::M(map)) => select(map.get(name)?, rest),
+        (Step::Index(index), AttributeValue::L(list)) => select(list.get(*index)?, rest),
+        (Step::Every, AttributeValue::L(list)) => Some(AttributeValue::L(
+            list.iter()
+                .filter_map(|element| select(element, rest))
+                .collect(),
+        )),
+        _ => None,
+    }
+}
+
+/// How to print a view.
+#[derive(Debug, Clone, Copy)]
+pub enum ViewFormat {
+    /// Columns aligned with spaces, under a header.
+    Table,
+    /// A header, then a row per item.
+    Csv,
+}
+
+#[derive(Debug)]
+pub struct ViewFormatParseError(String);
+
+impl Display for ViewFormatParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "view format '{}' is invalid (expected table or csv)",
+            self.0
+        ))
+    }
+}
+
+impl FromStr for ViewFormat {
+    type Err = ViewFormatParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Self::Table),
+            "csv" => Ok(Self::Csv),
+            _ => Err(ViewFormatParseError(s.to_string())),
+        }
+    }
+}
+
+/// The lines of a view of `rows` under `headers`.
+pub fn lines(format: ViewFormat, headers: Vec<String>, rows: Vec<Vec<String>>) -> Vec<String> {
+    let rows = std::iter::once(headers).chain(rows).collect::<Vec<_>>();
+    match format {
+        ViewFormat::Table => {
+            let mut widths = vec![0; rows[0].len()];
+            for row in &rows {
+                for (width, cell) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(cell.chars().count());
+                }
+            }
+            rows.iter()
+                .map(|row| {
+                    let line = row
+                        .iter()
+                        .zip(&widths)
+                        .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                        .collect::<Vec<_>>()
+                        .join("  ");
+                    line.trim_end().to_string()
+                })
+                .collect()
+        }
+        ViewFormat::Csv => rows
+            .iter()
+            .map(|row| {
+                let mut writer = csv::WriterBuilder::new()
+                    .terminator(csv::Terminator::Any(b'\n'))
+                    .from_writer(Vec::new());
+                let _ = writer.write_record(row);
+                let line = writer.into_inner().unwrap_or_default();
+                String::from_utf8_lossy(&line)
+                    .trim_end_matches('\n')
+                    .to_string()
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,197 @@
+//! Property tests of the semantics documented in `dynamodb_bulk_edit::rule`.
+
+use aws_sdk_dynamodb::model::AttributeValue;
+use dynamodb_bulk_edit::rename::{would_change, Remove};
+use dynamodb_bulk_edit::rule::{apply, Path, Rule};
+use dynamodb_bulk_edit::{OverwritePolicy, Replace};
+use proptest::prelude::*;
+use std::collections::HashMap;
+
+type Item = HashMap<String, AttributeValue>;
+
+/// Few enough names that rules often match (and collide), including ones that
+/// need quoting in rules.
+fn name() -> impl Strategy<Value = String> + Clone {
+    prop::sample::select(vec!["a", "aa", "b", "first name", "x:y"]).prop_map(String::from)
+}
+
+/// Any name a rule can have.
+fn any_name() -> impl Strategy<Value = String> + Clone {
+    "[^.]{1,8}".prop_filter("names can't contain [*]", |name| !name.contains("[*]"))
+}
+
+fn value() -> impl Strategy<Value = AttributeValue> {
+    let leaf = prop_oneof![
+        "[a-z]{0,3}".prop_map(AttributeValue::S),
+        any::<i16>().prop_map(|n| AttributeValue::N(n.to_string())),
+        any::<bool>().prop_map(AttributeValue::Bool),
+    ];
+    leaf.prop_recursive(4, 32, 4, |inner| {
+        prop_oneof![
+            prop::collection::hash_map(name(), inner.clone(), 0..4).prop_map(AttributeValue::M),
+            prop::collection::vec(inner, 0..3).prop_map(AttributeValue::L),
+        ]
+    })
+}
+
+fn item() -> impl Strategy<Value = Item> {
+    prop::collection::hash_map(name(), value(), 0..5)
+}
+
+fn path(name: impl Strategy<Value = String> + Clone) -> impl Strategy<Value = Path> {
+    (
+        any::<bool>(),
+        prop::collection::vec((name.clone(), any::<bool>()), 0..2),
+        name,
+    )
+        .prop_map(|(root, prefix, last)| {
+            let mut segments = prefix.into_iter().map(Some).chain([None]);
+            let first = segments.next().unwrap();
+            let (first, every) = first.unwrap_or((last.clone(), false));
+            let mut path = if root {
+                Path::root(first)
+            } else {
+                Path::any(first)
+            };
+            if every {
+                path = path.every();
+            }
+            for segment in segments {
+                match segment {
+                    Some((name, every)) => {
+                        path = path.field(name);
+                        if every {
+                            path = path.every();
+                        }
+                    }
+                    None => path = path.field(last.clone()),
+                }
+            }
+            path
+        })
+}
+
+fn policy() -> impl Strategy<Value = OverwritePolicy> {
+    prop::sample::select(vec![
+        OverwritePolicy::Error,
+        OverwritePolicy::Skip,
+        OverwritePolicy::Overwrite,
+    ])
+}
+
+fn depth() -> impl Strategy<Value = (Option<usize>, Option<usize>)> {
+    (prop::option::of(1..4usize), prop::option::of(1..4usize))
+}
+
+fn rename(name: impl Strategy<Value = String> + Clone) -> impl Strategy<Value = Rule> {
+    (path(name.clone()), name, policy(), depth()).prop_filter_map(
+        "the path must end in a name",
+        |(path, to, policy, (min, max))| {
+            let rule = Rule::rename(path, to).ok()?;
+            Some(rule.overwrite(policy).depth(min, max))
+        },
+    )
+}
+
+fn replace(rule: &Rule) -> &Replace {
+    match rule {
+        Rule::Rename(replace) => replace,
+        Rule::Remove(_) => unreachable!(),
+    }
+}
+
+/// Every scalar in `value`, and how many map entries it has.
+fn contents(value: &AttributeValue, scalars: &mut Vec<String>, entries: &mut usize) {
+    match value {
+        AttributeValue::M(map) => {
+            *entries += map.len();
+            for value in map.values() {
+                contents(value, scalars, entries);
+            }
+        }
+        AttributeValue::L(list) => {
+            for value in list {
+                contents(value, scalars, entries);
+            }
+        }
+        scalar => scalars.push(format!("{:?}", scalar)),
+    }
+}
+
+fn item_contents(item: &Item) -> (Vec<String>, usize) {
+    let (mut scalars, mut entries) = (Vec::new(), 0);
+    contents(&AttributeValue::M(item.clone()), &mut scalars, &mut entries);
+    scalars.sort_unstable();
+    (scalars, entries)
+}
+
+proptest! {
+    #[test]
+    fn renames_round_trip(rule in rename(any_name())) {
+        let parsed = rule.to_string().parse::<Replace>().unwrap();
+        prop_assert_eq!(format!("{:?}", parsed), format!("{:?}", replace(&rule)));
+    }
+
+    #[test]
+    fn removals_round_trip(path in path(any_name())) {
+        if let Ok(rule) = Rule::remove(path) {
+            let parsed = rule.to_string().parse::<Remove>().unwrap();
+            prop_assert_eq!(parsed.to_string(), rule.to_string());
+        }
+    }
+
+    #[test]
+    fn renames_are_lossless(rule in rename(name()), mut item in item()) {
+        let before = item_contents(&item);
+        let result = apply(std::slice::from_ref(&rule), &mut item);
+        if result.overwrites == 0 {
+            prop_assert_eq!(item_contents(&item), before);
+        }
+    }
+
+    #[test]
+    fn renames_are_idempotent(rule in rename(name()), mut item in item()) {
+        let rules = [rule];
+        apply(&rules, &mut item);
+        let once = item.clone();
+        apply(&rules, &mut item);
+        prop_assert_eq!(item, once);
+    }
+
+    #[test]
+    fn any_level_renames_ignore_where_maps_are(
+        rule in rename(name()),
+        mut item in item(),
+        wrapper in name(),
+    ) {
+        let rule = rule.depth(None, None);
+        if !replace(&rule).root {
+            // No rule matches the wrapper's name itself, only names ending
+            // with it.
+            let wrapper = format!("z{}", wrapper);
+            let mut wrapped = HashMap::from([(wrapper.clone(), AttributeValue::M(item.clone()))]);
+            apply(std::slice::from_ref(&rule), &mut wrapped);
+            apply(std::slice::from_ref(&rule), &mut item);
+            prop_assert_eq!(wrapped, HashMap::from([(wrapper, AttributeValue::M(item))]));
+        }
+    }
+
+    #[test]
+    fn renames_are_invertible(rule in rename(name()), original in item()) {
+        let inverse = replace(&rule).inverse();
+        // Otherwise, renaming a map can make the rule match (only) within it.
+        let renames_prefix = inverse
+            .prefix
+            .split('.')
+            .any(|segment| segment.trim_end_matches("[*]") == inverse.from);
+        let mut item = original.clone();
+        let result = apply(std::slice::from_ref(&rule), &mut item);
+        if result.overwrites == 0
+            && !renames_prefix
+            && !would_change(&original, std::slice::from_ref(&inverse))
+        {
+            apply(&[Rule::Rename(inverse)], &mut item);
+            prop_assert_eq!(item, original);
+        }
+    }
+}